@@ -1,5 +1,6 @@
-use super::super::{BotRuntime, GroupSendStatus};
-use axum::extract::{Json, Query};
+use super::super::{message_archive, BotRuntime, GroupSendStatus};
+use crate::models::SharedState;
+use axum::extract::{Json, Query, State};
 use axum::Extension;
 
 #[derive(serde::Deserialize)]
@@ -40,10 +41,87 @@ pub enum ChatSegment {
     Reply,
 }
 
+fn to_chat_segments(message: &serde_json::Value) -> Vec<ChatSegment> {
+    let Some(arr) = message.as_array() else {
+        return vec![];
+    };
+    arr.iter()
+        .filter_map(|seg| {
+            let seg_type = seg.get("type").and_then(|t| t.as_str())?;
+            let data = seg.get("data").cloned().unwrap_or(serde_json::json!({}));
+            match seg_type {
+                "text" => data
+                    .get("text")
+                    .and_then(|t| t.as_str())
+                    .map(|text| ChatSegment::Text {
+                        text: text.to_string(),
+                    }),
+                "image" => data.get("url").and_then(|u| u.as_str()).and_then(|url| {
+                    let url = url.trim();
+                    if url.starts_with("http://") || url.starts_with("https://") {
+                        Some(ChatSegment::Image {
+                            url: url.to_string(),
+                        })
+                    } else {
+                        None
+                    }
+                }),
+                "face" => Some(ChatSegment::Face),
+                "at" => Some(ChatSegment::At {
+                    qq: data
+                        .get("qq")
+                        .and_then(|q| q.as_str())
+                        .unwrap_or("all")
+                        .to_string(),
+                }),
+                "reply" => Some(ChatSegment::Reply),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// 开启消息存档的 bot 从本地存档读取聊天记录
+async fn archived_chat_history(query: &ChatHistoryQuery) -> Json<serde_json::Value> {
+    let archive_query = message_archive::ArchiveQuery {
+        bot_id: query.bot_id.clone(),
+        group_id: query.group_id,
+        peer_id: query.group_id.is_none().then_some(query.user_id).flatten(),
+        limit: Some(query.count as usize),
+        ..Default::default()
+    };
+    match message_archive::query_messages(archive_query).await {
+        Ok(rows) => {
+            // 存档按时间倒序返回，聊天页按时间正序展示
+            let chat_messages: Vec<ChatMessage> = rows
+                .into_iter()
+                .rev()
+                .map(|row| ChatMessage {
+                    message_id: row.message_id.unwrap_or(0),
+                    time: row.time.max(0) as u64,
+                    sender_id: row.user_id,
+                    sender_name: row.sender_name,
+                    segments: to_chat_segments(&row.message),
+                    is_self: row.outgoing,
+                })
+                .collect();
+            Json(serde_json::json!({ "status": "success", "messages": chat_messages }))
+        }
+        Err(e) => Json(serde_json::json!({ "status": "error", "message": e })),
+    }
+}
+
 pub async fn get_chat_history_handler(
+    State(state): State<SharedState>,
     Extension(runtime): Extension<std::sync::Arc<BotRuntime>>,
     Query(query): Query<ChatHistoryQuery>,
 ) -> Json<serde_json::Value> {
+    if (query.group_id.is_some() || query.user_id.is_some())
+        && message_archive::refresh_settings(&state, &query.bot_id)
+    {
+        return archived_chat_history(&query).await;
+    }
+
     // Get bot's own QQ ID for is_self detection
     let login_info = runtime
         .call_api(&query.bot_id, "get_login_info", serde_json::json!({}))
@@ -92,50 +170,10 @@ pub async fn get_chat_history_handler(
                         let sender = m.get("sender")?;
                         let sender_id = sender.get("user_id").and_then(|v| v.as_u64())?;
 
-                        let segments = if let Some(arr) =
-                            m.get("message").and_then(|msg| msg.as_array())
+                        let segments = if let Some(msg) =
+                            m.get("message").filter(|msg| msg.is_array())
                         {
-                            arr.iter()
-                                .filter_map(|seg| {
-                                    let seg_type = seg.get("type").and_then(|t| t.as_str())?;
-                                    let data =
-                                        seg.get("data").cloned().unwrap_or(serde_json::json!({}));
-                                    match seg_type {
-                                        "text" => {
-                                            data.get("text").and_then(|t| t.as_str()).map(|text| {
-                                                ChatSegment::Text {
-                                                    text: text.to_string(),
-                                                }
-                                            })
-                                        }
-                                        "image" => data
-                                            .get("url")
-                                            .and_then(|u| u.as_str())
-                                            .and_then(|url| {
-                                                let url = url.trim();
-                                                if url.starts_with("http://")
-                                                    || url.starts_with("https://")
-                                                {
-                                                    Some(ChatSegment::Image {
-                                                        url: url.to_string(),
-                                                    })
-                                                } else {
-                                                    None
-                                                }
-                                            }),
-                                        "face" => Some(ChatSegment::Face),
-                                        "at" => Some(ChatSegment::At {
-                                            qq: data
-                                                .get("qq")
-                                                .and_then(|q| q.as_str())
-                                                .unwrap_or("all")
-                                                .to_string(),
-                                        }),
-                                        "reply" => Some(ChatSegment::Reply),
-                                        _ => None,
-                                    }
-                                })
-                                .collect::<Vec<_>>()
+                            to_chat_segments(msg)
                         } else if let Some(raw) = m.get("raw_message").and_then(|r| r.as_str()) {
                             vec![ChatSegment::Text {
                                 text: raw.to_string(),
@@ -215,20 +253,48 @@ pub async fn send_chat_message_handler(
         );
     };
 
-    let result = runtime.call_api(&payload.bot_id, action, params).await;
+    let result = runtime
+        .call_api_checked(
+            &payload.bot_id,
+            action,
+            params.clone(),
+            std::time::Duration::from_secs(15),
+        )
+        .await;
 
     match result {
-        Some(resp) => {
-            if resp.get("status").and_then(|s| s.as_str()) == Some("ok") {
-                Json(serde_json::json!({ "status": "success" }))
-            } else {
-                let msg = resp
-                    .get("message")
-                    .and_then(|m| m.as_str())
-                    .unwrap_or("Unknown error");
-                Json(serde_json::json!({ "status": "error", "message": msg }))
-            }
+        Ok(resp) => {
+            message_archive::record_sent(&runtime, &payload.bot_id, &params, &resp).await;
+            Json(serde_json::json!({ "status": "success" }))
         }
-        None => Json(serde_json::json!({ "status": "error", "message": "Failed to send message" })),
+        Err(e) => Json(serde_json::json!({ "status": "error", "message": e.to_string() })),
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct ArchiveBotQuery {
+    pub bot_id: String,
+}
+
+/// 检索消息存档：关键词 + 群 / 私聊对象 / 发送者 / 时间过滤，按时间倒序，before_id 分页
+pub async fn search_chat_archive_handler(
+    Query(query): Query<message_archive::ArchiveQuery>,
+) -> Json<serde_json::Value> {
+    if query.bot_id.trim().is_empty() {
+        return Json(serde_json::json!({ "status": "error", "message": "Missing bot_id" }));
+    }
+    match message_archive::query_messages(query).await {
+        Ok(messages) => Json(serde_json::json!({ "status": "success", "messages": messages })),
+        Err(e) => Json(serde_json::json!({ "status": "error", "message": e })),
+    }
+}
+
+/// 清空该 bot 的消息存档
+pub async fn clear_chat_archive_handler(
+    Query(query): Query<ArchiveBotQuery>,
+) -> Json<serde_json::Value> {
+    match message_archive::clear_messages(query.bot_id).await {
+        Ok(removed) => Json(serde_json::json!({ "status": "success", "removed": removed })),
+        Err(e) => Json(serde_json::json!({ "status": "error", "message": e })),
     }
 }
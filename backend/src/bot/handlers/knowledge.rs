@@ -0,0 +1,157 @@
+use super::super::knowledge::{self, CreateKnowledgeBaseInput, KnowledgeSource};
+use crate::models::SharedState;
+use axum::extract::{Json, Path, State};
+use serde_json::json;
+
+pub async fn list_knowledge_bases_handler() -> Json<serde_json::Value> {
+    let bases = knowledge::list_knowledge_bases().await;
+    Json(json!({ "status": "success", "knowledge_bases": bases }))
+}
+
+pub async fn get_knowledge_base_handler(Path(id): Path<String>) -> Json<serde_json::Value> {
+    match knowledge::get_knowledge_base(&id).await {
+        Some(kb) => Json(json!({ "status": "success", "knowledge_base": kb })),
+        None => Json(json!({ "status": "error", "message": format!("知识库 '{}' 不存在", id) })),
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct CreateKnowledgeBasePayload {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+    #[serde(default)]
+    pub chunk_chars: Option<usize>,
+    #[serde(default)]
+    pub chunk_overlap: Option<usize>,
+}
+
+pub async fn create_knowledge_base_handler(
+    Json(payload): Json<CreateKnowledgeBasePayload>,
+) -> Json<serde_json::Value> {
+    let input = CreateKnowledgeBaseInput {
+        id: payload.id,
+        name: payload.name,
+        description: payload.description,
+        embedding_model: payload.embedding_model,
+        chunk_chars: payload.chunk_chars,
+        chunk_overlap: payload.chunk_overlap,
+    };
+    match knowledge::create_knowledge_base(input).await {
+        Ok(kb) => Json(json!({ "status": "success", "knowledge_base": kb })),
+        Err(e) => Json(json!({ "status": "error", "message": e })),
+    }
+}
+
+pub async fn delete_knowledge_base_handler(Path(id): Path<String>) -> Json<serde_json::Value> {
+    match knowledge::delete_knowledge_base(&id).await {
+        Ok(_) => Json(json!({ "status": "success" })),
+        Err(e) => Json(json!({ "status": "error", "message": e })),
+    }
+}
+
+/// 导入文档：content / url / archive_url 三选一
+#[derive(serde::Deserialize)]
+pub struct IngestKnowledgePayload {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub archive_url: Option<String>,
+    #[serde(default)]
+    pub file_name: Option<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// 使用哪个机器人的 LLM 配置（为空则使用全局配置）
+    #[serde(default)]
+    pub bot_id: String,
+}
+
+pub async fn ingest_knowledge_document_handler(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(payload): Json<IngestKnowledgePayload>,
+) -> Json<serde_json::Value> {
+    let file_name = payload
+        .file_name
+        .as_deref()
+        .filter(|s| !s.trim().is_empty());
+    let source = if let Some(content) = payload.content.as_deref().filter(|s| !s.trim().is_empty())
+    {
+        KnowledgeSource::Text(content)
+    } else if let Some(url) = payload.url.as_deref().filter(|s| !s.trim().is_empty()) {
+        KnowledgeSource::Url { url, file_name }
+    } else if let Some(url) = payload
+        .archive_url
+        .as_deref()
+        .filter(|s| !s.trim().is_empty())
+    {
+        KnowledgeSource::ArchiveUrl {
+            url,
+            file_name,
+            keywords: &payload.keywords,
+        }
+    } else {
+        return Json(
+            json!({ "status": "error", "message": "content / url / archive_url 至少提供一个" }),
+        );
+    };
+
+    match knowledge::ingest_knowledge_document(&state, &payload.bot_id, &id, &payload.title, source)
+        .await
+    {
+        Ok(doc) => Json(json!({ "status": "success", "document": doc })),
+        Err(e) => Json(json!({ "status": "error", "message": e })),
+    }
+}
+
+pub async fn delete_knowledge_document_handler(
+    Path((id, doc_id)): Path<(String, String)>,
+) -> Json<serde_json::Value> {
+    match knowledge::delete_knowledge_document(&id, &doc_id).await {
+        Ok(_) => Json(json!({ "status": "success" })),
+        Err(e) => Json(json!({ "status": "error", "message": e })),
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct QueryKnowledgePayload {
+    pub query: String,
+    #[serde(default = "default_top_k")]
+    pub top_k: usize,
+    #[serde(default)]
+    pub min_score: f32,
+    #[serde(default)]
+    pub bot_id: String,
+}
+
+fn default_top_k() -> usize {
+    4
+}
+
+pub async fn query_knowledge_base_handler(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(payload): Json<QueryKnowledgePayload>,
+) -> Json<serde_json::Value> {
+    match knowledge::query_knowledge_base(
+        &state,
+        &payload.bot_id,
+        &id,
+        &payload.query,
+        payload.top_k,
+        payload.min_score,
+    )
+    .await
+    {
+        Ok(hits) => Json(json!({ "status": "success", "results": hits })),
+        Err(e) => Json(json!({ "status": "error", "message": e })),
+    }
+}
@@ -0,0 +1,32 @@
+use super::super::llm_trace::{self, LlmTraceQuery};
+use axum::extract::{Json, Path, Query};
+use serde_json::json;
+
+/// 列出 LLM 调用追踪记录（摘要，按时间倒序），支持按 bot_id / plugin_id / model / status 过滤
+pub async fn list_llm_traces_handler(
+    Query(query): Query<LlmTraceQuery>,
+) -> Json<serde_json::Value> {
+    let traces = llm_trace::query_llm_traces(&query);
+    let next_before_id = traces
+        .last()
+        .and_then(|t| t.get("id"))
+        .and_then(|v| v.as_u64());
+    Json(json!({ "status": "success", "traces": traces, "next_before_id": next_before_id }))
+}
+
+/// 获取单条追踪记录详情（含脱敏后的提示词与回复）
+pub async fn get_llm_trace_handler(Path(id): Path<u64>) -> Json<serde_json::Value> {
+    match llm_trace::get_llm_trace(id) {
+        Some(trace) => Json(json!({ "status": "success", "trace": trace })),
+        None => {
+            Json(json!({ "status": "error", "message": format!("追踪记录 {} 不存在或已过期", id) }))
+        }
+    }
+}
+
+pub async fn clear_llm_traces_handler() -> Json<serde_json::Value> {
+    match llm_trace::clear_llm_traces() {
+        Ok(removed) => Json(json!({ "status": "success", "removed": removed })),
+        Err(e) => Json(json!({ "status": "error", "message": e })),
+    }
+}
@@ -0,0 +1,24 @@
+use super::super::media_cache;
+use axum::extract::{Json, Query};
+use serde_json::json;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct MediaCachePurgeQuery {
+    /// 为空清除全部；`source` 清除原始文件（连同派生产物）；`image` / `video_frames` / `transcript` 只清除对应派生产物
+    #[serde(default)]
+    pub kind: Option<String>,
+}
+
+/// 媒体缓存统计（容量、命中率、按类型分布）
+pub async fn get_media_cache_stats_handler() -> Json<serde_json::Value> {
+    Json(json!({ "status": "success", "stats": media_cache::media_cache_stats() }))
+}
+
+pub async fn purge_media_cache_handler(
+    Query(query): Query<MediaCachePurgeQuery>,
+) -> Json<serde_json::Value> {
+    match media_cache::purge_media_cache(query.kind.as_deref()) {
+        Ok(removed) => Json(json!({ "status": "success", "removed": removed })),
+        Err(e) => Json(json!({ "status": "error", "message": e })),
+    }
+}
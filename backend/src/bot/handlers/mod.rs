@@ -1,15 +1,23 @@
 mod bots;
 mod chat;
 mod contacts;
+mod knowledge;
+mod llm_trace;
 mod logs;
+mod media_cache;
 mod modules;
 mod napcat;
+mod privacy;
 mod stats;
 
 pub use bots::*;
 pub use chat::*;
 pub use contacts::*;
+pub use knowledge::*;
+pub use llm_trace::*;
 pub use logs::*;
+pub use media_cache::*;
 pub use modules::*;
 pub use napcat::*;
+pub use privacy::*;
 pub use stats::*;
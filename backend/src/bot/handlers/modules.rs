@@ -36,6 +36,7 @@ pub async fn update_bot_module_handler(
         }
         drop(bot);
         save_bots(&state.bots);
+        crate::bot::invalidate_module_caches(&payload.module_id);
         Json(serde_json::json!({ "status": "success" }))
     } else {
         Json(serde_json::json!({ "status": "error", "message": "Bot not found" }))
@@ -82,6 +83,7 @@ pub async fn delete_bot_module_override_handler(
         bot.modules_config.remove(&module_id);
         drop(bot);
         save_bots(&state.bots);
+        crate::bot::invalidate_module_caches(&module_id);
         Json(json!({ "status": "success" }))
     } else {
         Json(json!({ "status": "error", "message": "Bot not found" }))
@@ -0,0 +1,67 @@
+use super::super::privacy_policy::{self, PrivacyPolicy, Scope};
+use crate::models::SharedState;
+use axum::extract::{Json, State};
+use serde_json::json;
+
+#[derive(serde::Deserialize)]
+pub struct PrivacyPreviewPayload {
+    pub text: String,
+    /// 使用该 bot 的有效策略（为空则使用全局配置）
+    #[serde(default)]
+    pub bot_id: Option<String>,
+    /// 未保存的策略配置（结构同 `privacy` 模块配置），用于编辑时预览
+    #[serde(default)]
+    pub policy: Option<serde_json::Value>,
+    /// 只预览指定位置（为空则预览全部）
+    #[serde(default)]
+    pub scope: Option<Scope>,
+    /// 模拟当前事件中出现的成员 QQ 号
+    #[serde(default)]
+    pub sensitive_ids: Vec<String>,
+}
+
+/// 试运行隐私策略：返回各位置脱敏后的文本与匹配明细（不发送、不记录）。
+/// 发出消息中 QQ 号的昵称替换需要实时查询，这里以掩码代替。
+pub async fn privacy_preview_handler(
+    State(state): State<SharedState>,
+    Json(payload): Json<PrivacyPreviewPayload>,
+) -> Json<serde_json::Value> {
+    let policy = match (&payload.policy, payload.bot_id.as_deref()) {
+        (Some(config), _) => match PrivacyPolicy::from_config(config) {
+            Ok(p) => std::sync::Arc::new(p),
+            Err(e) => return Json(json!({ "status": "error", "message": e })),
+        },
+        (None, Some(bot_id)) if !bot_id.trim().is_empty() => {
+            privacy_policy::policy_for_bot(&state, bot_id.trim())
+        }
+        (None, _) => {
+            let config = state
+                .modules
+                .get("privacy")
+                .filter(|m| m.enabled)
+                .map(|m| m.config)
+                .unwrap_or(serde_json::Value::Null);
+            match PrivacyPolicy::from_config(&config) {
+                Ok(p) => std::sync::Arc::new(p),
+                Err(e) => return Json(json!({ "status": "error", "message": e })),
+            }
+        }
+    };
+
+    let scopes: Vec<Scope> = match payload.scope {
+        Some(scope) => vec![scope],
+        None => Scope::ALL.to_vec(),
+    };
+    let mut results = serde_json::Map::new();
+    for scope in scopes {
+        let matches = policy.find_matches(&payload.text, scope, &payload.sensitive_ids, None);
+        let text = policy.redact(&payload.text, scope, &payload.sensitive_ids, None);
+        let key = serde_json::to_value(scope)
+            .ok()
+            .and_then(|v| v.as_str().map(|s| s.to_string()))
+            .unwrap_or_default();
+        results.insert(key, json!({ "text": text, "matches": matches }));
+    }
+
+    Json(json!({ "status": "success", "results": results }))
+}
@@ -1,5 +1,6 @@
 use crate::models::SharedState;
 use axum::extract::{Json, State};
+use axum::Extension;
 use std::sync::atomic::Ordering;
 use std::time::SystemTime;
 use sysinfo::System;
@@ -67,6 +68,27 @@ pub async fn get_message_stats_handler(
     })
 }
 
+/// 入站事件调度：各 bot 的会话数、排队深度、处理中的事件数与接收 / 处理 / 丢弃计数
+pub async fn get_inbound_stats_handler(
+    Extension(runtime): Extension<std::sync::Arc<crate::bot::BotRuntime>>,
+) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "success", "stats": runtime.inbound_stats() }))
+}
+
+/// 出站发送队列：各 bot 的排队深度与发送 / 重试 / 失败 / 丢弃计数
+pub async fn get_outbound_stats_handler(
+    Extension(runtime): Extension<std::sync::Arc<crate::bot::BotRuntime>>,
+) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "success", "stats": runtime.outbound_stats() }))
+}
+
+/// OneBot API 调用统计：各 action 的调用次数、失败 / 超时次数与耗时
+pub async fn get_rpc_stats_handler(
+    Extension(runtime): Extension<std::sync::Arc<crate::bot::BotRuntime>>,
+) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "success", "stats": runtime.rpc_stats() }))
+}
+
 // System Info endpoint
 #[derive(serde::Serialize)]
 pub struct SystemInfo {
@@ -9,11 +9,19 @@ use std::sync::Arc;
 use tokio::time::{sleep, Duration};
 use tracing::{info, warn};
 
+use crate::qq_face;
+use crate::segment::{self, MessageSegment};
+
 use super::connection::{BotConnection, BotRuntime, DiscordConnection, GroupSendStatus};
-use super::privacy;
+use super::long_message::{self, Delivery};
+use super::msg_cache;
+use super::outbound::{self, OutboundQueue};
+use super::pre_send;
+use super::rpc::{check_response, send_ack, Ack, RpcError, DEFAULT_TIMEOUT};
+use super::privacy::{self, Action, Scope};
 
 const DISCORD_API_BASE: &str = "https://discord.com/api/v10";
-const DISCORD_MAX_CONTENT_CHARS: usize = 2000;
+pub(super) const DISCORD_MAX_CONTENT_CHARS: usize = 2000;
 const DISCORD_MAX_ATTACHMENTS: usize = 10;
 
 async fn resolve_group_member_name(
@@ -200,38 +208,46 @@ async fn sanitize_outgoing_text(
         out
     }
 
-    fn protect_cq_segments(message: &str) -> (String, Vec<(String, String)>) {
-        let mut out = String::with_capacity(message.len());
+    /// 按 CQ 码切分为 `(是否为 CQ 码, 片段)`：CQ 码原样保留，只对其间的文本做隐私处理
+    fn split_cq_segments(message: &str) -> Vec<(bool, &str)> {
+        let mut parts = Vec::new();
         let mut rest = message;
-        let mut replacements: Vec<(String, String)> = Vec::new();
-        let mut idx = 0usize;
 
         while let Some(start) = rest.find("[CQ:") {
-            out.push_str(&rest[..start]);
-            rest = &rest[start..];
-
-            let Some(end_rel) = rest.find(']') else {
-                out.push_str(rest);
-                return (out, replacements);
+            let Some(end_rel) = rest[start..].find(']') else {
+                break;
             };
-
-            let seg = &rest[..=end_rel];
-            rest = &rest[(end_rel + 1)..];
-
-            let placeholder = format!("__NBOT_CQ_SEG_{}__", idx);
-            idx += 1;
-            out.push_str(&placeholder);
-            replacements.push((placeholder, seg.to_string()));
+            if start > 0 {
+                parts.push((false, &rest[..start]));
+            }
+            let end = start + end_rel + 1;
+            parts.push((true, &rest[start..end]));
+            rest = &rest[end..];
         }
 
-        out.push_str(rest);
-        (out, replacements)
+        if !rest.is_empty() {
+            parts.push((false, rest));
+        }
+        parts
     }
 
-    let (protected, replacements) = protect_cq_segments(message);
-    let mut out = redact_sensitive_ids_plaintext(runtime, bot_id, group_id, &protected).await;
-    for (placeholder, original) in replacements {
-        out = out.replace(&placeholder, &original);
+    let policy = privacy::policy::cached_policy(bot_id);
+    let ids = privacy::get_sensitive_ids();
+    // QQ 号的 mask 动作在发出的消息中为替换成群名片/昵称，其余检测器按策略处理
+    let resolve_qq = matches!(policy.action_for("qq_id", Scope::Outgoing), Action::Mask);
+    let mut out = String::with_capacity(message.len());
+    for (is_cq, part) in split_cq_segments(message) {
+        if is_cq {
+            out.push_str(part);
+            continue;
+        }
+        let redacted = if resolve_qq {
+            let resolved = redact_sensitive_ids_plaintext(runtime, bot_id, group_id, part).await;
+            policy.redact(&resolved, Scope::Outgoing, &ids, Some("qq_id"))
+        } else {
+            policy.redact(part, Scope::Outgoing, &ids, None)
+        };
+        out.push_str(&redacted);
     }
     strip_transport_controls(&out)
 }
@@ -286,9 +302,60 @@ pub async fn send_reply(
     group_id: Option<u64>,
     message: &str,
 ) {
-    if let Some(gid) = group_id {
-        let message = sanitize_outgoing_text(runtime, bot_id, Some(gid), message).await;
+    let message = sanitize_outgoing_text(runtime, bot_id, group_id, message).await;
+    deliver_reply(
+        runtime,
+        bot_id,
+        user_id,
+        group_id,
+        &message,
+        Value::String(message.clone()),
+    )
+    .await;
+}
 
+/// 以消息段数组发送回复；文本段同样经过隐私处理
+pub async fn send_reply_segments(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    user_id: u64,
+    group_id: Option<u64>,
+    segments: &[MessageSegment],
+) {
+    let mut sanitized = Vec::with_capacity(segments.len());
+    for seg in segments {
+        match seg {
+            MessageSegment::Text { text } => {
+                let text = sanitize_outgoing_text(runtime, bot_id, group_id, text).await;
+                sanitized.push(MessageSegment::text(text));
+            }
+            other => sanitized.push(other.clone()),
+        }
+    }
+    if sanitized.is_empty() {
+        return;
+    }
+    let dedup_key = segment::to_cq_string(&sanitized);
+    deliver_reply(
+        runtime,
+        bot_id,
+        user_id,
+        group_id,
+        &dedup_key,
+        segment::to_onebot(&sanitized),
+    )
+    .await;
+}
+
+async fn deliver_reply(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    user_id: u64,
+    group_id: Option<u64>,
+    dedup_key: &str,
+    message: Value,
+) {
+    if let Some(gid) = group_id {
         match runtime.get_group_send_status(bot_id, gid).await {
             GroupSendStatus::Muted => {
                 warn!("[{}] 群 {} 内机器人被禁言，跳过发送群消息", bot_id, gid);
@@ -296,48 +363,118 @@ pub async fn send_reply(
             }
             GroupSendStatus::Unknown | GroupSendStatus::Allowed => {}
         }
+    }
 
-        // 去重检查：相同消息在5秒内不重复发送（仅在允许发送时才记录）
-        let hash = compute_message_hash(bot_id, gid, &message);
-        if runtime.message_dedup.lock().await.is_duplicate(hash) {
-            warn!("[{}] 消息去重: 跳过重复消息发送", bot_id);
-            return;
-        }
+    let action = if group_id.is_some() { "send_group_msg" } else { "send_private_msg" };
+    let Some(message) =
+        pre_send::run(runtime, bot_id, action, Some(user_id), group_id, message).await
+    else {
+        return;
+    };
 
-        send_api(
-            runtime,
-            bot_id,
-            "send_group_msg",
-            json!({
-                "group_id": gid,
-                "message": message
-            }),
-        )
-        .await;
-    } else {
-        let message = sanitize_outgoing_text(runtime, bot_id, None, message).await;
+    // 去重检查：相同消息在5秒内不重复发送（仅在允许发送时才记录）
+    let hash = compute_message_hash(bot_id, group_id.unwrap_or(user_id), dedup_key);
+    if runtime.message_dedup.lock().await.is_duplicate(hash) {
+        warn!("[{}] 消息去重: 跳过重复消息发送", bot_id);
+        return;
+    }
 
-        // 去重检查：相同消息在5秒内不重复发送（仅在允许发送时才记录）
-        let hash = compute_message_hash(bot_id, user_id, &message);
-        if runtime.message_dedup.lock().await.is_duplicate(hash) {
-            warn!("[{}] 消息去重: 跳过重复消息发送", bot_id);
-            return;
+    // 超长文本按该 bot / 群的策略拆分、转为合并转发或渲染为图片
+    let (forward_action, target) = match group_id {
+        Some(gid) => ("send_group_forward_msg", json!({ "group_id": gid })),
+        None => ("send_private_forward_msg", json!({ "user_id": user_id })),
+    };
+    let delivery = long_message::prepare(runtime, bot_id, group_id, message).await;
+    pre_send::skip_hooks(async {
+        match delivery {
+            Delivery::Messages(messages) => {
+                for message in messages {
+                    let mut params = target.clone();
+                    params["message"] = message;
+                    send_api(runtime, bot_id, action, params).await;
+                }
+            }
+            Delivery::Forward(nodes) => {
+                let mut params = target;
+                params["messages"] = Value::Array(nodes);
+                send_api(runtime, bot_id, forward_action, params).await;
+            }
         }
+    })
+    .await;
+}
 
-        send_api(
-            runtime,
-            bot_id,
-            "send_private_msg",
-            json!({
-                "user_id": user_id,
-                "message": message
-            }),
-        )
-        .await;
+pub async fn send_api(runtime: &Arc<BotRuntime>, bot_id: &str, action: &str, params: Value) {
+    dispatch_api(runtime, bot_id, action, params, None).await;
+}
+
+/// 发送并等待平台回执，可据此判断消息是否真正送达。
+/// `timeout` 包含在出站队列中排队的时间。
+pub async fn send_api_confirmed(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    action: &str,
+    params: Value,
+    timeout: Duration,
+) -> Result<Value, RpcError> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    dispatch_api(runtime, bot_id, action, params, Some(tx)).await;
+    match tokio::time::timeout(timeout, rx).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(_)) => Err(RpcError::Rejected("消息未发出".to_string())),
+        Err(_) => Err(RpcError::Timeout),
     }
 }
 
-pub async fn send_api(runtime: &Arc<BotRuntime>, bot_id: &str, action: &str, params: Value) {
+/// 对发送消息类 API 调用插件 preSend，返回 false 表示被拦截。
+/// 合并转发逐个节点调用，任一节点被拦截则整条转发都不发送
+async fn run_pre_send(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    action: &str,
+    group_id: Option<u64>,
+    params: &mut Value,
+) -> bool {
+    let user_id = parse_u64(params.get("user_id"));
+    match action {
+        "send_group_msg" | "send_private_msg" | "send_msg" => {
+            let Some(message) = params.get("message").cloned() else {
+                return true;
+            };
+            match pre_send::run(runtime, bot_id, action, user_id, group_id, message).await {
+                Some(message) => {
+                    params["message"] = message;
+                    true
+                }
+                None => false,
+            }
+        }
+        "send_forward_msg" | "send_group_forward_msg" | "send_private_forward_msg" => {
+            let Some(Value::Array(nodes)) = params.get_mut("messages") else {
+                return true;
+            };
+            for node in nodes.iter_mut() {
+                let Some(content) = node.get("data").and_then(|d| d.get("content")).cloned() else {
+                    continue;
+                };
+                match pre_send::run(runtime, bot_id, action, user_id, group_id, content).await {
+                    Some(content) => node["data"]["content"] = content,
+                    None => return false,
+                }
+            }
+            true
+        }
+        _ => true,
+    }
+}
+
+async fn dispatch_api(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    action: &str,
+    params: Value,
+    ack: Option<Ack>,
+) {
     let mut params = params;
 
     let group_id = extract_group_id_for_send_action(action, &params);
@@ -348,12 +485,19 @@ pub async fn send_api(runtime: &Arc<BotRuntime>, bot_id: &str, action: &str, par
                     "[{}] 群 {} 内机器人被禁言，跳过发送 {}",
                     bot_id, gid, action
                 );
+                send_ack(ack, Err(RpcError::Rejected("机器人在该群被禁言".to_string())));
                 return;
             }
             GroupSendStatus::Unknown | GroupSendStatus::Allowed => {}
         }
     }
 
+    // 插件 preSend 钩子：可改写或拦截消息
+    if !run_pre_send(runtime, bot_id, action, group_id, &mut params).await {
+        send_ack(ack, Err(RpcError::Rejected("消息被插件拦截".to_string())));
+        return;
+    }
+
     // Privacy: sanitize outgoing messages (CQ @ -> nickname; redact sensitive numeric IDs in plain text).
     match action {
         "send_group_msg" | "send_private_msg" | "send_msg" | "send_forward_msg" => {
@@ -392,36 +536,59 @@ pub async fn send_api(runtime: &Arc<BotRuntime>, bot_id: &str, action: &str, par
         _ => {}
     }
 
+    let ack = msg_cache::track_outgoing(runtime, bot_id, action, &params, group_id, ack);
+
+    // 发送消息类 API 经出站队列限速后发出
+    if runtime.outbound.enabled() && outbound::is_queued_action(action) {
+        OutboundQueue::enqueue(runtime, bot_id, action, params, group_id, ack);
+        return;
+    }
+
     let conns = runtime.connections.read().await;
     let Some(conn) = conns.get(bot_id).cloned() else {
         warn!("[{}] 无法发送API调用，连接不存在", bot_id);
+        send_ack(ack, Err(RpcError::NotConnected));
         return;
     };
     drop(conns);
 
     match conn {
-        BotConnection::OneBot { sender: tx } => {
-            let now_ms = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .map(|d| d.as_millis())
-                .unwrap_or(0);
-            let msg = json!({
-                "action": action,
-                "params": params,
-                "echo": format!("{}_{}", action, now_ms)
+        BotConnection::OneBot { .. } => {
+            let call = match runtime.send_onebot_request(bot_id, action, params).await {
+                Ok(call) => call,
+                Err(e) => {
+                    warn!("[{}] 发送API调用失败: {}: {}", bot_id, action, e);
+                    send_ack(ack, Err(e));
+                    return;
+                }
+            };
+            info!("[{}] 发送API: {}", bot_id, action);
+            // 后台等待回执：记录耗时与失败原因
+            let bot_id = bot_id.to_string();
+            let action = action.to_string();
+            tokio::spawn(async move {
+                let result = call.wait(DEFAULT_TIMEOUT).await.and_then(check_response);
+                if let Err(e) = &result {
+                    warn!("[{}] API {} 失败: {}", bot_id, action, e);
+                }
+                send_ack(ack, result);
             });
-            if tx.send(msg.to_string()).is_err() {
-                warn!("[{}] 发送API调用失败: {}", bot_id, action);
-            } else {
-                info!("[{}] 发送API: {}", bot_id, action);
-            }
         }
         BotConnection::Discord(conn) => {
-            if let Err(e) = discord_send_api(runtime, bot_id, &conn, action, &params).await {
-                warn!("[{}] Discord API {} 失败: {}", bot_id, action, e);
-            } else {
-                info!("[{}] Discord API: {}", bot_id, action);
+            let result = discord_send_api(runtime, bot_id, &conn, action, &params).await;
+            match &result {
+                Ok(()) => info!("[{}] Discord API: {}", bot_id, action),
+                Err(e) => warn!("[{}] Discord API {} 失败: {}", bot_id, action, e),
             }
+            send_ack(
+                ack,
+                result
+                    .map(|()| json!({ "status": "ok", "retcode": 0 }))
+                    .map_err(|message| RpcError::Failed {
+                        retcode: None,
+                        message,
+                    }),
+            );
         }
     }
 }
@@ -468,88 +635,162 @@ fn guess_image_ext(data: &[u8]) -> &'static str {
     }
 }
 
-fn extract_base64_cq_images(message: &str) -> (String, Vec<DiscordUploadFile>) {
-    // Minimal CQ parser: extract all `[CQ:image,file=base64://...]` images and strip them from content.
-    let mut content = message.to_string();
-    let mut files: Vec<DiscordUploadFile> = Vec::new();
-    let mut seen: HashSet<String> = HashSet::new();
+/// 渲染为 Discord 消息的内容
+#[derive(Debug, Default)]
+struct DiscordMessage {
+    content: String,
+    files: Vec<DiscordUploadFile>,
+    /// 回复的消息 ID（`message_reference`）
+    reply_to: Option<u64>,
+    /// 允许提醒的用户（`at` 消息段）
+    mention_users: Vec<u64>,
+    /// 是否允许 `@everyone` / `@here` 生效
+    mention_everyone: bool,
+}
 
-    while let Some(start) = content.find("[CQ:image") {
-        let end = match content[start..].find(']') {
-            Some(i) => start + i,
-            None => break,
+/// Discord 单条消息 `allowed_mentions.users` 的上限
+const DISCORD_MAX_ALLOWED_USERS: usize = 100;
+
+impl DiscordMessage {
+    /// 只提醒消息段中明确 @ 的用户；正文中的 `@everyone` 等默认不生效
+    fn allowed_mentions(&self) -> Value {
+        let parse: &[&str] = if self.mention_everyone {
+            &["everyone"]
+        } else {
+            &[]
         };
+        json!({
+            "parse": parse,
+            "users": self
+                .mention_users
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>(),
+            "replied_user": true,
+        })
+    }
+}
+
+/// 按消息段渲染 Discord 消息：@ 转为提及，base64 媒体转为附件，链接媒体附在正文，回复转为消息引用。
+/// `message` 可为 CQ 码字符串或 OneBot 消息段数组；`allow_everyone` 为假时 `@全体成员` 不会真正提醒。
+fn render_discord_message(message: Option<&Value>, allow_everyone: bool) -> DiscordMessage {
+    let segments = message.map(segment::parse_message).unwrap_or_default();
+    let mut out = DiscordMessage {
+        mention_everyone: allow_everyone,
+        ..Default::default()
+    };
+    let mut seen: HashSet<String> = HashSet::new();
 
-        let seg = content[start..=end].to_string();
-        let file_pos = match seg.find("file=") {
-            Some(p) => p + "file=".len(),
-            None => {
-                content.replace_range(start..=end, "");
+    for seg in segments {
+        let (file, name) = match seg {
+            MessageSegment::Text { text } => {
+                out.content.push_str(&text);
                 continue;
             }
-        };
-        let after = &seg[file_pos..seg.len() - 1];
-        let raw_file = after.split(',').next().unwrap_or(after).trim();
-        let b64 = raw_file
-            .strip_prefix("base64://")
-            .or_else(|| raw_file.strip_prefix("base64:"));
-
-        if let Some(b64) = b64 {
-            let b64 = b64.trim();
-            if !b64.is_empty() && seen.insert(b64.to_string()) {
-                if let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(b64) {
-                    let ext = guess_image_ext(&bytes);
-                    let filename = format!("image_{}.{}", files.len() + 1, ext);
-                    files.push(DiscordUploadFile { filename, bytes });
+            MessageSegment::At { qq, .. } => {
+                if qq == "all" {
+                    out.content.push_str("@everyone");
+                    continue;
+                }
+                if let Ok(id) = qq.trim().parse::<u64>() {
+                    if !out.mention_users.contains(&id)
+                        && out.mention_users.len() < DISCORD_MAX_ALLOWED_USERS
+                    {
+                        out.mention_users.push(id);
+                    }
                 }
+                out.content.push_str(&format!("<@{qq}>"));
+                continue;
             }
-        }
+            MessageSegment::Reply { id, .. } => {
+                out.reply_to = id.trim().parse().ok();
+                continue;
+            }
+            MessageSegment::Face { id, .. } => {
+                let name = qq_face::name_for_id(&id).unwrap_or("表情");
+                out.content.push_str(&format!("[{name}]"));
+                continue;
+            }
+            MessageSegment::Forward { .. } => {
+                out.content.push_str("[合并转发]");
+                continue;
+            }
+            MessageSegment::Other { .. } => continue,
+            MessageSegment::Image { file, .. } => (file, None),
+            MessageSegment::Record { file, .. } => (file, Some("audio")),
+            MessageSegment::Video { file, .. } => (file, Some("video")),
+            MessageSegment::File { file, name, .. } => {
+                let name = name.filter(|n| !n.trim().is_empty());
+                match decode_base64_media(&file) {
+                    Some(bytes) if seen.insert(file.clone()) => {
+                        let filename = name.unwrap_or_else(|| "file.bin".to_string());
+                        out.files.push(DiscordUploadFile { filename, bytes });
+                    }
+                    Some(_) => {}
+                    None => push_media_link(&mut out.content, &file),
+                }
+                continue;
+            }
+        };
 
-        content.replace_range(start..=end, "");
+        match decode_base64_media(&file) {
+            Some(bytes) if seen.insert(file.clone()) => {
+                let n = out.files.len() + 1;
+                let filename = match name {
+                    Some("audio") => format!("audio_{n}.mp3"),
+                    Some(prefix) => format!("{prefix}_{n}.mp4"),
+                    None => format!("image_{}.{}", n, guess_image_ext(&bytes)),
+                };
+                out.files.push(DiscordUploadFile { filename, bytes });
+            }
+            Some(_) => {}
+            None => push_media_link(&mut out.content, &file),
+        }
     }
 
-    (content.trim().to_string(), files)
+    out.content = out.content.trim().to_string();
+    out
 }
 
-fn split_discord_content(content: &str) -> Vec<String> {
-    let mut out = Vec::new();
-    let mut cur = String::new();
-
-    for part in content.split_inclusive('\n') {
-        let part_len = part.chars().count();
-        let cur_len = cur.chars().count();
-        if cur_len + part_len <= DISCORD_MAX_CONTENT_CHARS {
-            cur.push_str(part);
-            continue;
-        }
-
-        if !cur.trim().is_empty() {
-            out.push(cur.trim_end_matches('\n').to_string());
-            cur.clear();
-        }
-
-        if part_len <= DISCORD_MAX_CONTENT_CHARS {
-            cur.push_str(part);
-            continue;
-        }
-
-        // Very long single line: hard split by char count.
-        let mut buf = String::new();
-        for ch in part.chars() {
-            buf.push(ch);
-            if buf.chars().count() >= DISCORD_MAX_CONTENT_CHARS {
-                out.push(buf);
-                buf = String::new();
-            }
-        }
-        cur = buf;
+fn decode_base64_media(file: &str) -> Option<Vec<u8>> {
+    let b64 = file.strip_prefix("base64://")?.trim();
+    if b64.is_empty() {
+        return None;
     }
+    base64::engine::general_purpose::STANDARD.decode(b64).ok()
+}
 
-    if !cur.trim().is_empty() {
-        out.push(cur.trim_end_matches('\n').to_string());
+/// 链接形式的媒体附在正文（Discord 会自动预览）；本地路径无法发送，忽略
+fn push_media_link(content: &mut String, file: &str) {
+    if !(file.starts_with("http://") || file.starts_with("https://")) {
+        return;
+    }
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
     }
+    content.push_str(file);
+}
 
-    out
+/// OneBot 文件上传参数（仅支持 `base64://`）转为 Discord 附件
+fn decode_base64_upload_file(params: &Value) -> Result<DiscordUploadFile, String> {
+    let file = params
+        .get("file")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "missing file".to_string())?;
+    let b64 = file
+        .strip_prefix("base64://")
+        .ok_or_else(|| "only base64:// files are supported".to_string())?;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(b64.trim())
+        .map_err(|e| format!("invalid base64 file: {e}"))?;
+    let filename = params
+        .get("name")
+        .and_then(|v| v.as_str())
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .unwrap_or("file.bin")
+        .to_string();
+    Ok(DiscordUploadFile { filename, bytes })
 }
 
 fn discord_auth_header(token: &str) -> String {
@@ -676,15 +917,32 @@ async fn discord_send_channel_message(
     bot_id: &str,
     conn: &DiscordConnection,
     channel_id: u64,
-    content: &str,
-    files: Vec<DiscordUploadFile>,
+    message: DiscordMessage,
 ) -> Result<(), String> {
+    let allowed_mentions = message.allowed_mentions();
+    let DiscordMessage {
+        content,
+        files,
+        reply_to,
+        ..
+    } = message;
     if content.trim().is_empty() && files.is_empty() {
         return Ok(());
     }
 
     let url = format!("{}/channels/{}/messages", DISCORD_API_BASE, channel_id);
-    let mut chunks = split_discord_content(content);
+    let payload_for =
+        |chunk: &str| json!({ "content": chunk, "allowed_mentions": allowed_mentions });
+    // 回复引用只加在第一条上
+    let first_payload = |chunk: &str| {
+        let mut payload = payload_for(chunk);
+        if let Some(id) = reply_to {
+            payload["message_reference"] =
+                json!({ "message_id": id.to_string(), "fail_if_not_exists": false });
+        }
+        payload
+    };
+    let mut chunks = long_message::split_text(&content, DISCORD_MAX_CONTENT_CHARS);
     if chunks.is_empty() {
         chunks.push(String::new());
     }
@@ -698,7 +956,7 @@ async fn discord_send_channel_message(
 
             if remaining_files.is_empty() {
                 if !chunk.trim().is_empty() {
-                    let payload = json!({ "content": chunk });
+                    let payload = first_payload(&chunk);
                     let (status, body) =
                         discord_post_json_with_retry(&conn.http, &conn.token, &url, &payload)
                             .await?;
@@ -717,7 +975,7 @@ async fn discord_send_channel_message(
             let take = cmp::min(DISCORD_MAX_ATTACHMENTS, remaining_files.len());
             let batch: Vec<DiscordUploadFile> = remaining_files.drain(0..take).collect();
 
-            let payload = json!({ "content": chunk });
+            let payload = first_payload(&chunk);
             let payload_json = payload.to_string();
             let (status, body) = discord_post_multipart_with_retry(
                 &conn.http,
@@ -740,7 +998,7 @@ async fn discord_send_channel_message(
                 let take = cmp::min(DISCORD_MAX_ATTACHMENTS, remaining_files.len());
                 let batch: Vec<DiscordUploadFile> = remaining_files.drain(0..take).collect();
 
-                let payload = json!({ "content": "" });
+                let payload = payload_for("");
                 let payload_json = payload.to_string();
                 let (status, body) = discord_post_multipart_with_retry(
                     &conn.http,
@@ -764,7 +1022,7 @@ async fn discord_send_channel_message(
         }
 
         if !chunk.trim().is_empty() {
-            let payload = json!({ "content": chunk });
+            let payload = payload_for(&chunk);
             let (status, body) =
                 discord_post_json_with_retry(&conn.http, &conn.token, &url, &payload).await?;
             if !status.is_success() {
@@ -781,39 +1039,32 @@ async fn discord_send_channel_message(
     Ok(())
 }
 
-async fn discord_send_api(
+pub(super) async fn discord_send_api(
     runtime: &Arc<BotRuntime>,
     bot_id: &str,
     conn: &DiscordConnection,
     action: &str,
     params: &Value,
 ) -> Result<(), String> {
+    // `@everyone` 需调用方显式允许，避免转发的用户内容误提醒全体
+    let allow_everyone = params
+        .get("allow_mention_everyone")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
     match action {
         "send_group_msg" => {
             let channel_id =
                 parse_u64(params.get("group_id")).ok_or_else(|| "missing group_id".to_string())?;
-            let message = params
-                .get("message")
-                .and_then(|v| v.as_str())
-                .unwrap_or_default();
-
-            let (content, files) = extract_base64_cq_images(message);
-            discord_send_channel_message(runtime, bot_id, conn, channel_id, &content, files)
-                .await?;
+            let message = render_discord_message(params.get("message"), allow_everyone);
+            discord_send_channel_message(runtime, bot_id, conn, channel_id, message).await?;
             Ok(())
         }
         "send_private_msg" => {
             let user_id =
                 parse_u64(params.get("user_id")).ok_or_else(|| "missing user_id".to_string())?;
-            let message = params
-                .get("message")
-                .and_then(|v| v.as_str())
-                .unwrap_or_default();
-
             let dm_channel_id = discord_create_dm_channel(conn, user_id).await?;
-            let (content, files) = extract_base64_cq_images(message);
-            discord_send_channel_message(runtime, bot_id, conn, dm_channel_id, &content, files)
-                .await?;
+            let message = render_discord_message(params.get("message"), allow_everyone);
+            discord_send_channel_message(runtime, bot_id, conn, dm_channel_id, message).await?;
             Ok(())
         }
         "send_group_forward_msg" => {
@@ -825,14 +1076,11 @@ async fn discord_send_api(
                 .ok_or_else(|| "missing messages".to_string())?;
 
             for node in msgs {
-                let content = node
-                    .get("data")
-                    .and_then(|d| d.get("content"))
-                    .and_then(|v| v.as_str())
-                    .unwrap_or_default();
-                let (content, files) = extract_base64_cq_images(content);
-                discord_send_channel_message(runtime, bot_id, conn, channel_id, &content, files)
-                    .await?;
+                let message = render_discord_message(
+                    node.get("data").and_then(|d| d.get("content")),
+                    allow_everyone,
+                );
+                discord_send_channel_message(runtime, bot_id, conn, channel_id, message).await?;
             }
             Ok(())
         }
@@ -846,14 +1094,11 @@ async fn discord_send_api(
 
             let dm_channel_id = discord_create_dm_channel(conn, user_id).await?;
             for node in msgs {
-                let content = node
-                    .get("data")
-                    .and_then(|d| d.get("content"))
-                    .and_then(|v| v.as_str())
-                    .unwrap_or_default();
-                let (content, files) = extract_base64_cq_images(content);
-                discord_send_channel_message(runtime, bot_id, conn, dm_channel_id, &content, files)
-                    .await?;
+                let message = render_discord_message(
+                    node.get("data").and_then(|d| d.get("content")),
+                    allow_everyone,
+                );
+                discord_send_channel_message(runtime, bot_id, conn, dm_channel_id, message).await?;
             }
             Ok(())
         }
@@ -863,23 +1108,16 @@ async fn discord_send_api(
                 .and_then(|v| v.as_str())
                 .unwrap_or("private");
 
-            let message = params
-                .get("message")
-                .and_then(|v| v.as_str())
-                .unwrap_or_default();
-
+            let message = render_discord_message(params.get("message"), allow_everyone);
             if ty == "group" {
                 let channel_id = parse_u64(params.get("group_id"))
                     .ok_or_else(|| "missing group_id".to_string())?;
-                let (content, files) = extract_base64_cq_images(message);
-                discord_send_channel_message(runtime, bot_id, conn, channel_id, &content, files)
-                    .await?;
+                discord_send_channel_message(runtime, bot_id, conn, channel_id, message).await?;
             } else {
                 let user_id = parse_u64(params.get("user_id"))
                     .ok_or_else(|| "missing user_id".to_string())?;
                 let dm_channel_id = discord_create_dm_channel(conn, user_id).await?;
-                let (content, files) = extract_base64_cq_images(message);
-                discord_send_channel_message(runtime, bot_id, conn, dm_channel_id, &content, files)
+                discord_send_channel_message(runtime, bot_id, conn, dm_channel_id, message)
                     .await?;
             }
             Ok(())
@@ -892,16 +1130,12 @@ async fn discord_send_api(
 
             if let Some(channel_id) = parse_u64(params.get("group_id")) {
                 for node in msgs {
-                    let content = node
-                        .get("data")
-                        .and_then(|d| d.get("content"))
-                        .and_then(|v| v.as_str())
-                        .unwrap_or_default();
-                    let (content, files) = extract_base64_cq_images(content);
-                    discord_send_channel_message(
-                        runtime, bot_id, conn, channel_id, &content, files,
-                    )
-                    .await?;
+                    let message = render_discord_message(
+                        node.get("data").and_then(|d| d.get("content")),
+                        allow_everyone,
+                    );
+                    discord_send_channel_message(runtime, bot_id, conn, channel_id, message)
+                        .await?;
                 }
                 return Ok(());
             }
@@ -909,27 +1143,33 @@ async fn discord_send_api(
             if let Some(user_id) = parse_u64(params.get("user_id")) {
                 let dm_channel_id = discord_create_dm_channel(conn, user_id).await?;
                 for node in msgs {
-                    let content = node
-                        .get("data")
-                        .and_then(|d| d.get("content"))
-                        .and_then(|v| v.as_str())
-                        .unwrap_or_default();
-                    let (content, files) = extract_base64_cq_images(content);
-                    discord_send_channel_message(
-                        runtime,
-                        bot_id,
-                        conn,
-                        dm_channel_id,
-                        &content,
-                        files,
-                    )
-                    .await?;
+                    let message = render_discord_message(
+                        node.get("data").and_then(|d| d.get("content")),
+                        allow_everyone,
+                    );
+                    discord_send_channel_message(runtime, bot_id, conn, dm_channel_id, message)
+                        .await?;
                 }
                 return Ok(());
             }
 
             Err("missing group_id/user_id".to_string())
         }
+        "upload_group_file" | "upload_private_file" => {
+            let file = decode_base64_upload_file(params)?;
+            let channel_id = if action == "upload_group_file" {
+                parse_u64(params.get("group_id")).ok_or_else(|| "missing group_id".to_string())?
+            } else {
+                let user_id = parse_u64(params.get("user_id"))
+                    .ok_or_else(|| "missing user_id".to_string())?;
+                discord_create_dm_channel(conn, user_id).await?
+            };
+            let message = DiscordMessage {
+                files: vec![file],
+                ..Default::default()
+            };
+            discord_send_channel_message(runtime, bot_id, conn, channel_id, message).await
+        }
         _ => Err(format!("unsupported action: {}", action)),
     }
 }
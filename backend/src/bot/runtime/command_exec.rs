@@ -1,5 +1,6 @@
 use crate::command::{Command, CommandAction};
 use crate::models::SharedState;
+use crate::segment::MessageSegment;
 use serde_json::json;
 use std::sync::Arc;
 use tracing::warn;
@@ -9,9 +10,14 @@ use super::connection::BotRuntime;
 use super::help_image::generate_help_image;
 use super::message::is_admin;
 
+pub mod knowledge;
 mod llm_abuse;
 mod llm_forward;
+mod llm_structured;
+pub mod llm_trace;
+pub mod media_cache;
 mod plugin_outputs;
+pub mod web_search;
 
 pub struct CommandExecInput<'a> {
     pub user_id: u64,
@@ -51,7 +57,7 @@ pub async fn execute_command(
             if mode == "image" {
                 match generate_help_image(state, bot_id).await {
                     Ok(img_base64) => {
-                        let img_msg = format!("[CQ:image,file=base64://{}]", img_base64);
+                        let img_msg = MessageSegment::image_base64(&img_base64).to_cq();
                         send_reply(runtime, bot_id, user_id, group_id, &img_msg).await;
                     }
                     Err(e) => {
@@ -92,7 +98,10 @@ pub async fn execute_command(
 
             match state.plugin_manager.on_command(plugin_id, ctx).await {
                 Ok(outputs) => {
-                    plugin_outputs::process_plugin_outputs(state, runtime, bot_id, &outputs).await
+                    plugin_outputs::process_plugin_outputs_with_llm_response(
+                        state, runtime, bot_id, plugin_id, &outputs,
+                    )
+                    .await
                 }
                 Err(e) => {
                     warn!("[{}] 插件 {} onCommand 失败: {}", bot_id, plugin_id, e);
@@ -0,0 +1,106 @@
+/// 按段落切分文本，合并到不超过 `chunk_chars` 个字符的块，相邻块之间保留 `overlap` 个字符的重叠。
+/// 以字符（而非字节）计数，中文文本也能得到均匀的块。
+pub(super) fn split_into_chunks(text: &str, chunk_chars: usize, overlap: usize) -> Vec<String> {
+    let chunk_chars = chunk_chars.max(100);
+    let overlap = overlap.min(chunk_chars / 2);
+
+    let normalized = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut pieces: Vec<String> = Vec::new();
+    for para in normalized.split("\n\n") {
+        let para = para.trim();
+        if para.is_empty() {
+            continue;
+        }
+        let chars: Vec<char> = para.chars().collect();
+        if chars.len() <= chunk_chars {
+            pieces.push(para.to_string());
+            continue;
+        }
+        // 超长段落按固定窗口硬切
+        let step = chunk_chars - overlap;
+        let mut start = 0usize;
+        while start < chars.len() {
+            let end = (start + chunk_chars).min(chars.len());
+            pieces.push(chars[start..end].iter().collect());
+            if end == chars.len() {
+                break;
+            }
+            start += step;
+        }
+    }
+
+    let mut chunks: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0usize;
+    for piece in pieces {
+        let piece_len = piece.chars().count();
+        if current_len > 0 && current_len + piece_len + 2 > chunk_chars {
+            let tail = tail_chars(&current, overlap);
+            chunks.push(std::mem::take(&mut current));
+            current_len = 0;
+            if !tail.is_empty() && tail.chars().count() + piece_len + 2 <= chunk_chars {
+                current_len = tail.chars().count();
+                current = tail;
+            }
+        }
+        if current_len > 0 {
+            current.push_str("\n\n");
+            current_len += 2;
+        }
+        current.push_str(&piece);
+        current_len += piece_len;
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+fn tail_chars(s: &str, n: usize) -> String {
+    if n == 0 {
+        return String::new();
+    }
+    let total = s.chars().count();
+    s.chars().skip(total.saturating_sub(n)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_short_paragraphs_up_to_chunk_size() {
+        let para = "字".repeat(60);
+        let text = format!("{para}\r\n\r\n{para}\n\n\n\n{para}");
+        let chunks = split_into_chunks(&text, 130, 0);
+        assert_eq!(chunks, vec![format!("{para}\n\n{para}"), para.clone()]);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 130));
+    }
+
+    #[test]
+    fn splits_long_paragraph_with_overlap() {
+        let text: String = (0..250u32)
+            .map(|i| char::from_u32(0x4e00 + i).unwrap())
+            .collect();
+        let chunks = split_into_chunks(&text, 100, 20);
+        let lens: Vec<usize> = chunks.iter().map(|c| c.chars().count()).collect();
+        assert_eq!(lens, vec![100, 100, 90]);
+        // 相邻块共享 20 个字符
+        let first: Vec<char> = chunks[0].chars().collect();
+        let second: Vec<char> = chunks[1].chars().collect();
+        assert_eq!(first[80..], second[..20]);
+    }
+
+    #[test]
+    fn clamps_parameters_and_skips_blank_text() {
+        assert!(split_into_chunks(" \n\n \n", 500, 50).is_empty());
+        // chunk_chars 最小 100，overlap 不超过一半
+        let text = "a".repeat(150);
+        let lens: Vec<usize> = split_into_chunks(&text, 10, 90)
+            .iter()
+            .map(|c| c.len())
+            .collect();
+        assert_eq!(lens, vec![100, 100]);
+    }
+}
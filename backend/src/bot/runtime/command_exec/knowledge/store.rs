@@ -0,0 +1,430 @@
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeDocument {
+    pub id: String,
+    pub title: String,
+    /// 来源描述（"text" 或原始 URL）
+    pub source: String,
+    pub chunk_count: usize,
+    pub char_count: usize,
+    #[serde(default)]
+    pub truncated: bool,
+    pub added_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeBaseMeta {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// 向量化使用的模型映射名称（LLM 模块 models 中的键）
+    pub embedding_model: String,
+    pub chunk_chars: usize,
+    pub chunk_overlap: usize,
+    /// 向量维度，首次写入文档时确定
+    #[serde(default)]
+    pub dims: usize,
+    #[serde(default)]
+    pub documents: Vec<KnowledgeDocument>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct KnowledgeChunk {
+    pub(super) doc_id: String,
+    pub(super) text: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KnowledgeBaseFile {
+    meta: KnowledgeBaseMeta,
+    chunks: Vec<KnowledgeChunk>,
+}
+
+/// 单个知识库：元数据 + 文本块 + 已归一化的向量（按块顺序平铺存放）
+pub(super) struct KnowledgeBase {
+    pub(super) meta: KnowledgeBaseMeta,
+    pub(super) chunks: Vec<KnowledgeChunk>,
+    pub(super) vectors: Vec<f32>,
+    /// 已从存储中删除；仍持有它的导入 / 删除文档操作不再落盘
+    deleted: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KnowledgeHit {
+    pub doc_id: String,
+    pub title: String,
+    pub text: String,
+    pub score: f32,
+}
+
+impl KnowledgeBase {
+    pub(super) fn new(meta: KnowledgeBaseMeta) -> Self {
+        Self {
+            meta,
+            chunks: Vec::new(),
+            vectors: Vec::new(),
+            deleted: false,
+        }
+    }
+
+    /// 追加文档的文本块；先校验全部向量再写入，失败时知识库保持不变
+    pub(super) fn push_chunks(
+        &mut self,
+        doc_id: &str,
+        texts: Vec<String>,
+        vectors: Vec<Vec<f32>>,
+    ) -> Result<(), String> {
+        if texts.len() != vectors.len() {
+            return Err(format!(
+                "向量数量与文本块数量不一致（{} 个块，{} 个向量）",
+                texts.len(),
+                vectors.len()
+            ));
+        }
+        let dims = match self.meta.dims {
+            0 => vectors.first().map_or(0, Vec::len),
+            dims => dims,
+        };
+        if let Some(bad) = vectors.iter().find(|v| v.len() != dims || v.is_empty()) {
+            return Err(format!(
+                "向量维度不一致：知识库为 {}，本次返回 {}（是否更换了向量模型？）",
+                dims,
+                bad.len()
+            ));
+        }
+
+        if !vectors.is_empty() {
+            self.meta.dims = dims;
+        }
+        for (text, mut vector) in texts.into_iter().zip(vectors) {
+            normalize(&mut vector);
+            self.vectors.extend_from_slice(&vector);
+            self.chunks.push(KnowledgeChunk {
+                doc_id: doc_id.to_string(),
+                text,
+            });
+        }
+        Ok(())
+    }
+
+    pub(super) fn remove_document(&mut self, doc_id: &str) -> bool {
+        let before = self.meta.documents.len();
+        self.meta.documents.retain(|d| d.id != doc_id);
+        if self.meta.documents.len() == before {
+            return false;
+        }
+
+        let dims = self.meta.dims;
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        let mut vectors = Vec::with_capacity(self.vectors.len());
+        for (idx, chunk) in std::mem::take(&mut self.chunks).into_iter().enumerate() {
+            if chunk.doc_id == doc_id {
+                continue;
+            }
+            vectors.extend_from_slice(&self.vectors[idx * dims..(idx + 1) * dims]);
+            chunks.push(chunk);
+        }
+        self.chunks = chunks;
+        self.vectors = vectors;
+        if self.chunks.is_empty() {
+            self.meta.dims = 0;
+        }
+        true
+    }
+
+    pub(super) fn search(&self, query: &[f32], top_k: usize, min_score: f32) -> Vec<KnowledgeHit> {
+        let dims = self.meta.dims;
+        if dims == 0 || query.len() != dims {
+            return Vec::new();
+        }
+        let mut q = query.to_vec();
+        normalize(&mut q);
+
+        let mut scored: Vec<(usize, f32)> = self
+            .vectors
+            .chunks_exact(dims)
+            .enumerate()
+            .map(|(idx, v)| (idx, v.iter().zip(&q).map(|(a, b)| a * b).sum::<f32>()))
+            .filter(|(_, score)| *score >= min_score)
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(top_k);
+
+        scored
+            .into_iter()
+            .filter_map(|(idx, score)| {
+                let chunk = self.chunks.get(idx)?;
+                let title = self
+                    .meta
+                    .documents
+                    .iter()
+                    .find(|d| d.id == chunk.doc_id)
+                    .map(|d| d.title.clone())
+                    .unwrap_or_default();
+                Some(KnowledgeHit {
+                    doc_id: chunk.doc_id.clone(),
+                    title,
+                    text: chunk.text.clone(),
+                    score,
+                })
+            })
+            .collect()
+    }
+}
+
+fn normalize(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > f32::EPSILON {
+        for x in v.iter_mut() {
+            *x /= norm;
+        }
+    }
+}
+
+pub(super) struct KnowledgeStore {
+    dir: PathBuf,
+    bases: DashMap<String, Arc<RwLock<KnowledgeBase>>>,
+}
+
+pub(super) static KNOWLEDGE_STORE: Lazy<KnowledgeStore> = Lazy::new(|| {
+    let data_dir = std::env::var("NBOT_DATA_DIR").unwrap_or_else(|_| "data".to_string());
+    KnowledgeStore::load(Path::new(&data_dir).join("knowledge"))
+});
+
+impl KnowledgeStore {
+    fn load(dir: PathBuf) -> Self {
+        let bases = DashMap::new();
+        if let Ok(entries) = std::fs::read_dir(&dir) {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+                match load_base(&path) {
+                    Ok(kb) => {
+                        bases.insert(kb.meta.id.clone(), Arc::new(RwLock::new(kb)));
+                    }
+                    Err(e) => warn!("加载知识库 {:?} 失败: {}", path, e),
+                }
+            }
+        }
+        if !bases.is_empty() {
+            info!("已加载 {} 个知识库", bases.len());
+        }
+        Self { dir, bases }
+    }
+
+    pub(super) fn get(&self, id: &str) -> Option<Arc<RwLock<KnowledgeBase>>> {
+        self.bases.get(id).map(|r| r.value().clone())
+    }
+
+    pub(super) fn all(&self) -> Vec<Arc<RwLock<KnowledgeBase>>> {
+        self.bases.iter().map(|r| r.value().clone()).collect()
+    }
+
+    pub(super) fn insert(&self, kb: KnowledgeBase) -> Result<Arc<RwLock<KnowledgeBase>>, String> {
+        let id = kb.meta.id.clone();
+        match self.bases.entry(id.clone()) {
+            dashmap::mapref::entry::Entry::Occupied(_) => Err(format!("知识库 '{}' 已存在", id)),
+            dashmap::mapref::entry::Entry::Vacant(v) => {
+                let kb = Arc::new(RwLock::new(kb));
+                v.insert(kb.clone());
+                Ok(kb)
+            }
+        }
+    }
+
+    pub(super) async fn remove(&self, id: &str) -> Result<(), String> {
+        let Some((_, kb)) = self.bases.remove(id) else {
+            return Err(format!("知识库 '{}' 不存在", id));
+        };
+        // 在写锁内标记：进行中的保存先完成再删文件，之后的保存会被拒绝
+        kb.write().await.deleted = true;
+        let _ = tokio::fs::remove_file(self.dir.join(format!("{id}.json"))).await;
+        let _ = tokio::fs::remove_file(self.dir.join(format!("{id}.vec"))).await;
+        Ok(())
+    }
+
+    /// 写入磁盘；调用方需持有该知识库的锁，已删除的知识库不再写入
+    pub(super) async fn save(&self, kb: &KnowledgeBase) -> Result<(), String> {
+        if kb.deleted {
+            return Err("知识库已删除".to_string());
+        }
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| format!("创建知识库目录失败: {e}"))?;
+
+        let id = &kb.meta.id;
+        let file = KnowledgeBaseFile {
+            meta: kb.meta.clone(),
+            chunks: kb.chunks.clone(),
+        };
+        let json = serde_json::to_vec(&file).map_err(|e| format!("序列化知识库失败: {e}"))?;
+        let mut vec_bytes: Vec<u8> = Vec::with_capacity(kb.vectors.len() * 4);
+        for x in &kb.vectors {
+            vec_bytes.extend_from_slice(&x.to_le_bytes());
+        }
+
+        // 先写向量再写元数据，并通过临时文件 + rename 避免写一半
+        write_atomic(&self.dir.join(format!("{id}.vec")), &vec_bytes).await?;
+        write_atomic(&self.dir.join(format!("{id}.json")), &json).await?;
+        Ok(())
+    }
+}
+
+async fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let tmp = PathBuf::from(format!("{}.tmp", path.display()));
+    tokio::fs::write(&tmp, bytes)
+        .await
+        .map_err(|e| format!("写入知识库文件失败: {e}"))?;
+    tokio::fs::rename(&tmp, path)
+        .await
+        .map_err(|e| format!("写入知识库文件失败: {e}"))
+}
+
+fn load_base(json_path: &Path) -> Result<KnowledgeBase, String> {
+    let content = std::fs::read(json_path).map_err(|e| e.to_string())?;
+    let file: KnowledgeBaseFile = serde_json::from_slice(&content).map_err(|e| e.to_string())?;
+
+    let vec_path = json_path.with_extension("vec");
+    let vec_bytes = std::fs::read(&vec_path).unwrap_or_default();
+    let vectors: Vec<f32> = vec_bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+
+    if vectors.len() != file.chunks.len() * file.meta.dims {
+        return Err(format!(
+            "向量文件与文本块数量不匹配（{} 个块，{} 维，{} 个浮点数）",
+            file.chunks.len(),
+            file.meta.dims,
+            vectors.len()
+        ));
+    }
+
+    Ok(KnowledgeBase {
+        meta: file.meta,
+        chunks: file.chunks,
+        vectors,
+        deleted: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_base() -> KnowledgeBase {
+        let mut kb = KnowledgeBase::new(KnowledgeBaseMeta {
+            id: "kb".to_string(),
+            name: "kb".to_string(),
+            description: String::new(),
+            embedding_model: "embedding".to_string(),
+            chunk_chars: 800,
+            chunk_overlap: 100,
+            dims: 0,
+            documents: Vec::new(),
+            created_at: 0,
+            updated_at: 0,
+        });
+        for (id, title) in [("a", "文档 A"), ("b", "文档 B")] {
+            kb.meta.documents.push(KnowledgeDocument {
+                id: id.to_string(),
+                title: title.to_string(),
+                source: "text".to_string(),
+                chunk_count: 0,
+                char_count: 0,
+                truncated: false,
+                added_at: 0,
+            });
+        }
+        kb
+    }
+
+    #[test]
+    fn push_chunks_rejects_mismatch_without_partial_writes() {
+        let mut kb = test_base();
+        let texts = |n: usize| (0..n).map(|i| format!("块 {i}")).collect::<Vec<_>>();
+        kb.push_chunks("a", texts(2), vec![vec![1.0, 0.0], vec![0.0, 2.0]])
+            .unwrap();
+        assert_eq!((kb.meta.dims, kb.chunks.len(), kb.vectors.len()), (2, 2, 4));
+
+        // 第二个向量维度不对：整批拒绝
+        let err = kb
+            .push_chunks("b", texts(2), vec![vec![1.0, 1.0], vec![1.0, 1.0, 1.0]])
+            .unwrap_err();
+        assert!(err.contains("维度"), "{err}");
+        assert!(kb.push_chunks("b", texts(2), vec![vec![1.0, 1.0]]).is_err());
+        assert_eq!((kb.meta.dims, kb.chunks.len(), kb.vectors.len()), (2, 2, 4));
+
+        // 空知识库首批向量中途维度变化也不会留下维度
+        let mut empty = test_base();
+        assert!(empty
+            .push_chunks("a", texts(2), vec![vec![1.0], vec![1.0, 0.0]])
+            .is_err());
+        assert_eq!((empty.meta.dims, empty.chunks.len()), (0, 0));
+    }
+
+    #[test]
+    fn search_ranks_by_cosine_and_respects_limits() {
+        let mut kb = test_base();
+        kb.push_chunks(
+            "a",
+            vec!["东".to_string(), "东北".to_string()],
+            vec![vec![3.0, 0.0], vec![1.0, 1.0]],
+        )
+        .unwrap();
+        kb.push_chunks("b", vec!["北".to_string()], vec![vec![0.0, 5.0]])
+            .unwrap();
+
+        let hits = kb.search(&[1.0, 0.1], 10, 0.0);
+        let texts: Vec<&str> = hits.iter().map(|h| h.text.as_str()).collect();
+        assert_eq!(texts, vec!["东", "东北", "北"]);
+        assert_eq!(hits[0].title, "文档 A");
+        assert!((hits[0].score - 0.995).abs() < 0.01);
+
+        assert_eq!(kb.search(&[1.0, 0.1], 1, 0.0).len(), 1);
+        assert_eq!(kb.search(&[1.0, 0.1], 10, 0.5).len(), 2);
+        assert!(kb.search(&[1.0, 0.0, 0.0], 10, 0.0).is_empty());
+
+        assert!(kb.remove_document("a"));
+        let hits = kb.search(&[1.0, 0.1], 10, 0.0);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].doc_id, "b");
+    }
+
+    #[tokio::test]
+    async fn save_after_remove_does_not_resurrect() {
+        let dir = std::env::temp_dir().join(format!("nbot_knowledge_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = KnowledgeStore::load(dir.clone());
+        let kb = store.insert(test_base()).unwrap();
+        store.save(&*kb.read().await).await.unwrap();
+        assert!(dir.join("kb.json").exists());
+
+        // 模拟导入过程中知识库被删除：之前取得的句柄仍可写内存，但不能再落盘
+        store.remove("kb").await.unwrap();
+        let mut guard = kb.write().await;
+        guard
+            .push_chunks("a", vec!["块".to_string()], vec![vec![1.0, 0.0]])
+            .unwrap();
+        assert_eq!(store.save(&guard).await.unwrap_err(), "知识库已删除");
+        drop(guard);
+        assert!(!dir.join("kb.json").exists());
+        assert!(!dir.join("kb.vec").exists());
+        assert!(store.get("kb").is_none());
+        assert!(KnowledgeStore::load(dir.clone()).get("kb").is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
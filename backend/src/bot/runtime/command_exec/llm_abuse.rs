@@ -11,6 +11,8 @@ pub struct LlmAbuseConfig {
     pub max_concurrent_per_user: usize,
     pub max_concurrent_per_group: usize,
     pub min_interval_per_user: Duration,
+    /// 每个用户每天可生成的图片数（0 为不限制）
+    pub image_daily_per_user: u32,
 }
 
 impl LlmAbuseConfig {
@@ -50,12 +52,19 @@ impl LlmAbuseConfig {
             .unwrap_or(10)
             .clamp(0, 3600);
 
+        let image_daily_per_user = limits
+            .get("image_daily_per_user")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(20)
+            .clamp(0, 10_000) as u32;
+
         Self {
             enabled,
             max_concurrent_global,
             max_concurrent_per_user,
             max_concurrent_per_group,
             min_interval_per_user: Duration::from_secs(min_interval_secs),
+            image_daily_per_user,
         }
     }
 }
@@ -88,6 +97,8 @@ static GLOBAL_INFLIGHT: AtomicUsize = AtomicUsize::new(0);
 static USER_INFLIGHT: Lazy<DashMap<u64, AtomicUsize>> = Lazy::new(DashMap::new);
 static GROUP_INFLIGHT: Lazy<DashMap<u64, AtomicUsize>> = Lazy::new(DashMap::new);
 static USER_LAST_START: Lazy<DashMap<u64, Instant>> = Lazy::new(DashMap::new);
+/// 用户当日已生成的图片数：(日期, 数量)
+static USER_IMAGE_USAGE: Lazy<DashMap<u64, (chrono::NaiveDate, u32)>> = Lazy::new(DashMap::new);
 
 fn try_inc_global(max: usize) -> bool {
     let mut cur = GLOBAL_INFLIGHT.load(Ordering::Relaxed);
@@ -199,3 +210,40 @@ pub fn try_begin_llm_task(
         group_id,
     })
 }
+
+/// 预占用户当日的图片生成额度；生成失败时调用 `refund_image_quota` 退回。
+/// 没有用户（插件未传 userId，user_id 为 0）时不计入按用户的额度，避免所有此类调用共用一份额度
+pub fn try_consume_image_quota(
+    cfg: LlmAbuseConfig,
+    user_id: u64,
+    count: u32,
+) -> Result<(), LlmAbuseBlock> {
+    if !cfg.enabled || cfg.image_daily_per_user == 0 || user_id == 0 {
+        return Ok(());
+    }
+    let today = chrono::Local::now().date_naive();
+    let mut entry = USER_IMAGE_USAGE.entry(user_id).or_insert((today, 0));
+    if entry.0 != today {
+        *entry = (today, 0);
+    }
+    if entry.1.saturating_add(count) > cfg.image_daily_per_user {
+        let remain = cfg.image_daily_per_user.saturating_sub(entry.1);
+        return Err(LlmAbuseBlock {
+            message: format!(
+                "今日图片生成额度不足（每日 {} 张，剩余 {} 张）",
+                cfg.image_daily_per_user, remain
+            ),
+        });
+    }
+    entry.1 += count;
+    Ok(())
+}
+
+pub fn refund_image_quota(cfg: LlmAbuseConfig, user_id: u64, count: u32) {
+    if !cfg.enabled || cfg.image_daily_per_user == 0 || user_id == 0 {
+        return;
+    }
+    if let Some(mut entry) = USER_IMAGE_USAGE.get_mut(&user_id) {
+        entry.1 = entry.1.saturating_sub(count);
+    }
+}
@@ -1,39 +1,141 @@
+use super::charset::decode_text;
 use super::download::{DocumentMeta, TempFileGuard};
 use super::multimodal::common::download_binary_to_temp;
-use flate2::read::GzDecoder;
-use std::io::Read;
+use flate2::read::MultiGzDecoder;
+use std::io::{Cursor, Read, Seek, SeekFrom};
 use std::path::Path;
 use tar::Archive as TarArchive;
 use zip::ZipArchive;
 
+/// 嵌套压缩包最多展开的层数（顶层为第 0 层）
+const MAX_NESTING_DEPTH: u32 = 3;
+/// 发送给模型的文本总字符数上限（多文件时按排名依次分配）
+const MAX_TOTAL_CHARS: usize = 200_000;
+/// 多文件模式下文件清单最多列出的条目数
+const MAX_LISTED_FILES: usize = 50;
+/// 一次最多发送给模型的文件数（与插件参数 `maxBundleFiles` 的上限一致）
+const MAX_BUNDLE_FILES: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gz,
+    Xz,
+    Bz2,
+    Zst,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ArchiveKind {
     Zip,
-    Tar,
-    TarGz,
-    Gz,
+    SevenZ,
+    Tar(Compression),
+    /// 单个压缩流（.gz / .xz / .bz2 / .zst），解压后若为 tar 则继续展开
+    Single(Compression),
 }
 
-fn guess_kind(url: &str, file_name: Option<&str>) -> Option<ArchiveKind> {
-    let name = file_name
-        .or_else(|| url.split('/').last())
-        .unwrap_or("")
-        .trim()
-        .to_lowercase();
+/// 压缩包解压限制
+#[derive(Debug, Clone, Copy)]
+pub(in super::super) struct ArchiveLimits {
+    pub(in super::super) max_download_bytes: u64,
+    /// 所有层级合计的解压总量上限（防止压缩炸弹）
+    pub(in super::super) max_extract_bytes: u64,
+    pub(in super::super) max_file_bytes: u64,
+    /// 最多检查的条目数（含嵌套压缩包内的条目）
+    pub(in super::super) max_files: u32,
+    /// 发送给模型的文件数（按相关度取前 N 个）
+    pub(in super::super) max_bundle_files: u32,
+}
 
-    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
-        return Some(ArchiveKind::TarGz);
-    }
-    if name.ends_with(".tar") {
-        return Some(ArchiveKind::Tar);
+fn guess_kind(name: &str) -> Option<ArchiveKind> {
+    let name = name.trim().to_lowercase();
+
+    const TAR_SUFFIXES: &[(&str, Compression)] = &[
+        (".tar.gz", Compression::Gz),
+        (".tgz", Compression::Gz),
+        (".tar.xz", Compression::Xz),
+        (".txz", Compression::Xz),
+        (".tar.bz2", Compression::Bz2),
+        (".tbz2", Compression::Bz2),
+        (".tbz", Compression::Bz2),
+        (".tar.zst", Compression::Zst),
+        (".tzst", Compression::Zst),
+        (".tar", Compression::None),
+    ];
+    for (suffix, compression) in TAR_SUFFIXES {
+        if name.ends_with(suffix) {
+            return Some(ArchiveKind::Tar(*compression));
+        }
     }
-    if name.ends_with(".zip") {
+    if name.ends_with(".zip") || name.ends_with(".jar") {
         return Some(ArchiveKind::Zip);
     }
-    if name.ends_with(".gz") {
-        return Some(ArchiveKind::Gz);
+    if name.ends_with(".7z") {
+        return Some(ArchiveKind::SevenZ);
+    }
+
+    const SINGLE_SUFFIXES: &[(&str, Compression)] = &[
+        (".gz", Compression::Gz),
+        (".xz", Compression::Xz),
+        (".bz2", Compression::Bz2),
+        (".zst", Compression::Zst),
+    ];
+    SINGLE_SUFFIXES
+        .iter()
+        .find(|(suffix, _)| name.ends_with(suffix))
+        .map(|(_, compression)| ArchiveKind::Single(*compression))
+}
+
+/// 按文件头识别格式（文件名没有可用扩展名时使用）
+fn sniff_kind(head: &[u8]) -> Option<ArchiveKind> {
+    if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+        Some(ArchiveKind::Zip)
+    } else if head.starts_with(&[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C]) {
+        Some(ArchiveKind::SevenZ)
+    } else if head.starts_with(&[0x1F, 0x8B]) {
+        Some(ArchiveKind::Single(Compression::Gz))
+    } else if head.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+        Some(ArchiveKind::Single(Compression::Xz))
+    } else if head.starts_with(b"BZh") {
+        Some(ArchiveKind::Single(Compression::Bz2))
+    } else if head.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+        Some(ArchiveKind::Single(Compression::Zst))
+    } else if is_tar_header(head) {
+        Some(ArchiveKind::Tar(Compression::None))
+    } else {
+        None
     }
-    None
+}
+
+fn is_tar_header(head: &[u8]) -> bool {
+    head.len() >= 262 && &head[257..262] == b"ustar"
+}
+
+/// 去掉单流压缩的扩展名：`latest.log.gz` -> `latest.log`
+fn strip_compression_ext(name: &str) -> String {
+    let lower = name.to_lowercase();
+    for ext in [".gz", ".xz", ".bz2", ".zst"] {
+        if lower.ends_with(ext) {
+            return name[..name.len() - ext.len()].to_string();
+        }
+    }
+    name.to_string()
+}
+
+fn decompress<'a, R: Read + 'a>(
+    reader: R,
+    compression: Compression,
+) -> Result<Box<dyn Read + 'a>, String> {
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gz => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        Compression::Bz2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+        Compression::Zst => Box::new(
+            zstd::stream::read::Decoder::new(reader)
+                .map_err(|e| format!("init zstd decoder failed: {e}"))?,
+        ),
+    })
 }
 
 fn normalize_keywords(keywords: &[String]) -> Vec<String> {
@@ -44,50 +146,57 @@ fn normalize_keywords(keywords: &[String]) -> Vec<String> {
         .collect()
 }
 
-fn choose_best_file<'a>(candidates: &'a [ExtractCandidate], keywords: &[String]) -> Option<&'a ExtractCandidate> {
-    if candidates.is_empty() {
-        return None;
-    }
-
-    let kw = normalize_keywords(keywords);
-
-    let mut best: Option<(&ExtractCandidate, i64)> = None;
-    for c in candidates {
-        let mut score: i64 = 0;
-        let name = c.name.to_lowercase();
+/// 文件相关度：文件名特征 + 关键词命中（文件名与内容）+ 文件大小
+fn score_file(c: &ExtractedFile, keywords: &[String]) -> i64 {
+    let mut score: i64 = 0;
+    let name = c.name.to_lowercase();
+    let base_name = name.rsplit('/').next().unwrap_or(&name);
 
-        if name.ends_with("latest.log") {
-            score += 200;
-        }
-        if name.contains("crash") || name.contains("hs_err") {
-            score += 150;
-        }
-        if name.ends_with(".log") {
-            score += 40;
-        }
-        if name.ends_with(".txt") {
-            score += 20;
-        }
+    if base_name == "latest.log" || name.ends_with("latest.log") {
+        score += 200;
+    }
+    if name.contains("crash") || name.contains("hs_err") {
+        score += 150;
+    }
+    if name.ends_with(".log") {
+        score += 40;
+    }
+    if name.ends_with(".txt") {
+        score += 20;
+    }
 
-        for k in &kw {
-            if name == *k {
-                score += 500;
-            } else if name.contains(k) {
-                score += 80;
-            }
+    let content = if keywords.is_empty() {
+        String::new()
+    } else {
+        String::from_utf8_lossy(&c.bytes).to_lowercase()
+    };
+    for k in keywords {
+        if base_name == k || name == *k {
+            score += 500;
+        } else if name.contains(k.as_str()) {
+            score += 80;
         }
-
-        // Prefer larger files if scores tie (more context), but keep stable.
-        score += (c.size_bytes.min(5_000_000) as i64) / 50_000;
-
-        match best {
-            None => best = Some((c, score)),
-            Some((_, s)) if score > s => best = Some((c, score)),
-            _ => {}
+        if content.contains(k.as_str()) {
+            score += 60;
         }
     }
 
-    best.map(|(c, _)| c)
+    // Prefer larger files if scores tie (more context), but keep stable.
+    score += (c.size_bytes.min(5_000_000) as i64) / 50_000;
+    score
+}
+
+/// 按相关度排序，返回前 `top_n` 个文件的下标
+fn rank_files(files: &[ExtractedFile], keywords: &[String], top_n: usize) -> Vec<usize> {
+    let kw = normalize_keywords(keywords);
+    let mut scored: Vec<(usize, i64)> = files
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (i, score_file(f, &kw)))
+        .collect();
+    // 分数相同时保持在压缩包中的顺序
+    scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    scored.into_iter().take(top_n).map(|(i, _)| i).collect()
 }
 
 fn truncate_to_chars(s: String, max_chars: usize) -> (String, bool) {
@@ -99,9 +208,12 @@ fn truncate_to_chars(s: String, max_chars: usize) -> (String, bool) {
 }
 
 #[derive(Debug, Clone)]
-struct ExtractCandidate {
+struct ExtractedFile {
+    /// 含嵌套路径，如 `logs.zip/crash-reports/crash.txt`
     name: String,
     size_bytes: u64,
+    bytes: Vec<u8>,
+    truncated: bool,
 }
 
 fn is_text_candidate_name(name: &str) -> bool {
@@ -109,13 +221,15 @@ fn is_text_candidate_name(name: &str) -> bool {
     lower.ends_with(".log") || lower.ends_with(".txt")
 }
 
-fn read_limited_to_vec<R: Read>(mut r: R, max_bytes: u64) -> Result<(Vec<u8>, bool), String> {
+fn read_limited_to_vec<R: Read + ?Sized>(r: &mut R, max_bytes: u64) -> Result<(Vec<u8>, bool), String> {
     let mut buf: Vec<u8> = Vec::new();
     let mut truncated = false;
     let max = max_bytes as usize;
     let mut chunk = [0u8; 8192];
     loop {
-        let n = r.read(&mut chunk).map_err(|e| format!("read failed: {e}"))?;
+        // 最多多读 1 个字节用于判断是否截断，不多消耗后面的数据
+        let want = max.saturating_add(1).saturating_sub(buf.len()).min(chunk.len());
+        let n = r.read(&mut chunk[..want]).map_err(|e| format!("read failed: {e}"))?;
         if n == 0 {
             break;
         }
@@ -130,225 +244,366 @@ fn read_limited_to_vec<R: Read>(mut r: R, max_bytes: u64) -> Result<(Vec<u8>, bo
     Ok((buf, truncated))
 }
 
-fn extract_best_text_from_zip(
-    path: &Path,
-    max_extract_bytes: u64,
-    max_file_bytes: u64,
-    max_files: u32,
-    keywords: &[String],
-) -> Result<(String, Option<String>, u64, bool), String> {
-    let file = std::fs::File::open(path).map_err(|e| format!("open zip failed: {e}"))?;
-    let mut archive = ZipArchive::new(file).map_err(|e| {
-        let msg = e.to_string().to_lowercase();
-        if msg.contains("eocd") {
-            format!("zip 文件不完整或损坏（EOCD 缺失）：{e}")
-        } else {
-            format!("parse zip failed: {e}")
-        }
-    })?;
-
-    let mut candidates: Vec<ExtractCandidate> = Vec::new();
-    for i in 0..archive.len() {
-        let f = archive
-            .by_index(i)
-            .map_err(|e| format!("read zip entry failed: {e}"))?;
-        if f.is_dir() {
-            continue;
-        }
-        let name = f.name().to_string();
-        if !is_text_candidate_name(&name) {
-            continue;
+/// 遍历状态：所有层级共享同一份解压预算与条目计数
+struct Walker {
+    limits: ArchiveLimits,
+    remaining_bytes: u64,
+    entries_seen: u32,
+    files: Vec<ExtractedFile>,
+    /// 因预算 / 条目数 / 嵌套层数而未能完整展开
+    truncated: bool,
+    stopped: bool,
+}
+
+impl Walker {
+    fn new(limits: ArchiveLimits) -> Self {
+        Self {
+            limits,
+            remaining_bytes: limits.max_extract_bytes,
+            entries_seen: 0,
+            files: Vec::new(),
+            truncated: false,
+            stopped: false,
         }
-        candidates.push(ExtractCandidate {
-            name,
-            size_bytes: f.size(),
-        });
-        if candidates.len() as u32 >= max_files {
-            break;
+    }
+
+    /// 读取 `max_bytes` 以内的数据并计入解压总量
+    fn read_budgeted<R: Read + ?Sized>(&mut self, reader: &mut R, max_bytes: u64) -> Result<(Vec<u8>, bool), String> {
+        let allowed = max_bytes.min(self.remaining_bytes);
+        let (bytes, truncated) = read_limited_to_vec(reader, allowed)?;
+        // 截断时多读的 1 个字节同样已经解压
+        let consumed = bytes.len() as u64 + u64::from(truncated);
+        self.remaining_bytes = self.remaining_bytes.saturating_sub(consumed);
+        if truncated && allowed < max_bytes {
+            // 不是单文件上限而是总量预算耗尽，停止继续展开
+            self.truncated = true;
+            self.stopped = true;
         }
+        Ok((bytes, truncated))
     }
 
-    let Some(best) = choose_best_file(&candidates, keywords) else {
-        return Err("压缩包内未找到 .log/.txt 文件".to_string());
-    };
+    /// 丢弃条目中尚未读取的数据并计入解压总量：tar / 7z 的后续条目在同一个解压流里，
+    /// 跳过的条目同样要解压完才能读到下一个（7z 固实压缩不读完还会让下一个条目校验失败）
+    fn skip_budgeted<R: Read + ?Sized>(&mut self, reader: &mut R) -> Result<(), String> {
+        let allowed = self.remaining_bytes;
+        let skipped = std::io::copy(&mut Read::take(&mut *reader, allowed.saturating_add(1)), &mut std::io::sink())
+            .map_err(|e| format!("read failed: {e}"))?;
+        self.remaining_bytes = allowed.saturating_sub(skipped);
+        if skipped > allowed {
+            self.truncated = true;
+            self.stopped = true;
+        }
+        Ok(())
+    }
 
-    let mut total_extracted: u64 = 0;
-    let mut file = archive
-        .by_name(&best.name)
-        .map_err(|e| format!("open zip entry failed: {e}"))?;
-    let allowed = max_file_bytes.min(max_extract_bytes);
-    let (bytes, truncated_bytes) = read_limited_to_vec(&mut file, allowed)?;
-    total_extracted += bytes.len() as u64;
-
-    let text = String::from_utf8_lossy(&bytes).to_string();
-    // Keep some extra headroom for prompt wrapper and metadata; plugin can further truncate.
-    let (text, truncated_chars) = truncate_to_chars(text, 200_000);
-    let truncated = truncated_bytes || truncated_chars || total_extracted >= max_extract_bytes;
-    let ext = Path::new(&best.name)
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|s| s.to_lowercase());
-    Ok((text, ext, total_extracted, truncated))
-}
+    /// 处理一个条目：文本文件收集内容，压缩包在层数限制内递归展开，其他跳过；所有条目都计入条目数
+    fn visit<R: Read + ?Sized>(&mut self, name: String, size_bytes: u64, reader: &mut R, depth: u32) -> Result<(), String> {
+        if self.stopped {
+            return Ok(());
+        }
+        if self.entries_seen >= self.limits.max_files {
+            self.truncated = true;
+            self.stopped = true;
+            return Ok(());
+        }
+        self.entries_seen += 1;
 
-fn extract_best_text_from_tar_path(
-    path: &Path,
-    gz: bool,
-    max_extract_bytes: u64,
-    max_file_bytes: u64,
-    max_files: u32,
-    keywords: &[String],
-) -> Result<(String, Option<String>, u64, bool), String> {
-    // First pass: collect candidates.
-    let file = std::fs::File::open(path).map_err(|e| format!("open tar failed: {e}"))?;
-    let reader: Box<dyn Read> = if gz {
-        Box::new(GzDecoder::new(file))
-    } else {
-        Box::new(file)
-    };
-    let mut archive = TarArchive::new(reader);
-
-    let mut candidates: Vec<ExtractCandidate> = Vec::new();
-    let entries = archive.entries().map_err(|e| format!("parse tar failed: {e}"))?;
-    for entry in entries {
-        let entry = entry.map_err(|e| format!("read tar entry failed: {e}"))?;
-        let header = entry.header();
-        if header.entry_type().is_dir() {
-            continue;
+        if let Some(kind) = guess_kind(&name) {
+            if depth >= MAX_NESTING_DEPTH {
+                self.truncated = true;
+                return Ok(());
+            }
+            // 嵌套压缩包整体读入内存（计入解压总量），读不完整则无法解析，直接跳过
+            let (bytes, truncated) = self.read_budgeted(reader, self.limits.max_extract_bytes)?;
+            if truncated {
+                self.truncated = true;
+                return Ok(());
+            }
+            if let Err(e) = self.walk(Cursor::new(bytes), kind, &name, depth + 1) {
+                tracing::warn!("展开嵌套压缩包 {} 失败: {}", name, e);
+                self.truncated = true;
+            }
+            return Ok(());
         }
-        let p = entry
-            .path()
-            .map_err(|e| format!("read tar entry path failed: {e}"))?;
-        let name = p.to_string_lossy().to_string();
+
         if !is_text_candidate_name(&name) {
-            continue;
+            return Ok(());
         }
-        candidates.push(ExtractCandidate {
+        let (bytes, truncated) = self.read_budgeted(reader, self.limits.max_file_bytes)?;
+        self.files.push(ExtractedFile {
             name,
-            size_bytes: header.size().unwrap_or(0),
+            size_bytes: size_bytes.max(bytes.len() as u64),
+            bytes,
+            truncated,
         });
-        if candidates.len() as u32 >= max_files {
-            break;
+        Ok(())
+    }
+
+    fn walk<R: Read + Seek>(&mut self, mut source: R, kind: ArchiveKind, prefix: &str, depth: u32) -> Result<(), String> {
+        let join = |name: &str| {
+            if prefix.is_empty() {
+                name.to_string()
+            } else {
+                format!("{prefix}/{name}")
+            }
+        };
+
+        match kind {
+            ArchiveKind::Zip => {
+                let mut archive = ZipArchive::new(source).map_err(|e| {
+                    let msg = e.to_string().to_lowercase();
+                    if msg.contains("eocd") {
+                        format!("zip 文件不完整或损坏（EOCD 缺失）：{e}")
+                    } else {
+                        format!("parse zip failed: {e}")
+                    }
+                })?;
+                for i in 0..archive.len() {
+                    if self.stopped {
+                        break;
+                    }
+                    let mut f = archive
+                        .by_index(i)
+                        .map_err(|e| format!("read zip entry failed: {e}"))?;
+                    if f.is_dir() {
+                        continue;
+                    }
+                    let name = join(f.name());
+                    let size = f.size();
+                    self.visit(name, size, &mut f, depth)?;
+                }
+            }
+            ArchiveKind::SevenZ => {
+                let len = source
+                    .seek(SeekFrom::End(0))
+                    .and_then(|len| source.seek(SeekFrom::Start(0)).map(|_| len))
+                    .map_err(|e| format!("read 7z failed: {e}"))?;
+                let mut reader = sevenz_rust::SevenZReader::new(source, len, sevenz_rust::Password::empty())
+                    .map_err(|e| format!("parse 7z failed: {e}"))?;
+                let mut visit_err: Option<String> = None;
+                reader
+                    .for_each_entries(|entry, entry_reader| {
+                        if entry.is_directory() || !entry.has_stream() {
+                            return Ok(true);
+                        }
+                        let mut visited = self.visit(join(entry.name()), entry.size(), entry_reader, depth);
+                        if visited.is_ok() && !self.stopped {
+                            visited = self.skip_budgeted(entry_reader);
+                        }
+                        if let Err(e) = visited {
+                            visit_err = Some(e);
+                            return Ok(false);
+                        }
+                        Ok(!self.stopped)
+                    })
+                    .map_err(|e| format!("read 7z entry failed: {e}"))?;
+                if let Some(e) = visit_err {
+                    return Err(e);
+                }
+            }
+            ArchiveKind::Tar(compression) => {
+                let mut archive = TarArchive::new(decompress(source, compression)?);
+                let entries = archive.entries().map_err(|e| format!("parse tar failed: {e}"))?;
+                for entry in entries {
+                    if self.stopped {
+                        break;
+                    }
+                    let mut entry = entry.map_err(|e| format!("read tar entry failed: {e}"))?;
+                    let header = entry.header();
+                    if !header.entry_type().is_file() {
+                        continue;
+                    }
+                    let size = header.size().unwrap_or(0);
+                    let p = entry
+                        .path()
+                        .map_err(|e| format!("read tar entry path failed: {e}"))?;
+                    let name = join(&p.to_string_lossy());
+                    self.visit(name, size, &mut entry, depth)?;
+                    if !self.stopped {
+                        self.skip_budgeted(&mut entry)?;
+                    }
+                }
+            }
+            ArchiveKind::Single(compression) => {
+                let mut decoder = decompress(source, compression)?;
+                let (bytes, truncated) = self.read_budgeted(&mut decoder, self.limits.max_extract_bytes)?;
+                // .tar.gz 之类未按扩展名识别时，解压后按 tar 头判断
+                if !truncated && is_tar_header(&bytes) {
+                    return self.walk(Cursor::new(bytes), ArchiveKind::Tar(Compression::None), prefix, depth);
+                }
+                let name = strip_compression_ext(if prefix.is_empty() { "file.log" } else { prefix });
+                let file_name = if is_text_candidate_name(&name) {
+                    name
+                } else {
+                    format!("{name}.log")
+                };
+                self.entries_seen += 1;
+                let file_truncated = truncated || bytes.len() as u64 > self.limits.max_file_bytes;
+                let mut bytes = bytes;
+                bytes.truncate(self.limits.max_file_bytes as usize);
+                self.files.push(ExtractedFile {
+                    name: file_name,
+                    size_bytes: bytes.len() as u64,
+                    bytes,
+                    truncated: file_truncated,
+                });
+            }
         }
+        Ok(())
     }
+}
 
-    let Some(best) = choose_best_file(&candidates, keywords) else {
-        return Err("压缩包内未找到 .log/.txt 文件".to_string());
-    };
+/// 把排名靠前的文件拼成一段文本；只取一个文件时保持原样，多个文件时附带清单与文件头
+fn render_bundle(files: &[ExtractedFile], selected: &[usize], multi_file: bool) -> (String, bool) {
+    if !multi_file {
+        let f = &files[selected[0]];
+        let text = decode_text(&f.bytes, None);
+        // Keep some extra headroom for prompt wrapper and metadata; plugin can further truncate.
+        let (text, truncated_chars) = truncate_to_chars(text, MAX_TOTAL_CHARS);
+        return (text, f.truncated || truncated_chars);
+    }
 
-    // Second pass: read chosen entry.
-    let file = std::fs::File::open(path).map_err(|e| format!("open tar failed: {e}"))?;
-    let reader: Box<dyn Read> = if gz {
-        Box::new(GzDecoder::new(file))
-    } else {
-        Box::new(file)
-    };
-    let mut archive = TarArchive::new(reader);
-
-    let mut total_extracted: u64 = 0;
-    let entries = archive.entries().map_err(|e| format!("parse tar failed: {e}"))?;
-    for entry in entries {
-        let mut entry = entry.map_err(|e| format!("read tar entry failed: {e}"))?;
-        let header = entry.header();
-        if header.entry_type().is_dir() {
-            continue;
-        }
-        let p = entry
-            .path()
-            .map_err(|e| format!("read tar entry path failed: {e}"))?;
-        let name = p.to_string_lossy().to_string();
-        if name != best.name {
-            continue;
+    let mut out = String::new();
+    out.push_str(&format!(
+        "压缩包内共 {} 个文本文件，以下按相关度附上前 {} 个：\n",
+        files.len(),
+        selected.len()
+    ));
+    for (i, idx) in selected.iter().enumerate() {
+        out.push_str(&format!(
+            "{}. {}（{} bytes）\n",
+            i + 1,
+            files[*idx].name,
+            files[*idx].size_bytes
+        ));
+    }
+    let others: Vec<&str> = files
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !selected.contains(i))
+        .map(|(_, f)| f.name.as_str())
+        .collect();
+    if !others.is_empty() {
+        let listed = others.len().min(MAX_LISTED_FILES);
+        out.push_str(&format!("其他文件：{}", others[..listed].join("，")));
+        if others.len() > listed {
+            out.push_str(&format!(" 等 {} 个", others.len()));
         }
-
-        let allowed = max_file_bytes.min(max_extract_bytes);
-        let (bytes, truncated_bytes) = read_limited_to_vec(&mut entry, allowed)?;
-        total_extracted += bytes.len() as u64;
-        let text = String::from_utf8_lossy(&bytes).to_string();
-        let (text, truncated_chars) = truncate_to_chars(text, 200_000);
-        let truncated = truncated_bytes || truncated_chars || total_extracted >= max_extract_bytes;
-        let ext = Path::new(&best.name)
-            .extension()
-            .and_then(|e| e.to_str())
-            .map(|s| s.to_lowercase());
-        return Ok((text, ext, total_extracted, truncated));
+        out.push('\n');
     }
 
-    Err("压缩包内未找到可用日志文件".to_string())
+    let mut truncated = false;
+    let mut remaining_chars = MAX_TOTAL_CHARS.saturating_sub(out.chars().count());
+    for (i, idx) in selected.iter().enumerate() {
+        let f = &files[*idx];
+        // 剩余额度平均分给尚未输出的文件，靠前的文件没用完的额度顺延给后面
+        let share = remaining_chars / (selected.len() - i);
+        let (text, truncated_chars) = truncate_to_chars(decode_text(&f.bytes, None), share);
+        remaining_chars = remaining_chars.saturating_sub(text.chars().count());
+        let file_truncated = f.truncated || truncated_chars;
+        truncated |= file_truncated;
+        out.push_str(&format!(
+            "\n===== 文件 {}/{}：{}{} =====\n",
+            i + 1,
+            selected.len(),
+            f.name,
+            if file_truncated { "（已截断）" } else { "" }
+        ));
+        out.push_str(&text);
+        if !text.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    (out, truncated)
 }
 
-fn extract_best_text_from_gz_path(
+fn extract_archive_text(
     path: &Path,
-    max_extract_bytes: u64,
-    max_file_bytes: u64,
+    kind: ArchiveKind,
+    archive_name: &str,
+    limits: ArchiveLimits,
+    keywords: &[String],
 ) -> Result<(String, Option<String>, u64, bool), String> {
-    let file = std::fs::File::open(path).map_err(|e| format!("open gz failed: {e}"))?;
-    let mut decoder = GzDecoder::new(file);
-
-    let allowed = max_file_bytes.min(max_extract_bytes);
-    let (bytes, truncated_bytes) = read_limited_to_vec(&mut decoder, allowed)?;
-    let text = String::from_utf8_lossy(&bytes).to_string();
-    let (text, truncated_chars) = truncate_to_chars(text, 200_000);
-    let truncated = truncated_bytes || truncated_chars;
-    Ok((text, Some("gz".to_string()), bytes.len() as u64, truncated))
+    let file = std::fs::File::open(path).map_err(|e| format!("open archive failed: {e}"))?;
+    let mut walker = Walker::new(limits);
+    let prefix = match kind {
+        ArchiveKind::Single(_) => archive_name,
+        _ => "",
+    };
+    walker.walk(file, kind, prefix, 0)?;
+
+    if walker.files.is_empty() {
+        return Err("压缩包内未找到 .log/.txt 文件".to_string());
+    }
+    let top_n = (limits.max_bundle_files.max(1) as usize).min(walker.files.len());
+    let selected = rank_files(&walker.files, keywords, top_n);
+    let (text, truncated) = render_bundle(&walker.files, &selected, limits.max_bundle_files > 1);
+
+    let total_extracted = limits.max_extract_bytes - walker.remaining_bytes;
+    let ext = Path::new(&walker.files[selected[0]].name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|s| s.to_lowercase());
+    Ok((text, ext, total_extracted, truncated || walker.truncated))
 }
 
 pub(super) async fn download_archive_text(
     url: &str,
     file_name: Option<&str>,
     timeout_ms: u64,
-    max_download_bytes: u64,
-    max_extract_bytes: u64,
-    max_file_bytes: u64,
-    max_files: u32,
+    limits: ArchiveLimits,
     keywords: &[String],
 ) -> Result<(TempFileGuard, String, DocumentMeta), String> {
-    let max_extract_bytes = max_extract_bytes.clamp(1_000_000, 1_000_000_000);
-    let max_file_bytes = max_file_bytes.clamp(100_000, 200_000_000);
-    let max_files = max_files.clamp(1, 500);
+    let limits = ArchiveLimits {
+        max_download_bytes: limits.max_download_bytes,
+        max_extract_bytes: limits.max_extract_bytes.clamp(1_000_000, 1_000_000_000),
+        max_file_bytes: limits.max_file_bytes.clamp(100_000, 200_000_000),
+        max_files: limits.max_files.clamp(1, 500),
+        max_bundle_files: limits.max_bundle_files.clamp(1, MAX_BUNDLE_FILES),
+    };
 
     let mut last_err: Option<String> = None;
     for attempt in 0..2 {
-        let (guard, bin_meta) = download_binary_to_temp(url, file_name, timeout_ms, max_download_bytes).await?;
+        let (guard, bin_meta) =
+            download_binary_to_temp(url, file_name, timeout_ms, limits.max_download_bytes).await?;
         if bin_meta.truncated {
             return Err(format!(
                 "压缩包下载被截断（已下载 {} bytes，达到上限 {} bytes）。请提高 max_download_bytes 或上传更小的压缩包。",
-                bin_meta.size_bytes, max_download_bytes
+                bin_meta.size_bytes, limits.max_download_bytes
             ));
         }
 
-        let kind = guess_kind(url, bin_meta.file_name.as_deref().or(file_name)).ok_or_else(|| {
-            "不支持的压缩格式（仅支持 .zip / .tar / .tar.gz(.tgz) / .gz）".to_string()
-        })?;
+        let archive_name = bin_meta
+            .file_name
+            .as_deref()
+            .or(file_name)
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| {
+                let without_query = url.split(['?', '#']).next().unwrap_or(url);
+                without_query.rsplit('/').next().unwrap_or("").to_string()
+            });
+        let mut head = [0u8; 512];
+        let head_len = {
+            let mut f = std::fs::File::open(&guard.path).map_err(|e| format!("open archive failed: {e}"))?;
+            read_limited_to_vec(&mut f, head.len() as u64)
+                .map(|(bytes, _)| {
+                    head[..bytes.len()].copy_from_slice(&bytes);
+                    bytes.len()
+                })
+                .unwrap_or(0)
+        };
+        let kind = sniff_kind(&head[..head_len])
+            .filter(|k| !matches!(k, ArchiveKind::Single(_)) || guess_kind(&archive_name).is_none())
+            .or_else(|| guess_kind(&archive_name))
+            .ok_or_else(|| {
+                "不支持的压缩格式（支持 .zip / .7z / .tar / .tar.gz / .tar.xz / .tar.bz2 / .tar.zst / .gz / .xz / .bz2 / .zst）"
+                    .to_string()
+            })?;
 
         let path = guard.path.clone();
         let keywords = keywords.to_vec();
-
-        let extracted = tokio::task::spawn_blocking(move || match kind {
-            ArchiveKind::Zip => extract_best_text_from_zip(
-                &path,
-                max_extract_bytes,
-                max_file_bytes,
-                max_files,
-                &keywords,
-            ),
-            ArchiveKind::Tar => extract_best_text_from_tar_path(
-                &path,
-                false,
-                max_extract_bytes,
-                max_file_bytes,
-                max_files,
-                &keywords,
-            ),
-            ArchiveKind::TarGz => extract_best_text_from_tar_path(
-                &path,
-                true,
-                max_extract_bytes,
-                max_file_bytes,
-                max_files,
-                &keywords,
-            ),
-            ArchiveKind::Gz => extract_best_text_from_gz_path(&path, max_extract_bytes, max_file_bytes),
+        let extracted = tokio::task::spawn_blocking(move || {
+            extract_archive_text(&path, kind, &archive_name, limits, &keywords)
         })
         .await
         .map_err(|e| format!("解压任务失败: {e}"))?;
@@ -358,6 +613,7 @@ pub(super) async fn download_archive_text(
                 let meta = DocumentMeta {
                     title: String::new(),
                     file_ext: ext,
+                    content_type: None,
                     size_bytes: Some(extracted_bytes),
                     truncated,
                 };
@@ -378,3 +634,301 @@ pub(super) async fn download_archive_text(
 
     Err(last_err.unwrap_or_else(|| "解压失败".to_string()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn file(name: &str, content: &str) -> ExtractedFile {
+        ExtractedFile {
+            name: name.to_string(),
+            size_bytes: content.len() as u64,
+            bytes: content.as_bytes().to_vec(),
+            truncated: false,
+        }
+    }
+
+    fn test_limits() -> ArchiveLimits {
+        ArchiveLimits {
+            max_download_bytes: 10_000_000,
+            max_extract_bytes: 10_000_000,
+            max_file_bytes: 1_000_000,
+            max_files: 50,
+            max_bundle_files: 3,
+        }
+    }
+
+    #[test]
+    fn guess_kind_by_extension() {
+        assert_eq!(
+            guess_kind("Logs.TAR.GZ"),
+            Some(ArchiveKind::Tar(Compression::Gz))
+        );
+        assert_eq!(
+            guess_kind("logs.tgz"),
+            Some(ArchiveKind::Tar(Compression::Gz))
+        );
+        assert_eq!(
+            guess_kind("logs.tar.zst"),
+            Some(ArchiveKind::Tar(Compression::Zst))
+        );
+        assert_eq!(
+            guess_kind("logs.tar"),
+            Some(ArchiveKind::Tar(Compression::None))
+        );
+        assert_eq!(guess_kind("mods.jar"), Some(ArchiveKind::Zip));
+        assert_eq!(guess_kind("logs.7z"), Some(ArchiveKind::SevenZ));
+        assert_eq!(
+            guess_kind("latest.log.gz"),
+            Some(ArchiveKind::Single(Compression::Gz))
+        );
+        assert_eq!(
+            guess_kind("latest.log.xz"),
+            Some(ArchiveKind::Single(Compression::Xz))
+        );
+        assert_eq!(guess_kind("latest.log"), None);
+        assert_eq!(guess_kind("archive"), None);
+    }
+
+    #[test]
+    fn sniff_kind_by_magic_bytes() {
+        assert_eq!(sniff_kind(b"PK\x03\x04rest"), Some(ArchiveKind::Zip));
+        assert_eq!(
+            sniff_kind(&[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C, 0]),
+            Some(ArchiveKind::SevenZ)
+        );
+        assert_eq!(
+            sniff_kind(&[0x1F, 0x8B, 8]),
+            Some(ArchiveKind::Single(Compression::Gz))
+        );
+        assert_eq!(
+            sniff_kind(b"BZh91AY"),
+            Some(ArchiveKind::Single(Compression::Bz2))
+        );
+        assert_eq!(
+            sniff_kind(&[0x28, 0xB5, 0x2F, 0xFD, 0]),
+            Some(ArchiveKind::Single(Compression::Zst))
+        );
+
+        let mut tar_head = vec![0u8; 512];
+        tar_head[257..262].copy_from_slice(b"ustar");
+        assert_eq!(
+            sniff_kind(&tar_head),
+            Some(ArchiveKind::Tar(Compression::None))
+        );
+        assert_eq!(sniff_kind(&tar_head[..200]), None);
+        assert_eq!(sniff_kind(b"plain text log"), None);
+    }
+
+    #[test]
+    fn rank_files_prefers_names_and_keywords() {
+        let files = vec![
+            file("logs/debug.txt", "nothing here"),
+            file("logs/other.log", "nothing here"),
+            file("logs/latest.log", "game started"),
+            file("crash-reports/crash-1.txt", "Exception in thread"),
+            file("logs/mod.log", "NullPointerException at foo"),
+        ];
+        assert_eq!(rank_files(&files, &[], 3), vec![2, 3, 1]);
+        // 关键词按内容命中（不区分大小写），空白关键词忽略
+        let keywords = vec![" nullpointer ".to_string(), "  ".to_string()];
+        assert_eq!(rank_files(&files, &keywords, 2), vec![2, 3]);
+        assert_eq!(rank_files(&files, &["mod.log".to_string()], 1), vec![4]);
+        // 分数相同时保持原顺序
+        let ties = vec![file("a.txt", "x"), file("b.txt", "x")];
+        assert_eq!(rank_files(&ties, &[], 10), vec![0, 1]);
+    }
+
+    #[test]
+    fn render_bundle_single_and_multi_file() {
+        let files = vec![
+            file("latest.log", "line 1\nline 2"),
+            file("debug.txt", "debug"),
+            file("old.log", "old"),
+        ];
+        let (text, truncated) = render_bundle(&files, &[0], false);
+        assert_eq!(text, "line 1\nline 2");
+        assert!(!truncated);
+
+        let mut files = files;
+        files[1].truncated = true;
+        let (text, truncated) = render_bundle(&files, &[0, 1], true);
+        assert!(truncated);
+        assert!(
+            text.starts_with("压缩包内共 3 个文本文件，以下按相关度附上前 2 个：\n1. latest.log")
+        );
+        assert!(text.contains("其他文件：old.log\n"));
+        assert!(text.contains("\n===== 文件 1/2：latest.log =====\nline 1\nline 2\n"));
+        assert!(text.contains("\n===== 文件 2/2：debug.txt（已截断） =====\ndebug\n"));
+    }
+
+    #[test]
+    fn walk_expands_nested_archives() {
+        // zip（含 latest.log）放在 tar.gz 里
+        let mut zip_bytes = Vec::new();
+        {
+            let mut zip = zip::ZipWriter::new(Cursor::new(&mut zip_bytes));
+            let options = zip::write::SimpleFileOptions::default();
+            zip.start_file("logs/latest.log", options).unwrap();
+            zip.write_all(b"inner log").unwrap();
+            zip.start_file("logs/image.png", options).unwrap();
+            zip.write_all(b"binary").unwrap();
+            zip.finish().unwrap();
+        }
+        let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        for (name, data) in [
+            ("bundle.zip", zip_bytes.as_slice()),
+            ("readme.txt", b"hello".as_slice()),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, data).unwrap();
+        }
+        let tar_gz = tar.into_inner().unwrap().finish().unwrap();
+
+        let mut walker = Walker::new(test_limits());
+        walker
+            .walk(
+                Cursor::new(tar_gz),
+                ArchiveKind::Tar(Compression::Gz),
+                "",
+                0,
+            )
+            .unwrap();
+        let names: Vec<&str> = walker.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["bundle.zip/logs/latest.log", "readme.txt"]);
+        assert_eq!(walker.files[0].bytes, b"inner log");
+        assert!(!walker.truncated);
+
+        // 条目数上限：只检查第一个条目（嵌套压缩包本身计 1 个）
+        let mut walker = Walker::new(ArchiveLimits {
+            max_files: 1,
+            ..test_limits()
+        });
+        let mut tar = tar::Builder::new(Vec::new());
+        for name in ["a.log", "b.log"] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(1);
+            header.set_cksum();
+            tar.append_data(&mut header, name, b"x".as_slice()).unwrap();
+        }
+        walker
+            .walk(
+                Cursor::new(tar.into_inner().unwrap()),
+                ArchiveKind::Tar(Compression::None),
+                "",
+                0,
+            )
+            .unwrap();
+        assert_eq!(walker.files.len(), 1);
+        assert!(walker.truncated);
+    }
+
+    #[test]
+    fn walk_drains_skipped_entries_in_solid_7z() {
+        // 固实压缩：所有条目共用一个解压流，二进制条目在日志之前
+        let contents: [(&str, Vec<u8>); 3] = [
+            (
+                "crash.bin",
+                (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect(),
+            ),
+            ("logs/latest.log", b"[main/INFO] done".to_vec()),
+            ("logs/debug.log", "x".repeat(5000).into_bytes()),
+        ];
+        let mut writer = sevenz_rust::SevenZWriter::new(Cursor::new(Vec::new())).unwrap();
+        let entries = contents
+            .iter()
+            .map(|(name, data)| {
+                let mut entry = sevenz_rust::SevenZArchiveEntry::new();
+                entry.name = name.to_string();
+                entry.has_stream = true;
+                entry.size = data.len() as u64;
+                entry
+            })
+            .collect();
+        let readers: Vec<sevenz_rust::SourceReader<&[u8]>> = contents
+            .iter()
+            .map(|(_, data)| data.as_slice().into())
+            .collect();
+        writer
+            .push_archive_entries(entries, sevenz_rust::SeqReader::new(readers))
+            .unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+
+        // debug.log 只读取前 1000 字节，剩余部分同样要跳过
+        let mut walker = Walker::new(ArchiveLimits {
+            max_file_bytes: 1000,
+            ..test_limits()
+        });
+        walker
+            .walk(Cursor::new(archive), ArchiveKind::SevenZ, "", 0)
+            .unwrap();
+        let names: Vec<&str> = walker.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["logs/latest.log", "logs/debug.log"]);
+        assert_eq!(walker.files[0].bytes, b"[main/INFO] done");
+        assert!(walker.files[1].truncated);
+        // 跳过的数据同样计入解压总量
+        let total: u64 = contents.iter().map(|(_, data)| data.len() as u64).sum();
+        assert_eq!(
+            walker.limits.max_extract_bytes - walker.remaining_bytes,
+            total
+        );
+        assert!(!walker.truncated);
+    }
+
+    #[test]
+    fn walk_charges_skipped_entries_to_limits() {
+        let build = |entries: &[(&str, usize)]| {
+            let mut tar = tar::Builder::new(Vec::new());
+            for (name, size) in entries {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(*size as u64);
+                header.set_cksum();
+                tar.append_data(&mut header, name, vec![0u8; *size].as_slice())
+                    .unwrap();
+            }
+            tar.into_inner().unwrap()
+        };
+
+        // 超过解压总量的二进制条目会停止展开
+        let archive = build(&[("world.bin", 2_000_000), ("latest.log", 10)]);
+        let mut walker = Walker::new(ArchiveLimits {
+            max_extract_bytes: 1_000_000,
+            ..test_limits()
+        });
+        walker
+            .walk(
+                Cursor::new(archive),
+                ArchiveKind::Tar(Compression::None),
+                "",
+                0,
+            )
+            .unwrap();
+        assert!(walker.files.is_empty());
+        assert!(walker.truncated);
+        assert_eq!(walker.remaining_bytes, 0);
+
+        // 非文本条目同样计入条目数
+        let archive = build(&[("a.png", 1), ("b.png", 1), ("latest.log", 1)]);
+        let mut walker = Walker::new(ArchiveLimits {
+            max_files: 2,
+            ..test_limits()
+        });
+        walker
+            .walk(
+                Cursor::new(archive),
+                ArchiveKind::Tar(Compression::None),
+                "",
+                0,
+            )
+            .unwrap();
+        assert!(walker.files.is_empty());
+        assert!(walker.truncated);
+    }
+}
@@ -0,0 +1,80 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+
+/// 从 Content-Type（如 `text/html; charset=gbk`）中取出 charset
+fn charset_from_content_type(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|part| {
+        let (key, value) = part.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches(|c| c == '"' || c == '\''))
+    })
+}
+
+/// 在 HTML 头部查找 `<meta charset="...">` 或 `<meta http-equiv="Content-Type" content="...; charset=...">`
+fn charset_from_html_meta(bytes: &[u8]) -> Option<String> {
+    let head = &bytes[..bytes.len().min(4096)];
+    let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+    let mut rest = head.as_str();
+    while let Some(idx) = rest.find("<meta") {
+        rest = &rest[idx + 5..];
+        let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+        let Some(pos) = tag.find("charset") else {
+            continue;
+        };
+        let Some(value) = tag[pos + 7..].trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start().trim_start_matches(['"', '\'']);
+        let end = value
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .unwrap_or(value.len());
+        if end > 0 {
+            return Some(value[..end].to_string());
+        }
+    }
+    None
+}
+
+/// 判断字节串能否被完整解码（允许末尾因截断而缺少 1~3 个字节）
+fn decodes_cleanly(encoding: &'static Encoding, bytes: &[u8]) -> bool {
+    (0..=3.min(bytes.len())).any(|cut| {
+        encoding
+            .decode_without_bom_handling_and_without_replacement(&bytes[..bytes.len() - cut])
+            .is_some()
+    })
+}
+
+/// 将下载到的字节解码为文本。
+///
+/// 优先级：BOM → Content-Type 中的 charset → HTML meta → UTF-8 → 按内容猜测（GBK/GB18030、Big5 等）。
+pub(super) fn decode_text(bytes: &[u8], content_type: Option<&str>) -> String {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return text.into_owned();
+    }
+
+    let declared = content_type
+        .and_then(charset_from_content_type)
+        .map(|s| s.to_string())
+        .or_else(|| charset_from_html_meta(bytes))
+        .and_then(|label| Encoding::for_label(label.as_bytes()));
+    if let Some(encoding) = declared {
+        // 声明为 UTF-8 但实际不是时（常见于国内老站点），继续走下面的探测
+        if encoding != UTF_8 || decodes_cleanly(UTF_8, bytes) {
+            let (text, _) = encoding.decode_without_bom_handling(bytes);
+            return text.into_owned();
+        }
+    }
+
+    if decodes_cleanly(UTF_8, bytes) {
+        let (text, _) = UTF_8.decode_without_bom_handling(bytes);
+        return text.into_owned();
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let (text, _) = detector
+        .guess(None, false)
+        .decode_without_bom_handling(bytes);
+    text.into_owned()
+}
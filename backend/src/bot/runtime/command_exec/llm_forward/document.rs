@@ -0,0 +1,728 @@
+//! 文档格式识别与文本提取：PDF（文字层）、DOCX / PPTX / XLSX（OOXML）、EPUB
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+use super::html::html_to_text;
+
+/// 单个压缩包条目最多解压的字节数（防止压缩炸弹）
+const MAX_ENTRY_BYTES: u64 = 64 * 1024 * 1024;
+/// 每个工作表最多渲染的行 / 列
+const MAX_SHEET_ROWS: usize = 2000;
+const MAX_SHEET_COLS: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum DocumentKind {
+    Pdf,
+    Docx,
+    Pptx,
+    Xlsx,
+    Epub,
+}
+
+impl DocumentKind {
+    pub(super) fn ext(self) -> &'static str {
+        match self {
+            Self::Pdf => "pdf",
+            Self::Docx => "docx",
+            Self::Pptx => "pptx",
+            Self::Xlsx => "xlsx",
+            Self::Epub => "epub",
+        }
+    }
+}
+
+pub(super) struct ExtractedDocument {
+    pub(super) title: String,
+    pub(super) text: String,
+}
+
+/// 按文件头识别文档格式；普通文本或不认识的格式返回 None
+pub(super) fn sniff_document_kind(bytes: &[u8]) -> Option<DocumentKind> {
+    if bytes.starts_with(b"%PDF-") {
+        return Some(DocumentKind::Pdf);
+    }
+    if !bytes.starts_with(b"PK\x03\x04") {
+        return None;
+    }
+    // EPUB 规定 mimetype 必须是第一个且不压缩的条目
+    if bytes.len() > 58
+        && &bytes[30..38] == b"mimetype"
+        && bytes[38..].starts_with(b"application/epub+zip")
+    {
+        return Some(DocumentKind::Epub);
+    }
+    let archive = ZipArchive::new(Cursor::new(bytes)).ok()?;
+    let names: Vec<&str> = archive.file_names().collect();
+    let has = |name: &str| names.contains(&name);
+    if has("word/document.xml") {
+        Some(DocumentKind::Docx)
+    } else if has("ppt/presentation.xml") {
+        Some(DocumentKind::Pptx)
+    } else if has("xl/workbook.xml") {
+        Some(DocumentKind::Xlsx)
+    } else if has("META-INF/container.xml") {
+        Some(DocumentKind::Epub)
+    } else {
+        None
+    }
+}
+
+/// 粗略判断是否为二进制数据（含 NUL 字节）
+pub(super) fn looks_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(8192).any(|b| *b == 0)
+}
+
+/// 提取文档文本（CPU 密集，调用方应放在 spawn_blocking 中执行）
+pub(super) fn extract_document(
+    kind: DocumentKind,
+    bytes: &[u8],
+) -> Result<ExtractedDocument, String> {
+    match kind {
+        DocumentKind::Pdf => extract_pdf(bytes),
+        DocumentKind::Docx => extract_docx(bytes),
+        DocumentKind::Pptx => extract_pptx(bytes),
+        DocumentKind::Xlsx => extract_xlsx(bytes),
+        DocumentKind::Epub => extract_epub(bytes),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// PDF
+// ---------------------------------------------------------------------------
+
+/// PDF 文本字符串：带 BOM 的 UTF-16BE，否则按 PDFDocEncoding（近似 Latin-1）处理
+fn decode_pdf_string(raw: &[u8]) -> String {
+    if let Some(utf16) = raw.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    match std::str::from_utf8(raw) {
+        Ok(s) => s.to_string(),
+        Err(_) => raw.iter().map(|b| *b as char).collect(),
+    }
+}
+
+fn pdf_title(doc: &pdf_extract::Document) -> Option<String> {
+    let info = doc.trailer.get(b"Info").ok()?;
+    let (_, info) = doc.dereference(info).ok()?;
+    let title = info.as_dict().ok()?.get(b"Title").ok()?;
+    let (_, title) = doc.dereference(title).ok()?;
+    let title = decode_pdf_string(title.as_str().ok()?);
+    let title = title.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+fn extract_pdf(bytes: &[u8]) -> Result<ExtractedDocument, String> {
+    let mut doc =
+        pdf_extract::Document::load_mem(bytes).map_err(|e| format!("PDF 解析失败: {e}"))?;
+    if doc.is_encrypted() {
+        // 只处理无打开密码（仅限制编辑/复制）的文件
+        doc.decrypt("")
+            .map_err(|_| "PDF 已加密，无法读取内容".to_string())?;
+    }
+    let title = pdf_title(&doc).unwrap_or_default();
+
+    let mut text = String::new();
+    for page in doc.get_pages().keys() {
+        let mut page_text = String::new();
+        let mut output = pdf_extract::PlainTextOutput::new(&mut page_text);
+        if pdf_extract::output_doc_page(&doc, &mut output, *page).is_err() {
+            continue;
+        }
+        let page_text = page_text.trim();
+        if page_text.is_empty() {
+            continue;
+        }
+        text.push_str(&format!("--- 第 {page} 页 ---\n{page_text}\n\n"));
+    }
+    if text.trim().is_empty() {
+        return Err("PDF 中没有可提取的文字（可能是扫描件）".to_string());
+    }
+    Ok(ExtractedDocument { title, text })
+}
+
+// ---------------------------------------------------------------------------
+// OOXML / EPUB 公共部分
+// ---------------------------------------------------------------------------
+
+fn open_zip(bytes: &[u8]) -> Result<ZipArchive<Cursor<&[u8]>>, String> {
+    ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("文档解压失败: {e}"))
+}
+
+fn read_zip_entry(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Option<String> {
+    let entry = archive.by_name(name).ok()?;
+    let mut buf = Vec::new();
+    entry.take(MAX_ENTRY_BYTES).read_to_end(&mut buf).ok()?;
+    Some(String::from_utf8_lossy(&buf).into_owned())
+}
+
+enum XmlNode<'a> {
+    /// (本地标签名, 元素, 是否自闭合)
+    Open(String, BytesStart<'a>, bool),
+    Close(String),
+    Text(String),
+}
+
+fn local_name(e: &BytesStart) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).into_owned()
+}
+
+fn attr(e: &BytesStart, local: &str) -> Option<String> {
+    e.attributes().flatten().find_map(|a| {
+        (a.key.local_name().as_ref() == local.as_bytes())
+            .then(|| a.unescape_value().ok().map(|v| v.into_owned()))
+            .flatten()
+    })
+}
+
+/// 顺序遍历 XML，回调收到开始 / 结束标签和文本（实体已解码）
+fn walk_xml(xml: &str, mut on_node: impl FnMut(XmlNode)) {
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => on_node(XmlNode::Open(local_name(&e), e, false)),
+            Ok(Event::Empty(e)) => {
+                let name = local_name(&e);
+                on_node(XmlNode::Open(name.clone(), e, true));
+                on_node(XmlNode::Close(name));
+            }
+            Ok(Event::End(e)) => {
+                on_node(XmlNode::Close(
+                    String::from_utf8_lossy(e.local_name().as_ref()).into_owned(),
+                ));
+            }
+            Ok(Event::Text(e)) => {
+                if let Ok(text) = e.decode() {
+                    on_node(XmlNode::Text(text.into_owned()));
+                }
+            }
+            Ok(Event::CData(e)) => {
+                if let Ok(text) = e.decode() {
+                    on_node(XmlNode::Text(text.into_owned()));
+                }
+            }
+            Ok(Event::GeneralRef(e)) => {
+                let resolved = match e.resolve_char_ref() {
+                    Ok(Some(c)) => Some(c.to_string()),
+                    _ => match e.decode().as_deref() {
+                        Ok("amp") => Some("&".to_string()),
+                        Ok("lt") => Some("<".to_string()),
+                        Ok("gt") => Some(">".to_string()),
+                        Ok("quot") => Some("\"".to_string()),
+                        Ok("apos") => Some("'".to_string()),
+                        _ => None,
+                    },
+                };
+                if let Some(text) = resolved {
+                    on_node(XmlNode::Text(text));
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+}
+
+/// 读取 docProps/core.xml 中的 dc:title
+fn ooxml_title(archive: &mut ZipArchive<Cursor<&[u8]>>) -> String {
+    let Some(xml) = read_zip_entry(archive, "docProps/core.xml") else {
+        return String::new();
+    };
+    first_element_text(&xml, "title")
+}
+
+fn first_element_text(xml: &str, name: &str) -> String {
+    let mut depth = 0usize;
+    let mut out = String::new();
+    let mut done = false;
+    walk_xml(xml, |node| match node {
+        XmlNode::Open(n, _, false) if n == name && !done => depth += 1,
+        XmlNode::Close(n) if n == name && depth > 0 => {
+            depth -= 1;
+            done = true;
+        }
+        XmlNode::Text(t) if depth > 0 => out.push_str(&t),
+        _ => {}
+    });
+    out.trim().to_string()
+}
+
+/// 按文件名中的数字排序（slide2.xml 排在 slide10.xml 前面）
+fn numbered_entries(archive: &ZipArchive<Cursor<&[u8]>>, prefix: &str) -> Vec<String> {
+    let mut names: Vec<(u32, String)> = archive
+        .file_names()
+        .filter_map(|n| {
+            let num = n.strip_prefix(prefix)?.strip_suffix(".xml")?.parse().ok()?;
+            Some((num, n.to_string()))
+        })
+        .collect();
+    names.sort();
+    names.into_iter().map(|(_, n)| n).collect()
+}
+
+fn collapse_blank_lines(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut blank = 0;
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            blank += 1;
+            if blank > 1 {
+                continue;
+            }
+        } else {
+            blank = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.trim().to_string()
+}
+
+// ---------------------------------------------------------------------------
+// DOCX
+// ---------------------------------------------------------------------------
+
+fn docx_body_text(xml: &str) -> String {
+    let mut out = String::new();
+    let mut in_text = false;
+    let mut table_depth = 0usize;
+    let mut table_rows = 0usize;
+    let mut row_cells = 0usize;
+    let mut heading: Option<usize> = None;
+    let mut para_start = 0usize;
+
+    walk_xml(xml, |node| match node {
+        XmlNode::Open(name, e, empty) => match name.as_str() {
+            "t" => in_text = !empty,
+            "p" => {
+                para_start = out.len();
+                heading = None;
+            }
+            "pStyle" => {
+                let style = attr(&e, "val").unwrap_or_default().to_ascii_lowercase();
+                heading = if style == "title" {
+                    Some(1)
+                } else {
+                    style
+                        .strip_prefix("heading")
+                        .and_then(|n| n.trim().parse::<usize>().ok())
+                        .map(|n| n.clamp(1, 6))
+                };
+            }
+            "tab" => out.push('\t'),
+            "br" | "cr" => out.push(if table_depth > 0 { ' ' } else { '\n' }),
+            "tbl" => {
+                table_depth += 1;
+                if table_depth == 1 {
+                    table_rows = 0;
+                    out.push('\n');
+                }
+            }
+            "tr" if table_depth == 1 => {
+                row_cells = 0;
+                out.push('|');
+            }
+            _ => {}
+        },
+        XmlNode::Close(name) => match name.as_str() {
+            "t" => in_text = false,
+            "p" => {
+                if let Some(level) = heading.take().filter(|_| table_depth == 0) {
+                    if out.len() > para_start {
+                        out.insert_str(para_start, &format!("{} ", "#".repeat(level)));
+                    }
+                }
+                out.push(if table_depth > 0 { ' ' } else { '\n' });
+            }
+            "tc" if table_depth == 1 => {
+                let trimmed = out.trim_end_matches(' ').len();
+                out.truncate(trimmed);
+                out.push_str(" |");
+                row_cells += 1;
+            }
+            "tr" if table_depth == 1 => {
+                out.push('\n');
+                if table_rows == 0 {
+                    out.push('|');
+                    out.push_str(&" --- |".repeat(row_cells.max(1)));
+                    out.push('\n');
+                }
+                table_rows += 1;
+            }
+            "tbl" => {
+                table_depth = table_depth.saturating_sub(1);
+                if table_depth == 0 {
+                    out.push('\n');
+                }
+            }
+            _ => {}
+        },
+        XmlNode::Text(t) if in_text => {
+            if table_depth > 0 {
+                if out.ends_with('|') {
+                    out.push(' ');
+                }
+                out.push_str(&t.replace('|', "\\|"));
+            } else {
+                out.push_str(&t);
+            }
+        }
+        XmlNode::Text(_) => {}
+    });
+    collapse_blank_lines(&out)
+}
+
+fn extract_docx(bytes: &[u8]) -> Result<ExtractedDocument, String> {
+    let mut archive = open_zip(bytes)?;
+    let title = ooxml_title(&mut archive);
+    let xml = read_zip_entry(&mut archive, "word/document.xml")
+        .ok_or_else(|| "DOCX 缺少 word/document.xml".to_string())?;
+    let mut text = docx_body_text(&xml);
+
+    for (label, prefix) in [
+        ("脚注", "word/footnotes.xml"),
+        ("尾注", "word/endnotes.xml"),
+    ] {
+        if let Some(xml) = read_zip_entry(&mut archive, prefix) {
+            let notes = docx_body_text(&xml);
+            if !notes.is_empty() {
+                text.push_str(&format!("\n\n## {label}\n{notes}"));
+            }
+        }
+    }
+    Ok(ExtractedDocument { title, text })
+}
+
+// ---------------------------------------------------------------------------
+// PPTX
+// ---------------------------------------------------------------------------
+
+/// 幻灯片 / 备注中的文字：每个 a:p 一行
+fn drawingml_text(xml: &str) -> String {
+    let mut out = String::new();
+    let mut in_text = false;
+    walk_xml(xml, |node| match node {
+        XmlNode::Open(name, _, empty) if name == "t" => in_text = !empty,
+        XmlNode::Open(name, _, _) if name == "br" => out.push('\n'),
+        XmlNode::Close(name) if name == "t" => in_text = false,
+        XmlNode::Close(name) if name == "p" => out.push('\n'),
+        XmlNode::Text(t) if in_text => out.push_str(&t),
+        _ => {}
+    });
+    collapse_blank_lines(&out)
+}
+
+fn extract_pptx(bytes: &[u8]) -> Result<ExtractedDocument, String> {
+    let mut archive = open_zip(bytes)?;
+    let title = ooxml_title(&mut archive);
+    let slides = numbered_entries(&archive, "ppt/slides/slide");
+    if slides.is_empty() {
+        return Err("PPTX 中没有幻灯片".to_string());
+    }
+
+    let mut text = String::new();
+    for (idx, name) in slides.iter().enumerate() {
+        let n = idx + 1;
+        let body = read_zip_entry(&mut archive, name)
+            .map(|xml| drawingml_text(&xml))
+            .unwrap_or_default();
+        text.push_str(&format!("## 幻灯片 {n}\n{body}\n"));
+
+        let notes_name = format!("ppt/notesSlides/notesSlide{n}.xml");
+        if let Some(xml) = read_zip_entry(&mut archive, &notes_name) {
+            // 备注页会重复幻灯片编号等占位文字，只保留较长的内容
+            let notes = drawingml_text(&xml);
+            if notes.chars().count() > 3 {
+                text.push_str(&format!("备注:\n{notes}\n"));
+            }
+        }
+        text.push('\n');
+    }
+    Ok(ExtractedDocument { title, text })
+}
+
+// ---------------------------------------------------------------------------
+// XLSX
+// ---------------------------------------------------------------------------
+
+fn xlsx_shared_strings(archive: &mut ZipArchive<Cursor<&[u8]>>) -> Vec<String> {
+    let Some(xml) = read_zip_entry(archive, "xl/sharedStrings.xml") else {
+        return Vec::new();
+    };
+    let mut strings = Vec::new();
+    let mut current: Option<String> = None;
+    // 注音（rPh）中的文字不属于单元格内容
+    let mut in_phonetic = false;
+    walk_xml(&xml, |node| match node {
+        XmlNode::Open(name, _, false) if name == "si" => current = Some(String::new()),
+        XmlNode::Open(name, _, false) if name == "rPh" => in_phonetic = true,
+        XmlNode::Close(name) if name == "rPh" => in_phonetic = false,
+        XmlNode::Close(name) if name == "si" => strings.push(current.take().unwrap_or_default()),
+        XmlNode::Text(t) if !in_phonetic => {
+            if let Some(s) = current.as_mut() {
+                s.push_str(&t);
+            }
+        }
+        _ => {}
+    });
+    strings
+}
+
+/// 工作表名称及其 XML 路径（按工作簿中的顺序）
+fn xlsx_sheets(archive: &mut ZipArchive<Cursor<&[u8]>>) -> Vec<(String, String)> {
+    let mut rels = HashMap::new();
+    if let Some(xml) = read_zip_entry(archive, "xl/_rels/workbook.xml.rels") {
+        walk_xml(&xml, |node| {
+            if let XmlNode::Open(name, e, _) = node {
+                if name == "Relationship" {
+                    if let (Some(id), Some(target)) = (attr(&e, "Id"), attr(&e, "Target")) {
+                        let target = target.trim_start_matches('/');
+                        let path = if target.starts_with("xl/") {
+                            target.to_string()
+                        } else {
+                            format!("xl/{target}")
+                        };
+                        rels.insert(id, path);
+                    }
+                }
+            }
+        });
+    }
+
+    let mut sheets = Vec::new();
+    if let Some(xml) = read_zip_entry(archive, "xl/workbook.xml") {
+        walk_xml(&xml, |node| {
+            if let XmlNode::Open(name, e, _) = node {
+                if name == "sheet" {
+                    let sheet_name = attr(&e, "name").unwrap_or_default();
+                    if let Some(path) = attr(&e, "id").and_then(|id| rels.get(&id)) {
+                        sheets.push((sheet_name, path.clone()));
+                    }
+                }
+            }
+        });
+    }
+    if sheets.is_empty() {
+        sheets = numbered_entries(archive, "xl/worksheets/sheet")
+            .into_iter()
+            .enumerate()
+            .map(|(i, path)| (format!("Sheet{}", i + 1), path))
+            .collect();
+    }
+    sheets
+}
+
+/// 单元格引用（如 "AB12"）中的列号，从 0 开始
+fn column_index(cell_ref: &str) -> Option<usize> {
+    let letters: String = cell_ref
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect();
+    if letters.is_empty() {
+        return None;
+    }
+    letters
+        .bytes()
+        .try_fold(0usize, |acc, b| {
+            Some(acc * 26 + (b.to_ascii_uppercase() - b'A') as usize + 1)
+        })
+        .map(|n| n - 1)
+}
+
+fn xlsx_sheet_rows(xml: &str, shared: &[String]) -> (Vec<Vec<String>>, bool) {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut cell_col = 0usize;
+    let mut cell_type = String::new();
+    let mut value = String::new();
+    let mut in_value = false;
+    let mut truncated = false;
+
+    walk_xml(xml, |node| match node {
+        XmlNode::Open(name, e, _) => match name.as_str() {
+            "row" => row.clear(),
+            "c" => {
+                cell_col = attr(&e, "r")
+                    .and_then(|r| column_index(&r))
+                    .unwrap_or(row.len());
+                cell_type = attr(&e, "t").unwrap_or_default();
+                value.clear();
+            }
+            "v" | "t" => in_value = true,
+            _ => {}
+        },
+        XmlNode::Close(name) => match name.as_str() {
+            "v" | "t" => in_value = false,
+            "c" => {
+                if cell_col >= MAX_SHEET_COLS {
+                    truncated = true;
+                    return;
+                }
+                let text = match cell_type.as_str() {
+                    "s" => value
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| shared.get(i))
+                        .cloned()
+                        .unwrap_or_default(),
+                    "b" => if value.trim() == "1" { "TRUE" } else { "FALSE" }.to_string(),
+                    _ => value.clone(),
+                };
+                if row.len() <= cell_col {
+                    row.resize(cell_col + 1, String::new());
+                }
+                row[cell_col] = text;
+            }
+            "row" => {
+                if rows.len() >= MAX_SHEET_ROWS {
+                    truncated = true;
+                } else if row.iter().any(|c| !c.trim().is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                }
+            }
+            _ => {}
+        },
+        XmlNode::Text(t) if in_value => value.push_str(&t),
+        _ => {}
+    });
+    (rows, truncated)
+}
+
+fn render_markdown_table(rows: &[Vec<String>]) -> String {
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+    let cell = |s: &str| s.replace('|', "\\|").replace(['\r', '\n'], " ");
+    let mut out = String::new();
+    for (i, row) in rows.iter().enumerate() {
+        out.push('|');
+        for col in 0..width {
+            out.push(' ');
+            out.push_str(&cell(row.get(col).map(String::as_str).unwrap_or("")));
+            out.push_str(" |");
+        }
+        out.push('\n');
+        if i == 0 {
+            out.push('|');
+            out.push_str(&" --- |".repeat(width));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn extract_xlsx(bytes: &[u8]) -> Result<ExtractedDocument, String> {
+    let mut archive = open_zip(bytes)?;
+    let title = ooxml_title(&mut archive);
+    let shared = xlsx_shared_strings(&mut archive);
+    let sheets = xlsx_sheets(&mut archive);
+    if sheets.is_empty() {
+        return Err("XLSX 中没有工作表".to_string());
+    }
+
+    let mut text = String::new();
+    for (name, path) in sheets {
+        let Some(xml) = read_zip_entry(&mut archive, &path) else {
+            continue;
+        };
+        let (rows, truncated) = xlsx_sheet_rows(&xml, &shared);
+        text.push_str(&format!("## 工作表: {name}\n"));
+        if rows.is_empty() {
+            text.push_str("（空）\n\n");
+            continue;
+        }
+        text.push_str(&render_markdown_table(&rows));
+        if truncated {
+            text.push_str(&format!(
+                "（仅显示前 {MAX_SHEET_ROWS} 行 / {MAX_SHEET_COLS} 列）\n"
+            ));
+        }
+        text.push('\n');
+    }
+    Ok(ExtractedDocument { title, text })
+}
+
+// ---------------------------------------------------------------------------
+// EPUB
+// ---------------------------------------------------------------------------
+
+fn join_zip_path(base_dir: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or("").replace("%20", " ");
+    let mut parts: Vec<&str> = base_dir.split('/').filter(|p| !p.is_empty()).collect();
+    for seg in href.split('/') {
+        match seg {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            other => parts.push(other),
+        }
+    }
+    parts.join("/")
+}
+
+fn extract_epub(bytes: &[u8]) -> Result<ExtractedDocument, String> {
+    let mut archive = open_zip(bytes)?;
+    let container = read_zip_entry(&mut archive, "META-INF/container.xml")
+        .ok_or_else(|| "EPUB 缺少 META-INF/container.xml".to_string())?;
+    let mut opf_path = None;
+    walk_xml(&container, |node| {
+        if let XmlNode::Open(name, e, _) = node {
+            if name == "rootfile" && opf_path.is_none() {
+                opf_path = attr(&e, "full-path");
+            }
+        }
+    });
+    let opf_path = opf_path.ok_or_else(|| "EPUB 缺少 rootfile".to_string())?;
+    let opf =
+        read_zip_entry(&mut archive, &opf_path).ok_or_else(|| format!("EPUB 缺少 {opf_path}"))?;
+    let base_dir = opf_path.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
+
+    let title = first_element_text(&opf, "title");
+    let mut manifest = HashMap::new();
+    let mut spine = Vec::new();
+    walk_xml(&opf, |node| {
+        if let XmlNode::Open(name, e, _) = node {
+            match name.as_str() {
+                "item" => {
+                    if let (Some(id), Some(href)) = (attr(&e, "id"), attr(&e, "href")) {
+                        manifest.insert(id, href);
+                    }
+                }
+                "itemref" => {
+                    if let Some(idref) = attr(&e, "idref") {
+                        spine.push(idref);
+                    }
+                }
+                _ => {}
+            }
+        }
+    });
+
+    let mut text = String::new();
+    for idref in spine {
+        let Some(href) = manifest.get(&idref) else {
+            continue;
+        };
+        let path = join_zip_path(base_dir, href);
+        let Some(html) = read_zip_entry(&mut archive, &path) else {
+            continue;
+        };
+        let chapter = html_to_text(&html);
+        if !chapter.is_empty() {
+            text.push_str(&chapter);
+            text.push_str("\n\n");
+        }
+    }
+    if text.trim().is_empty() {
+        return Err("EPUB 中没有可提取的文字".to_string());
+    }
+    Ok(ExtractedDocument { title, text })
+}
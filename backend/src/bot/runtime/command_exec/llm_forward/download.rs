@@ -1,6 +1,10 @@
 use std::path::{Path, PathBuf};
 use tokio::io::AsyncWriteExt;
 
+use super::charset::decode_text;
+use super::document::{extract_document, looks_binary, sniff_document_kind};
+use super::html::{extract_article, looks_like_html};
+
 pub(super) struct TempFileGuard {
     pub(super) path: PathBuf,
 }
@@ -82,6 +86,8 @@ fn truncate_to_chars(s: &mut String, max_chars: usize) -> bool {
 pub(super) struct DocumentMeta {
     pub(super) title: String,
     pub(super) file_ext: Option<String>,
+    /// 响应的 MIME 类型（不含参数），仅 URL 下载时有值
+    pub(super) content_type: Option<String>,
     pub(super) size_bytes: Option<u64>,
     pub(super) truncated: bool,
 }
@@ -99,17 +105,23 @@ pub(super) async fn download_document_text(
     let max_bytes = max_bytes.clamp(1024, 50_000_000);
     let max_chars = max_chars.clamp(1000, 200_000) as usize;
 
-    let client = reqwest::Client::new();
-    let resp = client
-        .get(url)
+    let resp = crate::outbound::get(url)
+        .await?
         .timeout(timeout)
         .send()
         .await
-        .map_err(|e| format!("Download failed: {e}"))?;
+        .map_err(|e| format!("Download failed: {}", crate::outbound::describe_error(&e)))?;
 
     if !resp.status().is_success() {
         return Err(format!("Download failed: HTTP {}", resp.status()));
     }
+    let content_type = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    // 重定向后的最终地址，用于解析网页中的相对链接
+    let final_url = resp.url().clone();
 
     let mut file = tokio::fs::File::create(&guard.path)
         .await
@@ -147,14 +159,49 @@ pub(super) async fn download_document_text(
         }
     }
 
-    let mut text = String::from_utf8_lossy(&buf).to_string();
+    let mut file_ext = file_name
+        .and_then(|s| Path::new(s).extension().and_then(|e| e.to_str()))
+        .map(|s| s.to_lowercase());
+    let (mut text, title) = if let Some(kind) = sniff_document_kind(&buf) {
+        if truncated_by_bytes {
+            return Err(format!(
+                "{} 文件超过 {max_bytes} 字节上限，无法完整解析",
+                kind.ext().to_uppercase()
+            ));
+        }
+        file_ext = Some(kind.ext().to_string());
+        let doc = tokio::task::spawn_blocking(move || extract_document(kind, &buf))
+            .await
+            .map_err(|e| format!("文档解析异常: {e}"))??;
+        (doc.text, doc.title)
+    } else if looks_binary(&buf) {
+        return Err(format!(
+            "不支持的文件格式{}（仅支持文本、网页、PDF、DOCX、PPTX、XLSX、EPUB）",
+            file_ext
+                .as_deref()
+                .map(|e| format!(" .{e}"))
+                .unwrap_or_default()
+        ));
+    } else {
+        let text = decode_text(&buf, content_type.as_deref());
+        if looks_like_html(content_type.as_deref(), &text) {
+            let article = extract_article(&text, Some(&final_url));
+            file_ext.get_or_insert_with(|| "html".to_string());
+            (article.to_llm_text(final_url.as_str()), article.title)
+        } else {
+            (text, String::new())
+        }
+    };
     let truncated_by_chars = truncate_to_chars(&mut text, max_chars);
 
     let meta = DocumentMeta {
-        title: String::new(),
-        file_ext: file_name
-            .and_then(|s| Path::new(s).extension().and_then(|e| e.to_str()))
-            .map(|s| s.to_lowercase()),
+        title,
+        file_ext,
+        content_type: content_type
+            .as_deref()
+            .and_then(|ct| ct.split(';').next())
+            .map(|ct| ct.trim().to_ascii_lowercase())
+            .filter(|ct| !ct.is_empty()),
         size_bytes: Some(downloaded),
         truncated: truncated_by_bytes || truncated_by_chars,
     };
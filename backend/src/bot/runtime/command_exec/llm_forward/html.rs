@@ -0,0 +1,603 @@
+//! 网页正文提取：去掉脚本、导航、侧边栏等模板内容，保留标题、元信息、正文和链接列表。
+//! 打分方式参考 Readability：按段落文字量给父节点累计分数，再按链接密度折算。
+
+use scraper::{ElementRef, Html, Node, Selector};
+use std::collections::HashMap;
+
+const MIN_PARAGRAPH_CHARS: usize = 25;
+const MIN_ARTICLE_CHARS: usize = 200;
+const MAX_LINKS: usize = 30;
+
+/// 不参与正文的标签
+const SKIP_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "nav", "aside", "footer", "form", "iframe", "svg",
+    "canvas", "button", "select", "input", "textarea", "object", "embed", "dialog",
+];
+
+const BLOCK_TAGS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "header",
+    "blockquote",
+    "pre",
+    "table",
+    "tr",
+    "ul",
+    "ol",
+    "dl",
+    "dt",
+    "dd",
+    "figure",
+    "figcaption",
+    "hr",
+    "address",
+    "details",
+    "summary",
+];
+
+const POSITIVE_HINTS: &[&str] = &[
+    "article", "body", "content", "entry", "main", "page", "post", "text", "blog", "story",
+    "detail", "news",
+];
+
+const NEGATIVE_HINTS: &[&str] = &[
+    "comment",
+    "footer",
+    "footnote",
+    "nav",
+    "sidebar",
+    "side-bar",
+    "share",
+    "social",
+    "related",
+    "recommend",
+    "breadcrumb",
+    "menu",
+    "widget",
+    "banner",
+    "advert",
+    "sponsor",
+    "popup",
+    "modal",
+    "login",
+    "subscribe",
+    "copyright",
+    "toolbar",
+    "hot-list",
+    "hotlist",
+];
+
+pub(super) struct HtmlArticle {
+    pub(super) title: String,
+    pub(super) site_name: String,
+    pub(super) byline: String,
+    pub(super) published: String,
+    pub(super) description: String,
+    pub(super) text: String,
+    /// (链接文字, 绝对地址)
+    pub(super) links: Vec<(String, String)>,
+}
+
+impl HtmlArticle {
+    /// 组装成交给 LLM 的纯文本
+    pub(super) fn to_llm_text(&self, url: &str) -> String {
+        let mut out = String::new();
+        for (label, value) in [
+            ("标题", self.title.as_str()),
+            ("站点", self.site_name.as_str()),
+            ("作者", self.byline.as_str()),
+            ("发布时间", self.published.as_str()),
+            ("摘要", self.description.as_str()),
+            ("来源", url),
+        ] {
+            if !value.is_empty() {
+                out.push_str(&format!("{label}: {value}\n"));
+            }
+        }
+        out.push_str("\n正文:\n");
+        out.push_str(&self.text);
+        out.push('\n');
+        if !self.links.is_empty() {
+            out.push_str("\n正文中的链接:\n");
+            for (text, href) in &self.links {
+                out.push_str(&format!("- [{text}]({href})\n"));
+            }
+        }
+        out
+    }
+}
+
+/// 根据 Content-Type 或内容开头判断是否为 HTML
+pub(super) fn looks_like_html(content_type: Option<&str>, text: &str) -> bool {
+    if let Some(ct) = content_type {
+        let ct = ct.to_ascii_lowercase();
+        if ct.contains("text/html") || ct.contains("application/xhtml") {
+            return true;
+        }
+        if !ct.starts_with("text/plain") && !ct.contains("octet-stream") && !ct.is_empty() {
+            return false;
+        }
+    }
+    let head: String = text
+        .trim_start()
+        .chars()
+        .take(512)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    head.starts_with("<!doctype html") || head.starts_with("<html") || head.contains("<head")
+}
+
+fn selector(css: &str) -> Selector {
+    Selector::parse(css).expect("valid selector")
+}
+
+fn normalize_ws(input: &str) -> String {
+    input.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn first_meta(doc: &Html, selectors: &[&str]) -> String {
+    for css in selectors {
+        let sel = selector(css);
+        for el in doc.select(&sel) {
+            let value = el
+                .value()
+                .attr("content")
+                .or_else(|| el.value().attr("datetime"))
+                .map(normalize_ws)
+                .unwrap_or_else(|| normalize_ws(&el.text().collect::<String>()));
+            if !value.is_empty() {
+                return value;
+            }
+        }
+    }
+    String::new()
+}
+
+fn class_and_id(el: &ElementRef) -> String {
+    let mut s = el.value().attr("class").unwrap_or("").to_ascii_lowercase();
+    s.push(' ');
+    s.push_str(&el.value().id().unwrap_or("").to_ascii_lowercase());
+    s
+}
+
+fn class_weight(el: &ElementRef) -> f64 {
+    let hints = class_and_id(el);
+    let mut weight = 0.0;
+    if POSITIVE_HINTS.iter().any(|h| hints.contains(h)) {
+        weight += 25.0;
+    }
+    if NEGATIVE_HINTS.iter().any(|h| hints.contains(h)) {
+        weight -= 25.0;
+    }
+    weight
+}
+
+/// 明显是模板内容的节点（导航、评论、分享栏等）
+fn is_boilerplate(el: &ElementRef) -> bool {
+    let name = el.value().name();
+    if SKIP_TAGS.contains(&name) {
+        return true;
+    }
+    if matches!(name, "body" | "html" | "article" | "main") {
+        return false;
+    }
+    if el.value().attr("hidden").is_some()
+        || el.value().attr("aria-hidden") == Some("true")
+        || el
+            .value()
+            .attr("style")
+            .is_some_and(|s| s.replace(' ', "").contains("display:none"))
+    {
+        return true;
+    }
+    let hints = class_and_id(el);
+    NEGATIVE_HINTS.iter().any(|h| hints.contains(h))
+        && !POSITIVE_HINTS.iter().any(|h| hints.contains(h))
+}
+
+fn has_boilerplate_ancestor(el: &ElementRef) -> bool {
+    std::iter::once(*el)
+        .chain(el.ancestors().filter_map(ElementRef::wrap))
+        .any(|e| is_boilerplate(&e))
+}
+
+fn initial_score(el: &ElementRef) -> f64 {
+    let base = match el.value().name() {
+        "article" => 10.0,
+        "div" | "section" | "main" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    base + class_weight(el)
+}
+
+fn text_len(el: &ElementRef) -> usize {
+    el.text().map(|t| t.trim().chars().count()).sum()
+}
+
+fn link_density(el: &ElementRef, link_sel: &Selector) -> f64 {
+    let total = text_len(el);
+    if total == 0 {
+        return 1.0;
+    }
+    let linked: usize = el.select(link_sel).map(|a| text_len(&a)).sum();
+    linked as f64 / total as f64
+}
+
+/// 选出最可能是正文的节点；得分接近的兄弟节点（正文被拆成多个块时）一并返回，按文档顺序排列
+fn find_content_roots(doc: &Html) -> Vec<ElementRef<'_>> {
+    let link_sel = selector("a");
+    let mut scores = HashMap::new();
+
+    for para in doc.select(&selector("p, pre, td, blockquote")) {
+        if has_boilerplate_ancestor(&para) {
+            continue;
+        }
+        let text = normalize_ws(&para.text().collect::<String>());
+        let len = text.chars().count();
+        if len < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        let commas = text.matches([',', '，', '、', '。']).count() as f64;
+        let score = 1.0 + commas + (len as f64 / 100.0).min(3.0);
+
+        let ancestors = para.ancestors().filter_map(ElementRef::wrap).take(2);
+        for (level, ancestor) in ancestors.enumerate() {
+            let entry = scores
+                .entry(ancestor.id())
+                .or_insert_with(|| (ancestor, initial_score(&ancestor)));
+            entry.1 += if level == 0 { score } else { score / 2.0 };
+        }
+    }
+
+    let scores: HashMap<_, _> = scores
+        .into_iter()
+        .map(|(id, (el, score))| (id, (el, score * (1.0 - link_density(&el, &link_sel)))))
+        .collect();
+    let best = scores
+        .values()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .filter(|(el, _)| text_len(el) >= MIN_ARTICLE_CHARS)
+        .copied();
+
+    let Some((best, best_score)) = best else {
+        return ["article", "main", "[role=main]", "body"]
+            .iter()
+            .find_map(|css| doc.select(&selector(css)).next())
+            .into_iter()
+            .collect();
+    };
+
+    let threshold = (best_score * 0.2).max(10.0);
+    match best.parent() {
+        Some(parent) if best.value().name() != "body" => parent
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|sibling| {
+                sibling.id() == best.id()
+                    || scores
+                        .get(&sibling.id())
+                        .is_some_and(|(_, score)| *score >= threshold)
+            })
+            .collect(),
+        _ => vec![best],
+    }
+}
+
+/// 把节点渲染为带换行的纯文本（标题加 #，列表项加 -）
+#[derive(Default)]
+struct TextWriter {
+    out: String,
+    /// 源文本中此处有空白，下一个词前需要补一个空格
+    pending_space: bool,
+}
+
+/// 中日韩文字之间不补空格
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32, 0x2E80..=0x9FFF | 0xF900..=0xFAFF | 0xFF00..=0xFFEF)
+}
+
+impl TextWriter {
+    fn newlines(&mut self, n: usize) {
+        self.pending_space = false;
+        let trailing = self.out.chars().rev().take_while(|c| *c == '\n').count();
+        if self.out.is_empty() {
+            return;
+        }
+        for _ in trailing..n {
+            self.out.push('\n');
+        }
+    }
+
+    fn push_words(&mut self, text: &str) {
+        if text.starts_with(char::is_whitespace) {
+            self.pending_space = true;
+        }
+        for (i, word) in text.split_whitespace().enumerate() {
+            let last = self.out.chars().last();
+            let needs_space = (i > 0 || self.pending_space)
+                && last.is_some_and(|c| !c.is_whitespace())
+                && !(last.is_some_and(is_cjk) && word.starts_with(is_cjk));
+            if needs_space {
+                self.out.push(' ');
+            }
+            self.out.push_str(word);
+            self.pending_space = false;
+        }
+        if text.ends_with(char::is_whitespace) {
+            self.pending_space = true;
+        }
+    }
+
+    fn render(&mut self, el: ElementRef, root: bool) {
+        if !root && is_boilerplate(&el) {
+            return;
+        }
+        let name = el.value().name();
+        match name {
+            "br" => {
+                self.trim_trailing_spaces();
+                self.out.push('\n');
+                return;
+            }
+            "img" => {
+                if let Some(alt) = el
+                    .value()
+                    .attr("alt")
+                    .map(normalize_ws)
+                    .filter(|a| !a.is_empty())
+                {
+                    self.push_words(&format!("[图片: {alt}]"));
+                }
+                return;
+            }
+            "pre" => {
+                self.trim_trailing_spaces();
+                self.newlines(2);
+                let code: String = el.text().collect();
+                self.out.push_str("```\n");
+                self.out.push_str(code.trim_matches('\n'));
+                self.out.push_str("\n```");
+                self.newlines(2);
+                return;
+            }
+            _ => {}
+        }
+
+        let heading = name
+            .strip_prefix('h')
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|n| (1..=6).contains(n));
+        let is_block = heading.is_some() || BLOCK_TAGS.contains(&name);
+
+        if let Some(level) = heading {
+            self.trim_trailing_spaces();
+            self.newlines(2);
+            self.out.push_str(&"#".repeat(level));
+            self.out.push(' ');
+        } else if name == "li" {
+            self.trim_trailing_spaces();
+            self.newlines(1);
+            self.out.push_str("- ");
+        } else if is_block {
+            self.trim_trailing_spaces();
+            self.newlines(if name == "p" { 2 } else { 1 });
+        }
+
+        for child in el.children() {
+            match child.value() {
+                Node::Text(text) => self.push_words(text),
+                Node::Element(_) => {
+                    if let Some(child_el) = ElementRef::wrap(child) {
+                        self.render(child_el, false);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if heading.is_some() || is_block {
+            self.trim_trailing_spaces();
+            self.newlines(if heading.is_some() || name == "p" {
+                2
+            } else {
+                1
+            });
+        }
+    }
+
+    fn trim_trailing_spaces(&mut self) {
+        while self.out.ends_with(' ') {
+            self.out.pop();
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut result = String::with_capacity(self.out.len());
+        let mut blank_run = 0;
+        for line in self.out.lines() {
+            let line = line.trim_end();
+            if line.trim().is_empty() || line.trim() == "-" {
+                blank_run += 1;
+                if blank_run > 1 {
+                    continue;
+                }
+                result.push('\n');
+                continue;
+            }
+            blank_run = 0;
+            result.push_str(line);
+            result.push('\n');
+        }
+        result.trim().to_string()
+    }
+}
+
+fn collect_links(roots: &[ElementRef], base_url: Option<&reqwest::Url>) -> Vec<(String, String)> {
+    let link_sel = selector("a[href]");
+    let mut links = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    for a in roots.iter().flat_map(|root| root.select(&link_sel)) {
+        if has_boilerplate_ancestor(&a) {
+            continue;
+        }
+        let href = a.value().attr("href").unwrap_or("").trim();
+        if href.is_empty() || href.starts_with('#') {
+            continue;
+        }
+        let lower = href.to_ascii_lowercase();
+        if lower.starts_with("javascript:")
+            || lower.starts_with("mailto:")
+            || lower.starts_with("tel:")
+        {
+            continue;
+        }
+        let resolved = match base_url {
+            Some(base) => match base.join(href) {
+                Ok(u) => u.to_string(),
+                Err(_) => continue,
+            },
+            None => href.to_string(),
+        };
+        if !resolved.starts_with("http://") && !resolved.starts_with("https://") {
+            continue;
+        }
+        let text = normalize_ws(&a.text().collect::<String>());
+        if text.is_empty() || !seen.insert(resolved.clone()) {
+            continue;
+        }
+        links.push((text.chars().take(80).collect(), resolved));
+        if links.len() >= MAX_LINKS {
+            break;
+        }
+    }
+    links
+}
+
+/// 提取网页正文与元信息
+pub(super) fn extract_article(html: &str, base_url: Option<&reqwest::Url>) -> HtmlArticle {
+    let doc = Html::parse_document(html);
+
+    let mut title = first_meta(
+        &doc,
+        &[
+            "meta[property='og:title']",
+            "meta[name='twitter:title']",
+            "title",
+            "h1",
+        ],
+    );
+    let site_name = first_meta(
+        &doc,
+        &[
+            "meta[property='og:site_name']",
+            "meta[name='application-name']",
+        ],
+    );
+    // <title> 经常带 " - 站点名" 后缀
+    if !site_name.is_empty() {
+        for sep in [" - ", " | ", " _ ", " — ", "_"] {
+            if let Some(stripped) = title.strip_suffix(&format!("{sep}{site_name}")) {
+                title = stripped.trim().to_string();
+                break;
+            }
+        }
+    }
+    let byline = first_meta(
+        &doc,
+        &[
+            "meta[name='author']",
+            "meta[property='article:author']",
+            "[rel='author']",
+            "[itemprop='author']",
+        ],
+    );
+    let published = first_meta(
+        &doc,
+        &[
+            "meta[property='article:published_time']",
+            "meta[name='pubdate']",
+            "meta[name='publishdate']",
+            "meta[itemprop='datePublished']",
+            "time[datetime]",
+        ],
+    );
+    let description = first_meta(
+        &doc,
+        &[
+            "meta[name='description']",
+            "meta[property='og:description']",
+        ],
+    );
+
+    let roots = find_content_roots(&doc);
+    let mut writer = TextWriter::default();
+    for root in &roots {
+        writer.render(*root, true);
+    }
+    let text = writer.finish();
+    let links = collect_links(&roots, base_url);
+
+    HtmlArticle {
+        title,
+        site_name,
+        byline: byline.chars().take(100).collect(),
+        published,
+        description: description.chars().take(500).collect(),
+        text,
+        links,
+    }
+}
+
+/// 不做正文识别，直接把整页（如 EPUB 章节）渲染为纯文本
+pub(super) fn html_to_text(html: &str) -> String {
+    let doc = Html::parse_document(html);
+    let body = selector("body");
+    let mut writer = TextWriter::default();
+    if let Some(root) = doc.select(&body).next() {
+        writer.render(root, true);
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extract_article;
+
+    #[test]
+    fn keeps_article_and_drops_boilerplate() {
+        let page = r#"<html><head><title>新闻标题 - 示例网</title>
+            <meta property="og:site_name" content="示例网"><script>var x = 1;</script></head>
+            <body><nav><a href="/">首页</a></nav>
+            <div class="sidebar"><p>热门推荐：侧边栏里的推荐文本，不应该出现在正文里，再长一点点。</p></div>
+            <div id="article-content">
+              <p>今天，某地发生了一件重大的事情，引起了广泛关注。相关部门表示，将会尽快调查清楚。</p>
+              <p>据了解，这件事情的起因是多方面的，专家认为需要进一步研究，详见<a href="/report/1">调查报告</a>。</p>
+              <p>Another paragraph with <b>bold</b> text, and more words, to make the article long enough.</p>
+            </div>
+            <div class="comments"><p>评论：这篇文章写得真好，我非常喜欢，希望能看到更多类似的内容。</p></div>
+            </body></html>"#;
+        let base = reqwest::Url::parse("https://news.example.com/a/b.html").unwrap();
+        let article = extract_article(page, Some(&base));
+
+        assert_eq!(article.title, "新闻标题");
+        assert_eq!(article.site_name, "示例网");
+        assert!(article.text.contains("引起了广泛关注"));
+        assert!(article.text.contains("with bold text"));
+        assert!(!article.text.contains("热门推荐"));
+        assert!(!article.text.contains("评论"));
+        assert!(!article.text.contains("var x"));
+        assert_eq!(
+            article.links,
+            vec![(
+                "调查报告".to_string(),
+                "https://news.example.com/report/1".to_string()
+            )]
+        );
+    }
+}
@@ -8,7 +8,7 @@ use super::super::LlmForwardAudioFromUrlInput;
 use super::common::{
     call_chat_completions, download_binary_to_temp, get_record_base64_as_temp, log_llm_error,
     log_llm_len, read_file_as_data_url, reply_err, resolve_llm_config_by_name,
-    send_llm_markdown_as_forward_image, SendForwardImageInput,
+    send_llm_markdown_output, SendLlmOutputInput,
 };
 
 fn is_http_url(url: &str) -> bool {
@@ -45,6 +45,7 @@ pub(in super::super::super) async fn process_llm_forward_audio_from_url(
     let system_prompt = input.system_prompt;
     let prompt = input.prompt;
     let title = input.title;
+    let output_mode = input.output_mode;
 
     // Prefer OneBot `get_record` when available (handles silk/amr and returns a standard format).
     let (guard, bin_meta) = if let Some(record_file) = input.record_file {
@@ -221,15 +222,16 @@ pub(in super::super::super) async fn process_llm_forward_audio_from_url(
     };
 
     log_llm_len("语音分析", reply_content.len());
-    send_llm_markdown_as_forward_image(
+    send_llm_markdown_output(
         state,
         runtime,
         bot_id,
-        SendForwardImageInput {
+        SendLlmOutputInput {
             user_id,
             group_id,
             title,
             markdown: &reply_content,
+            output_mode,
         },
     )
     .await;
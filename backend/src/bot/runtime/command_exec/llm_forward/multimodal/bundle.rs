@@ -3,6 +3,7 @@ use std::path::Path;
 use std::sync::Arc;
 use tracing::warn;
 
+use crate::bot::runtime::privacy::{self, Scope};
 use crate::bot::runtime::BotRuntime;
 use crate::models::SharedState;
 
@@ -10,7 +11,7 @@ use super::super::LlmForwardMediaBundleInput;
 use super::common::{
     call_chat_completions, download_binary_to_temp, get_record_base64_as_temp, log_llm_error,
     log_llm_len, nonce12, read_file_as_data_url, reply_err, resolve_llm_config_by_name,
-    send_llm_markdown_as_forward_image, BinaryMeta, SendForwardImageInput,
+    send_llm_markdown_output, BinaryMeta, SendLlmOutputInput,
 };
 use super::image::{prepare_image_data_url, PreparedImageMeta};
 
@@ -43,6 +44,7 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
     let system_prompt = input.system_prompt;
     let prompt = input.prompt;
     let title = input.title;
+    let output_mode = input.output_mode;
 
     if input.items.is_empty() && input.text.map(|s| s.trim().is_empty()).unwrap_or(true) {
         reply_err(
@@ -350,7 +352,7 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
         let nonce = nonce12();
         let begin = format!("<<BEGIN_UNTRUSTED_FORWARD_TEXT:{}>>", nonce);
         let end = format!("<<END_UNTRUSTED_FORWARD_TEXT:{}>>", nonce);
-        let text = super::super::redact::redact_qq_ids(text);
+        let text = privacy::redact_text(bot_id, Scope::Llm, text);
         content_parts.push(json!({
             "type": "text",
             "text": format!("{begin}\n{text}\n{end}")
@@ -433,15 +435,16 @@ pub(in super::super::super) async fn process_llm_forward_media_bundle(
     };
 
     log_llm_len("多媒体分析", reply_content.len());
-    send_llm_markdown_as_forward_image(
+    send_llm_markdown_output(
         state,
         runtime,
         bot_id,
-        SendForwardImageInput {
+        SendLlmOutputInput {
             user_id,
             group_id,
             title,
             markdown: &reply_content,
+            output_mode,
         },
     )
     .await;
@@ -13,11 +13,16 @@ use crate::bot::runtime::api::send_reply;
 use crate::bot::runtime::BotRuntime;
 use crate::models::SharedState;
 
+use super::super::super::llm_trace::TraceCall;
+use super::super::super::media_cache;
+use super::super::super::web_search::{
+    is_search_tool, search_tool_definition, web_search, SearchSettings,
+};
 use super::super::download::TempFileGuard;
 
 mod forward;
 
-pub(in super::super) use forward::{send_llm_markdown_as_forward_image, SendForwardImageInput};
+pub(in super::super) use forward::{send_llm_markdown_output, SendLlmOutputInput};
 
 #[derive(Debug, Clone)]
 pub(in super::super::super) struct LlmConfig {
@@ -94,7 +99,7 @@ fn llm_http_semaphore() -> Arc<Semaphore> {
         .clone()
 }
 
-async fn acquire_llm_http_permit() -> Result<OwnedSemaphorePermit, LlmCallError> {
+pub(super) async fn acquire_llm_http_permit() -> Result<OwnedSemaphorePermit, LlmCallError> {
     llm_http_semaphore()
         .acquire_owned()
         .await
@@ -130,6 +135,8 @@ pub(in super::super) struct BinaryMeta {
     pub(in super::super) file_ext: Option<String>,
     pub(in super::super) size_bytes: u64,
     pub(in super::super) truncated: bool,
+    /// 内容哈希（进入媒体缓存时才有），用于缓存派生产物
+    pub(in super::super) content_hash: Option<String>,
 }
 
 pub(in super::super) async fn download_binary_to_temp(
@@ -143,14 +150,52 @@ pub(in super::super) async fn download_binary_to_temp(
 
     let timeout = std::time::Duration::from_millis(timeout_ms.clamp(1000, 120000));
     let max_bytes = max_bytes.clamp(10_000, 200_000_000);
+    let file_ext = file_name
+        .and_then(|s| Path::new(s).extension().and_then(|e| e.to_str()))
+        .map(|s| s.to_lowercase());
 
-    let client = reqwest::Client::new();
-    let resp = client
-        .get(url)
-        .timeout(timeout)
-        .send()
-        .await
-        .map_err(|e| format!("Download failed: {e}"))?;
+    // 先过出站策略（SSRF 防护与插件主机白名单），再查缓存：被拒绝的 URL 不能借缓存拿到内容
+    let request = crate::outbound::get(url).await?.timeout(timeout);
+
+    let send = |request: reqwest::RequestBuilder| async move {
+        request
+            .send()
+            .await
+            .map_err(|e| format!("Download failed: {}", crate::outbound::describe_error(&e)))
+    };
+    let cached_meta = |hit: media_cache::CachedSource| BinaryMeta {
+        file_name: file_name.map(|s| s.to_string()),
+        file_ext: file_ext.clone(),
+        size_bytes: hit.size,
+        truncated: false,
+        content_hash: Some(hit.hash),
+    };
+
+    let cache_key = media_cache::source_key_for_url(url);
+    let resp = match media_cache::check_source(&cache_key) {
+        Some(media_cache::SourceCheck::Immutable) => {
+            match media_cache::restore_source(&cache_key, &guard.path, max_bytes).await {
+                Some(hit) => return Ok((guard, cached_meta(hit))),
+                None => send(request).await?,
+            }
+        }
+        Some(media_cache::SourceCheck::Revalidate(validators)) => match request.try_clone() {
+            Some(conditional) => {
+                let resp = send(validators.apply(conditional)).await?;
+                if resp.status() != reqwest::StatusCode::NOT_MODIFIED {
+                    resp
+                } else if let Some(hit) =
+                    media_cache::restore_source(&cache_key, &guard.path, max_bytes).await
+                {
+                    return Ok((guard, cached_meta(hit)));
+                } else {
+                    send(request).await?
+                }
+            }
+            None => send(request).await?,
+        },
+        None => send(request).await?,
+    };
 
     if !resp.status().is_success() {
         return Err(format!("Download failed: HTTP {}", resp.status()));
@@ -165,6 +210,7 @@ pub(in super::super) async fn download_binary_to_temp(
             ));
         }
     }
+    let validators = media_cache::Validators::from_headers(resp.headers());
 
     let mut file = tokio::fs::File::create(&guard.path)
         .await
@@ -208,13 +254,24 @@ pub(in super::super) async fn download_binary_to_temp(
         }
     }
 
+    file.flush()
+        .await
+        .map_err(|e| format!("Write temp file failed: {e}"))?;
+    drop(file);
+
+    // 截断的文件不进入缓存
+    let content_hash = if truncated_by_bytes {
+        None
+    } else {
+        media_cache::store_source(&cache_key, &guard.path, validators).await
+    };
+
     let meta = BinaryMeta {
         file_name: file_name.map(|s| s.to_string()),
-        file_ext: file_name
-            .and_then(|s| Path::new(s).extension().and_then(|e| e.to_str()))
-            .map(|s| s.to_lowercase()),
+        file_ext,
         size_bytes: downloaded,
         truncated: truncated_by_bytes,
+        content_hash,
     };
 
     Ok((guard, meta))
@@ -358,6 +415,31 @@ pub(super) async fn get_record_base64_as_temp(
     file_name: Option<&str>,
     max_bytes: u64,
 ) -> Result<(TempFileGuard, BinaryMeta), String> {
+    let max_bytes = max_bytes.clamp(10_000, 200_000_000);
+    let safe_name = file_name.unwrap_or("record.wav");
+    let guard = TempFileGuard::new("record", Some(safe_name)).await?;
+    let file_ext = file_name
+        .and_then(|s| Path::new(s).extension().and_then(|e| e.to_str()))
+        .map(|s| s.to_lowercase());
+
+    // 语音经由 bot 自己的 OneBot 连接获取，不走出站 HTTP，无需主机策略检查；
+    // 文件 ID 只在所属 bot 内有效，缓存 key 按 bot 区分
+    let cache_key = media_cache::source_key_for_record(bot_id, record_file);
+    let cached = match media_cache::check_source(&cache_key) {
+        Some(_) => media_cache::restore_source(&cache_key, &guard.path, max_bytes).await,
+        None => None,
+    };
+    if let Some(hit) = cached {
+        let meta = BinaryMeta {
+            file_name: file_name.map(|s| s.to_string()),
+            file_ext,
+            size_bytes: hit.size,
+            truncated: false,
+            content_hash: Some(hit.hash),
+        };
+        return Ok((guard, meta));
+    }
+
     let resp = runtime
         .call_api(
             bot_id,
@@ -384,7 +466,6 @@ pub(super) async fn get_record_base64_as_temp(
         .decode(b64.as_bytes())
         .map_err(|e| format!("解析语音 base64 失败: {e}"))?;
 
-    let max_bytes = max_bytes.clamp(10_000, 200_000_000);
     if (bytes.len() as u64) > max_bytes {
         return Err(format!(
             "语音过大：{} bytes，超过限制 {} bytes",
@@ -393,23 +474,28 @@ pub(super) async fn get_record_base64_as_temp(
         ));
     }
 
-    let safe_name = file_name.unwrap_or("record.wav");
-    let guard = TempFileGuard::new("record", Some(safe_name)).await?;
-
     let mut file = tokio::fs::File::create(&guard.path)
         .await
         .map_err(|e| format!("Create temp file failed: {e}"))?;
     file.write_all(&bytes)
         .await
         .map_err(|e| format!("Write temp file failed: {e}"))?;
+    file.flush()
+        .await
+        .map_err(|e| format!("Write temp file failed: {e}"))?;
+    drop(file);
 
     let meta = BinaryMeta {
         file_name: file_name.map(|s| s.to_string()),
-        file_ext: file_name
-            .and_then(|s| Path::new(s).extension().and_then(|e| e.to_str()))
-            .map(|s| s.to_lowercase()),
+        file_ext,
         size_bytes: bytes.len() as u64,
         truncated: false,
+        content_hash: media_cache::store_source(
+            &cache_key,
+            &guard.path,
+            media_cache::Validators::default(),
+        )
+        .await,
     };
 
     Ok((guard, meta))
@@ -527,6 +613,27 @@ pub(in super::super::super) async fn call_chat_completions(
     api_key: &str,
     request_body: &serde_json::Value,
     max_request_bytes: u64,
+) -> Result<String, LlmCallError> {
+    let trace = TraceCall::begin("chat", base_url, request_body);
+    let mut attempts = 0usize;
+    let result =
+        send_chat_completions(base_url, api_key, request_body, max_request_bytes, &mut attempts)
+            .await;
+    if let Some(trace) = trace {
+        match &result {
+            Ok(content) => trace.finish_ok(attempts, content),
+            Err(e) => trace.finish_err(attempts, &e.to_string(), e.http_status()),
+        }
+    }
+    result
+}
+
+async fn send_chat_completions(
+    base_url: &str,
+    api_key: &str,
+    request_body: &serde_json::Value,
+    max_request_bytes: u64,
+    attempts: &mut usize,
 ) -> Result<String, LlmCallError> {
     fn mask_long_digits_for_log(input: &str) -> String {
         let mut out = String::with_capacity(input.len());
@@ -572,10 +679,9 @@ pub(in super::super::super) async fn call_chat_completions(
 
     fn extract_content_from_choice(choice: &serde_json::Value) -> Option<String> {
         let message = choice.get("message")?;
-        let content = message.get("content")?;
-        match content {
-            serde_json::Value::String(s) => Some(s.to_string()),
-            serde_json::Value::Array(parts) => {
+        let content = match message.get("content") {
+            Some(serde_json::Value::String(s)) if !s.trim().is_empty() => Some(s.to_string()),
+            Some(serde_json::Value::Array(parts)) => {
                 let mut out = String::new();
                 for part in parts {
                     match part {
@@ -599,7 +705,18 @@ pub(in super::super::super) async fn call_chat_completions(
                 }
             }
             _ => None,
-        }
+        };
+        // 强制工具调用（结构化输出）时 content 为 null 或空串，结果在 tool_calls 的 arguments 里
+        content.or_else(|| {
+            message
+                .get("tool_calls")
+                .and_then(|t| t.get(0))
+                .and_then(|t| t.get("function"))
+                .and_then(|f| f.get("arguments"))
+                .and_then(|a| a.as_str())
+                .filter(|a| !a.trim().is_empty())
+                .map(|a| a.to_string())
+        })
     }
 
     fn extract_chat_content(v: &serde_json::Value) -> Option<String> {
@@ -654,6 +771,7 @@ pub(in super::super::super) async fn call_chat_completions(
     let max_attempts: usize = 3;
 
     for attempt in 0..max_attempts {
+        *attempts = attempt + 1;
         let attempt_result: Result<(reqwest::StatusCode, reqwest::header::HeaderMap, String), LlmCallError> =
             {
                 let _permit = acquire_llm_http_permit().await?;
@@ -775,119 +893,60 @@ pub(in super::super::super) async fn call_chat_completions(
     Err(LlmCallError::Transport("LLM 重试失败".to_string()))
 }
 
-/// Tavily 搜索工具定义
-fn tavily_tool_definition() -> serde_json::Value {
-    json!({
-        "type": "function",
-        "function": {
-            "name": "tavily_search",
-            "description": "Search the web for current information using Tavily. Use this when you need to find up-to-date information, facts, news, or any information that might have changed after your knowledge cutoff.",
-            "parameters": {
-                "type": "object",
-                "properties": {
-                    "query": {
-                        "type": "string",
-                        "description": "The search query to look up on the web"
-                    }
-                },
-                "required": ["query"]
-            }
-        }
-    })
-}
-
-/// 调用 Tavily 搜索 API
-async fn call_tavily_search(tavily_api_key: &str, query: &str) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let resp = client
-        .post("https://api.tavily.com/search")
-        .header("Content-Type", "application/json")
-        .json(&json!({
-            "api_key": tavily_api_key,
-            "query": query,
-            "search_depth": "basic",
-            "include_answer": true,
-            "include_raw_content": false,
-            "max_results": 5
-        }))
-        .timeout(std::time::Duration::from_secs(30))
-        .send()
-        .await
-        .map_err(|e| format!("Tavily 请求失败: {e}"))?;
-
-    let status = resp.status();
-    let text = resp
-        .text()
-        .await
-        .map_err(|e| format!("读取 Tavily 响应失败: {e}"))?;
-
-    if !status.is_success() {
-        return Err(format!("Tavily API 错误 (HTTP {}): {}", status, text));
-    }
-
-    let v: serde_json::Value =
-        serde_json::from_str(&text).map_err(|e| format!("解析 Tavily 响应失败: {e}"))?;
-
-    // 构建搜索结果摘要
-    let mut result = String::new();
-
-    // 如果有 answer，优先使用
-    if let Some(answer) = v.get("answer").and_then(|a| a.as_str()) {
-        result.push_str("## 搜索摘要\n");
-        result.push_str(answer);
-        result.push_str("\n\n");
-    }
-
-    // 添加搜索结果
-    if let Some(results) = v.get("results").and_then(|r| r.as_array()) {
-        result.push_str("## 搜索结果\n\n");
-        for (i, item) in results.iter().take(5).enumerate() {
-            let title = item
-                .get("title")
-                .and_then(|t| t.as_str())
-                .unwrap_or("无标题");
-            let url = item.get("url").and_then(|u| u.as_str()).unwrap_or("");
-            let content = item
-                .get("content")
-                .and_then(|c| c.as_str())
-                .unwrap_or("无内容");
-
-            result.push_str(&format!("### {}. {}\n", i + 1, title));
-            result.push_str(&format!("链接: {}\n", url));
-            result.push_str(&format!("{}\n\n", content));
+/// 调用支持联网搜索的 LLM
+/// 如果配置了搜索服务，则使用函数调用模式
+/// 否则回退到简单的搜索参数模式
+pub(in super::super::super) async fn call_chat_completions_with_search(
+    base_url: &str,
+    api_key: &str,
+    request_body: &serde_json::Value,
+    max_request_bytes: u64,
+    enable_search: bool,
+    search: Option<&SearchSettings>,
+) -> Result<String, LlmCallError> {
+    let trace = TraceCall::begin("chat_with_search", base_url, request_body);
+    let mut attempts = 0usize;
+    let result = send_chat_completions_with_search(
+        base_url,
+        api_key,
+        request_body,
+        max_request_bytes,
+        enable_search,
+        search,
+        &mut attempts,
+    )
+    .await;
+    if let Some(trace) = trace {
+        match &result {
+            Ok(content) => trace.finish_ok(attempts, content),
+            Err(e) => trace.finish_err(attempts, &e.to_string(), e.http_status()),
         }
     }
-
-    if result.is_empty() {
-        result = "未找到相关搜索结果".to_string();
-    }
-
-    Ok(result)
+    result
 }
 
-/// 调用支持 Tavily 搜索的 LLM
-/// 如果提供了 tavily_api_key，则使用函数调用模式
-/// 否则回退到简单的搜索参数模式
-pub(in super::super::super) async fn call_chat_completions_with_tavily(
+async fn send_chat_completions_with_search(
     base_url: &str,
     api_key: &str,
     request_body: &serde_json::Value,
     max_request_bytes: u64,
     enable_search: bool,
-    tavily_api_key: Option<&str>,
+    search: Option<&SearchSettings>,
+    attempts: &mut usize,
 ) -> Result<String, LlmCallError> {
     let client = reqwest::Client::new();
     let url = format!("{}/chat/completions", base_url.trim_end_matches('/'));
 
-    // 如果有 Tavily API key 且启用搜索，使用函数调用模式
-    if enable_search && tavily_api_key.is_some() && !tavily_api_key.unwrap().is_empty() {
-        return call_with_tavily_tool_loop(
+    // 如果配置了搜索服务且启用搜索，使用函数调用模式
+    if let Some(search) = search.filter(|_| enable_search) {
+        return call_with_search_tool_loop(
             &client,
             &url,
             api_key,
             request_body,
             max_request_bytes,
-            tavily_api_key.unwrap(),
+            search,
+            attempts,
         )
         .await;
     }
@@ -915,6 +974,7 @@ pub(in super::super::super) async fn call_chat_completions_with_tavily(
     let max_attempts: usize = 3;
 
     for attempt in 0..max_attempts {
+        *attempts += 1;
         let attempt_result: Result<(reqwest::StatusCode, reqwest::header::HeaderMap, String), LlmCallError> =
             {
                 let _permit = acquire_llm_http_permit().await?;
@@ -1008,15 +1068,16 @@ pub(in super::super::super) async fn call_chat_completions_with_tavily(
     Err(LlmCallError::Transport("LLM 重试失败".to_string()))
 }
 
-/// 使用 Tavily 工具的循环调用
-/// 处理 LLM 的 tool_calls，调用 Tavily，然后继续对话直到获得最终回复
-async fn call_with_tavily_tool_loop(
+/// 使用搜索工具的循环调用
+/// 处理 LLM 的 tool_calls，调用搜索服务，然后继续对话直到获得最终回复
+async fn call_with_search_tool_loop(
     client: &reqwest::Client,
     url: &str,
     api_key: &str,
     request_body: &serde_json::Value,
     max_request_bytes: u64,
-    tavily_api_key: &str,
+    search: &SearchSettings,
+    attempts: &mut usize,
 ) -> Result<String, LlmCallError> {
     let mut messages = request_body
         .get("messages")
@@ -1036,7 +1097,7 @@ async fn call_with_tavily_tool_loop(
         let mut body = json!({
             "model": model,
             "messages": messages,
-            "tools": [tavily_tool_definition()],
+            "tools": [search_tool_definition()],
             "tool_choice": "auto"
         });
         if let Some(max_tok) = &max_tokens {
@@ -1057,6 +1118,7 @@ async fn call_with_tavily_tool_loop(
         let mut ok_text: Option<String> = None;
 
         for attempt in 0..max_attempts {
+            *attempts += 1;
             let attempt_result: Result<(reqwest::StatusCode, reqwest::header::HeaderMap, String), LlmCallError> =
                 {
                     let _permit = acquire_llm_http_permit().await?;
@@ -1173,7 +1235,7 @@ async fn call_with_tavily_tool_loop(
                         .and_then(|n| n.as_str())
                         .unwrap_or("");
 
-                    if function_name == "tavily_search" {
+                    if is_search_tool(function_name) {
                         let arguments = function
                             .and_then(|f| f.get("arguments"))
                             .and_then(|a| a.as_str())
@@ -1183,13 +1245,12 @@ async fn call_with_tavily_tool_loop(
                             serde_json::from_str(arguments).unwrap_or(json!({}));
                         let query = args.get("query").and_then(|q| q.as_str()).unwrap_or("");
 
-                        info!("Tavily 搜索: {}", query);
+                        info!("{} 搜索: {}", search.provider.name(), query);
 
-                        // 调用 Tavily
-                        let search_result = match call_tavily_search(tavily_api_key, query).await {
-                            Ok(result) => result,
+                        let search_result = match web_search(search, query).await {
+                            Ok(result) => result.to_markdown(),
                             Err(e) => {
-                                error!("Tavily 搜索失败: {}", e);
+                                error!("{} 搜索失败: {}", search.provider.name(), e);
                                 format!("搜索失败: {}", e)
                             }
                         };
@@ -1281,6 +1342,15 @@ pub(super) async fn call_audio_transcription(
         .await
         .map_err(|e| format!("读取音频失败: {e}"))?;
 
+    let content_hash = media_cache::hash_bytes(&bytes);
+    let cache_params = format!("{}|{model}", base_url.trim_end_matches('/'));
+    if let Some(text) = media_cache::get_derived(&content_hash, "transcript", &cache_params)
+        .await
+        .and_then(|b| String::from_utf8(b).ok())
+    {
+        return Ok(text);
+    }
+
     let part = reqwest::multipart::Part::bytes(bytes)
         .file_name(file_name.to_string())
         .mime_str(guess_transcription_mime(file_name))
@@ -1329,12 +1399,108 @@ pub(super) async fn call_audio_transcription(
 
     let v: serde_json::Value =
         serde_json::from_str(&text).map_err(|e| format!("解析转写响应失败: {e}"))?;
-    v.get("text")
+    let transcript = v
+        .get("text")
         .or_else(|| v.get("transcript"))
         .or_else(|| v.get("data").and_then(|d| d.get("text")))
         .and_then(|v| v.as_str())
         .map(|s| s.to_string())
-        .ok_or_else(|| "音频转写失败：无法获取文本".to_string())
+        .ok_or_else(|| "音频转写失败：无法获取文本".to_string())?;
+    media_cache::put_derived(
+        &content_hash,
+        "transcript",
+        &cache_params,
+        transcript.as_bytes(),
+    )
+    .await;
+    Ok(transcript)
+}
+
+/// 调用 OpenAI 兼容的 `/embeddings` 接口，按输入顺序返回向量
+pub(in super::super::super) async fn call_embeddings(
+    base_url: &str,
+    api_key: &str,
+    model: &str,
+    inputs: &[String],
+) -> Result<Vec<Vec<f32>>, String> {
+    if inputs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let client = reqwest::Client::new();
+    let url = format!("{}/embeddings", base_url.trim_end_matches('/'));
+    let body = json!({
+        "model": model,
+        "input": inputs,
+    });
+
+    let (status, text) = {
+        let _permit = acquire_llm_http_permit()
+            .await
+            .map_err(|e| format!("向量化并发控制失败: {e}"))?;
+        let resp = client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .json(&body)
+            .timeout(std::time::Duration::from_secs(120))
+            .send()
+            .await
+            .map_err(|e| format!("向量化请求失败: {}", e))?;
+
+        let status = resp.status();
+        let text = resp
+            .text()
+            .await
+            .map_err(|e| format!("读取向量化响应失败: {}", e))?;
+        (status, text)
+    };
+
+    if !status.is_success() {
+        let msg = serde_json::from_str::<serde_json::Value>(&text)
+            .ok()
+            .and_then(|v| {
+                v.get("error")
+                    .and_then(|e| e.get("message").cloned().or_else(|| Some(e.clone())))
+                    .or_else(|| v.get("message").cloned())
+            })
+            .and_then(|v| v.as_str().map(|s| s.to_string()))
+            .unwrap_or_else(|| text.chars().take(400).collect());
+        return Err(format!("向量化失败 (HTTP {}): {}", status, msg));
+    }
+
+    let v: serde_json::Value =
+        serde_json::from_str(&text).map_err(|e| format!("解析向量化响应失败: {e}"))?;
+    let data = v
+        .get("data")
+        .and_then(|d| d.as_array())
+        .ok_or_else(|| "向量化失败：响应缺少 data 字段".to_string())?;
+
+    let mut out: Vec<Option<Vec<f32>>> = vec![None; inputs.len()];
+    for (pos, item) in data.iter().enumerate() {
+        let idx = item
+            .get("index")
+            .and_then(|i| i.as_u64())
+            .map(|i| i as usize)
+            .unwrap_or(pos);
+        let Some(slot) = out.get_mut(idx) else {
+            continue;
+        };
+        let embedding = item
+            .get("embedding")
+            .and_then(|e| e.as_array())
+            .ok_or_else(|| "向量化失败：响应缺少 embedding 字段".to_string())?;
+        *slot = Some(
+            embedding
+                .iter()
+                .filter_map(|x| x.as_f64())
+                .map(|x| x as f32)
+                .collect(),
+        );
+    }
+
+    out.into_iter()
+        .map(|v| v.ok_or_else(|| "向量化失败：返回的向量数量与输入不一致".to_string()))
+        .collect()
 }
 
 pub(super) fn nonce12() -> String {
@@ -1,48 +1,242 @@
-use crate::bot::runtime::api::{send_api, send_reply};
+use crate::bot::runtime::api::{send_api, send_api_confirmed, send_reply_segments};
+use crate::bot::runtime::long_message::split_text;
+use crate::bot::runtime::privacy::{self, Scope};
 use crate::bot::runtime::BotRuntime;
 use crate::models::SharedState;
 use crate::render_image::render_markdown_image;
+use crate::segment::MessageSegment;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use serde_json::json;
 use std::sync::Arc;
-use tracing::error;
+use std::time::Duration;
+use tracing::{error, warn};
 
-pub(in super::super::super) struct SendForwardImageInput<'a> {
+use super::super::super::output_extract::build_plain_supplement_nodes;
+
+/// 纯文本模式每条消息的字符数（QQ 单条过长会被拒收或折叠）
+const TEXT_CHUNK_CHARS: usize = 1500;
+/// 合并转发模式每个文本节点的字符数
+const FORWARD_NODE_CHARS: usize = 3000;
+
+/// LLM 结果的发送方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputMode {
+    /// 渲染为图片，放在合并转发中（默认）
+    Image,
+    /// 合并转发，内容为文本节点
+    Forward,
+    /// 普通文本消息，按长度拆成多条
+    Text,
+    /// 作为 `.md` 文件上传
+    File,
+}
+
+impl OutputMode {
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "image" => Some(Self::Image),
+            "forward" => Some(Self::Forward),
+            "text" => Some(Self::Text),
+            "file" | "md" | "markdown" => Some(Self::File),
+            _ => None,
+        }
+    }
+}
+
+/// 发送方式优先级：调用参数 > LLM 模块配置 `output_mode` > 平台默认（Discord 为文本，其余为图片）
+async fn resolve_output_mode(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    requested: Option<&str>,
+) -> OutputMode {
+    if let Some(mode) = requested.and_then(OutputMode::parse) {
+        return mode;
+    }
+    let configured = crate::module::get_effective_module(state, bot_id, "llm").and_then(|m| {
+        m.config
+            .get("output_mode")
+            .and_then(|v| v.as_str())
+            .and_then(OutputMode::parse)
+    });
+    if let Some(mode) = configured {
+        return mode;
+    }
+    if runtime.is_discord(bot_id).await {
+        OutputMode::Text
+    } else {
+        OutputMode::Image
+    }
+}
+
+pub(in super::super::super) struct SendLlmOutputInput<'a> {
     pub(in super::super::super) user_id: u64,
     pub(in super::super::super) group_id: u64,
     pub(in super::super::super) title: &'a str,
     pub(in super::super::super) markdown: &'a str,
+    /// 本次调用指定的发送方式（为空则按 bot 配置）
+    pub(in super::super::super) output_mode: Option<&'a str>,
 }
 
-pub(in super::super::super) async fn send_llm_markdown_as_forward_image(
+/// 按发送方式输出 LLM 结果；图片渲染失败时改用文本合并转发，文件上传失败时改用纯文本
+pub(in super::super::super) async fn send_llm_markdown_output(
     state: &SharedState,
     runtime: &Arc<BotRuntime>,
     bot_id: &str,
-    input: SendForwardImageInput<'_>,
+    input: SendLlmOutputInput<'_>,
 ) {
     let user_id = input.user_id;
     let group_id = input.group_id;
     let title = input.title;
-    let markdown = super::super::super::redact::redact_qq_ids(input.markdown);
+    let markdown = privacy::redact_text(bot_id, Scope::Outgoing, input.markdown);
+
+    let mode = resolve_output_mode(state, runtime, bot_id, input.output_mode).await;
+    match mode {
+        OutputMode::Image => match render_markdown_image(title, "分析报告", &markdown, 520).await
+        {
+            Ok(img_base64) => {
+                let mut contents = vec![MessageSegment::image_base64(&img_base64)];
+                contents.extend(
+                    build_plain_supplement_nodes(&markdown)
+                        .into_iter()
+                        .map(MessageSegment::text),
+                );
+                send_forward_nodes(state, runtime, bot_id, user_id, group_id, title, contents)
+                    .await;
+            }
+            Err(e) => {
+                error!("[{}] render_markdown_image failed: {}", bot_id, e);
+                send_text_forward(state, runtime, bot_id, user_id, group_id, title, &markdown)
+                    .await;
+            }
+        },
+        OutputMode::Forward => {
+            send_text_forward(state, runtime, bot_id, user_id, group_id, title, &markdown).await;
+        }
+        OutputMode::Text => {
+            send_text_chunks(runtime, bot_id, user_id, group_id, title, &markdown).await;
+        }
+        OutputMode::File => {
+            if let Err(e) =
+                upload_markdown_file(runtime, bot_id, user_id, group_id, title, &markdown).await
+            {
+                warn!("[{}] 上传 markdown 文件失败，改为文本发送: {}", bot_id, e);
+                send_text_chunks(runtime, bot_id, user_id, group_id, title, &markdown).await;
+                return;
+            }
+            // 文件不便直接复制，链接与代码块另行发送
+            let group_id_opt = (group_id != 0).then_some(group_id);
+            for content in build_plain_supplement_nodes(&markdown) {
+                let segments = [MessageSegment::text(content)];
+                send_reply_segments(runtime, bot_id, user_id, group_id_opt, &segments).await;
+            }
+        }
+    }
+}
 
+/// 合并转发：正文按节点长度拆分，后附链接与代码块；LLM 输出一律作为文本段，不解析其中的 CQ 码
+async fn send_text_forward(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    user_id: u64,
+    group_id: u64,
+    title: &str,
+    markdown: &str,
+) {
+    let contents = split_text(markdown, FORWARD_NODE_CHARS)
+        .into_iter()
+        .chain(build_plain_supplement_nodes(markdown))
+        .map(MessageSegment::text)
+        .collect();
+    send_forward_nodes(state, runtime, bot_id, user_id, group_id, title, contents).await;
+}
+
+/// 纯文本：按长度拆成多条，代码块尽量完整地放在同一条中便于复制；以文本段发送，避免 CQ 码注入
+async fn send_text_chunks(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    user_id: u64,
+    group_id: u64,
+    title: &str,
+    markdown: &str,
+) {
     let group_id_opt = (group_id != 0).then_some(group_id);
-    let now = chrono::Local::now();
+    let chunks = split_text(markdown, TEXT_CHUNK_CHARS);
+    let total = chunks.len();
+    for (idx, chunk) in chunks.into_iter().enumerate() {
+        let msg = match (idx, total) {
+            (0, 1) => format!("{title}\n{chunk}"),
+            (0, _) => format!("{title}（1/{total}）\n{chunk}"),
+            _ => format!("（{}/{}）\n{}", idx + 1, total, chunk),
+        };
+        let segments = [MessageSegment::text(msg)];
+        send_reply_segments(runtime, bot_id, user_id, group_id_opt, &segments).await;
+    }
+}
 
-    let img_base64 = match render_markdown_image(title, "分析报告", &markdown, 520).await {
-        Ok(img) => img,
-        Err(e) => {
-            error!("[{}] render_markdown_image failed: {}", bot_id, e);
-            send_reply(
-                runtime,
-                bot_id,
-                user_id,
-                group_id_opt,
-                "分析失败：结果渲染为图片失败（wkhtmltoimage 不可用或渲染报错）",
-            )
-            .await;
-            return;
-        }
+fn markdown_file_name(title: &str) -> String {
+    let name: String = title
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(60)
+        .collect();
+    let name = name.trim();
+    if name.is_empty() {
+        "result.md".to_string()
+    } else {
+        format!("{name}.md")
+    }
+}
+
+/// 以 `.md` 文件上传到群文件 / 私聊
+async fn upload_markdown_file(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    user_id: u64,
+    group_id: u64,
+    title: &str,
+    markdown: &str,
+) -> Result<(), String> {
+    let body = format!("# {}\n\n{}\n", title.trim(), markdown.trim_end());
+    let file = format!("base64://{}", BASE64.encode(body.as_bytes()));
+    let name = markdown_file_name(title);
+    let (action, params) = if group_id != 0 {
+        (
+            "upload_group_file",
+            json!({ "group_id": group_id, "file": file, "name": name }),
+        )
+    } else {
+        (
+            "upload_private_file",
+            json!({ "user_id": user_id, "file": file, "name": name }),
+        )
     };
 
+    // 经出站队列发送并等待回执，失败时由调用方改用文本
+    send_api_confirmed(runtime, bot_id, action, params, Duration::from_secs(120))
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+/// 发送合并转发：首个节点为标题与时间，其后每个消息段各占一个节点（文本段会转义为 CQ 文本）
+async fn send_forward_nodes(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    user_id: u64,
+    group_id: u64,
+    title: &str,
+    contents: Vec<MessageSegment>,
+) {
+    let now = chrono::Local::now();
+
     let bot_name = state
         .bots
         .get(bot_id)
@@ -66,42 +260,27 @@ pub(in super::super::super) async fn send_llm_markdown_as_forward_image(
         .or_else(|| bot_id.parse::<u64>().ok())
         .unwrap_or(10000);
 
-    let mut nodes: Vec<serde_json::Value> = vec![
-        json!({
-            "type": "node",
-            "data": {
-                "name": bot_name,
-                "uin": bot_qq.to_string(),
-                "content": format!("{}\nTime: {}", title, now.format("%Y-%m-%d %H:%M:%S"))
-            }
-        }),
-        json!({
-            "type": "node",
-            "data": {
-                "name": bot_name,
-                "uin": bot_qq.to_string(),
-                "content": format!("[CQ:image,file=base64://{}]", img_base64)
-            }
-        }),
-    ];
-
-    for content in super::super::super::output_extract::build_plain_supplement_nodes(&markdown) {
-        nodes.push(json!({
-            "type": "node",
-            "data": {
-                "name": bot_name,
-                "uin": bot_qq.to_string(),
-                "content": content
-            }
-        }));
-    }
+    let header = format!("{}\nTime: {}", title, now.format("%Y-%m-%d %H:%M:%S"));
+    let nodes: Vec<serde_json::Value> = std::iter::once(MessageSegment::text(header))
+        .chain(contents)
+        .map(|seg| {
+            json!({
+                "type": "node",
+                "data": {
+                    "name": bot_name,
+                    "uin": bot_qq.to_string(),
+                    "content": seg.to_cq()
+                }
+            })
+        })
+        .collect();
 
-    if let Some(gid) = group_id_opt {
+    if group_id != 0 {
         send_api(
             runtime,
             bot_id,
             "send_group_forward_msg",
-            json!({ "group_id": gid, "messages": nodes }),
+            json!({ "group_id": group_id, "messages": nodes }),
         )
         .await;
     } else {
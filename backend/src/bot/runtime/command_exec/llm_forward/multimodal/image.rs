@@ -1,20 +1,32 @@
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use image::codecs::gif::GifDecoder;
 use image::codecs::jpeg::JpegEncoder;
-use image::{DynamicImage, GenericImageView};
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, DynamicImage, Frames, GenericImageView};
 use serde_json::json;
+use std::io::Cursor;
 use std::path::Path;
 use std::sync::Arc;
 
 use crate::bot::runtime::BotRuntime;
 use crate::models::SharedState;
 
+use super::super::super::media_cache;
 use super::super::LlmForwardImageFromUrlInput;
 use super::common::{
     call_chat_completions, download_binary_to_temp, log_llm_error, log_llm_len, reply_err,
-    resolve_llm_config_by_name, send_llm_markdown_as_forward_image, SendForwardImageInput,
+    resolve_llm_config_by_name, send_llm_markdown_output, BinaryMeta, SendLlmOutputInput,
 };
+use super::sampling::{dhash, format_timestamp, hamming, select_distinct_frames, FrameCandidate};
 
-#[derive(Debug, Clone)]
+/// 动图最多解码的帧数（超出部分忽略）
+const ANIMATED_MAX_DECODED_FRAMES: usize = 300;
+/// 动图最多解码的总像素数（宽 × 高 × 帧数，超出部分忽略），限制大尺寸长动图的解码开销
+const ANIMATED_MAX_DECODED_PIXELS: u64 = 200_000_000;
+/// 动图最多发送给模型的帧数
+const ANIMATED_MAX_FRAMES: usize = 8;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub(super) struct PreparedImageMeta {
     pub(super) width: u32,
     pub(super) height: u32,
@@ -54,6 +66,7 @@ fn encode_jpeg(rgb: &image::RgbImage, quality: u8) -> Result<Vec<u8>, String> {
     Ok(out)
 }
 
+/// 压缩图片并转为 data URL；相同内容与参数的结果从媒体缓存读取
 pub(super) async fn prepare_image_data_url(
     path: &Path,
     max_width: u32,
@@ -65,7 +78,46 @@ pub(super) async fn prepare_image_data_url(
         .await
         .map_err(|e| format!("Read image failed: {e}"))?;
 
-    let img = image::load_from_memory(&input).map_err(|e| format!("Decode image failed: {e}"))?;
+    let content_hash = media_cache::hash_bytes(&input);
+    let params = format!("{max_width}x{max_height}|q{jpeg_quality}|{max_output_bytes}");
+    if let Some(cached) = media_cache::get_derived(&content_hash, "image", &params)
+        .await
+        .and_then(|bytes| serde_json::from_slice::<(String, PreparedImageMeta)>(&bytes).ok())
+    {
+        return Ok(cached);
+    }
+
+    let prepared = encode_image_data_url(
+        &input,
+        max_width,
+        max_height,
+        jpeg_quality,
+        max_output_bytes,
+    )?;
+    if let Ok(bytes) = serde_json::to_vec(&prepared) {
+        media_cache::put_derived(&content_hash, "image", &params, &bytes).await;
+    }
+    Ok(prepared)
+}
+
+fn encode_image_data_url(
+    input: &[u8],
+    max_width: u32,
+    max_height: u32,
+    jpeg_quality: u8,
+    max_output_bytes: u64,
+) -> Result<(String, PreparedImageMeta), String> {
+    let img = image::load_from_memory(input).map_err(|e| format!("Decode image failed: {e}"))?;
+    encode_dynamic_image_data_url(&img, max_width, max_height, jpeg_quality, max_output_bytes)
+}
+
+fn encode_dynamic_image_data_url(
+    img: &DynamicImage,
+    max_width: u32,
+    max_height: u32,
+    jpeg_quality: u8,
+    max_output_bytes: u64,
+) -> Result<(String, PreparedImageMeta), String> {
     let (orig_w, orig_h) = img.dimensions();
 
     let (mut target_w, mut target_h) = (orig_w, orig_h);
@@ -81,7 +133,7 @@ pub(super) async fn prepare_image_data_url(
         let resized = img.resize_exact(target_w, target_h, image::imageops::FilterType::Lanczos3);
         composite_rgba_on_white(&resized)
     } else {
-        composite_rgba_on_white(&img)
+        composite_rgba_on_white(img)
     };
 
     let mut quality = jpeg_quality.clamp(30, 95);
@@ -124,6 +176,188 @@ pub(super) async fn prepare_image_data_url(
     ))
 }
 
+/// 动图（GIF / 动态 WebP）的逐帧解码器；静态图或无法识别时返回 None
+fn animation_frames(input: &[u8]) -> Option<Frames<'_>> {
+    if input.starts_with(b"GIF8") {
+        Some(GifDecoder::new(Cursor::new(input)).ok()?.into_frames())
+    } else if input.len() >= 12 && &input[0..4] == b"RIFF" && &input[8..12] == b"WEBP" {
+        let decoder = WebPDecoder::new(Cursor::new(input)).ok()?;
+        decoder.has_animation().then(|| decoder.into_frames())
+    } else {
+        None
+    }
+}
+
+/// 第一遍：逐帧计算时间戳与感知哈希，不保留像素。帧数或总像素超出上限后不再解码
+fn scan_animation_frames(input: &[u8]) -> Option<Vec<FrameCandidate>> {
+    let frames = animation_frames(input)?;
+    let mut candidates: Vec<FrameCandidate> = Vec::new();
+    let mut prev_hash: Option<u64> = None;
+    let mut ts_ms = 0u64;
+    let mut pixels = 0u64;
+    for frame in frames.take(ANIMATED_MAX_DECODED_FRAMES) {
+        let Ok(frame) = frame else {
+            break;
+        };
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        let delay_ms = if denom == 0 {
+            0
+        } else {
+            u64::from(numer) / u64::from(denom)
+        };
+        let img = DynamicImage::ImageRgba8(frame.into_buffer());
+        pixels = pixels.saturating_add(u64::from(img.width()) * u64::from(img.height()));
+        let hash = dhash(&img);
+        // 首帧优先；其余按与前一帧的变化幅度排序
+        let score = prev_hash.map_or(f64::from(u64::BITS), |prev| f64::from(hamming(prev, hash)));
+        prev_hash = Some(hash);
+        candidates.push(FrameCandidate { ts_ms, score, hash });
+        ts_ms = ts_ms.saturating_add(delay_ms);
+        if pixels >= ANIMATED_MAX_DECODED_PIXELS {
+            break;
+        }
+    }
+    (candidates.len() > 1).then_some(candidates)
+}
+
+/// 第二遍：只保留选中的帧（`selected` 为升序下标）
+fn decode_selected_frames(input: &[u8], selected: &[usize]) -> Option<Vec<DynamicImage>> {
+    let last = *selected.last()?;
+    let mut out = Vec::with_capacity(selected.len());
+    for (idx, frame) in animation_frames(input)?.enumerate().take(last + 1) {
+        let Ok(frame) = frame else {
+            break;
+        };
+        if selected.binary_search(&idx).is_ok() {
+            out.push(DynamicImage::ImageRgba8(frame.into_buffer()));
+        }
+    }
+    (out.len() == selected.len()).then_some(out)
+}
+
+/// 动图多帧采样：按与前一帧的差异排序，去掉几乎相同的帧后保留至多 `ANIMATED_MAX_FRAMES` 帧。
+/// 返回 `(帧总数, [(时间戳毫秒, data URL, meta)])`；静态图返回 None
+fn encode_animated_frames(
+    input: &[u8],
+    max_width: u32,
+    max_height: u32,
+    jpeg_quality: u8,
+    max_output_bytes: u64,
+) -> Result<Option<AnimatedFrames>, String> {
+    // 两遍解码：先只算哈希选帧，再取出选中的帧，内存中不同时保留所有帧
+    let Some(candidates) = scan_animation_frames(input) else {
+        return Ok(None);
+    };
+    let mut selected = select_distinct_frames(&candidates, ANIMATED_MAX_FRAMES);
+    selected.sort_unstable();
+    let Some(images) = decode_selected_frames(input, &selected) else {
+        return Err("Decode animation failed: frames changed between passes".to_string());
+    };
+
+    let frame_budget = max_output_bytes / selected.len().max(1) as u64;
+    let mut frames = Vec::with_capacity(selected.len());
+    for (idx, img) in selected.into_iter().zip(images) {
+        let (data_url, meta) =
+            encode_dynamic_image_data_url(&img, max_width, max_height, jpeg_quality, frame_budget)?;
+        frames.push((candidates[idx].ts_ms, data_url, meta));
+    }
+    Ok(Some(AnimatedFrames {
+        frames_total: candidates.len(),
+        frames,
+    }))
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AnimatedFrames {
+    frames_total: usize,
+    frames: Vec<(u64, String, PreparedImageMeta)>,
+}
+
+/// 动图抽帧并转为 data URL；相同内容与参数的结果从媒体缓存读取
+async fn prepare_animated_frames(
+    path: &Path,
+    max_width: u32,
+    max_height: u32,
+    jpeg_quality: u8,
+    max_output_bytes: u64,
+) -> Result<Option<AnimatedFrames>, String> {
+    let input = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("Read image failed: {e}"))?;
+
+    let content_hash = media_cache::hash_bytes(&input);
+    let params = format!(
+        "{ANIMATED_MAX_FRAMES}|{max_width}x{max_height}|q{jpeg_quality}|{max_output_bytes}"
+    );
+    if let Some(cached) = media_cache::get_derived(&content_hash, "animated_frames", &params)
+        .await
+        .and_then(|bytes| serde_json::from_slice::<AnimatedFrames>(&bytes).ok())
+    {
+        return Ok(Some(cached));
+    }
+
+    let prepared = tokio::task::spawn_blocking(move || {
+        encode_animated_frames(
+            &input,
+            max_width,
+            max_height,
+            jpeg_quality,
+            max_output_bytes,
+        )
+    })
+    .await
+    .map_err(|e| format!("Decode animation failed: {e}"))??;
+    if let Some(prepared) = &prepared {
+        if let Ok(bytes) = serde_json::to_vec(prepared) {
+            media_cache::put_derived(&content_hash, "animated_frames", &params, &bytes).await;
+        }
+    }
+    Ok(prepared)
+}
+
+/// 静态图：压缩为单张 data URL 并附上上下文
+async fn build_static_image_content(
+    path: &Path,
+    input: &LlmForwardImageFromUrlInput<'_>,
+    prompt: &str,
+    title: &str,
+    bin_meta: &BinaryMeta,
+    environment: serde_json::Value,
+) -> Result<Vec<serde_json::Value>, String> {
+    let (data_url, prepared_meta) = prepare_image_data_url(
+        path,
+        input.max_width,
+        input.max_height,
+        input.jpeg_quality,
+        input.max_output_bytes,
+    )
+    .await?;
+
+    let ctx = json!({
+        "task": prompt,
+        "title": title,
+        "document": {
+            "type": "image",
+            "file_ext": bin_meta.file_ext,
+            "size_bytes": bin_meta.size_bytes,
+            "truncated": bin_meta.truncated,
+            "prepared": {
+                "mime": prepared_meta.mime,
+                "width": prepared_meta.width,
+                "height": prepared_meta.height,
+                "bytes": prepared_meta.output_bytes,
+                "jpeg_quality": prepared_meta.quality
+            }
+        },
+        "environment": environment
+    });
+    let ctx_pretty = serde_json::to_string_pretty(&ctx).unwrap_or_else(|_| ctx.to_string());
+    Ok(vec![
+        json!({"type": "text", "text": format!("上下文信息（JSON）：\n{}", ctx_pretty)}),
+        json!({"type": "image_url", "image_url": {"url": data_url}}),
+    ])
+}
+
 pub(in super::super::super) async fn process_llm_forward_image_from_url(
     state: &SharedState,
     runtime: &Arc<BotRuntime>,
@@ -135,6 +369,7 @@ pub(in super::super::super) async fn process_llm_forward_image_from_url(
     let system_prompt = input.system_prompt;
     let prompt = input.prompt;
     let title = input.title;
+    let output_mode = input.output_mode;
 
     let (guard, bin_meta) = match download_binary_to_temp(
         input.url,
@@ -166,7 +401,7 @@ pub(in super::super::super) async fn process_llm_forward_image_from_url(
         }
     };
 
-    let (data_url, prepared_meta) = match prepare_image_data_url(
+    let animated = match prepare_animated_frames(
         &guard.path,
         input.max_width,
         input.max_height,
@@ -182,7 +417,7 @@ pub(in super::super::super) async fn process_llm_forward_image_from_url(
                 bot_id,
                 user_id,
                 group_id,
-                &format!("图片处理失败：{e}"),
+                &format!("动图处理失败：{e}"),
             )
             .await;
             return;
@@ -201,42 +436,79 @@ pub(in super::super::super) async fn process_llm_forward_image_from_url(
         .map(|b| b.value().platform.clone())
         .unwrap_or_else(|| "unknown".to_string());
     let now = chrono::Local::now();
+    let environment = json!({
+        "bot_id": bot_id,
+        "bot_name": bot_name,
+        "platform": bot_platform,
+        "chat_type": if group_id_opt.is_some() { "group" } else { "private" },
+        "time": now.to_rfc3339(),
+    });
 
-    let ctx = json!({
-        "task": prompt,
-        "title": title,
-        "document": {
-            "type": "image",
-            "file_ext": bin_meta.file_ext,
-            "size_bytes": bin_meta.size_bytes,
-            "truncated": bin_meta.truncated,
-            "prepared": {
-                "mime": prepared_meta.mime,
-                "width": prepared_meta.width,
-                "height": prepared_meta.height,
-                "bytes": prepared_meta.output_bytes,
-                "jpeg_quality": prepared_meta.quality
+    let user_content = if let Some(animated) = animated {
+        let ctx = json!({
+            "task": prompt,
+            "title": title,
+            "document": {
+                "type": "animated_image",
+                "file_ext": bin_meta.file_ext,
+                "size_bytes": bin_meta.size_bytes,
+                "truncated": bin_meta.truncated,
+                "frames_total": animated.frames_total,
+                "frames_selected": animated.frames.len(),
+                "frames": animated.frames.iter().map(|(ts_ms, _, meta)| {
+                    json!({
+                        "timestamp_ms": ts_ms,
+                        "timestamp": format_timestamp(*ts_ms),
+                        "prepared": {
+                            "mime": meta.mime,
+                            "width": meta.width,
+                            "height": meta.height,
+                            "bytes": meta.output_bytes,
+                            "jpeg_quality": meta.quality
+                        }
+                    })
+                }).collect::<Vec<_>>()
+            },
+            "environment": environment
+        });
+        let ctx_pretty = serde_json::to_string_pretty(&ctx).unwrap_or_else(|_| ctx.to_string());
+        let mut parts = vec![json!({
+            "type": "text",
+            "text": format!("上下文信息（JSON）：\n{}", ctx_pretty)
+        })];
+        for (pos, (ts_ms, data_url, _meta)) in animated.frames.iter().enumerate() {
+            parts.push(json!({
+                "type": "text",
+                "text": format!("Frame {} @ {}", pos + 1, format_timestamp(*ts_ms))
+            }));
+            parts.push(json!({ "type": "image_url", "image_url": { "url": data_url } }));
+        }
+        parts
+    } else {
+        match build_static_image_content(&guard.path, &input, prompt, title, &bin_meta, environment)
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                reply_err(
+                    runtime,
+                    bot_id,
+                    user_id,
+                    group_id,
+                    &format!("图片处理失败：{e}"),
+                )
+                .await;
+                return;
             }
-        },
-        "environment": {
-            "bot_id": bot_id,
-            "bot_name": bot_name,
-            "platform": bot_platform,
-            "chat_type": if group_id_opt.is_some() { "group" } else { "private" },
-            "time": now.to_rfc3339(),
         }
-    });
-    let ctx_pretty = serde_json::to_string_pretty(&ctx).unwrap_or_else(|_| ctx.to_string());
+    };
 
     let request_body = json!({
         "model": llm.model_name,
         "messages": [
             {"role": "system", "content": system_prompt},
             {"role": "system", "content": super::super::build_prompt_injection_guard()},
-            {"role": "user", "content": [
-                {"type": "text", "text": format!("上下文信息（JSON）：\n{}", ctx_pretty)},
-                {"type": "image_url", "image_url": {"url": data_url}}
-            ]}
+            {"role": "user", "content": user_content}
         ],
         "max_tokens": 4096
     });
@@ -265,15 +537,16 @@ pub(in super::super::super) async fn process_llm_forward_image_from_url(
     };
 
     log_llm_len("图片分析", reply_content.len());
-    send_llm_markdown_as_forward_image(
+    send_llm_markdown_output(
         state,
         runtime,
         bot_id,
-        SendForwardImageInput {
+        SendLlmOutputInput {
             user_id,
             group_id,
             title,
             markdown: &reply_content,
+            output_mode,
         },
     )
     .await;
@@ -0,0 +1,294 @@
+//! 图片生成：调用 OpenAI 兼容的 `/images/generations`，提供参考图时改用 `/images/edits`
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use futures_util::StreamExt;
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::bot::runtime::api::send_reply;
+use crate::bot::runtime::BotRuntime;
+use crate::models::SharedState;
+use crate::segment::MessageSegment;
+
+use super::super::LlmImageGenerationInput;
+use super::common::{acquire_llm_http_permit, download_binary_to_temp, resolve_llm_config_by_name};
+
+/// 未指定模型映射时优先使用名为 `image` 的映射
+const DEFAULT_IMAGE_MAPPING: &str = "image";
+const REFERENCE_MAX_BYTES: u64 = 20_000_000;
+const MAX_IMAGE_RESPONSE_BYTES: usize = 80_000_000;
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(in super::super::super) struct GeneratedImage {
+    pub(in super::super::super) base64: String,
+    pub(in super::super::super) mime: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(in super::super::super) revised_prompt: Option<String>,
+}
+
+fn sniff_image_mime(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0x89, b'P', b'N', b'G']) {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+fn mapping_str(mapping: &serde_json::Value, key: &str) -> Option<String> {
+    mapping
+        .get("image")
+        .and_then(|v| v.get(key))
+        .and_then(|v| v.as_str())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// 读取模型映射中的图片生成默认值（`models.<name>.image`），返回映射名与其配置
+fn resolve_image_mapping(
+    state: &SharedState,
+    bot_id: &str,
+    model_mapping_name: Option<&str>,
+) -> (Option<String>, serde_json::Value) {
+    let Some(module) = crate::module::get_effective_module(state, bot_id, "llm") else {
+        return (model_mapping_name.map(|s| s.to_string()), json!({}));
+    };
+    let models = module.config.get("models").and_then(|v| v.as_object());
+    let name = match model_mapping_name {
+        Some(name) if !name.trim().is_empty() => Some(name.trim().to_string()),
+        _ => models
+            .filter(|m| m.contains_key(DEFAULT_IMAGE_MAPPING))
+            .map(|_| DEFAULT_IMAGE_MAPPING.to_string()),
+    };
+    let mapping = name
+        .as_deref()
+        .and_then(|n| models.and_then(|m| m.get(n)))
+        .cloned()
+        .unwrap_or_else(|| json!({}));
+    (name, mapping)
+}
+
+/// 下载参考图；非 PNG/JPEG/WebP 的格式转为 PNG
+async fn load_reference_image(url: &str, timeout_ms: u64) -> Result<(Vec<u8>, String), String> {
+    let (guard, meta) =
+        download_binary_to_temp(url, Some("reference.bin"), timeout_ms, REFERENCE_MAX_BYTES)
+            .await?;
+    if meta.truncated {
+        return Err("参考图过大".to_string());
+    }
+    let bytes = tokio::fs::read(&guard.path)
+        .await
+        .map_err(|e| format!("读取参考图失败: {e}"))?;
+    if let Some(mime) = sniff_image_mime(&bytes) {
+        return Ok((bytes, mime.to_string()));
+    }
+
+    let img = image::load_from_memory(&bytes).map_err(|e| format!("参考图解码失败: {e}"))?;
+    let mut png: Vec<u8> = Vec::new();
+    img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| format!("参考图转码失败: {e}"))?;
+    Ok((png, "image/png".to_string()))
+}
+
+/// 流式读取响应体，超过 `MAX_IMAGE_RESPONSE_BYTES` 时中止
+async fn read_body_capped(resp: reqwest::Response, what: &str) -> Result<Vec<u8>, String> {
+    if resp
+        .content_length()
+        .is_some_and(|len| len > MAX_IMAGE_RESPONSE_BYTES as u64)
+    {
+        return Err(format!("{what}失败：响应过大"));
+    }
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut stream = resp.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("读取{what}响应失败: {e}"))?;
+        if bytes.len() + chunk.len() > MAX_IMAGE_RESPONSE_BYTES {
+            return Err(format!("{what}失败：响应过大"));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// 解析响应中的图片：`b64_json` 直接解码，`url` 则经出站策略下载
+async fn collect_images(
+    v: &serde_json::Value,
+    timeout_ms: u64,
+) -> Result<Vec<GeneratedImage>, String> {
+    let items = v
+        .get("data")
+        .and_then(|d| d.as_array())
+        .ok_or_else(|| "图片生成失败：响应缺少 data".to_string())?;
+
+    let mut images = Vec::new();
+    for item in items {
+        let bytes = if let Some(b64) = item.get("b64_json").and_then(|v| v.as_str()) {
+            BASE64
+                .decode(b64.trim())
+                .map_err(|e| format!("图片 base64 解码失败: {e}"))?
+        } else if let Some(url) = item.get("url").and_then(|v| v.as_str()) {
+            let resp = crate::outbound::get(url)
+                .await?
+                .timeout(std::time::Duration::from_millis(timeout_ms))
+                .send()
+                .await
+                .map_err(|e| {
+                    format!(
+                        "下载生成的图片失败: {}",
+                        crate::outbound::describe_error(&e)
+                    )
+                })?;
+            if !resp.status().is_success() {
+                return Err(format!("下载生成的图片失败 (HTTP {})", resp.status()));
+            }
+            read_body_capped(resp, "下载生成的图片").await?
+        } else {
+            continue;
+        };
+
+        images.push(GeneratedImage {
+            mime: sniff_image_mime(&bytes).unwrap_or("image/png").to_string(),
+            base64: BASE64.encode(&bytes),
+            revised_prompt: item
+                .get("revised_prompt")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
+        });
+    }
+    if images.is_empty() {
+        return Err("图片生成失败：响应中没有图片".to_string());
+    }
+    Ok(images)
+}
+
+/// 调用图片生成接口
+pub(in super::super::super) async fn generate_images(
+    state: &SharedState,
+    bot_id: &str,
+    input: LlmImageGenerationInput<'_>,
+) -> Result<Vec<GeneratedImage>, String> {
+    let prompt = input.prompt.trim();
+    if prompt.is_empty() {
+        return Err("图片生成失败：提示词为空".to_string());
+    }
+
+    let (mapping_name, mapping) = resolve_image_mapping(state, bot_id, input.model_name);
+    let llm = resolve_llm_config_by_name(state, bot_id, mapping_name.as_deref())?;
+    let size = input
+        .size
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .or_else(|| mapping_str(&mapping, "size"));
+    let quality = input
+        .quality
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .or_else(|| mapping_str(&mapping, "quality"));
+    // gpt-image 系列不接受 response_format，仅在映射中显式配置时发送
+    let response_format = mapping_str(&mapping, "response_format");
+    let base_url = llm.base_url.trim_end_matches('/');
+
+    let client = reqwest::Client::new();
+    let request = match input.image_url.filter(|u| !u.trim().is_empty()) {
+        Some(image_url) => {
+            let (bytes, mime) = load_reference_image(image_url.trim(), input.timeout_ms).await?;
+            let ext = mime.rsplit('/').next().unwrap_or("png").to_string();
+            let part = reqwest::multipart::Part::bytes(bytes)
+                .file_name(format!("reference.{ext}"))
+                .mime_str(&mime)
+                .map_err(|e| format!("构造参考图 multipart 失败: {e}"))?;
+            let mut form = reqwest::multipart::Form::new()
+                .text("model", llm.model_name.clone())
+                .text("prompt", prompt.to_string())
+                .text("n", input.n.to_string())
+                .part("image", part);
+            for (key, value) in [
+                ("size", &size),
+                ("quality", &quality),
+                ("response_format", &response_format),
+            ] {
+                if let Some(value) = value {
+                    form = form.text(key, value.clone());
+                }
+            }
+            client
+                .post(format!("{base_url}/images/edits"))
+                .multipart(form)
+        }
+        None => {
+            let mut body = json!({
+                "model": llm.model_name,
+                "prompt": prompt,
+                "n": input.n,
+            });
+            for (key, value) in [
+                ("size", &size),
+                ("quality", &quality),
+                ("response_format", &response_format),
+            ] {
+                if let Some(value) = value {
+                    body[key] = json!(value);
+                }
+            }
+            client
+                .post(format!("{base_url}/images/generations"))
+                .json(&body)
+        }
+    };
+
+    let (status, bytes) = {
+        let _permit = acquire_llm_http_permit()
+            .await
+            .map_err(|e| format!("图片生成并发控制失败: {e}"))?;
+        let resp = request
+            .header("Authorization", format!("Bearer {}", llm.api_key))
+            .timeout(std::time::Duration::from_secs(300))
+            .send()
+            .await
+            .map_err(|e| format!("图片生成请求失败: {}", e))?;
+        let status = resp.status();
+        (status, read_body_capped(resp, "图片生成").await?)
+    };
+
+    let parsed = serde_json::from_slice::<serde_json::Value>(&bytes).ok();
+    if !status.is_success() {
+        let msg = parsed
+            .as_ref()
+            .and_then(|v| {
+                let err = v.get("error").or_else(|| v.get("message"))?;
+                err.as_str()
+                    .or_else(|| err.get("message").and_then(|m| m.as_str()))
+                    .map(|s| s.to_string())
+            })
+            .unwrap_or_else(|| String::from_utf8_lossy(&bytes).chars().take(400).collect());
+        return Err(format!("图片生成失败 (HTTP {}): {}", status, msg));
+    }
+    let parsed = parsed.ok_or_else(|| "图片生成失败：无法解析响应".to_string())?;
+    collect_images(&parsed, input.timeout_ms).await
+}
+
+/// 以图片消息发送生成结果
+pub(in super::super::super) async fn send_generated_images(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    user_id: u64,
+    group_id: u64,
+    images: &[GeneratedImage],
+) {
+    let msg: String = images
+        .iter()
+        .map(|img| MessageSegment::image_base64(&img.base64).to_cq())
+        .collect();
+    send_reply(
+        runtime,
+        bot_id,
+        user_id,
+        (group_id != 0).then_some(group_id),
+        &msg,
+    )
+    .await;
+}
@@ -2,9 +2,14 @@ mod audio;
 mod bundle;
 pub(in super::super) mod common;
 mod image;
+mod image_gen;
+mod sampling;
+mod speech;
 mod video;
 
 pub(in super::super) use audio::process_llm_forward_audio_from_url;
 pub(in super::super) use bundle::process_llm_forward_media_bundle;
 pub(in super::super) use image::process_llm_forward_image_from_url;
+pub(in super::super) use image_gen::{generate_images, send_generated_images, GeneratedImage};
+pub(in super::super) use speech::process_llm_speech;
 pub(in super::super) use video::process_llm_forward_video_from_url;
@@ -0,0 +1,120 @@
+//! 多帧采样的公共工具：感知哈希去重、帧预算内的取舍与时间戳格式化（视频抽帧与动图共用）
+
+use image::DynamicImage;
+
+/// dHash 汉明距离不超过该值的两帧视为几乎相同
+pub(super) const NEAR_DUPLICATE_DISTANCE: u32 = 5;
+
+/// 差值哈希（dHash）：缩放为 9x8 灰度图，比较水平相邻像素的明暗
+pub(super) fn dhash(img: &DynamicImage) -> u64 {
+    let small = img
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y).0[0];
+            let right = small.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash
+}
+
+pub(super) fn hamming(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// 候选帧
+pub(super) struct FrameCandidate {
+    pub(super) ts_ms: u64,
+    /// 选取优先级（场景切换得分等），预算不足时先丢弃得分低的帧
+    pub(super) score: f64,
+    pub(super) hash: u64,
+}
+
+/// 去掉与已保留帧几乎相同的帧，再按得分保留至多 `budget` 帧，返回按时间排序的下标
+pub(super) fn select_distinct_frames(candidates: &[FrameCandidate], budget: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by(|&a, &b| {
+        candidates[b]
+            .score
+            .total_cmp(&candidates[a].score)
+            .then(candidates[a].ts_ms.cmp(&candidates[b].ts_ms))
+    });
+
+    let mut kept: Vec<usize> = Vec::new();
+    for idx in order {
+        if kept.len() >= budget {
+            break;
+        }
+        let hash = candidates[idx].hash;
+        if kept
+            .iter()
+            .any(|&k| hamming(candidates[k].hash, hash) <= NEAR_DUPLICATE_DISTANCE)
+        {
+            continue;
+        }
+        kept.push(idx);
+    }
+    kept.sort_by_key(|&i| candidates[i].ts_ms);
+    kept
+}
+
+/// 将毫秒格式化为 `mm:ss.s`（超过一小时为 `h:mm:ss.s`）
+pub(super) fn format_timestamp(ts_ms: u64) -> String {
+    let tenths = (ts_ms % 1000) / 100;
+    let total_secs = ts_ms / 1000;
+    let (h, m, s) = (total_secs / 3600, (total_secs / 60) % 60, total_secs % 60);
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}.{tenths}")
+    } else {
+        format!("{m:02}:{s:02}.{tenths}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(ts_ms: u64, score: f64, hash: u64) -> FrameCandidate {
+        FrameCandidate { ts_ms, score, hash }
+    }
+
+    #[test]
+    fn dhash_tracks_brightness_gradient() {
+        let flat =
+            DynamicImage::ImageLuma8(image::GrayImage::from_pixel(18, 16, image::Luma([128])));
+        assert_eq!(dhash(&flat), 0);
+
+        // 从左到右变暗：每个像素都比右侧亮
+        let gradient = DynamicImage::ImageLuma8(image::GrayImage::from_fn(18, 16, |x, _| {
+            image::Luma([255 - (x as u8) * 14])
+        }));
+        assert_eq!(dhash(&gradient), u64::MAX);
+        assert_eq!(hamming(dhash(&flat), dhash(&gradient)), 64);
+    }
+
+    #[test]
+    fn select_drops_near_duplicates_and_keeps_time_order() {
+        let candidates = [
+            candidate(0, 64.0, 0),
+            candidate(100, 2.0, 0b11),
+            candidate(200, 30.0, u64::MAX),
+            candidate(300, 20.0, 0xFFFF_0000),
+            candidate(400, 10.0, u64::MAX - 1),
+        ];
+        // 100 与首帧、400 与 200 几乎相同
+        assert_eq!(select_distinct_frames(&candidates, 8), vec![0, 2, 3]);
+        // 预算不足时先丢弃得分低的帧，结果仍按时间排序
+        assert_eq!(select_distinct_frames(&candidates, 2), vec![0, 2]);
+        assert!(select_distinct_frames(&candidates, 0).is_empty());
+    }
+
+    #[test]
+    fn format_timestamp_with_and_without_hours() {
+        assert_eq!(format_timestamp(0), "00:00.0");
+        assert_eq!(format_timestamp(65_432), "01:05.4");
+        assert_eq!(format_timestamp(3_725_900), "1:02:05.9");
+    }
+}
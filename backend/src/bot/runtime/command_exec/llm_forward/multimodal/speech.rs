@@ -0,0 +1,328 @@
+//! 文本转语音：调用 OpenAI 兼容的 `/audio/speech`（或模型映射中配置的本地 TTS 服务），
+//! 经 ffmpeg 转码为 NapCat 可直接发送的 mp3 后以语音（record）消息发出
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use futures_util::StreamExt;
+use serde_json::json;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::bot::runtime::api::send_reply;
+use crate::bot::runtime::BotRuntime;
+use crate::models::SharedState;
+use crate::segment::MessageSegment;
+
+use super::super::super::media_cache;
+use super::super::download::TempFileGuard;
+use super::super::LlmSpeechInput;
+use super::common::{acquire_llm_http_permit, reply_err, resolve_llm_config_by_name};
+use super::video::ffmpeg::run_program;
+
+/// 未指定模型映射时优先使用名为 `tts` 的映射
+const DEFAULT_TTS_MAPPING: &str = "tts";
+const DEFAULT_VOICE: &str = "alloy";
+/// OpenAI `/audio/speech` 单次输入上限
+const MAX_INPUT_CHARS: usize = 4096;
+const MAX_SPEECH_RESPONSE_BYTES: usize = 20_000_000;
+
+/// 模型映射中的 TTS 设置（`models.<name>.tts`）
+struct TtsConfig {
+    /// 完整的合成接口地址
+    endpoint: String,
+    api_key: String,
+    model: String,
+    voice: String,
+    speed: Option<f64>,
+    instructions: Option<String>,
+    response_format: String,
+}
+
+fn json_str(v: &serde_json::Value, key: &str) -> Option<String> {
+    v.get(key)
+        .and_then(|v| v.as_str())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// 解析 TTS 配置：`tts.endpoint` 存在时直接请求该地址（本地 TTS 服务，请求体与 OpenAI 相同），
+/// 否则使用模型映射对应提供商的 `{base_url}/audio/speech`
+fn resolve_tts_config(
+    state: &SharedState,
+    bot_id: &str,
+    model_mapping_name: Option<&str>,
+) -> Result<TtsConfig, String> {
+    let llm_module = match crate::module::get_effective_module(state, bot_id, "llm") {
+        Some(m) if m.enabled => m,
+        _ => return Err("LLM 模块未启用，请先在设置中启用并配置 LLM 模块".to_string()),
+    };
+    let models = llm_module
+        .config
+        .get("models")
+        .and_then(|v| v.as_object())
+        .ok_or_else(|| "LLM 模块配置错误：models 缺失或格式不正确".to_string())?;
+
+    let target = match model_mapping_name {
+        Some(name) if !name.trim().is_empty() => name.trim().to_string(),
+        _ if models.contains_key(DEFAULT_TTS_MAPPING) => DEFAULT_TTS_MAPPING.to_string(),
+        _ => llm_module
+            .config
+            .get("default_model")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| "LLM 模块配置错误：default_model 缺失".to_string())?
+            .to_string(),
+    };
+    let mapping = models
+        .get(&target)
+        .ok_or_else(|| format!("LLM 模块配置错误：未找到模型映射 '{}'", target))?;
+    let tts = mapping.get("tts").cloned().unwrap_or_else(|| json!({}));
+
+    let (endpoint, api_key, model) = match json_str(&tts, "endpoint") {
+        Some(endpoint) => (
+            endpoint,
+            json_str(&tts, "api_key").unwrap_or_default(),
+            json_str(&tts, "model")
+                .or_else(|| json_str(mapping, "model"))
+                .unwrap_or_else(|| "tts-1".to_string()),
+        ),
+        None => {
+            let llm = resolve_llm_config_by_name(state, bot_id, Some(&target))?;
+            (
+                format!("{}/audio/speech", llm.base_url.trim_end_matches('/')),
+                llm.api_key,
+                json_str(&tts, "model").unwrap_or(llm.model_name),
+            )
+        }
+    };
+
+    Ok(TtsConfig {
+        endpoint,
+        api_key,
+        model,
+        voice: json_str(&tts, "voice").unwrap_or_else(|| DEFAULT_VOICE.to_string()),
+        speed: tts.get("speed").and_then(|v| v.as_f64()),
+        instructions: json_str(&tts, "instructions"),
+        response_format: json_str(&tts, "format").unwrap_or_else(|| "mp3".to_string()),
+    })
+}
+
+/// 调用语音合成接口，返回原始音频字节；相同文本与参数的结果从媒体缓存读取
+async fn call_speech_synthesis(cfg: &TtsConfig, text: &str) -> Result<Vec<u8>, String> {
+    let mut body = json!({
+        "model": cfg.model,
+        "input": text,
+        "voice": cfg.voice,
+        "response_format": cfg.response_format,
+    });
+    if let Some(speed) = cfg.speed {
+        body["speed"] = json!(speed.clamp(0.25, 4.0));
+    }
+    if let Some(instructions) = &cfg.instructions {
+        body["instructions"] = json!(instructions);
+    }
+
+    let text_hash = media_cache::hash_bytes(text.as_bytes());
+    let cache_params = format!("{}|{}", cfg.endpoint, body);
+    if let Some(audio) = media_cache::get_derived(&text_hash, "speech", &cache_params).await {
+        return Ok(audio);
+    }
+
+    let client = reqwest::Client::new();
+    let mut request = client
+        .post(&cfg.endpoint)
+        .json(&body)
+        .timeout(std::time::Duration::from_secs(120));
+    if !cfg.api_key.is_empty() {
+        request = request.header("Authorization", format!("Bearer {}", cfg.api_key));
+    }
+
+    let (status, content_type, bytes) = {
+        let _permit = acquire_llm_http_permit()
+            .await
+            .map_err(|e| format!("语音合成并发控制失败: {e}"))?;
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| format!("语音合成请求失败: {}", e))?;
+        let status = resp.status();
+        let content_type = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut stream = resp.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| format!("读取语音合成响应失败: {}", e))?;
+            if bytes.len() + chunk.len() > MAX_SPEECH_RESPONSE_BYTES {
+                return Err("语音合成失败：响应过大".to_string());
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        (status, content_type, bytes)
+    };
+
+    let parsed_json = content_type
+        .contains("json")
+        .then(|| serde_json::from_slice::<serde_json::Value>(&bytes).ok())
+        .flatten();
+    if !status.is_success() {
+        let msg = parsed_json
+            .as_ref()
+            .and_then(|v| {
+                let err = v.get("error").or_else(|| v.get("message"))?;
+                err.as_str()
+                    .map(|s| s.to_string())
+                    .or_else(|| json_str(err, "message"))
+            })
+            .unwrap_or_else(|| String::from_utf8_lossy(&bytes).chars().take(400).collect());
+        return Err(format!("语音合成失败 (HTTP {}): {}", status, msg));
+    }
+
+    // 部分本地 TTS 服务以 JSON 返回 base64 音频
+    let audio = match parsed_json {
+        Some(v) => {
+            let b64 = json_str(&v, "audio")
+                .or_else(|| v.get("data").and_then(|d| json_str(d, "audio")))
+                .ok_or_else(|| "语音合成失败：响应中没有音频数据".to_string())?;
+            let b64 = b64.split_once("base64,").map_or(b64.as_str(), |(_, b)| b);
+            BASE64
+                .decode(b64.trim())
+                .map_err(|e| format!("语音合成失败：音频 base64 解码失败: {e}"))?
+        }
+        None => bytes,
+    };
+    if audio.is_empty() {
+        return Err("语音合成失败：返回的音频为空".to_string());
+    }
+
+    media_cache::put_derived(&text_hash, "speech", &cache_params, &audio).await;
+    Ok(audio)
+}
+
+/// 转码为单声道 mp3（NapCat 会再转为 silk 发送）；ffmpeg 缺少 mp3 编码器时回退为 wav
+async fn transcode_for_record(audio: &[u8], max_seconds: u32) -> Result<Vec<u8>, String> {
+    let input = TempFileGuard::new("tts", Some("speech.bin")).await?;
+    tokio::fs::write(&input.path, audio)
+        .await
+        .map_err(|e| format!("写入临时音频失败: {e}"))?;
+    let work_dir = input
+        .path
+        .parent()
+        .ok_or_else(|| "临时目录无效".to_string())?;
+    let input_name = input
+        .path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| "临时文件名无效".to_string())?;
+    let max_seconds = max_seconds.to_string();
+
+    let mut last_err = String::new();
+    for (ext, codec_args) in [
+        ("mp3", ["-c:a", "libmp3lame", "-b:a", "64k"]),
+        ("wav", ["-c:a", "pcm_s16le", "-f", "wav"]),
+    ] {
+        let output = TempFileGuard::new("tts", Some(&format!("speech.{ext}"))).await?;
+        let Some(output_name) = output.path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let mut args = vec![
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-y",
+            "-i",
+            input_name,
+            "-t",
+            &max_seconds,
+            "-vn",
+            "-ac",
+            "1",
+            "-ar",
+            "24000",
+        ];
+        args.extend(codec_args);
+        args.push(output_name);
+
+        let res = run_program("ffmpeg", work_dir, &args).await?;
+        if res.status.success() {
+            return tokio::fs::read(&output.path)
+                .await
+                .map_err(|e| format!("读取转码结果失败: {e}"));
+        }
+        last_err = String::from_utf8_lossy(&res.stderr).trim().to_string();
+        warn!("ffmpeg transcode speech to {} failed: {}", ext, last_err);
+    }
+    Err(format!(
+        "音频转码失败: {}",
+        last_err.chars().take(400).collect::<String>()
+    ))
+}
+
+/// 合成语音并以语音消息发送
+pub(in super::super::super) async fn process_llm_speech(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    input: LlmSpeechInput<'_>,
+) {
+    let user_id = input.user_id;
+    let group_id = input.group_id;
+
+    let text = input.text.trim();
+    if text.is_empty() {
+        reply_err(runtime, bot_id, user_id, group_id, "语音合成失败：文本为空").await;
+        return;
+    }
+    let text: String = text.chars().take(MAX_INPUT_CHARS).collect();
+
+    let mut cfg = match resolve_tts_config(state, bot_id, input.model_name) {
+        Ok(v) => v,
+        Err(e) => {
+            reply_err(runtime, bot_id, user_id, group_id, &e).await;
+            return;
+        }
+    };
+    if let Some(voice) = input.voice.filter(|s| !s.trim().is_empty()) {
+        cfg.voice = voice.trim().to_string();
+    }
+    if input.speed.is_some() {
+        cfg.speed = input.speed;
+    }
+    if let Some(instructions) = input.instructions.filter(|s| !s.trim().is_empty()) {
+        cfg.instructions = Some(instructions.trim().to_string());
+    }
+
+    let audio = match call_speech_synthesis(&cfg, &text).await {
+        Ok(v) => v,
+        Err(e) => {
+            reply_err(runtime, bot_id, user_id, group_id, &e).await;
+            return;
+        }
+    };
+    let record = match transcode_for_record(&audio, input.max_seconds).await {
+        Ok(v) => v,
+        Err(e) => {
+            reply_err(
+                runtime,
+                bot_id,
+                user_id,
+                group_id,
+                &format!("语音合成失败：{e}"),
+            )
+            .await;
+            return;
+        }
+    };
+
+    let msg = MessageSegment::record(format!("base64://{}", BASE64.encode(&record))).to_cq();
+    send_reply(
+        runtime,
+        bot_id,
+        user_id,
+        (group_id != 0).then_some(group_id),
+        &msg,
+    )
+    .await;
+}
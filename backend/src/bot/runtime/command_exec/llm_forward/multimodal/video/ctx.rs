@@ -4,6 +4,7 @@ use crate::models::SharedState;
 
 use super::super::common::BinaryMeta;
 use super::super::image::PreparedImageMeta;
+use super::super::sampling::format_timestamp;
 
 pub(super) struct VideoCtxInput<'a> {
     pub(super) state: &'a SharedState,
@@ -49,6 +50,7 @@ pub(super) fn build_video_ctx(input: VideoCtxInput<'_>) -> serde_json::Value {
             "frames": input.frames.iter().map(|(ts_ms, meta)| {
                 json!({
                     "timestamp_ms": ts_ms,
+                    "timestamp": format_timestamp(*ts_ms),
                     "prepared": {
                         "mime": meta.mime,
                         "width": meta.width,
@@ -25,7 +25,7 @@ async fn run_program_local(
         .await
 }
 
-pub(super) async fn run_program(
+pub(in super::super) async fn run_program(
     program: &str,
     work_dir: &Path,
     args: &[&str],
@@ -1,8 +1,10 @@
 use std::path::{Path, PathBuf};
 use tracing::warn;
 
+use super::super::super::super::media_cache;
 use super::super::common::nonce12;
 use super::super::image::{prepare_image_data_url, PreparedImageMeta};
+use super::super::sampling::{dhash, select_distinct_frames, FrameCandidate};
 use super::ffmpeg::run_program;
 
 pub(super) fn evenly_spaced_indices(total: usize, keep: usize) -> Vec<usize> {
@@ -48,8 +50,128 @@ pub(super) async fn probe_video_duration_seconds(video_path: &Path) -> Option<f6
     s.trim().parse::<f64>().ok()
 }
 
+/// ffmpeg 场景切换检测阈值（0~1，越小越敏感）
+const SCENE_THRESHOLD: f64 = 0.3;
+/// 场景检测只分析前若干秒，避免超长视频耗时过久
+const SCENE_DETECT_MAX_SECONDS: u32 = 1800;
+
+/// 用 ffmpeg 的 scene 滤镜检测场景切换，返回 (时间秒, 切换得分)
+async fn detect_scene_changes(work_dir: &Path, input_name: &str) -> Vec<(f64, f64)> {
+    let filter = format!("fps=5,scale=320:-2,select=gt(scene\\,{SCENE_THRESHOLD}),metadata=print");
+    let res = match run_program(
+        "ffmpeg",
+        work_dir,
+        &[
+            "-hide_banner",
+            "-nostats",
+            "-t",
+            &SCENE_DETECT_MAX_SECONDS.to_string(),
+            "-i",
+            input_name,
+            "-an",
+            "-sn",
+            "-vf",
+            &filter,
+            "-f",
+            "null",
+            "-",
+        ],
+    )
+    .await
+    {
+        Ok(res) if res.status.success() => res,
+        Ok(res) => {
+            warn!(
+                "ffmpeg scene detection failed: {}",
+                String::from_utf8_lossy(&res.stderr)
+            );
+            return Vec::new();
+        }
+        Err(e) => {
+            warn!("ffmpeg scene detection failed: {}", e);
+            return Vec::new();
+        }
+    };
+
+    // metadata=print 的输出形如：
+    // [Parsed_metadata_3 @ 0x..] frame:3    pts:57      pts_time:11.4
+    // [Parsed_metadata_3 @ 0x..] lavfi.scene_score=0.612
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    let mut changes: Vec<(f64, f64)> = Vec::new();
+    let mut pending_ts: Option<f64> = None;
+    for line in stderr.lines() {
+        if let Some(pos) = line.find("pts_time:") {
+            pending_ts = line[pos + 9..]
+                .split_whitespace()
+                .next()
+                .and_then(|v| v.parse::<f64>().ok());
+        } else if let Some(pos) = line.find("lavfi.scene_score=") {
+            let score = line[pos + 18..]
+                .trim()
+                .parse::<f64>()
+                .unwrap_or(SCENE_THRESHOLD);
+            if let Some(ts) = pending_ts.take() {
+                changes.push((ts, score));
+            }
+        }
+    }
+    changes
+}
+
+/// 规划候选时间点：开头一帧 + 场景切换点（按得分取前若干）+ 均匀分布的补充帧，
+/// 候选数量约为帧预算的两倍，之后再通过感知哈希去重并按得分取舍
+fn plan_candidate_timestamps(
+    duration: f64,
+    scene_changes: &[(f64, f64)],
+    max_frames: usize,
+) -> Vec<(f64, f64)> {
+    let oversample = max_frames * 2;
+    let mut planned: Vec<(f64, f64)> = Vec::new();
+
+    let start = if duration > 1.0 { 0.2 } else { 0.0 };
+    // 开头一帧优先级最高
+    planned.push((start, 2.0));
+
+    let mut scenes: Vec<(f64, f64)> = scene_changes.to_vec();
+    scenes.sort_by(|a, b| b.1.total_cmp(&a.1));
+    // 切换点之后稍等一下，避开转场过渡帧
+    planned.extend(
+        scenes
+            .into_iter()
+            .take(oversample.saturating_sub(1))
+            .map(|(ts, score)| ((ts + 0.1).min(duration.max(0.0)), 1.0 + score)),
+    );
+
+    // 均匀补充，保证静止段与没有场景切换的视频也有覆盖
+    let min_gap = if duration > 0.1 {
+        duration / (max_frames as f64 * 4.0)
+    } else {
+        0.5
+    };
+    let fill_count = oversample.saturating_sub(planned.len()).max(max_frames);
+    for i in 0..fill_count {
+        let ts = if duration > 0.1 {
+            ((i as f64 + 0.5) / fill_count as f64) * duration
+        } else {
+            i as f64
+        };
+        if planned.iter().all(|(t, _)| (t - ts).abs() >= min_gap) {
+            planned.push((ts, 0.0));
+        }
+    }
+
+    planned.sort_by(|a, b| a.0.total_cmp(&b.0));
+    planned
+}
+
+/// 抽帧并压缩为 data URL。
+///
+/// 先用 ffmpeg 场景检测找出镜头切换点，与均匀分布的时间点一起作为候选，
+/// 再按感知哈希（dHash）去掉几乎相同的帧，在 `max_frames` 预算内优先保留场景切换帧。
+/// `content_hash` 为视频的内容哈希（来自媒体缓存），有值时复用缓存的抽帧结果
 pub(super) async fn extract_video_frames_as_data_urls(
     video_path: &Path,
+    content_hash: Option<&str>,
     max_frames: u32,
     max_width: u32,
     max_height: u32,
@@ -64,18 +186,26 @@ pub(super) async fn extract_video_frames_as_data_urls(
         .and_then(|n| n.to_str())
         .ok_or_else(|| "视频文件名无效".to_string())?;
 
+    let max_frames = max_frames.clamp(1, 24);
+    let cache_params = format!(
+        "scene|{max_frames}|{max_width}x{max_height}|q{jpeg_quality}|{frame_max_output_bytes}"
+    );
+    if let Some(hash) = content_hash {
+        if let Some(frames) = media_cache::get_derived(hash, "video_frames", &cache_params)
+            .await
+            .and_then(|bytes| {
+                serde_json::from_slice::<Vec<(u64, String, PreparedImageMeta)>>(&bytes).ok()
+            })
+        {
+            return Ok(frames);
+        }
+    }
+
     let duration = probe_video_duration_seconds(video_path)
         .await
         .unwrap_or(0.0);
-    let max_frames = max_frames.clamp(1, 24);
-
-    let timestamps: Vec<f64> = if duration > 0.1 {
-        (0..max_frames)
-            .map(|i| ((i as f64 + 0.5) / max_frames as f64) * duration)
-            .collect()
-    } else {
-        (0..max_frames).map(|i| i as f64).collect()
-    };
+    let scene_changes = detect_scene_changes(work_dir, input_name).await;
+    let timestamps = plan_candidate_timestamps(duration, &scene_changes, max_frames as usize);
 
     let tmp_dir_name = format!("frames_{}", nonce12());
     let tmp_dir = work_dir.join(&tmp_dir_name);
@@ -83,14 +213,15 @@ pub(super) async fn extract_video_frames_as_data_urls(
         .await
         .map_err(|e| format!("Create temp dir failed: {e}"))?;
 
-    let mut frames: Vec<(u64, String, PreparedImageMeta)> = Vec::new();
-    for (idx, ts) in timestamps.iter().enumerate() {
+    let scale = format!(
+        "scale=w='min({max_width},iw)':h='min({max_height},ih)':force_original_aspect_ratio=decrease"
+    );
+    let mut candidates: Vec<FrameCandidate> = Vec::new();
+    let mut candidate_paths: Vec<PathBuf> = Vec::new();
+    for (idx, (ts, score)) in timestamps.iter().enumerate() {
         let out_file = format!("frame_{idx}.png");
         let out_rel = format!("{tmp_dir_name}/{out_file}");
         let out_path = tmp_dir.join(&out_file);
-        let scale = format!(
-            "scale=w='min({max_width},iw)':h='min({max_height},ih)':force_original_aspect_ratio=decrease"
-        );
         let ts_str = format!("{:.3}", ts);
 
         let res = run_program(
@@ -122,21 +253,36 @@ pub(super) async fn extract_video_frames_as_data_urls(
             continue;
         }
 
+        let Ok(img) = image::open(&out_path) else {
+            continue;
+        };
+        candidates.push(FrameCandidate {
+            ts_ms: (*ts * 1000.0).max(0.0) as u64,
+            score: *score,
+            hash: dhash(&img),
+        });
+        candidate_paths.push(out_path);
+    }
+
+    let mut frames: Vec<(u64, String, PreparedImageMeta)> = Vec::new();
+    for idx in select_distinct_frames(&candidates, max_frames as usize) {
         let (data_url, meta) = prepare_image_data_url(
-            &out_path,
+            &candidate_paths[idx],
             max_width,
             max_height,
             jpeg_quality,
             frame_max_output_bytes,
         )
         .await?;
-        let _ = tokio::fs::remove_file(&out_path).await;
-
-        let ts_ms = (*ts * 1000.0).max(0.0) as u64;
-        frames.push((ts_ms, data_url, meta));
+        frames.push((candidates[idx].ts_ms, data_url, meta));
     }
 
     let _ = tokio::fs::remove_dir_all(&tmp_dir).await;
+    if let (Some(hash), false) = (content_hash, frames.is_empty()) {
+        if let Ok(bytes) = serde_json::to_vec(&frames) {
+            media_cache::put_derived(hash, "video_frames", &cache_params, &bytes).await;
+        }
+    }
     Ok(frames)
 }
 
@@ -1,6 +1,6 @@
 mod ctx;
 mod direct;
-mod ffmpeg;
+pub(super) mod ffmpeg;
 mod frames;
 
 use serde_json::json;
@@ -13,10 +13,11 @@ use crate::models::SharedState;
 use super::super::LlmForwardVideoFromUrlInput;
 use super::common::{
     call_audio_transcription, call_chat_completions, download_binary_to_temp, log_llm_error,
-    log_llm_len, reply_err, resolve_llm_config_by_name, send_llm_markdown_as_forward_image,
-    SendForwardImageInput,
+    log_llm_len, reply_err, resolve_llm_config_by_name, send_llm_markdown_output,
+    SendLlmOutputInput,
 };
 
+use super::sampling::format_timestamp;
 use ctx::{build_video_ctx, VideoCtxInput};
 use direct::prepare_video_data_url_with_budget;
 use frames::{
@@ -35,6 +36,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
     let system_prompt = input.system_prompt;
     let prompt = input.prompt;
     let title = input.title;
+    let output_mode = input.output_mode;
     let mode = input.mode.trim().to_ascii_lowercase();
 
     let (guard, bin_meta) = match download_binary_to_temp(
@@ -229,15 +231,16 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
             };
 
             log_llm_len("视频分析", reply_content.len());
-            send_llm_markdown_as_forward_image(
+            send_llm_markdown_output(
                 state,
                 runtime,
                 bot_id,
-                SendForwardImageInput {
+                SendLlmOutputInput {
                     user_id,
                     group_id,
                     title,
                     markdown: &reply_content,
+                    output_mode,
                 },
             )
             .await;
@@ -253,6 +256,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
 
     let frames = match extract_video_frames_as_data_urls(
         &guard.path,
+        bin_meta.content_hash.as_deref(),
         input.max_frames,
         input.frame_max_width,
         input.frame_max_height,
@@ -389,7 +393,7 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
             };
             content_parts.push(json!({
                 "type": "text",
-                "text": format!("Frame {} @ {}ms", pos + 1, ts_ms)
+                "text": format!("Frame {} @ {}", pos + 1, format_timestamp(*ts_ms))
             }));
             content_parts.push(json!({ "type": "image_url", "image_url": { "url": data_url } }));
         }
@@ -436,15 +440,16 @@ pub(in super::super::super) async fn process_llm_forward_video_from_url(
         };
 
         log_llm_len("视频分析", reply_content.len());
-        send_llm_markdown_as_forward_image(
+        send_llm_markdown_output(
             state,
             runtime,
             bot_id,
-            SendForwardImageInput {
+            SendLlmOutputInput {
                 user_id,
                 group_id,
                 title,
                 markdown: &reply_content,
+                output_mode,
             },
         )
         .await;
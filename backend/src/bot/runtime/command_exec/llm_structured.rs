@@ -0,0 +1,262 @@
+//! 结构化输出：让 LLM 按插件给定的 JSON Schema 返回结果，在 Rust 侧解析和校验后再交给插件
+
+use serde_json::{json, Value};
+use tracing::warn;
+
+use super::llm_forward::multimodal::common::{call_chat_completions, LlmCallError, LlmConfig};
+
+mod schema;
+
+/// 结构化输出的实现方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum JsonMode {
+    /// 依次尝试 response_format → 强制工具调用 → 仅提示词，遇到 provider 不支持时自动降级
+    Auto,
+    ResponseFormat,
+    Tool,
+    Prompt,
+}
+
+impl JsonMode {
+    pub(super) fn parse(mode: Option<&str>) -> Self {
+        match mode.map(|s| s.trim().to_ascii_lowercase()).as_deref() {
+            Some("response_format") => Self::ResponseFormat,
+            Some("tool") => Self::Tool,
+            Some("prompt") => Self::Prompt,
+            _ => Self::Auto,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::ResponseFormat => "response_format",
+            Self::Tool => "tool",
+            Self::Prompt => "prompt",
+        }
+    }
+}
+
+/// 结构化输出失败（回传给插件的 error 对象）
+#[derive(Debug)]
+pub(super) struct StructuredOutputError {
+    /// "llm_error" | "invalid_schema" | "invalid_json" | "schema_mismatch"
+    pub(super) kind: &'static str,
+    pub(super) message: String,
+    /// 校验错误（路径 + 原因）
+    pub(super) errors: Vec<String>,
+    /// 模型最后一次的原始输出
+    pub(super) raw: Option<String>,
+}
+
+impl StructuredOutputError {
+    fn new(kind: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            errors: Vec::new(),
+            raw: None,
+        }
+    }
+
+    pub(super) fn to_json(&self) -> Value {
+        json!({
+            "type": self.kind,
+            "message": self.message,
+            "errors": self.errors,
+            "raw": self.raw,
+        })
+    }
+}
+
+fn sanitize_schema_name(name: Option<&str>) -> String {
+    let cleaned: String = name
+        .unwrap_or("")
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .take(64)
+        .collect();
+    if cleaned.is_empty() {
+        "result".to_string()
+    } else {
+        cleaned
+    }
+}
+
+/// 从模型输出中提取 JSON：兼容 ```json 代码块和前后多余文字
+fn extract_json(content: &str) -> Result<Value, String> {
+    let trimmed = content.trim();
+    let parse_err = match serde_json::from_str::<Value>(trimmed) {
+        Ok(v) => return Ok(v),
+        Err(e) => e.to_string(),
+    };
+
+    let unfenced = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|s| s.trim_end().strip_suffix("```"))
+        .map(str::trim);
+    if let Some(inner) = unfenced {
+        if let Ok(v) = serde_json::from_str::<Value>(inner) {
+            return Ok(v);
+        }
+    }
+
+    let start = trimmed.find(['{', '[']);
+    let end = trimmed.rfind(['}', ']']);
+    if let (Some(start), Some(end)) = (start, end) {
+        if start < end {
+            if let Ok(v) = serde_json::from_str::<Value>(&trimmed[start..=end]) {
+                return Ok(v);
+            }
+        }
+    }
+
+    Err(parse_err)
+}
+
+fn schema_instruction(schema: &Value) -> String {
+    format!(
+        "请只输出一个符合以下 JSON Schema 的 JSON 值，不要输出代码块标记、解释或任何其他文字。\nJSON Schema:\n{}",
+        serde_json::to_string(schema).unwrap_or_default()
+    )
+}
+
+fn build_request(base: &Value, schema: &Value, schema_name: &str, mode: JsonMode) -> Value {
+    let mut body = base.clone();
+    match mode {
+        JsonMode::ResponseFormat => {
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": { "name": schema_name, "schema": schema, "strict": false },
+            });
+        }
+        JsonMode::Tool => {
+            body["tools"] = json!([{
+                "type": "function",
+                "function": {
+                    "name": schema_name,
+                    "description": "以结构化 JSON 提交最终结果",
+                    "parameters": schema,
+                },
+            }]);
+            body["tool_choice"] =
+                json!({ "type": "function", "function": { "name": schema_name } });
+        }
+        JsonMode::Auto | JsonMode::Prompt => {}
+    }
+    body
+}
+
+/// provider 不支持 response_format / tools 时通常返回 400/404/422
+fn is_unsupported_feature_error(err: &LlmCallError) -> bool {
+    matches!(err, LlmCallError::Http { status, .. } if matches!(status, 400 | 404 | 422))
+}
+
+/// 调用 LLM 并返回符合 schema 的 JSON（原始文本 + 解析后的值）。
+/// 解析或校验失败时，把错误反馈给模型重试一次。
+pub(super) async fn call_structured_chat(
+    llm: &LlmConfig,
+    request_body: &Value,
+    schema: &Value,
+    schema_name: Option<&str>,
+    mode: JsonMode,
+) -> Result<(String, Value), StructuredOutputError> {
+    if !schema.is_object() {
+        return Err(StructuredOutputError::new(
+            "invalid_schema",
+            "responseSchema 必须是 JSON Schema 对象",
+        ));
+    }
+    let schema_name = sanitize_schema_name(schema_name);
+    let is_object_schema = schema.get("type").and_then(|t| t.as_str()) == Some("object");
+
+    let mut strategies = match mode {
+        JsonMode::Auto => vec![JsonMode::ResponseFormat, JsonMode::Tool, JsonMode::Prompt],
+        other => vec![other],
+    };
+    // 工具参数只能是 object
+    if !is_object_schema {
+        if mode == JsonMode::Tool {
+            return Err(StructuredOutputError::new(
+                "invalid_schema",
+                "jsonMode 为 tool 时 responseSchema 的顶层 type 必须是 object",
+            ));
+        }
+        strategies.retain(|m| *m != JsonMode::Tool);
+    }
+
+    // 所有方式都附带 schema 说明，部分 provider 会忽略 response_format
+    let mut base = request_body.clone();
+    if let Some(messages) = base.get_mut("messages").and_then(|m| m.as_array_mut()) {
+        messages.insert(
+            0,
+            json!({ "role": "system", "content": schema_instruction(schema) }),
+        );
+    }
+
+    let mut strategy_idx = 0usize;
+    let mut retried = false;
+    loop {
+        let strategy = strategies[strategy_idx];
+        let body = build_request(&base, schema, &schema_name, strategy);
+        let content =
+            match call_chat_completions(&llm.base_url, &llm.api_key, &body, llm.max_request_bytes)
+                .await
+            {
+                Ok(content) => content,
+                Err(e)
+                    if strategy_idx + 1 < strategies.len() && is_unsupported_feature_error(&e) =>
+                {
+                    warn!(
+                        "结构化输出方式 {} 不受支持，降级重试: {}",
+                        strategy.as_str(),
+                        e
+                    );
+                    strategy_idx += 1;
+                    continue;
+                }
+                Err(e) => return Err(StructuredOutputError::new("llm_error", e.to_string())),
+            };
+
+        let (kind, message, errors) = match extract_json(&content) {
+            Ok(value) => {
+                let errors = schema::validate(schema, &value);
+                if errors.is_empty() {
+                    return Ok((content, value));
+                }
+                (
+                    "schema_mismatch",
+                    "LLM 输出不符合 responseSchema".to_string(),
+                    errors,
+                )
+            }
+            Err(e) => (
+                "invalid_json",
+                "LLM 输出不是合法的 JSON".to_string(),
+                vec![format!("$: JSON 解析失败: {e}")],
+            ),
+        };
+
+        if retried {
+            return Err(StructuredOutputError {
+                kind,
+                message,
+                errors,
+                raw: Some(content),
+            });
+        }
+        retried = true;
+
+        if let Some(messages) = base.get_mut("messages").and_then(|m| m.as_array_mut()) {
+            messages.push(json!({ "role": "assistant", "content": content }));
+            messages.push(json!({
+                "role": "user",
+                "content": format!(
+                    "你上一次的输出未通过校验：\n- {}\n请修正后重新输出完整的 JSON，不要输出其他文字。",
+                    errors.join("\n- ")
+                ),
+            }));
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use serde_json::Value;
+
+/// 最多收集的错误数量（错误会回传给模型，过多没有意义）
+const MAX_ERRORS: usize = 20;
+
+/// 按 JSON Schema 的常用子集校验 `value`，返回所有不满足的位置（为空表示通过）。
+///
+/// 支持：type、enum、const、properties、required、additionalProperties、items、
+/// minItems/maxItems、minLength/maxLength、minimum/maximum、anyOf/oneOf/allOf。
+/// 其余关键字（如 pattern、format、$ref）会被忽略。
+pub(super) fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(schema, value, "$", &mut errors);
+    errors.truncate(MAX_ERRORS);
+    errors
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        other => type_name(value) == other,
+    }
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    if errors.len() >= MAX_ERRORS {
+        return;
+    }
+    let Some(schema) = schema.as_object() else {
+        // true / {} 允许任意值；false 拒绝任意值
+        if schema == &Value::Bool(false) {
+            errors.push(format!("{path}: 不允许出现该字段"));
+        }
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(s) => vec![s.as_str()],
+            Value::Array(arr) => arr.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| matches_type(t, value)) {
+            errors.push(format!(
+                "{path}: 期望类型 {}，实际为 {}",
+                types.join(" | "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(|v| v.as_array()) {
+        if !allowed.contains(value) {
+            let list = allowed
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            errors.push(format!("{path}: 取值必须是 [{list}] 之一，实际为 {value}"));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            errors.push(format!("{path}: 取值必须为 {expected}，实际为 {value}"));
+        }
+    }
+
+    validate_combinators(schema, value, path, errors);
+
+    match value {
+        Value::Object(map) => {
+            let properties = schema.get("properties").and_then(|v| v.as_object());
+            if let Some(required) = schema.get("required").and_then(|v| v.as_array()) {
+                for key in required.iter().filter_map(|k| k.as_str()) {
+                    if !map.contains_key(key) {
+                        errors.push(format!("{path}: 缺少必填字段 \"{key}\""));
+                    }
+                }
+            }
+            for (key, child) in map {
+                let child_path = format!("{path}.{key}");
+                match properties.and_then(|p| p.get(key)) {
+                    Some(child_schema) => validate_at(child_schema, child, &child_path, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{path}: 不允许额外字段 \"{key}\""))
+                        }
+                        Some(extra @ Value::Object(_)) => {
+                            validate_at(extra, child, &child_path, errors)
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema.get("minItems").and_then(|v| v.as_u64()) {
+                if (items.len() as u64) < min {
+                    errors.push(format!(
+                        "{path}: 至少需要 {min} 个元素，实际 {}",
+                        items.len()
+                    ));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(|v| v.as_u64()) {
+                if (items.len() as u64) > max {
+                    errors.push(format!("{path}: 最多 {max} 个元素，实际 {}", items.len()));
+                }
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (idx, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{path}[{idx}]"), errors);
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(|v| v.as_u64()) {
+                if len < min {
+                    errors.push(format!("{path}: 长度至少为 {min}，实际 {len}"));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(|v| v.as_u64()) {
+                if len > max {
+                    errors.push(format!("{path}: 长度最多为 {max}，实际 {len}"));
+                }
+            }
+        }
+        Value::Number(n) => {
+            let x = n.as_f64().unwrap_or(0.0);
+            if let Some(min) = schema.get("minimum").and_then(|v| v.as_f64()) {
+                if x < min {
+                    errors.push(format!("{path}: 不能小于 {min}，实际 {n}"));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(|v| v.as_f64()) {
+                if x > max {
+                    errors.push(format!("{path}: 不能大于 {max}，实际 {n}"));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn validate_combinators(
+    schema: &serde_json::Map<String, Value>,
+    value: &Value,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    let count_matches = |branches: &[Value]| {
+        branches
+            .iter()
+            .filter(|b| {
+                let mut sub = Vec::new();
+                validate_at(b, value, path, &mut sub);
+                sub.is_empty()
+            })
+            .count()
+    };
+
+    if let Some(branches) = schema.get("anyOf").and_then(|v| v.as_array()) {
+        if count_matches(branches) == 0 {
+            errors.push(format!("{path}: 不满足 anyOf 中的任何一个分支"));
+        }
+    }
+    if let Some(branches) = schema.get("oneOf").and_then(|v| v.as_array()) {
+        let n = count_matches(branches);
+        if n != 1 {
+            errors.push(format!(
+                "{path}: 必须恰好满足 oneOf 中的一个分支（实际满足 {n} 个）"
+            ));
+        }
+    }
+    if let Some(branches) = schema.get("allOf").and_then(|v| v.as_array()) {
+        for branch in branches {
+            validate_at(branch, value, path, errors);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate;
+    use serde_json::json;
+
+    #[test]
+    fn accepts_matching_object() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "label": { "type": "string", "enum": ["spam", "ok"] },
+                "score": { "type": "number", "minimum": 0, "maximum": 1 },
+                "tags": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["label", "score"],
+            "additionalProperties": false
+        });
+        let value = json!({ "label": "spam", "score": 0.9, "tags": ["ad"] });
+        assert!(validate(&schema, &value).is_empty());
+    }
+
+    #[test]
+    fn reports_each_violation_with_path() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "label": { "type": "string", "enum": ["spam", "ok"] },
+                "score": { "type": "number", "maximum": 1 },
+                "tags": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["label", "reason"],
+            "additionalProperties": false
+        });
+        let value = json!({ "label": "maybe", "score": 3, "tags": [1], "extra": true });
+        let errors = validate(&schema, &value);
+        assert_eq!(errors.len(), 5, "{errors:?}");
+        assert!(errors.iter().any(|e| e.contains("\"reason\"")));
+        assert!(errors.iter().any(|e| e.starts_with("$.label")));
+        assert!(errors.iter().any(|e| e.starts_with("$.score")));
+        assert!(errors.iter().any(|e| e.starts_with("$.tags[0]")));
+        assert!(errors.iter().any(|e| e.contains("\"extra\"")));
+    }
+
+    #[test]
+    fn integer_accepts_whole_floats_only() {
+        let schema = json!({ "type": "integer" });
+        assert!(validate(&schema, &json!(3)).is_empty());
+        assert!(validate(&schema, &json!(3.0)).is_empty());
+        assert!(!validate(&schema, &json!(3.5)).is_empty());
+    }
+}
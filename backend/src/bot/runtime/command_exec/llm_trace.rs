@@ -0,0 +1,770 @@
+//! LLM 调用追踪：按需记录每次调用的请求元数据、脱敏后的提示词、截断的回复、耗时与重试情况，
+//! 用于排查“AI 分析结果不对”时到底发了什么给模型。
+//!
+//! 默认关闭，在 LLM 模块配置的 `trace` 中开启：
+//! `{ "enabled": true, "retention_days": 7, "max_entries": 2000, "max_prompt_chars": 20000, "max_response_chars": 4000 }`
+
+use crate::models::SharedState;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+use tokio::task_local;
+use tracing::warn;
+
+use super::super::privacy;
+
+/// 内存中最多保留的记录数（所有 bot 合计）
+const HARD_MAX_ENTRIES: usize = 20_000;
+/// 两次清理过期文件之间的最小间隔
+const PRUNE_INTERVAL_MS: i64 = 3_600_000;
+/// 未配置（或未开启追踪）时的保留天数
+const DEFAULT_RETENTION_DAYS: i64 = 7;
+const DAY_MS: i64 = 86_400_000;
+
+#[derive(Debug, Clone)]
+struct TraceSettings {
+    retention_days: i64,
+    max_entries: usize,
+    max_prompt_chars: usize,
+    max_response_chars: usize,
+}
+
+impl TraceSettings {
+    /// 未开启时返回 None
+    fn from_state(state: &SharedState, bot_id: &str) -> Option<Self> {
+        let module = crate::module::get_effective_module(state, bot_id, "llm")?;
+        let trace = module.config.get("trace")?;
+        if !trace
+            .get("enabled")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+        {
+            return None;
+        }
+
+        let get_u64 =
+            |key: &str, default: u64| trace.get(key).and_then(|v| v.as_u64()).unwrap_or(default);
+        Some(Self {
+            retention_days: get_u64("retention_days", 7).clamp(1, 365) as i64,
+            max_entries: get_u64("max_entries", 2000).clamp(10, HARD_MAX_ENTRIES as u64) as usize,
+            max_prompt_chars: get_u64("max_prompt_chars", 20_000).clamp(500, 500_000) as usize,
+            max_response_chars: get_u64("max_response_chars", 4000).clamp(200, 200_000) as usize,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TraceScope {
+    bot_id: String,
+    plugin_id: Option<String>,
+    settings: TraceSettings,
+}
+
+task_local! {
+    static TRACE_SCOPE: TraceScope;
+}
+
+/// 在追踪上下文中执行 `fut`：其中发起的 LLM 调用会记录 bot / 插件信息。
+/// `plugin_id` 为 None 时沿用外层上下文的插件 ID。未开启追踪时直接执行。
+pub(super) async fn with_trace_scope<T>(
+    state: &SharedState,
+    bot_id: &str,
+    plugin_id: Option<&str>,
+    fut: impl std::future::Future<Output = T>,
+) -> T {
+    let Some(settings) = TraceSettings::from_state(state, bot_id) else {
+        return fut.await;
+    };
+    let plugin_id = plugin_id
+        .map(str::to_string)
+        .or_else(|| TRACE_SCOPE.try_with(|s| s.plugin_id.clone()).ok().flatten());
+    let scope = TraceScope {
+        bot_id: bot_id.to_string(),
+        plugin_id,
+        settings,
+    };
+    TRACE_SCOPE.scope(scope, fut).await
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmTracePromptMessage {
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmTraceEntry {
+    pub id: u64,
+    /// Unix 毫秒时间戳
+    pub created_at: i64,
+    pub bot_id: String,
+    #[serde(default)]
+    pub plugin_id: Option<String>,
+    /// 调用类型（"chat" / "chat_with_search"）
+    pub kind: String,
+    pub model: String,
+    /// 上游地址（仅 host，不含路径与密钥）
+    pub endpoint: String,
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    /// 请求中的其他关键参数（response_format / tools / temperature 等）
+    #[serde(default)]
+    pub options: Value,
+    pub message_count: usize,
+    /// 非文本附件（图片 / 音频 / 视频）数量
+    pub attachment_count: usize,
+    pub request_bytes: usize,
+    /// 脱敏后的提示词（附件以占位符表示）
+    pub prompt: Vec<LlmTracePromptMessage>,
+    #[serde(default)]
+    pub prompt_truncated: bool,
+    #[serde(default)]
+    pub response: Option<String>,
+    #[serde(default)]
+    pub response_chars: usize,
+    #[serde(default)]
+    pub response_truncated: bool,
+    pub latency_ms: u64,
+    /// HTTP 请求次数（含重试）
+    pub attempts: usize,
+    /// "ok" | "error"
+    pub status: String,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub http_status: Option<u16>,
+}
+
+impl LlmTraceEntry {
+    fn summary(&self) -> Value {
+        json!({
+            "id": self.id,
+            "created_at": self.created_at,
+            "bot_id": self.bot_id,
+            "plugin_id": self.plugin_id,
+            "kind": self.kind,
+            "model": self.model,
+            "endpoint": self.endpoint,
+            "message_count": self.message_count,
+            "attachment_count": self.attachment_count,
+            "request_bytes": self.request_bytes,
+            "response_chars": self.response_chars,
+            "latency_ms": self.latency_ms,
+            "attempts": self.attempts,
+            "status": self.status,
+            "error": self.error,
+            "http_status": self.http_status,
+        })
+    }
+}
+
+fn truncate_chars(s: &str, max_chars: usize) -> (String, bool) {
+    match s.char_indices().nth(max_chars) {
+        Some((idx, _)) => (s[..idx].to_string(), true),
+        None => (s.to_string(), false),
+    }
+}
+
+fn redact(bot_id: &str, text: &str) -> String {
+    privacy::redact_text(bot_id, privacy::Scope::Logs, text)
+}
+
+fn endpoint_host(base_url: &str) -> String {
+    reqwest::Url::parse(base_url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+        .unwrap_or_default()
+}
+
+/// 把消息内容转成可读文本，附件替换为占位符。返回 (文本, 附件数量)
+fn describe_content(content: &Value) -> (String, usize) {
+    match content {
+        Value::String(s) => (s.clone(), 0),
+        Value::Array(parts) => {
+            let mut out: Vec<String> = Vec::new();
+            let mut attachments = 0usize;
+            for part in parts {
+                let kind = part.get("type").and_then(|t| t.as_str()).unwrap_or("");
+                match kind {
+                    "text" => {
+                        if let Some(t) = part.get("text").and_then(|t| t.as_str()) {
+                            out.push(t.to_string());
+                        }
+                    }
+                    "" => {
+                        if let Some(s) = part.as_str() {
+                            out.push(s.to_string());
+                        }
+                    }
+                    other => {
+                        attachments += 1;
+                        let size = serde_json::to_string(part).map(|s| s.len()).unwrap_or(0);
+                        out.push(format!("[{other} 附件，约 {} KB]", size / 1024));
+                    }
+                }
+            }
+            (out.join("\n"), attachments)
+        }
+        Value::Null => (String::new(), 0),
+        other => (other.to_string(), 0),
+    }
+}
+
+/// 一次进行中的 LLM 调用（仅在追踪开启时存在）
+pub(super) struct TraceCall {
+    scope: TraceScope,
+    started: Instant,
+    entry: LlmTraceEntry,
+}
+
+impl TraceCall {
+    /// 当前任务不在追踪上下文中（或未开启）时返回 None
+    pub(super) fn begin(kind: &str, base_url: &str, request_body: &Value) -> Option<Self> {
+        let scope = TRACE_SCOPE.try_with(|s| s.clone()).ok()?;
+
+        let messages = request_body
+            .get("messages")
+            .and_then(|m| m.as_array())
+            .map(|m| m.as_slice())
+            .unwrap_or_default();
+        let mut prompt = Vec::with_capacity(messages.len());
+        let mut attachment_count = 0usize;
+        let mut budget = scope.settings.max_prompt_chars;
+        let mut prompt_truncated = false;
+        for msg in messages {
+            let role = msg
+                .get("role")
+                .and_then(|r| r.as_str())
+                .unwrap_or("")
+                .to_string();
+            let (text, attachments) = describe_content(msg.get("content").unwrap_or(&Value::Null));
+            attachment_count += attachments;
+            let (content, truncated) = truncate_chars(&redact(&scope.bot_id, &text), budget);
+            budget = budget.saturating_sub(content.chars().count());
+            prompt_truncated |= truncated;
+            prompt.push(LlmTracePromptMessage { role, content });
+        }
+
+        let mut options = serde_json::Map::new();
+        if let Some(obj) = request_body.as_object() {
+            for (key, value) in obj {
+                if matches!(key.as_str(), "model" | "messages" | "max_tokens") {
+                    continue;
+                }
+                options.insert(key.clone(), value.clone());
+            }
+        }
+
+        let entry = LlmTraceEntry {
+            id: 0,
+            created_at: chrono::Utc::now().timestamp_millis(),
+            bot_id: scope.bot_id.clone(),
+            plugin_id: scope.plugin_id.clone(),
+            kind: kind.to_string(),
+            model: request_body
+                .get("model")
+                .and_then(|m| m.as_str())
+                .unwrap_or("")
+                .to_string(),
+            endpoint: endpoint_host(base_url),
+            max_tokens: request_body.get("max_tokens").and_then(|v| v.as_u64()),
+            options: Value::Object(options),
+            message_count: messages.len(),
+            attachment_count,
+            request_bytes: serde_json::to_vec(request_body)
+                .map(|b| b.len())
+                .unwrap_or(0),
+            prompt,
+            prompt_truncated,
+            response: None,
+            response_chars: 0,
+            response_truncated: false,
+            latency_ms: 0,
+            attempts: 0,
+            status: String::new(),
+            error: None,
+            http_status: None,
+        };
+
+        Some(Self {
+            scope,
+            started: Instant::now(),
+            entry,
+        })
+    }
+
+    pub(super) fn finish_ok(mut self, attempts: usize, content: &str) {
+        let (response, truncated) = truncate_chars(
+            &redact(&self.scope.bot_id, content),
+            self.scope.settings.max_response_chars,
+        );
+        self.entry.response = Some(response);
+        self.entry.response_chars = content.chars().count();
+        self.entry.response_truncated = truncated;
+        self.entry.status = "ok".to_string();
+        self.finish(attempts);
+    }
+
+    pub(super) fn finish_err(mut self, attempts: usize, error: &str, http_status: Option<u16>) {
+        self.entry.error = Some(truncate_chars(&redact(&self.scope.bot_id, error), 2000).0);
+        self.entry.http_status = http_status;
+        self.entry.status = "error".to_string();
+        self.finish(attempts);
+    }
+
+    fn finish(mut self, attempts: usize) {
+        self.entry.latency_ms = self.started.elapsed().as_millis() as u64;
+        self.entry.attempts = attempts;
+        LLM_TRACE_STORE.record(self.entry, &self.scope.settings);
+    }
+}
+
+/// 追踪记录查询条件
+#[derive(Debug, Default, Deserialize)]
+pub struct LlmTraceQuery {
+    #[serde(default)]
+    pub bot_id: Option<String>,
+    #[serde(default)]
+    pub plugin_id: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// "ok" | "error"
+    #[serde(default)]
+    pub status: Option<String>,
+    /// 只返回 id 小于该值的记录（向前翻页）
+    #[serde(default)]
+    pub before_id: Option<u64>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Default)]
+struct TraceStoreInner {
+    next_id: u64,
+    entries: VecDeque<LlmTraceEntry>,
+    last_prune_at: i64,
+}
+
+struct TraceStore {
+    dir: PathBuf,
+    /// 各 bot 当前的保留天数；日志文件由所有 bot 共用，按其中最长的清理
+    retention_days: fn(&str) -> i64,
+    inner: Mutex<TraceStoreInner>,
+}
+
+static LLM_TRACE_STORE: Lazy<TraceStore> = Lazy::new(|| {
+    let data_dir = std::env::var("NBOT_DATA_DIR").unwrap_or_else(|_| "data".to_string());
+    TraceStore::load(Path::new(&data_dir).join("llm_traces"), retention_days_for)
+});
+
+/// 该 bot 当前配置的保留天数；未开启追踪或拿不到配置时使用默认值
+fn retention_days_for(bot_id: &str) -> i64 {
+    super::super::app_state()
+        .and_then(|state| TraceSettings::from_state(&state, bot_id))
+        .map_or(DEFAULT_RETENTION_DAYS, |s| s.retention_days)
+}
+
+fn day_file_name(ts_ms: i64) -> String {
+    let dt = chrono::DateTime::from_timestamp_millis(ts_ms)
+        .unwrap_or_default()
+        .with_timezone(&chrono::Local);
+    format!("{}.jsonl", dt.format("%Y-%m-%d"))
+}
+
+impl TraceStore {
+    /// 读取已有记录，丢弃超过各 bot 保留天数的记录并删除过期的日志文件
+    fn load(dir: PathBuf, retention_days: fn(&str) -> i64) -> Self {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)
+            .map(|rd| {
+                rd.flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("jsonl"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+
+        let mut entries: VecDeque<LlmTraceEntry> = VecDeque::new();
+        for path in files {
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            for line in content.lines().filter(|l| !l.trim().is_empty()) {
+                match serde_json::from_str::<LlmTraceEntry>(line) {
+                    Ok(entry) => entries.push_back(entry),
+                    Err(e) => warn!("解析 LLM 追踪记录失败 {:?}: {}", path, e),
+                }
+            }
+            while entries.len() > HARD_MAX_ENTRIES {
+                entries.pop_front();
+            }
+        }
+        let next_id = entries.iter().map(|e| e.id).max().unwrap_or(0);
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut retention: HashMap<String, i64> = HashMap::new();
+        entries.retain(|e| {
+            let days = *retention
+                .entry(e.bot_id.clone())
+                .or_insert_with(|| retention_days(&e.bot_id));
+            e.created_at >= now - days * DAY_MS
+        });
+        let max_days = max_retention_days(retention.keys().map(String::as_str), |bot_id| {
+            retention[bot_id]
+        });
+        prune_day_files(&dir, &day_file_name(now - max_days * DAY_MS));
+
+        Self {
+            dir,
+            retention_days,
+            inner: Mutex::new(TraceStoreInner {
+                next_id,
+                entries,
+                last_prune_at: 0,
+            }),
+        }
+    }
+
+    fn record(&self, mut entry: LlmTraceEntry, settings: &TraceSettings) {
+        let now = chrono::Utc::now().timestamp_millis();
+        let cutoff = now - settings.retention_days * DAY_MS;
+        // 到了清理文件的时间时返回当前存有记录的 bot
+        let prune_bots = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.next_id = inner.next_id.saturating_add(1);
+            entry.id = inner.next_id;
+            inner.entries.push_back(entry.clone());
+            prune_bot_entries(
+                &mut inner.entries,
+                &entry.bot_id,
+                cutoff,
+                settings.max_entries,
+            );
+
+            let due = now - inner.last_prune_at >= PRUNE_INTERVAL_MS;
+            if due {
+                inner.last_prune_at = now;
+            }
+            due.then(|| {
+                inner
+                    .entries
+                    .iter()
+                    .map(|e| e.bot_id.clone())
+                    .collect::<HashSet<_>>()
+            })
+        };
+        let keep_from = prune_bots.map(|bot_ids| {
+            let days = max_retention_days(bot_ids.iter().map(String::as_str), |bot_id| {
+                if bot_id == entry.bot_id {
+                    settings.retention_days
+                } else {
+                    (self.retention_days)(bot_id)
+                }
+            });
+            day_file_name(now - days * DAY_MS)
+        });
+
+        let dir = self.dir.clone();
+        let line = match serde_json::to_string(&entry) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("序列化 LLM 追踪记录失败: {}", e);
+                return;
+            }
+        };
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = append_line(&dir, &day_file_name(entry.created_at), &line) {
+                warn!("写入 LLM 追踪记录失败: {}", e);
+            }
+            if let Some(keep_from) = keep_from {
+                prune_day_files(&dir, &keep_from);
+            }
+        });
+    }
+
+    fn query(&self, q: &LlmTraceQuery) -> Vec<Value> {
+        let limit = q.limit.unwrap_or(50).clamp(1, 500);
+        let matches = |field: &Option<String>, value: Option<&str>| match field
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+        {
+            Some(want) => value == Some(want),
+            None => true,
+        };
+
+        let inner = self.inner.lock().unwrap();
+        inner
+            .entries
+            .iter()
+            .rev()
+            .filter(|e| q.before_id.is_none_or(|before| e.id < before))
+            .filter(|e| matches(&q.bot_id, Some(&e.bot_id)))
+            .filter(|e| matches(&q.plugin_id, e.plugin_id.as_deref()))
+            .filter(|e| matches(&q.model, Some(&e.model)))
+            .filter(|e| matches(&q.status, Some(&e.status)))
+            .take(limit)
+            .map(|e| e.summary())
+            .collect()
+    }
+
+    fn get(&self, id: u64) -> Option<LlmTraceEntry> {
+        let inner = self.inner.lock().unwrap();
+        inner.entries.iter().find(|e| e.id == id).cloned()
+    }
+
+    fn clear(&self) -> Result<usize, String> {
+        let removed = {
+            let mut inner = self.inner.lock().unwrap();
+            let n = inner.entries.len();
+            inner.entries.clear();
+            n
+        };
+        if let Ok(rd) = std::fs::read_dir(&self.dir) {
+            for path in rd.flatten().map(|e| e.path()) {
+                if path.extension().and_then(|e| e.to_str()) == Some("jsonl") {
+                    std::fs::remove_file(&path)
+                        .map_err(|e| format!("删除 {:?} 失败: {e}", path))?;
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// 按该 bot 的设置清理它自己的过期 / 超量记录（最旧的先删），不影响其他 bot
+fn prune_bot_entries(
+    entries: &mut VecDeque<LlmTraceEntry>,
+    bot_id: &str,
+    cutoff: i64,
+    max_entries: usize,
+) {
+    let kept = entries
+        .iter()
+        .filter(|e| e.bot_id == bot_id && e.created_at >= cutoff)
+        .count();
+    let mut excess = kept.saturating_sub(max_entries);
+    entries.retain(|e| {
+        if e.bot_id != bot_id {
+            return true;
+        }
+        if e.created_at < cutoff {
+            return false;
+        }
+        if excess > 0 {
+            excess -= 1;
+            return false;
+        }
+        true
+    });
+    while entries.len() > HARD_MAX_ENTRIES {
+        entries.pop_front();
+    }
+}
+
+/// 这些 bot 中最长的保留天数（没有记录时使用默认值）
+fn max_retention_days<'a>(
+    bot_ids: impl Iterator<Item = &'a str>,
+    retention_days: impl Fn(&str) -> i64,
+) -> i64 {
+    bot_ids
+        .map(retention_days)
+        .max()
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+}
+
+fn append_line(dir: &Path, file_name: &str, line: &str) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(file_name))?;
+    file.write_all(format!("{line}\n").as_bytes())
+}
+
+/// 删除早于 `keep_from`（按文件名 YYYY-MM-DD.jsonl 比较）的日志文件
+fn prune_day_files(dir: &Path, keep_from: &str) {
+    let Ok(rd) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for path in rd.flatten().map(|e| e.path()) {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if name.ends_with(".jsonl") && name < keep_from {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+
+/// 按条件列出追踪记录摘要（按时间倒序）
+pub fn query_llm_traces(q: &LlmTraceQuery) -> Vec<Value> {
+    LLM_TRACE_STORE.query(q)
+}
+
+/// 获取单条追踪记录（含提示词与回复）
+pub fn get_llm_trace(id: u64) -> Option<LlmTraceEntry> {
+    LLM_TRACE_STORE.get(id)
+}
+
+/// 清空所有追踪记录，返回清除的条数
+pub fn clear_llm_traces() -> Result<usize, String> {
+    LLM_TRACE_STORE.clear()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_entry(id: u64, bot_id: &str, created_at: i64) -> LlmTraceEntry {
+        LlmTraceEntry {
+            id,
+            created_at,
+            bot_id: bot_id.to_string(),
+            plugin_id: None,
+            kind: "chat".to_string(),
+            model: "gpt".to_string(),
+            endpoint: String::new(),
+            max_tokens: None,
+            options: Value::Null,
+            message_count: 0,
+            attachment_count: 0,
+            request_bytes: 0,
+            prompt: Vec::new(),
+            prompt_truncated: false,
+            response: None,
+            response_chars: 0,
+            response_truncated: false,
+            latency_ms: 0,
+            attempts: 1,
+            status: "ok".to_string(),
+            error: None,
+            http_status: None,
+        }
+    }
+
+    #[test]
+    fn load_applies_retention_per_bot() {
+        let dir = std::env::temp_dir().join(format!("nbot_llm_trace_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let now = chrono::Utc::now().timestamp_millis();
+        let entries = [
+            test_entry(1, "a", now - 10 * DAY_MS),
+            test_entry(2, "a", now - 2 * DAY_MS),
+            test_entry(3, "b", now - 2 * DAY_MS),
+            test_entry(4, "b", now),
+        ];
+        for entry in &entries {
+            let line = serde_json::to_string(entry).unwrap();
+            append_line(&dir, &day_file_name(entry.created_at), &line).unwrap();
+        }
+        let old_file = dir.join(day_file_name(now - 10 * DAY_MS));
+        assert!(old_file.exists());
+
+        let store = TraceStore::load(dir.clone(), |bot_id| if bot_id == "a" { 7 } else { 1 });
+        let inner = store.inner.lock().unwrap();
+        let ids: Vec<u64> = inner.entries.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![2, 4]);
+        assert_eq!(inner.next_id, 4);
+        assert!(!old_file.exists());
+        assert!(dir.join(day_file_name(now - 2 * DAY_MS)).exists());
+        drop(inner);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prune_only_touches_the_recording_bot() {
+        let now = chrono::Utc::now().timestamp_millis();
+        let mut entries: VecDeque<LlmTraceEntry> = [
+            test_entry(1, "a", now - 5 * DAY_MS),
+            test_entry(2, "b", now - 5 * DAY_MS),
+            test_entry(3, "a", now - 3),
+            test_entry(4, "b", now - 2),
+            test_entry(5, "a", now - 1),
+            test_entry(6, "a", now),
+        ]
+        .into_iter()
+        .collect();
+
+        // a：保留 1 天、最多 2 条；b 的旧记录不受影响
+        prune_bot_entries(&mut entries, "a", now - DAY_MS, 2);
+        let ids: Vec<u64> = entries.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![2, 4, 5, 6]);
+
+        let days = max_retention_days(
+            ["a", "b"].into_iter(),
+            |bot_id| {
+                if bot_id == "a" {
+                    1
+                } else {
+                    30
+                }
+            },
+        );
+        assert_eq!(days, 30);
+        assert_eq!(
+            max_retention_days(std::iter::empty(), |_| 1),
+            DEFAULT_RETENTION_DAYS
+        );
+    }
+
+    #[test]
+    fn begin_redacts_and_truncates_prompt() {
+        let scope = TraceScope {
+            bot_id: "trace_test".to_string(),
+            plugin_id: Some("demo".to_string()),
+            settings: TraceSettings {
+                retention_days: 7,
+                max_entries: 100,
+                max_prompt_chars: 40,
+                max_response_chars: 200,
+            },
+        };
+        let body = json!({
+            "model": "gpt",
+            "max_tokens": 256,
+            "temperature": 0.2,
+            "messages": [
+                { "role": "system", "content": "联系电话 13812345678" },
+                { "role": "user", "content": [
+                    { "type": "text", "text": "看看这张图".repeat(10) },
+                    { "type": "image_url", "image_url": { "url": "data:image/png;base64,AAAA" } },
+                ] },
+            ],
+        });
+        let call = TRACE_SCOPE
+            .sync_scope(scope, || {
+                TraceCall::begin("chat", "https://api.example.com/v1?key=secret", &body)
+            })
+            .unwrap();
+        let entry = call.entry;
+
+        assert_eq!(entry.endpoint, "api.example.com");
+        assert_eq!(entry.plugin_id.as_deref(), Some("demo"));
+        assert_eq!(entry.max_tokens, Some(256));
+        assert_eq!(entry.options, json!({ "temperature": 0.2 }));
+        assert_eq!((entry.message_count, entry.attachment_count), (2, 1));
+
+        assert_eq!(entry.prompt[0].role, "system");
+        assert!(entry.prompt[0].content.starts_with("联系电话"));
+        assert!(!entry.prompt[0].content.contains("13812345678"));
+        let total: usize = entry.prompt.iter().map(|m| m.content.chars().count()).sum();
+        assert_eq!(total, 40);
+        assert!(entry.prompt_truncated);
+
+        // 不在追踪上下文中时不记录
+        assert!(TraceCall::begin("chat", "", &body).is_none());
+    }
+
+    #[test]
+    fn truncate_chars_counts_characters() {
+        assert_eq!(truncate_chars("你好世界", 2), ("你好".to_string(), true));
+        assert_eq!(truncate_chars("你好", 2), ("你好".to_string(), false));
+    }
+}
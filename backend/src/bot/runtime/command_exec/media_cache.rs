@@ -0,0 +1,802 @@
+//! 多模态媒体缓存：按 URL / 文件 ID 与内容哈希缓存下载的原始文件，并缓存其派生产物
+//! （压缩后的图片 data URL、视频抽帧、音频转写），同一份上传被多个插件分析或用户重试时不再重复下载和处理。
+//!
+//! 缓存位于 `data/cache/media/`：
+//! - `objects/<前两位>/<id>`：原始文件以内容 SHA-256 为 id（相同内容只存一份），派生产物以 `源哈希|类型|参数` 的哈希为 id
+//! - `index.json`：URL → 内容哈希（及源站的 ETag / Last-Modified）的映射与各对象的大小、创建时间、最近访问时间
+//!
+//! 普通 URL 的缓存须带 `If-None-Match` / `If-Modified-Since` 向源站确认未变化（HTTP 304）才会使用，
+//! 源站不提供校验信息时每次重新下载；QQ 文件 ID 与语音文件 ID 对应固定内容，命中即可使用。
+//!
+//! 环境变量：
+//! - `NBOT_MEDIA_CACHE_MAX_MB`：容量上限（默认 1024，设为 0 关闭缓存），超出后按最近最少使用淘汰
+//! - `NBOT_MEDIA_CACHE_TTL_HOURS`：对象自创建起的有效期（默认 24）
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncReadExt;
+use tracing::warn;
+
+const DEFAULT_MAX_MB: u64 = 1024;
+const DEFAULT_TTL_HOURS: u64 = 24;
+/// 原始文件的类型名，其余类型均为派生产物
+const SOURCE_KIND: &str = "source";
+
+struct CacheSettings {
+    max_bytes: u64,
+    ttl_ms: i64,
+}
+
+impl CacheSettings {
+    fn from_env() -> Self {
+        let env_u64 = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            max_bytes: env_u64("NBOT_MEDIA_CACHE_MAX_MB", DEFAULT_MAX_MB)
+                .saturating_mul(1024 * 1024),
+            ttl_ms: (env_u64("NBOT_MEDIA_CACHE_TTL_HOURS", DEFAULT_TTL_HOURS).max(1) * 3_600_000)
+                as i64,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.max_bytes > 0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheObject {
+    kind: String,
+    /// 派生产物对应的原始文件哈希
+    #[serde(default)]
+    source: Option<String>,
+    size: u64,
+    created_at: i64,
+    last_access: i64,
+    #[serde(default)]
+    hits: u64,
+}
+
+/// 源站提供的缓存校验信息
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct Validators {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+}
+
+impl Validators {
+    pub(super) fn from_headers(headers: &reqwest::header::HeaderMap) -> Self {
+        let get = |name: reqwest::header::HeaderName| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        Self {
+            etag: get(reqwest::header::ETAG),
+            last_modified: get(reqwest::header::LAST_MODIFIED),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// 给请求加上条件请求头，源站内容未变化时返回 304
+    pub(super) fn apply(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SourceEntry {
+    hash: String,
+    #[serde(flatten)]
+    validators: Validators,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheIndex {
+    /// 来源 key（URL / 文件 ID）→ 原始文件哈希
+    #[serde(default)]
+    sources: HashMap<String, SourceEntry>,
+    objects: HashMap<String, CacheObject>,
+}
+
+/// 缓存中的原始文件如何才能使用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum SourceCheck {
+    /// 来源 key 对应固定内容，可直接使用
+    Immutable,
+    /// 需带上校验信息向源站确认未变化后才能使用
+    Revalidate(Validators),
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+struct MediaCache {
+    dir: PathBuf,
+    settings: CacheSettings,
+    inner: Mutex<(CacheIndex, Counters)>,
+    /// 索引快照序号（在 `inner` 锁内分配）与已落盘的最新序号，避免旧快照覆盖新快照
+    index_seq: AtomicU64,
+    index_written: Arc<Mutex<u64>>,
+}
+
+static MEDIA_CACHE: Lazy<MediaCache> = Lazy::new(|| {
+    let data_dir = std::env::var("NBOT_DATA_DIR").unwrap_or_else(|_| "data".to_string());
+    MediaCache::load(
+        Path::new(&data_dir).join("cache").join("media"),
+        CacheSettings::from_env(),
+    )
+});
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// 计算内容哈希（用于派生产物的缓存 key）
+pub(super) fn hash_bytes(bytes: &[u8]) -> String {
+    sha256_hex(bytes)
+}
+
+/// 流式计算文件的内容哈希
+async fn hash_file(path: &Path) -> Result<String, String> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| format!("打开文件失败: {e}"))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .await
+            .map_err(|e| format!("读取文件失败: {e}"))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// URL 对应的来源 key。QQ 的媒体链接带有会变化的 rkey，按其中的 fileid 识别同一文件
+pub(super) fn source_key_for_url(url: &str) -> String {
+    let url = url.trim();
+    if let Ok(parsed) = reqwest::Url::parse(url) {
+        if let Some((_, file_id)) = parsed
+            .query_pairs()
+            .find(|(k, v)| k.eq_ignore_ascii_case("fileid") && !v.is_empty())
+        {
+            return format!("qq-file:{file_id}");
+        }
+    }
+    format!("url:{}", url.split('#').next().unwrap_or(url))
+}
+
+/// 语音文件的来源 key（文件 ID 只在所属 bot 内有效）
+pub(super) fn source_key_for_record(bot_id: &str, record_file: &str) -> String {
+    format!("record:{bot_id}:{record_file}")
+}
+
+/// 文件 ID 类的 key 对应固定内容，不需要向源站确认
+fn is_immutable_key(key: &str) -> bool {
+    key.starts_with("qq-file:") || key.starts_with("record:")
+}
+
+static NEXT_PART: AtomicU64 = AtomicU64::new(1);
+
+fn part_path(to: &Path) -> PathBuf {
+    let name = to.file_name().and_then(|n| n.to_str()).unwrap_or("media");
+    to.with_file_name(format!(
+        ".{name}.{}.{}.part",
+        std::process::id(),
+        NEXT_PART.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// 复制到同目录的临时文件再改名替换 `to`。不用硬链接：缓存对象与调用方的文件若共用 inode，
+/// 任何一方原地改写都会写穿另一方；改名则保证 `to` 原有的内容只会被整体替换
+async fn copy_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    let part = part_path(to);
+    let result = match tokio::fs::copy(from, &part).await {
+        Ok(_) => tokio::fs::rename(&part, to).await,
+        Err(e) => Err(e),
+    };
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&part).await;
+    }
+    result
+}
+
+async fn write_file(to: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let part = part_path(to);
+    let result = match tokio::fs::write(&part, bytes).await {
+        Ok(()) => tokio::fs::rename(&part, to).await,
+        Err(e) => Err(e),
+    };
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&part).await;
+    }
+    result
+}
+
+impl MediaCache {
+    fn load(dir: PathBuf, settings: CacheSettings) -> Self {
+        let index = std::fs::read_to_string(dir.join("index.json"))
+            .ok()
+            .and_then(|s| match serde_json::from_str::<CacheIndex>(&s) {
+                Ok(index) => Some(index),
+                Err(e) => {
+                    warn!("解析媒体缓存索引失败，将重建: {}", e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+        Self {
+            dir,
+            settings,
+            inner: Mutex::new((index, Counters::default())),
+            index_seq: AtomicU64::new(0),
+            index_written: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    fn object_path(&self, id: &str) -> PathBuf {
+        self.dir.join("objects").join(&id[..2]).join(id)
+    }
+
+    /// 在 `inner` 锁内序列化索引，落盘交给阻塞线程池
+    fn save_index(&self, index: &CacheIndex) {
+        let Ok(content) = serde_json::to_string(index) else {
+            return;
+        };
+        let seq = self.index_seq.fetch_add(1, Ordering::Relaxed) + 1;
+        let dir = self.dir.clone();
+        let written = self.index_written.clone();
+        let write = move || {
+            let mut written = written.lock().unwrap();
+            if *written >= seq {
+                return;
+            }
+            let tmp = dir.join("index.json.tmp");
+            let result = std::fs::create_dir_all(&dir)
+                .and_then(|_| std::fs::write(&tmp, content))
+                .and_then(|_| std::fs::rename(&tmp, dir.join("index.json")));
+            match result {
+                Ok(()) => *written = seq,
+                Err(e) => warn!("写入媒体缓存索引失败: {}", e),
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => drop(handle.spawn_blocking(write)),
+            Err(_) => write(),
+        }
+    }
+
+    fn remove_files(&self, ids: &[String]) {
+        for id in ids {
+            let _ = std::fs::remove_file(self.object_path(id));
+        }
+    }
+
+    /// 从索引中删除对象（原始文件会连同其派生产物与指向它的 key 一起删除），返回被删除的 id
+    fn remove_objects(index: &mut CacheIndex, ids: &[String]) -> Vec<String> {
+        let mut removed: Vec<String> = Vec::new();
+        for id in ids {
+            if index.objects.remove(id).is_none() {
+                continue;
+            }
+            removed.push(id.clone());
+            let derived: Vec<String> = index
+                .objects
+                .iter()
+                .filter(|(_, o)| o.source.as_deref() == Some(id.as_str()))
+                .map(|(k, _)| k.clone())
+                .collect();
+            for d in derived {
+                index.objects.remove(&d);
+                removed.push(d);
+            }
+            index.sources.retain(|_, entry| entry.hash != *id);
+        }
+        removed
+    }
+
+    /// 清理过期对象并按 LRU 淘汰到容量以内（不淘汰刚写入的 `keep`），返回被删除的 id
+    fn enforce_limits(
+        &self,
+        index: &mut CacheIndex,
+        counters: &mut Counters,
+        keep: &str,
+    ) -> Vec<String> {
+        let cutoff = now_ms() - self.settings.ttl_ms;
+        let expired: Vec<String> = index
+            .objects
+            .iter()
+            .filter(|(_, o)| o.created_at < cutoff)
+            .map(|(k, _)| k.clone())
+            .collect();
+        let mut removed = Self::remove_objects(index, &expired);
+
+        let mut total: u64 = index.objects.values().map(|o| o.size).sum();
+        if total > self.settings.max_bytes {
+            // 删除原始文件会连带删除其派生产物，刚写入的派生产物的来源也不能淘汰
+            let keep_source = index.objects.get(keep).and_then(|o| o.source.clone());
+            let mut by_access: Vec<(i64, String)> = index
+                .objects
+                .iter()
+                .filter(|(k, _)| *k != keep && Some(k.as_str()) != keep_source.as_deref())
+                .map(|(k, o)| (o.last_access, k.clone()))
+                .collect();
+            by_access.sort();
+            for (_, id) in by_access {
+                if total <= self.settings.max_bytes {
+                    break;
+                }
+                for gone in Self::remove_objects(index, std::slice::from_ref(&id)) {
+                    counters.evictions += 1;
+                    removed.push(gone);
+                }
+                total = index.objects.values().map(|o| o.size).sum();
+            }
+        }
+        removed
+    }
+
+    /// 查找对象并更新访问时间；过期或文件丢失时视为未命中
+    fn touch(&self, id: &str) -> Option<u64> {
+        let mut guard = self.inner.lock().unwrap();
+        let (index, counters) = &mut *guard;
+        let now = now_ms();
+        let alive = index
+            .objects
+            .get(id)
+            .is_some_and(|o| o.created_at >= now - self.settings.ttl_ms)
+            && self.object_path(id).exists();
+        if !alive {
+            counters.misses += 1;
+            let removed = Self::remove_objects(index, &[id.to_string()]);
+            drop(guard);
+            self.remove_files(&removed);
+            return None;
+        }
+        let object = index.objects.get_mut(id)?;
+        object.last_access = now;
+        object.hits += 1;
+        counters.hits += 1;
+        Some(object.size)
+    }
+
+    fn insert(&self, id: &str, object: CacheObject, source: Option<(&str, Validators)>) {
+        let mut guard = self.inner.lock().unwrap();
+        let (index, counters) = &mut *guard;
+        if let Some((key, validators)) = source {
+            let entry = SourceEntry {
+                hash: id.to_string(),
+                validators,
+            };
+            index.sources.insert(key.to_string(), entry);
+        }
+        index.objects.insert(id.to_string(), object);
+        let removed = self.enforce_limits(index, counters, id);
+        self.save_index(index);
+        drop(guard);
+        self.remove_files(&removed);
+    }
+
+    fn check_source(&self, key: &str) -> Option<SourceCheck> {
+        if !self.settings.enabled() {
+            return None;
+        }
+        let mut guard = self.inner.lock().unwrap();
+        let check = guard.0.sources.get(key).and_then(|entry| {
+            if !entry.validators.is_empty() {
+                Some(SourceCheck::Revalidate(entry.validators.clone()))
+            } else if is_immutable_key(key) {
+                Some(SourceCheck::Immutable)
+            } else {
+                None
+            }
+        });
+        if check.is_none() {
+            guard.1.misses += 1;
+        }
+        check
+    }
+
+    async fn restore_source(&self, key: &str, dest: &Path, max_bytes: u64) -> Option<CachedSource> {
+        if !self.settings.enabled() {
+            return None;
+        }
+        let hash = {
+            let mut guard = self.inner.lock().unwrap();
+            let hash = guard.0.sources.get(key).map(|entry| entry.hash.clone());
+            if hash.is_none() {
+                guard.1.misses += 1;
+            }
+            hash?
+        };
+        let size = self.touch(&hash)?;
+        if size > max_bytes {
+            return None;
+        }
+        if let Err(e) = copy_file(&self.object_path(&hash), dest).await {
+            warn!("读取媒体缓存失败: {}", e);
+            return None;
+        }
+        Some(CachedSource { hash, size })
+    }
+
+    async fn store_source(&self, key: &str, path: &Path, validators: Validators) -> Option<String> {
+        if !self.settings.enabled() {
+            return None;
+        }
+        let hash = match hash_file(path).await {
+            Ok(h) => h,
+            Err(e) => {
+                warn!("计算媒体缓存哈希失败: {}", e);
+                return None;
+            }
+        };
+        let size = tokio::fs::metadata(path).await.ok()?.len();
+        if size > self.settings.max_bytes {
+            return Some(hash);
+        }
+
+        let object_path = self.object_path(&hash);
+        if !object_path.exists() {
+            if let Some(parent) = object_path.parent() {
+                let _ = tokio::fs::create_dir_all(parent).await;
+            }
+            if let Err(e) = copy_file(path, &object_path).await {
+                warn!("写入媒体缓存失败: {}", e);
+                return Some(hash);
+            }
+        }
+        let now = now_ms();
+        let object = CacheObject {
+            kind: SOURCE_KIND.to_string(),
+            source: None,
+            size,
+            created_at: now,
+            last_access: now,
+            hits: 0,
+        };
+        // 既非固定内容又无校验信息的 URL 无法判断是否变化，只按内容存一份供派生产物复用
+        let source = (is_immutable_key(key) || !validators.is_empty()).then_some((key, validators));
+        self.insert(&hash, object, source);
+        Some(hash)
+    }
+
+    async fn get_derived(&self, source_hash: &str, kind: &str, params: &str) -> Option<Vec<u8>> {
+        if !self.settings.enabled() {
+            return None;
+        }
+        let id = derived_id(source_hash, kind, params);
+        self.touch(&id)?;
+        tokio::fs::read(self.object_path(&id)).await.ok()
+    }
+
+    async fn put_derived(&self, source_hash: &str, kind: &str, params: &str, bytes: &[u8]) {
+        if !self.settings.enabled() || bytes.len() as u64 > self.settings.max_bytes {
+            return;
+        }
+        let id = derived_id(source_hash, kind, params);
+        let path = self.object_path(&id);
+        if let Some(parent) = path.parent() {
+            let _ = tokio::fs::create_dir_all(parent).await;
+        }
+        if let Err(e) = write_file(&path, bytes).await {
+            warn!("写入媒体缓存失败: {}", e);
+            return;
+        }
+        let now = now_ms();
+        let object = CacheObject {
+            kind: kind.to_string(),
+            source: Some(source_hash.to_string()),
+            size: bytes.len() as u64,
+            created_at: now,
+            last_access: now,
+            hits: 0,
+        };
+        self.insert(&id, object, None);
+    }
+}
+
+pub(super) struct CachedSource {
+    pub(super) hash: String,
+    pub(super) size: u64,
+}
+
+/// 来源 key 是否有可用的缓存，以及使用前是否需要向源站确认
+pub(super) fn check_source(key: &str) -> Option<SourceCheck> {
+    MEDIA_CACHE.check_source(key)
+}
+
+/// 把来源 key 对应的原始文件复制到 `dest`（不超过 `max_bytes` 才算命中）。
+/// 普通 URL 须先经 [`check_source`] 与源站确认未变化
+pub(super) async fn restore_source(key: &str, dest: &Path, max_bytes: u64) -> Option<CachedSource> {
+    MEDIA_CACHE.restore_source(key, dest, max_bytes).await
+}
+
+/// 登记下载完成的原始文件（按内容去重）及源站的校验信息，返回内容哈希
+pub(super) async fn store_source(key: &str, path: &Path, validators: Validators) -> Option<String> {
+    MEDIA_CACHE.store_source(key, path, validators).await
+}
+
+fn derived_id(source_hash: &str, kind: &str, params: &str) -> String {
+    sha256_hex(format!("{source_hash}|{kind}|{params}").as_bytes())
+}
+
+/// 读取派生产物（如 `image` / `video_frames` / `transcript`），`params` 为影响结果的处理参数
+pub(super) async fn get_derived(source_hash: &str, kind: &str, params: &str) -> Option<Vec<u8>> {
+    MEDIA_CACHE.get_derived(source_hash, kind, params).await
+}
+
+/// 写入派生产物
+pub(super) async fn put_derived(source_hash: &str, kind: &str, params: &str, bytes: &[u8]) {
+    MEDIA_CACHE
+        .put_derived(source_hash, kind, params, bytes)
+        .await
+}
+
+/// 缓存统计：总量、命中率与按类型的分布
+pub fn media_cache_stats() -> Value {
+    let cache = &*MEDIA_CACHE;
+    let guard = cache.inner.lock().unwrap();
+    let (index, counters) = &*guard;
+
+    let mut by_kind: HashMap<&str, (u64, u64, u64)> = HashMap::new();
+    for object in index.objects.values() {
+        let entry = by_kind.entry(object.kind.as_str()).or_default();
+        entry.0 += 1;
+        entry.1 += object.size;
+        entry.2 += object.hits;
+    }
+    let kinds: serde_json::Map<String, Value> = by_kind
+        .into_iter()
+        .map(|(kind, (count, bytes, hits))| {
+            (
+                kind.to_string(),
+                json!({ "count": count, "bytes": bytes, "hits": hits }),
+            )
+        })
+        .collect();
+    let lookups = counters.hits + counters.misses;
+
+    json!({
+        "enabled": cache.settings.enabled(),
+        "max_bytes": cache.settings.max_bytes,
+        "ttl_hours": cache.settings.ttl_ms / 3_600_000,
+        "objects": index.objects.len(),
+        "keys": index.sources.len(),
+        "total_bytes": index.objects.values().map(|o| o.size).sum::<u64>(),
+        "kinds": kinds,
+        "hits": counters.hits,
+        "misses": counters.misses,
+        "hit_rate": if lookups > 0 { counters.hits as f64 / lookups as f64 } else { 0.0 },
+        "evictions": counters.evictions,
+    })
+}
+
+/// 清除缓存。`kind` 为空时清除全部；为 `source` 时清除原始文件（连同其派生产物）；
+/// 其他值只清除该类型的派生产物。返回删除的对象数
+pub fn purge_media_cache(kind: Option<&str>) -> Result<usize, String> {
+    let cache = &*MEDIA_CACHE;
+    let mut guard = cache.inner.lock().unwrap();
+    let (index, _) = &mut *guard;
+    let kind = kind.map(str::trim).filter(|k| !k.is_empty());
+
+    let removed = match kind {
+        None => {
+            let removed = index.objects.len();
+            *index = CacheIndex::default();
+            cache.save_index(index);
+            drop(guard);
+            let objects_dir = cache.dir.join("objects");
+            if objects_dir.exists() {
+                std::fs::remove_dir_all(&objects_dir)
+                    .map_err(|e| format!("删除缓存目录失败: {e}"))?;
+            }
+            return Ok(removed);
+        }
+        Some(kind) => {
+            let ids: Vec<String> = index
+                .objects
+                .iter()
+                .filter(|(_, o)| o.kind == kind)
+                .map(|(k, _)| k.clone())
+                .collect();
+            MediaCache::remove_objects(index, &ids)
+        }
+    };
+    cache.save_index(index);
+    drop(guard);
+    cache.remove_files(&removed);
+    Ok(removed.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_cache(name: &str, max_bytes: u64) -> (MediaCache, PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "nbot_media_cache_test_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let settings = CacheSettings {
+            max_bytes,
+            ttl_ms: 3_600_000,
+        };
+        (MediaCache::load(dir.join("cache"), settings), dir)
+    }
+
+    fn etag(value: &str) -> Validators {
+        Validators {
+            etag: Some(value.to_string()),
+            last_modified: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn store_and_restore_source() {
+        let (cache, dir) = test_cache("restore", 1024 * 1024);
+        let download = dir.join("download.bin");
+        std::fs::write(&download, b"hello media").unwrap();
+
+        let hash = cache
+            .store_source("url:https://a.test/x.png", &download, etag("\"v1\""))
+            .await
+            .unwrap();
+        assert_eq!(
+            cache.check_source("url:https://a.test/x.png"),
+            Some(SourceCheck::Revalidate(etag("\"v1\"")))
+        );
+
+        let dest = dir.join("restored.bin");
+        let hit = cache
+            .restore_source("url:https://a.test/x.png", &dest, 1024)
+            .await
+            .unwrap();
+        assert_eq!((hit.hash.as_str(), hit.size), (hash.as_str(), 11));
+        assert_eq!(std::fs::read(&dest).unwrap(), b"hello media");
+
+        // 改写取出的文件不能影响缓存对象
+        std::fs::write(&dest, b"changed").unwrap();
+        std::fs::write(&download, b"changed too").unwrap();
+        cache
+            .restore_source("url:https://a.test/x.png", &dest, 1024)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"hello media");
+
+        // 超过调用方上限不算命中
+        assert!(cache
+            .restore_source("url:https://a.test/x.png", &dest, 4)
+            .await
+            .is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn urls_without_validators_are_not_reused() {
+        let (cache, dir) = test_cache("validators", 1024 * 1024);
+        let download = dir.join("download.bin");
+        std::fs::write(&download, b"content").unwrap();
+
+        let hash = cache
+            .store_source("url:https://a.test/y", &download, Validators::default())
+            .await;
+        assert!(hash.is_some());
+        assert_eq!(cache.check_source("url:https://a.test/y"), None);
+
+        cache
+            .store_source("qq-file:abc", &download, Validators::default())
+            .await;
+        assert_eq!(
+            cache.check_source("qq-file:abc"),
+            Some(SourceCheck::Immutable)
+        );
+        assert_eq!(
+            cache.check_source(&source_key_for_record("bot", "a.amr")),
+            None
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used_with_derived() {
+        let (cache, dir) = test_cache("evict", 10);
+        let download = dir.join("download.bin");
+
+        std::fs::write(&download, b"first!").unwrap();
+        let first = cache
+            .store_source("qq-file:1", &download, Validators::default())
+            .await
+            .unwrap();
+        cache.put_derived(&first, "image", "", b"d").await;
+        assert_eq!(
+            cache.get_derived(&first, "image", "").await,
+            Some(b"d".to_vec())
+        );
+
+        std::fs::write(&download, b"second").unwrap();
+        cache
+            .store_source("qq-file:2", &download, Validators::default())
+            .await
+            .unwrap();
+
+        assert_eq!(cache.check_source("qq-file:1"), None);
+        assert_eq!(cache.get_derived(&first, "image", "").await, None);
+        assert!(!cache.object_path(&first).exists());
+        let dest = dir.join("restored.bin");
+        assert!(cache
+            .restore_source("qq-file:2", &dest, 1024)
+            .await
+            .is_some());
+        assert_eq!(cache.inner.lock().unwrap().1.evictions, 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn expired_objects_miss() {
+        let (cache, dir) = test_cache("expire", 1024 * 1024);
+        let download = dir.join("download.bin");
+        std::fs::write(&download, b"old").unwrap();
+        let hash = cache
+            .store_source("qq-file:old", &download, Validators::default())
+            .await
+            .unwrap();
+        cache
+            .inner
+            .lock()
+            .unwrap()
+            .0
+            .objects
+            .get_mut(&hash)
+            .unwrap()
+            .created_at -= 2 * 3_600_000;
+
+        let dest = dir.join("restored.bin");
+        assert!(cache
+            .restore_source("qq-file:old", &dest, 1024)
+            .await
+            .is_none());
+        assert_eq!(cache.check_source("qq-file:old"), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,30 +1,32 @@
 use crate::models::SharedState;
 use crate::plugin::runtime::{ForwardNode, PluginOutput};
 use crate::plugin::PluginOutputWithSource;
+use crate::segment::MessageContent;
 use serde_json::json;
 use std::sync::Arc;
 use tracing::warn;
 
-use super::super::api::{send_api, send_reply};
+use super::super::api::{send_api, send_reply, send_reply_segments};
 use super::super::connection::{BotRuntime, GroupSendStatus};
-use super::llm_abuse::{try_begin_llm_task, LlmAbuseConfig, LlmTaskGuard};
+use super::super::message_archive;
+use super::super::outbound::{self, Priority};
+use super::super::pre_send;
+use super::super::prompt;
+use super::knowledge::{inject_knowledge_context, query_knowledge_base};
+use super::llm_abuse::{
+    refund_image_quota, try_begin_llm_task, try_consume_image_quota, LlmAbuseConfig, LlmTaskGuard,
+};
 use super::llm_forward::{
-    process_llm_forward, process_llm_forward_audio_from_url, process_llm_forward_image_from_url,
-    process_llm_forward_media_bundle, process_llm_forward_video_from_url,
-    LlmForwardAudioFromUrlInput, LlmForwardImageFromUrlInput, LlmForwardInput,
+    generate_images, process_llm_forward, process_llm_forward_audio_from_url,
+    process_llm_forward_image_from_url, process_llm_forward_media_bundle,
+    process_llm_forward_video_from_url, process_llm_speech, send_generated_images, ArchiveLimits,
+    GeneratedImage, LlmForwardAudioFromUrlInput, LlmForwardImageFromUrlInput, LlmForwardInput,
     LlmForwardMediaBundleInput, LlmForwardSource, LlmForwardVideoFromUrlInput,
+    LlmImageGenerationInput, LlmSpeechInput,
 };
-
-/// 从 LLM 模块配置中获取 Tavily API key
-fn get_tavily_api_key(state: &SharedState, bot_id: &str) -> Option<String> {
-    crate::module::get_effective_module(state, bot_id, "llm").and_then(|m| {
-        m.config
-            .get("tavily_api_key")
-            .and_then(|v| v.as_str())
-            .filter(|s| !s.is_empty())
-            .map(|s| s.to_string())
-    })
-}
+use super::llm_structured::{call_structured_chat, JsonMode};
+use super::llm_trace::with_trace_scope;
+use super::web_search::search_settings_for_bot;
 
 async fn begin_llm_task_guard(
     runtime: &Arc<BotRuntime>,
@@ -62,6 +64,41 @@ async fn begin_llm_task_guard(
     }
 }
 
+/// 扣除图片生成额度后调用生成接口，失败时退回额度
+async fn generate_images_with_quota(
+    state: &SharedState,
+    bot_id: &str,
+    abuse_cfg: LlmAbuseConfig,
+    user_id: u64,
+    input: LlmImageGenerationInput<'_>,
+) -> Result<Vec<GeneratedImage>, String> {
+    let n = input.n;
+    try_consume_image_quota(abuse_cfg, user_id, n).map_err(|block| block.message)?;
+    let result = generate_images(state, bot_id, input).await;
+    if result.is_err() {
+        refund_image_quota(abuse_cfg, user_id, n);
+    }
+    result
+}
+
+/// 若插件指定了知识库，检索参考资料并插入到消息列表中
+async fn apply_knowledge_base(
+    state: &SharedState,
+    bot_id: &str,
+    knowledge_base: Option<&str>,
+    top_k: Option<u32>,
+    messages: &mut Vec<serde_json::Value>,
+) -> Result<(), String> {
+    let Some(kb_id) = knowledge_base else {
+        return Ok(());
+    };
+    let top_k = top_k.unwrap_or(4) as usize;
+    inject_knowledge_context(state, bot_id, kb_id, top_k, messages)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("知识库检索失败：{e}"))
+}
+
 async fn inline_multimodal_media_in_messages(
     messages: &mut Vec<serde_json::Value>,
     timeout_ms: u64,
@@ -171,16 +208,37 @@ pub(super) async fn process_plugin_outputs(
                 user_id,
                 group_id,
                 content,
+                priority,
             } => {
-                send_reply(runtime, bot_id, *user_id, *group_id, content).await;
+                let send = async {
+                    match content {
+                        MessageContent::Text(text) => {
+                            send_reply(runtime, bot_id, *user_id, *group_id, text).await
+                        }
+                        MessageContent::Segments(segments) => {
+                            send_reply_segments(runtime, bot_id, *user_id, *group_id, segments)
+                                .await
+                        }
+                    }
+                };
+                outbound::with_priority(output_priority(priority), send).await;
             }
-            PluginOutput::CallApi { action, params } => {
-                send_api(runtime, bot_id, action, params.clone()).await;
+            PluginOutput::CallApi {
+                action,
+                params,
+                priority,
+            } => {
+                outbound::with_priority(
+                    output_priority(priority),
+                    send_api(runtime, bot_id, action, params.clone()),
+                )
+                .await;
             }
             PluginOutput::CallLlmAndForward {
                 user_id,
                 group_id,
                 model_name,
+                output_mode,
                 system_prompt,
                 prompt,
                 content,
@@ -200,6 +258,7 @@ pub(super) async fn process_plugin_outputs(
                         user_id: *user_id,
                         group_id: *group_id,
                         model_name: model_name.as_deref(),
+                        output_mode: output_mode.as_deref(),
                         system_prompt,
                         prompt,
                         title,
@@ -212,6 +271,7 @@ pub(super) async fn process_plugin_outputs(
                 user_id,
                 group_id,
                 model_name,
+                output_mode,
                 system_prompt,
                 prompt,
                 url,
@@ -235,6 +295,7 @@ pub(super) async fn process_plugin_outputs(
                         user_id: *user_id,
                         group_id: *group_id,
                         model_name: model_name.as_deref(),
+                        output_mode: output_mode.as_deref(),
                         system_prompt,
                         prompt,
                         title,
@@ -253,6 +314,7 @@ pub(super) async fn process_plugin_outputs(
                 user_id,
                 group_id,
                 model_name,
+                output_mode,
                 system_prompt,
                 prompt,
                 url,
@@ -263,6 +325,7 @@ pub(super) async fn process_plugin_outputs(
                 max_extract_bytes,
                 max_file_bytes,
                 max_files,
+                max_bundle_files,
                 keywords,
             } => {
                 let Some(_guard) =
@@ -279,6 +342,7 @@ pub(super) async fn process_plugin_outputs(
                         user_id: *user_id,
                         group_id: *group_id,
                         model_name: model_name.as_deref(),
+                        output_mode: output_mode.as_deref(),
                         system_prompt,
                         prompt,
                         title,
@@ -286,10 +350,13 @@ pub(super) async fn process_plugin_outputs(
                             url,
                             file_name: file_name.as_deref(),
                             timeout_ms: *timeout_ms,
-                            max_download_bytes: *max_download_bytes,
-                            max_extract_bytes: *max_extract_bytes,
-                            max_file_bytes: *max_file_bytes,
-                            max_files: *max_files,
+                            limits: ArchiveLimits {
+                                max_download_bytes: *max_download_bytes,
+                                max_extract_bytes: *max_extract_bytes,
+                                max_file_bytes: *max_file_bytes,
+                                max_files: *max_files,
+                                max_bundle_files: *max_bundle_files,
+                            },
                             keywords,
                         },
                     },
@@ -300,6 +367,7 @@ pub(super) async fn process_plugin_outputs(
                 user_id,
                 group_id,
                 model_name,
+                output_mode,
                 system_prompt,
                 prompt,
                 url,
@@ -326,6 +394,7 @@ pub(super) async fn process_plugin_outputs(
                         user_id: *user_id,
                         group_id: *group_id,
                         model_name: model_name.as_deref(),
+                        output_mode: output_mode.as_deref(),
                         system_prompt,
                         prompt,
                         url,
@@ -345,6 +414,7 @@ pub(super) async fn process_plugin_outputs(
                 user_id,
                 group_id,
                 model_name,
+                output_mode,
                 system_prompt,
                 prompt,
                 url,
@@ -377,6 +447,7 @@ pub(super) async fn process_plugin_outputs(
                         user_id: *user_id,
                         group_id: *group_id,
                         model_name: model_name.as_deref(),
+                        output_mode: output_mode.as_deref(),
                         system_prompt,
                         prompt,
                         url,
@@ -402,6 +473,7 @@ pub(super) async fn process_plugin_outputs(
                 user_id,
                 group_id,
                 model_name,
+                output_mode,
                 system_prompt,
                 prompt,
                 url,
@@ -427,6 +499,7 @@ pub(super) async fn process_plugin_outputs(
                         user_id: *user_id,
                         group_id: *group_id,
                         model_name: model_name.as_deref(),
+                        output_mode: output_mode.as_deref(),
                         system_prompt,
                         prompt,
                         url,
@@ -445,6 +518,7 @@ pub(super) async fn process_plugin_outputs(
                 user_id,
                 group_id,
                 model_name,
+                output_mode,
                 system_prompt,
                 prompt,
                 title,
@@ -473,6 +547,7 @@ pub(super) async fn process_plugin_outputs(
                         user_id: *user_id,
                         group_id: *group_id,
                         model_name: model_name.as_deref(),
+                        output_mode: output_mode.as_deref(),
                         system_prompt,
                         prompt,
                         title,
@@ -490,6 +565,84 @@ pub(super) async fn process_plugin_outputs(
                 )
                 .await;
             }
+            PluginOutput::SendSpeech {
+                user_id,
+                group_id,
+                model_name,
+                text,
+                voice,
+                speed,
+                instructions,
+                max_seconds,
+            } => {
+                let Some(_guard) =
+                    begin_llm_task_guard(runtime, bot_id, abuse_cfg, *user_id, *group_id).await
+                else {
+                    continue;
+                };
+
+                process_llm_speech(
+                    state,
+                    runtime,
+                    bot_id,
+                    LlmSpeechInput {
+                        user_id: *user_id,
+                        group_id: *group_id,
+                        model_name: model_name.as_deref(),
+                        text,
+                        voice: voice.as_deref(),
+                        speed: *speed,
+                        instructions: instructions.as_deref(),
+                        max_seconds: *max_seconds,
+                    },
+                )
+                .await;
+            }
+            PluginOutput::GenerateImage {
+                user_id,
+                group_id,
+                request_id: None,
+                model_name,
+                prompt,
+                image_url,
+                size,
+                quality,
+                n,
+                timeout_ms,
+            } => {
+                let Some(_guard) =
+                    begin_llm_task_guard(runtime, bot_id, abuse_cfg, *user_id, *group_id).await
+                else {
+                    continue;
+                };
+
+                let input = LlmImageGenerationInput {
+                    model_name: model_name.as_deref(),
+                    prompt,
+                    image_url: image_url.as_deref(),
+                    size: size.as_deref(),
+                    quality: quality.as_deref(),
+                    n: *n,
+                    timeout_ms: *timeout_ms,
+                };
+                match generate_images_with_quota(state, bot_id, abuse_cfg, *user_id, input).await {
+                    Ok(images) => {
+                        send_generated_images(runtime, bot_id, *user_id, *group_id, &images).await
+                    }
+                    Err(e) => {
+                        send_reply(
+                            runtime,
+                            bot_id,
+                            *user_id,
+                            (*group_id != 0).then_some(*group_id),
+                            &e,
+                        )
+                        .await
+                    }
+                }
+            }
+            // GenerateImage with request_id is handled in process_plugin_outputs_with_llm_response
+            PluginOutput::GenerateImage { .. } => {}
             // CallLlmChat is handled in process_plugin_outputs_with_llm_response
             PluginOutput::CallLlmChat { .. } => {}
             // CallLlmChatWithSearch is handled in process_plugin_outputs_with_llm_response
@@ -503,18 +656,34 @@ pub(super) async fn process_plugin_outputs(
             PluginOutput::FetchGroupList { .. } => {}
             PluginOutput::FetchGroupMemberList { .. } => {}
             PluginOutput::DownloadFile { .. } => {}
+            PluginOutput::SearchKnowledge { .. } => {}
+            PluginOutput::QueryMessageHistory { .. } => {}
+            PluginOutput::AwaitReply { .. } => {}
             // SendForwardMessage sends merged forward message
             PluginOutput::SendForwardMessage {
                 user_id,
                 group_id,
                 nodes,
+                priority,
             } => {
-                send_forward_message(state, runtime, bot_id, *user_id, *group_id, nodes).await;
+                outbound::with_priority(
+                    output_priority(priority),
+                    send_forward_message(state, runtime, bot_id, *user_id, *group_id, nodes),
+                )
+                .await;
             }
         }
     }
 }
 
+/// 插件指定的发送优先级，未指定时沿用触发上下文
+fn output_priority(priority: &Option<String>) -> Priority {
+    priority
+        .as_deref()
+        .and_then(Priority::parse)
+        .unwrap_or_else(outbound::current_priority)
+}
+
 /// 发送合并转发消息
 async fn send_forward_message(
     state: &SharedState,
@@ -576,6 +745,85 @@ async fn send_forward_message(
     }
 }
 
+/// LLM 对话调用结果（回传给插件 onLlmResponse）
+struct LlmChatResult {
+    success: bool,
+    content: String,
+    /// 结构化输出时的附加字段（data / error）
+    extra: Option<serde_json::Value>,
+}
+
+impl LlmChatResult {
+    fn plain(success: bool, content: String) -> Self {
+        Self {
+            success,
+            content,
+            extra: None,
+        }
+    }
+}
+
+/// 回调插件 onLlmResponse，并递归处理回调产生的新输出
+async fn deliver_llm_response(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    plugin_id: &str,
+    request_id: &str,
+    result: LlmChatResult,
+) {
+    match state
+        .plugin_manager
+        .on_llm_response(
+            plugin_id,
+            request_id,
+            result.success,
+            &result.content,
+            result.extra,
+        )
+        .await
+    {
+        Ok(new_outputs) => {
+            Box::pin(process_plugin_outputs_with_llm_response(
+                state,
+                runtime,
+                bot_id,
+                plugin_id,
+                &new_outputs,
+            ))
+            .await;
+        }
+        Err(e) => {
+            warn!("[{}] 插件 {} onLlmResponse 失败: {}", bot_id, plugin_id, e);
+        }
+    }
+}
+
+/// 在插件上下文中执行 `fut`：LLM 调用追踪记录插件 ID，下载遵循插件声明的主机策略，
+/// 发出的消息记为该插件发出（preSend 防循环）
+pub(super) async fn with_plugin_scope<T>(
+    state: &SharedState,
+    bot_id: &str,
+    plugin_id: &str,
+    fut: impl std::future::Future<Output = T>,
+) -> T {
+    let policy = state
+        .plugins
+        .get(plugin_id)
+        .map(|p| p.manifest.network)
+        .unwrap_or_default();
+    crate::outbound::with_host_policy(
+        policy,
+        with_trace_scope(
+            state,
+            bot_id,
+            Some(plugin_id),
+            pre_send::with_send_source(plugin_id, fut),
+        ),
+    )
+    .await
+}
+
 /// 处理插件输出，支持 LLM 回调
 /// 当遇到 CallLlmChat 时，调用 LLM 并通过 onLlmResponse 钩子回调插件
 /// plugin_id: 发起请求的插件 ID
@@ -585,9 +833,25 @@ pub(super) async fn process_plugin_outputs_with_llm_response(
     bot_id: &str,
     plugin_id: &str,
     outputs: &[PluginOutput],
+) {
+    with_plugin_scope(
+        state,
+        bot_id,
+        plugin_id,
+        dispatch_plugin_outputs(state, runtime, bot_id, plugin_id, outputs),
+    )
+    .await
+}
+
+async fn dispatch_plugin_outputs(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    plugin_id: &str,
+    outputs: &[PluginOutput],
 ) {
     use super::llm_forward::multimodal::common::{
-        call_chat_completions, call_chat_completions_with_tavily, resolve_llm_config_by_name,
+        call_chat_completions, call_chat_completions_with_search, resolve_llm_config_by_name,
     };
 
     for output in outputs {
@@ -597,35 +861,72 @@ pub(super) async fn process_plugin_outputs_with_llm_response(
                 model_name,
                 messages,
                 max_tokens,
+                knowledge_base,
+                knowledge_top_k,
+                response_schema,
+                schema_name,
+                json_mode,
             } => {
+                let mut prepared_messages = messages.clone();
+                let knowledge = apply_knowledge_base(
+                    state,
+                    bot_id,
+                    knowledge_base.as_deref(),
+                    *knowledge_top_k,
+                    &mut prepared_messages,
+                )
+                .await;
                 // 解析 LLM 配置
-                let (success, content) =
-                    match resolve_llm_config_by_name(state, bot_id, model_name.as_deref()) {
-                        Ok(llm) => {
-                            // If the plugin provided multimodal image_url parts, inline them as data URLs.
-                            let mut prepared_messages = messages.clone();
-                            let _ = inline_multimodal_media_in_messages(
-                                &mut prepared_messages,
-                                30_000,
-                                15_000_000,
-                                1024,
-                                1024,
-                                80,
-                                600_000,
-                                2,
-                            )
-                            .await;
-                            // 构建请求
-                            let mut request_body = json!({
-                                "model": llm.model_name,
-                                "messages": prepared_messages,
-                            });
-                            if let Some(max_tok) = max_tokens {
-                                request_body["max_tokens"] = json!(max_tok);
-                            }
+                let result = match knowledge
+                    .and_then(|_| resolve_llm_config_by_name(state, bot_id, model_name.as_deref()))
+                {
+                    Ok(llm) => {
+                        // If the plugin provided multimodal image_url parts, inline them as data URLs.
+                        let _ = inline_multimodal_media_in_messages(
+                            &mut prepared_messages,
+                            30_000,
+                            15_000_000,
+                            1024,
+                            1024,
+                            80,
+                            600_000,
+                            2,
+                        )
+                        .await;
+                        // 构建请求
+                        let mut request_body = json!({
+                            "model": llm.model_name,
+                            "messages": prepared_messages,
+                        });
+                        if let Some(max_tok) = max_tokens {
+                            request_body["max_tokens"] = json!(max_tok);
+                        }
 
-                            // 调用 LLM
-                            match call_chat_completions(
+                        // 调用 LLM（指定 responseSchema 时走结构化输出）
+                        match response_schema {
+                            Some(schema) => {
+                                match call_structured_chat(
+                                    &llm,
+                                    &request_body,
+                                    schema,
+                                    schema_name.as_deref(),
+                                    JsonMode::parse(json_mode.as_deref()),
+                                )
+                                .await
+                                {
+                                    Ok((content, data)) => LlmChatResult {
+                                        success: true,
+                                        content,
+                                        extra: Some(json!({ "data": data })),
+                                    },
+                                    Err(e) => LlmChatResult {
+                                        success: false,
+                                        content: e.message.clone(),
+                                        extra: Some(json!({ "error": e.to_json() })),
+                                    },
+                                }
+                            }
+                            None => match call_chat_completions(
                                 &llm.base_url,
                                 &llm.api_key,
                                 &request_body,
@@ -633,34 +934,16 @@ pub(super) async fn process_plugin_outputs_with_llm_response(
                             )
                             .await
                             {
-                                Ok(content) => (true, content),
-                                Err(e) => (false, e.to_string()),
-                            }
+                                Ok(content) => LlmChatResult::plain(true, content),
+                                Err(e) => LlmChatResult::plain(false, e.to_string()),
+                            },
                         }
-                        Err(e) => (false, e),
-                    };
+                    }
+                    Err(e) => LlmChatResult::plain(false, e),
+                };
 
                 // 回调插件
-                match state
-                    .plugin_manager
-                    .on_llm_response(plugin_id, request_id, success, &content)
-                    .await
-                {
-                    Ok(new_outputs) => {
-                        // 递归处理回调产生的新输出
-                        Box::pin(process_plugin_outputs_with_llm_response(
-                            state,
-                            runtime,
-                            bot_id,
-                            plugin_id,
-                            &new_outputs,
-                        ))
-                        .await;
-                    }
-                    Err(e) => {
-                        warn!("[{}] 插件 {} onLlmResponse 失败: {}", bot_id, plugin_id, e);
-                    }
-                }
+                deliver_llm_response(state, runtime, bot_id, plugin_id, request_id, result).await;
             }
             PluginOutput::CallLlmChatWithSearch {
                 request_id,
@@ -668,73 +951,66 @@ pub(super) async fn process_plugin_outputs_with_llm_response(
                 messages,
                 max_tokens,
                 enable_search,
+                knowledge_base,
+                knowledge_top_k,
             } => {
+                let mut prepared_messages = messages.clone();
+                let knowledge = apply_knowledge_base(
+                    state,
+                    bot_id,
+                    knowledge_base.as_deref(),
+                    *knowledge_top_k,
+                    &mut prepared_messages,
+                )
+                .await;
                 // 解析 LLM 配置（优先使用 websearch 模型）
                 let model_to_use = model_name.as_deref().or(Some("websearch"));
-                let tavily_key = get_tavily_api_key(state, bot_id);
-                let (success, content) =
-                    match resolve_llm_config_by_name(state, bot_id, model_to_use) {
-                        Ok(llm) => {
-                            let mut prepared_messages = messages.clone();
-                            let _ = inline_multimodal_media_in_messages(
-                                &mut prepared_messages,
-                                30_000,
-                                15_000_000,
-                                1024,
-                                1024,
-                                80,
-                                600_000,
-                                2,
-                            )
-                            .await;
-                            // 构建请求
-                            let mut request_body = json!({
-                                "model": llm.model_name,
-                                "messages": prepared_messages,
-                            });
-                            if let Some(max_tok) = max_tokens {
-                                request_body["max_tokens"] = json!(max_tok);
-                            }
+                let search = search_settings_for_bot(state, bot_id);
+                let result = match knowledge
+                    .and_then(|_| resolve_llm_config_by_name(state, bot_id, model_to_use))
+                {
+                    Ok(llm) => {
+                        let _ = inline_multimodal_media_in_messages(
+                            &mut prepared_messages,
+                            30_000,
+                            15_000_000,
+                            1024,
+                            1024,
+                            80,
+                            600_000,
+                            2,
+                        )
+                        .await;
+                        // 构建请求
+                        let mut request_body = json!({
+                            "model": llm.model_name,
+                            "messages": prepared_messages,
+                        });
+                        if let Some(max_tok) = max_tokens {
+                            request_body["max_tokens"] = json!(max_tok);
+                        }
 
-                            // 调用支持搜索的 LLM（使用 Tavily 函数调用）
-                            let search_enabled = enable_search.unwrap_or(true);
-                            match call_chat_completions_with_tavily(
-                                &llm.base_url,
-                                &llm.api_key,
-                                &request_body,
-                                llm.max_request_bytes,
-                                search_enabled,
-                                tavily_key.as_deref(),
-                            )
-                            .await
-                            {
-                                Ok(content) => (true, content),
-                                Err(e) => (false, e.to_string()),
-                            }
+                        // 调用支持搜索的 LLM（使用搜索工具函数调用）
+                        let search_enabled = enable_search.unwrap_or(true);
+                        match call_chat_completions_with_search(
+                            &llm.base_url,
+                            &llm.api_key,
+                            &request_body,
+                            llm.max_request_bytes,
+                            search_enabled,
+                            search.as_ref(),
+                        )
+                        .await
+                        {
+                            Ok(content) => LlmChatResult::plain(true, content),
+                            Err(e) => LlmChatResult::plain(false, e.to_string()),
                         }
-                        Err(e) => (false, e),
-                    };
+                    }
+                    Err(e) => LlmChatResult::plain(false, e),
+                };
 
                 // 回调插件
-                match state
-                    .plugin_manager
-                    .on_llm_response(plugin_id, request_id, success, &content)
-                    .await
-                {
-                    Ok(new_outputs) => {
-                        Box::pin(process_plugin_outputs_with_llm_response(
-                            state,
-                            runtime,
-                            bot_id,
-                            plugin_id,
-                            &new_outputs,
-                        ))
-                        .await;
-                    }
-                    Err(e) => {
-                        warn!("[{}] 插件 {} onLlmResponse 失败: {}", bot_id, plugin_id, e);
-                    }
-                }
+                deliver_llm_response(state, runtime, bot_id, plugin_id, request_id, result).await;
             }
             // Group info fetch outputs
             PluginOutput::FetchGroupNotice {
@@ -864,258 +1140,72 @@ pub(super) async fn process_plugin_outputs_with_llm_response(
                 )
                 .await;
             }
-            PluginOutput::DownloadFile {
+            PluginOutput::SearchKnowledge {
                 request_id,
-                url,
-                thread_count,
-                headers,
+                knowledge_base,
+                query,
+                top_k,
+                min_score,
             } => {
-                let mut params = json!({ "url": url });
-                if let Some(tc) = thread_count {
-                    params["thread_count"] = json!(tc);
-                }
-                if let Some(h) = headers {
-                    params["headers"] = json!(h);
-                }
-                process_group_info_request(
+                process_knowledge_search_request(
                     state,
                     runtime,
                     bot_id,
                     plugin_id,
-                    request_id,
-                    "download",
-                    "download_file",
-                    params,
-                )
-                .await;
-            }
-            // 其他输出类型委托给普通处理函数
-            _ => {
-                process_plugin_outputs(state, runtime, bot_id, std::slice::from_ref(output)).await;
-            }
-        }
-    }
-}
-
-/// 处理带有来源插件 ID 的输出列表
-/// 支持 LLM 回调
-pub(super) async fn process_plugin_outputs_with_source(
-    state: &SharedState,
-    runtime: &Arc<BotRuntime>,
-    bot_id: &str,
-    outputs: &[PluginOutputWithSource],
-) {
-    use super::llm_forward::multimodal::common::{
-        call_chat_completions, call_chat_completions_with_tavily, resolve_llm_config_by_name,
-    };
-
-    for output_with_source in outputs {
-        let plugin_id = &output_with_source.plugin_id;
-        let output = &output_with_source.output;
-
-        match output {
-            PluginOutput::CallLlmChat {
-                request_id,
-                model_name,
-                messages,
-                max_tokens,
-            } => {
-                // 解析 LLM 配置
-                let (success, content) =
-                    match resolve_llm_config_by_name(state, bot_id, model_name.as_deref()) {
-                        Ok(llm) => {
-                            // 构建请求
-                            let mut request_body = json!({
-                                "model": llm.model_name,
-                                "messages": messages,
-                            });
-                            if let Some(max_tok) = max_tokens {
-                                request_body["max_tokens"] = json!(max_tok);
-                            }
-
-                            // 调用 LLM
-                            match call_chat_completions(
-                                &llm.base_url,
-                                &llm.api_key,
-                                &request_body,
-                                llm.max_request_bytes,
-                            )
-                            .await
-                            {
-                                Ok(content) => (true, content),
-                                Err(e) => (false, e.to_string()),
-                            }
-                        }
-                        Err(e) => (false, e),
-                    };
-
-                // 回调插件
-                match state
-                    .plugin_manager
-                    .on_llm_response(plugin_id, request_id, success, &content)
-                    .await
-                {
-                    Ok(new_outputs) => {
-                        // 递归处理回调产生的新输出
-                        Box::pin(process_plugin_outputs_with_llm_response(
-                            state,
-                            runtime,
-                            bot_id,
-                            plugin_id,
-                            &new_outputs,
-                        ))
-                        .await;
-                    }
-                    Err(e) => {
-                        warn!("[{}] 插件 {} onLlmResponse 失败: {}", bot_id, plugin_id, e);
-                    }
-                }
-            }
-            PluginOutput::CallLlmChatWithSearch {
-                request_id,
-                model_name,
-                messages,
-                max_tokens,
-                enable_search,
-            } => {
-                // 解析 LLM 配置（优先使用 websearch 模型）
-                let model_to_use = model_name.as_deref().or(Some("websearch"));
-                let tavily_key = get_tavily_api_key(state, bot_id);
-                let (success, content) =
-                    match resolve_llm_config_by_name(state, bot_id, model_to_use) {
-                        Ok(llm) => {
-                            // 构建请求
-                            let mut request_body = json!({
-                                "model": llm.model_name,
-                                "messages": messages,
-                            });
-                            if let Some(max_tok) = max_tokens {
-                                request_body["max_tokens"] = json!(max_tok);
-                            }
-
-                            // 调用支持搜索的 LLM（使用 Tavily 函数调用）
-                            let search_enabled = enable_search.unwrap_or(true);
-                            match call_chat_completions_with_tavily(
-                                &llm.base_url,
-                                &llm.api_key,
-                                &request_body,
-                                llm.max_request_bytes,
-                                search_enabled,
-                                tavily_key.as_deref(),
-                            )
-                            .await
-                            {
-                                Ok(content) => (true, content),
-                                Err(e) => (false, e.to_string()),
-                            }
-                        }
-                        Err(e) => (false, e),
-                    };
-
-                // 回调插件
-                match state
-                    .plugin_manager
-                    .on_llm_response(plugin_id, request_id, success, &content)
-                    .await
-                {
-                    Ok(new_outputs) => {
-                        Box::pin(process_plugin_outputs_with_llm_response(
-                            state,
-                            runtime,
-                            bot_id,
-                            plugin_id,
-                            &new_outputs,
-                        ))
-                        .await;
-                    }
-                    Err(e) => {
-                        warn!("[{}] 插件 {} onLlmResponse 失败: {}", bot_id, plugin_id, e);
-                    }
-                }
-            }
-            // Group info fetch outputs
-            PluginOutput::FetchGroupNotice {
-                request_id,
-                group_id,
-            } => {
-                process_group_info_request(
-                    state,
-                    runtime,
-                    bot_id,
-                    plugin_id,
-                    request_id,
-                    "notice",
-                    "_get_group_notice",
-                    json!({ "group_id": group_id }),
-                )
-                .await;
-            }
-            PluginOutput::FetchGroupMsgHistory {
-                request_id,
-                group_id,
-                count,
-                message_seq,
-            } => {
-                let mut params = json!({ "group_id": group_id });
-                if let Some(c) = count {
-                    params["count"] = json!(c);
-                }
-                if let Some(seq) = message_seq {
-                    params["message_seq"] = json!(seq);
-                }
-                process_group_info_request(
-                    state,
-                    runtime,
-                    bot_id,
-                    plugin_id,
-                    request_id,
-                    "msg_history",
-                    "get_group_msg_history",
-                    params,
+                    KnowledgeSearchRequest {
+                        request_id,
+                        knowledge_base,
+                        query,
+                        top_k: *top_k,
+                        min_score: *min_score,
+                    },
                 )
                 .await;
             }
-            PluginOutput::FetchGroupFiles {
+            PluginOutput::QueryMessageHistory {
                 request_id,
                 group_id,
-                folder_id,
+                peer_id,
+                user_id,
+                keyword,
+                since,
+                until,
+                before_id,
+                limit,
             } => {
-                let action = if folder_id.is_some() {
-                    "get_group_files_by_folder"
-                } else {
-                    "get_group_root_files"
+                let query = message_archive::ArchiveQuery {
+                    bot_id: bot_id.to_string(),
+                    group_id: *group_id,
+                    peer_id: *peer_id,
+                    user_id: *user_id,
+                    keyword: keyword.clone(),
+                    since: *since,
+                    until: *until,
+                    before_id: *before_id,
+                    limit: Some(*limit as usize),
                 };
-                let mut params = json!({ "group_id": group_id });
-                if let Some(fid) = folder_id {
-                    params["folder_id"] = json!(fid);
-                }
-                process_group_info_request(
-                    state, runtime, bot_id, plugin_id, request_id, "files", action, params,
+                process_message_history_request(
+                    state, runtime, bot_id, plugin_id, request_id, query,
                 )
                 .await;
             }
-            PluginOutput::FetchGroupFileUrl {
+            PluginOutput::AwaitReply {
                 request_id,
+                user_id,
                 group_id,
-                file_id,
-                busid,
+                timeout_ms,
+                cancel_keywords,
             } => {
-                let mut params = json!({
-                    "group_id": group_id,
-                    "file_id": file_id,
-                });
-                if let Some(b) = busid {
-                    params["busid"] = json!(b);
-                }
-                process_group_info_request(
+                prompt::begin(
                     state,
                     runtime,
                     bot_id,
                     plugin_id,
                     request_id,
-                    "file_url",
-                    "get_group_file_url",
-                    params,
+                    *user_id,
+                    *group_id,
+                    *timeout_ms,
+                    cancel_keywords,
                 )
                 .await;
             }
@@ -1144,6 +1234,48 @@ pub(super) async fn process_plugin_outputs_with_source(
                 )
                 .await;
             }
+            PluginOutput::GenerateImage {
+                user_id,
+                group_id,
+                request_id: Some(request_id),
+                model_name,
+                prompt,
+                image_url,
+                size,
+                quality,
+                n,
+                timeout_ms,
+            } => {
+                let abuse_cfg = LlmAbuseConfig::from_state(state, bot_id);
+                let input = LlmImageGenerationInput {
+                    model_name: model_name.as_deref(),
+                    prompt,
+                    image_url: image_url.as_deref(),
+                    size: size.as_deref(),
+                    quality: quality.as_deref(),
+                    n: *n,
+                    timeout_ms: *timeout_ms,
+                };
+                let result = match try_begin_llm_task(abuse_cfg, *user_id, *group_id) {
+                    Ok(_guard) => {
+                        generate_images_with_quota(state, bot_id, abuse_cfg, *user_id, input).await
+                    }
+                    Err(block) => Err(block.message),
+                };
+                let result = match result {
+                    Ok(images) => LlmChatResult {
+                        success: true,
+                        content: String::new(),
+                        extra: Some(json!({ "data": { "images": images } })),
+                    },
+                    Err(e) => LlmChatResult {
+                        success: false,
+                        content: e.clone(),
+                        extra: Some(json!({ "error": { "type": "llm_error", "message": e } })),
+                    },
+                };
+                deliver_llm_response(state, runtime, bot_id, plugin_id, request_id, result).await;
+            }
             // 其他输出类型委托给普通处理函数
             _ => {
                 process_plugin_outputs(state, runtime, bot_id, std::slice::from_ref(output)).await;
@@ -1152,6 +1284,127 @@ pub(super) async fn process_plugin_outputs_with_source(
     }
 }
 
+/// 处理带有来源插件 ID 的输出列表
+/// 支持 LLM 回调
+pub(super) async fn process_plugin_outputs_with_source(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    outputs: &[PluginOutputWithSource],
+) {
+    for output_with_source in outputs {
+        process_plugin_outputs_with_llm_response(
+            state,
+            runtime,
+            bot_id,
+            &output_with_source.plugin_id,
+            std::slice::from_ref(&output_with_source.output),
+        )
+        .await;
+    }
+}
+
+struct KnowledgeSearchRequest<'a> {
+    request_id: &'a str,
+    knowledge_base: &'a str,
+    query: &'a str,
+    top_k: u32,
+    min_score: f32,
+}
+
+/// 检索知识库并通过 onGroupInfoResponse 回调插件（infoType = "knowledge"）
+async fn process_knowledge_search_request(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    plugin_id: &str,
+    req: KnowledgeSearchRequest<'_>,
+) {
+    let (success, data) = match query_knowledge_base(
+        state,
+        bot_id,
+        req.knowledge_base,
+        req.query,
+        req.top_k as usize,
+        req.min_score,
+    )
+    .await
+    {
+        Ok(hits) => (
+            true,
+            serde_json::to_string(&hits).unwrap_or_else(|_| "[]".to_string()),
+        ),
+        Err(e) => (false, e),
+    };
+
+    match state
+        .plugin_manager
+        .on_group_info_response(plugin_id, req.request_id, "knowledge", success, &data)
+        .await
+    {
+        Ok(new_outputs) => {
+            Box::pin(process_plugin_outputs_with_llm_response(
+                state,
+                runtime,
+                bot_id,
+                plugin_id,
+                &new_outputs,
+            ))
+            .await;
+        }
+        Err(e) => {
+            warn!(
+                "[{}] Plugin {} onGroupInfoResponse failed: {}",
+                bot_id, plugin_id, e
+            );
+        }
+    }
+}
+
+async fn process_message_history_request(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    plugin_id: &str,
+    request_id: &str,
+    query: message_archive::ArchiveQuery,
+) {
+    let (success, data) = if !message_archive::refresh_settings(state, bot_id) {
+        (false, "该机器人未开启消息存档".to_string())
+    } else {
+        match message_archive::query_messages(query).await {
+            Ok(messages) => (
+                true,
+                serde_json::to_string(&messages).unwrap_or_else(|_| "[]".to_string()),
+            ),
+            Err(e) => (false, e),
+        }
+    };
+
+    match state
+        .plugin_manager
+        .on_group_info_response(plugin_id, request_id, "message_history", success, &data)
+        .await
+    {
+        Ok(new_outputs) => {
+            Box::pin(process_plugin_outputs_with_llm_response(
+                state,
+                runtime,
+                bot_id,
+                plugin_id,
+                &new_outputs,
+            ))
+            .await;
+        }
+        Err(e) => {
+            warn!(
+                "[{}] Plugin {} onGroupInfoResponse failed: {}",
+                bot_id, plugin_id, e
+            );
+        }
+    }
+}
+
 /// Helper function to process group info requests
 async fn process_group_info_request(
     state: &SharedState,
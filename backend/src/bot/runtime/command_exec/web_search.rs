@@ -0,0 +1,383 @@
+//! 联网搜索：可插拔的搜索服务（Tavily / SearXNG / Bing / Brave），供 LLM 工具调用使用
+//!
+//! 配置位于 LLM 模块配置的 `search` 字段：
+//! `{ "provider": "searxng", "max_results": 5, "searxng": { "base_url": "..." }, ... }`。
+//! 未配置 `search` 时兼容旧的 `tavily_api_key`。
+
+use serde_json::{json, Value};
+
+use crate::models::SharedState;
+
+const DEFAULT_MAX_RESULTS: usize = 5;
+const MAX_RESULTS_LIMIT: usize = 20;
+const SEARCH_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_BING_ENDPOINT: &str = "https://api.bing.microsoft.com/v7.0/search";
+
+/// 搜索服务及其配置
+#[derive(Debug, Clone)]
+pub enum SearchProvider {
+    Tavily {
+        api_key: String,
+    },
+    Searxng {
+        base_url: String,
+        /// 逗号分隔的引擎列表，留空使用实例默认
+        engines: String,
+        language: String,
+    },
+    Bing {
+        api_key: String,
+        endpoint: String,
+        market: String,
+    },
+    Brave {
+        api_key: String,
+    },
+}
+
+impl SearchProvider {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Tavily { .. } => "Tavily",
+            Self::Searxng { .. } => "SearXNG",
+            Self::Bing { .. } => "Bing",
+            Self::Brave { .. } => "Brave",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchSettings {
+    pub provider: SearchProvider,
+    pub max_results: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SearchHit {
+    pub title: String,
+    pub url: String,
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct SearchResponse {
+    /// 搜索服务直接给出的答案（Tavily answer / SearXNG answers）
+    pub answer: Option<String>,
+    pub results: Vec<SearchHit>,
+}
+
+impl SearchResponse {
+    /// 转为提供给模型的 Markdown 文本
+    pub(super) fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        if let Some(answer) = self.answer.as_deref().filter(|a| !a.trim().is_empty()) {
+            out.push_str("## 搜索摘要\n");
+            out.push_str(answer);
+            out.push_str("\n\n");
+        }
+        if !self.results.is_empty() {
+            out.push_str("## 搜索结果\n\n");
+            for (i, hit) in self.results.iter().enumerate() {
+                let title = if hit.title.is_empty() {
+                    "无标题"
+                } else {
+                    &hit.title
+                };
+                let snippet = if hit.snippet.is_empty() {
+                    "无内容"
+                } else {
+                    &hit.snippet
+                };
+                out.push_str(&format!("### {}. {}\n", i + 1, title));
+                out.push_str(&format!("链接: {}\n", hit.url));
+                out.push_str(&format!("{}\n\n", snippet));
+            }
+        }
+        if out.is_empty() {
+            out = "未找到相关搜索结果".to_string();
+        }
+        out
+    }
+}
+
+fn str_field<'a>(v: Option<&'a Value>, key: &str) -> &'a str {
+    v.and_then(|v| v.get(key))
+        .and_then(|v| v.as_str())
+        .map(str::trim)
+        .unwrap_or("")
+}
+
+/// 返回有效的 `search` 配置对象（没有时由旧的 `tavily_api_key` 生成）
+pub fn effective_search_config(llm_config: &Value) -> Value {
+    if let Some(search) = llm_config.get("search").filter(|v| v.is_object()) {
+        return search.clone();
+    }
+    let legacy_key = llm_config
+        .get("tavily_api_key")
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    json!({
+        "provider": "tavily",
+        "max_results": DEFAULT_MAX_RESULTS,
+        "tavily": { "api_key": legacy_key },
+    })
+}
+
+/// 解析 `search` 配置；未选择服务或缺少必要参数时返回错误说明
+pub fn parse_search_settings(search: &Value) -> Result<SearchSettings, String> {
+    let provider_name = str_field(Some(search), "provider").to_ascii_lowercase();
+    let max_results = search
+        .get("max_results")
+        .and_then(|v| v.as_u64())
+        .map(|n| (n as usize).clamp(1, MAX_RESULTS_LIMIT))
+        .unwrap_or(DEFAULT_MAX_RESULTS);
+
+    let section = search.get(provider_name.as_str());
+    let provider = match provider_name.as_str() {
+        "" | "none" => return Err("未启用联网搜索服务".to_string()),
+        "tavily" => {
+            let api_key = str_field(section, "api_key");
+            if api_key.is_empty() {
+                return Err("Tavily API Key 不能为空".to_string());
+            }
+            SearchProvider::Tavily {
+                api_key: api_key.to_string(),
+            }
+        }
+        "searxng" => {
+            let base_url = str_field(section, "base_url").trim_end_matches('/');
+            if base_url.is_empty() {
+                return Err("SearXNG 地址不能为空".to_string());
+            }
+            if !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+                return Err("SearXNG 地址必须以 http:// 或 https:// 开头".to_string());
+            }
+            SearchProvider::Searxng {
+                base_url: base_url.to_string(),
+                engines: str_field(section, "engines").to_string(),
+                language: str_field(section, "language").to_string(),
+            }
+        }
+        "bing" => {
+            let api_key = str_field(section, "api_key");
+            if api_key.is_empty() {
+                return Err("Bing API Key 不能为空".to_string());
+            }
+            let endpoint = str_field(section, "endpoint");
+            SearchProvider::Bing {
+                api_key: api_key.to_string(),
+                endpoint: if endpoint.is_empty() {
+                    DEFAULT_BING_ENDPOINT.to_string()
+                } else {
+                    endpoint.to_string()
+                },
+                market: str_field(section, "market").to_string(),
+            }
+        }
+        "brave" => {
+            let api_key = str_field(section, "api_key");
+            if api_key.is_empty() {
+                return Err("Brave API Key 不能为空".to_string());
+            }
+            SearchProvider::Brave {
+                api_key: api_key.to_string(),
+            }
+        }
+        other => return Err(format!("不支持的搜索服务: {other}")),
+    };
+
+    Ok(SearchSettings {
+        provider,
+        max_results,
+    })
+}
+
+/// 读取 bot 生效的 LLM 模块配置中的搜索设置；未配置时返回 None
+pub(super) fn search_settings_for_bot(state: &SharedState, bot_id: &str) -> Option<SearchSettings> {
+    let module = crate::module::get_effective_module(state, bot_id, "llm")?;
+    parse_search_settings(&effective_search_config(&module.config)).ok()
+}
+
+/// 提供给 LLM 的搜索工具定义
+pub(super) fn search_tool_definition() -> Value {
+    json!({
+        "type": "function",
+        "function": {
+            "name": "web_search",
+            "description": "Search the web for current information. Use this when you need to find up-to-date information, facts, news, or any information that might have changed after your knowledge cutoff.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The search query to look up on the web"
+                    }
+                },
+                "required": ["query"]
+            }
+        }
+    })
+}
+
+/// 是否为搜索工具调用（兼容旧的 tavily_search 名称）
+pub(super) fn is_search_tool(name: &str) -> bool {
+    matches!(name, "web_search" | "tavily_search")
+}
+
+/// 执行一次搜索
+pub async fn web_search(settings: &SearchSettings, query: &str) -> Result<SearchResponse, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Err("搜索关键词为空".to_string());
+    }
+    let client = reqwest::Client::new();
+    let name = settings.provider.name();
+    let limit = settings.max_results;
+
+    let request = match &settings.provider {
+        SearchProvider::Tavily { api_key } => client
+            .post("https://api.tavily.com/search")
+            .header("Content-Type", "application/json")
+            .json(&json!({
+                "api_key": api_key,
+                "query": query,
+                "search_depth": "basic",
+                "include_answer": true,
+                "include_raw_content": false,
+                "max_results": limit
+            })),
+        SearchProvider::Searxng {
+            base_url,
+            engines,
+            language,
+        } => {
+            let mut params = vec![("q", query), ("format", "json")];
+            if !engines.is_empty() {
+                params.push(("engines", engines));
+            }
+            if !language.is_empty() {
+                params.push(("language", language));
+            }
+            client.get(format!("{base_url}/search")).query(&params)
+        }
+        SearchProvider::Bing {
+            api_key,
+            endpoint,
+            market,
+        } => {
+            let count = limit.to_string();
+            let mut params = vec![("q", query), ("count", count.as_str())];
+            if !market.is_empty() {
+                params.push(("mkt", market));
+            }
+            client
+                .get(endpoint)
+                .header("Ocp-Apim-Subscription-Key", api_key)
+                .query(&params)
+        }
+        SearchProvider::Brave { api_key } => {
+            let count = limit.to_string();
+            client
+                .get("https://api.search.brave.com/res/v1/web/search")
+                .header("Accept", "application/json")
+                .header("X-Subscription-Token", api_key)
+                .query(&[("q", query), ("count", count.as_str())])
+        }
+    };
+
+    let resp = request
+        .timeout(std::time::Duration::from_secs(SEARCH_TIMEOUT_SECS))
+        .send()
+        .await
+        .map_err(|e| format!("{name} 请求失败: {e}"))?;
+    let status = resp.status();
+    let text = resp
+        .text()
+        .await
+        .map_err(|e| format!("读取 {name} 响应失败: {e}"))?;
+    if !status.is_success() {
+        let hint = match (&settings.provider, status.as_u16()) {
+            (SearchProvider::Searxng { .. }, 403) => {
+                "（请在 SearXNG 的 settings.yml 中为 search.formats 启用 json）"
+            }
+            _ => "",
+        };
+        let body: String = text.chars().take(400).collect();
+        return Err(format!("{name} API 错误 (HTTP {status}){hint}: {body}"));
+    }
+
+    let v: Value = serde_json::from_str(&text).map_err(|e| format!("解析 {name} 响应失败: {e}"))?;
+    let mut response = match &settings.provider {
+        SearchProvider::Tavily { .. } => {
+            let mut r = parse_results(&v, &["results"], "title", "content");
+            r.answer = v
+                .get("answer")
+                .and_then(|a| a.as_str())
+                .map(|s| s.to_string());
+            r
+        }
+        SearchProvider::Searxng { .. } => {
+            let mut r = parse_results(&v, &["results"], "title", "content");
+            r.answer = v
+                .get("answers")
+                .and_then(|a| a.as_array())
+                .and_then(|a| a.first())
+                .and_then(|a| {
+                    a.as_str()
+                        .or_else(|| a.get("answer").and_then(|s| s.as_str()))
+                })
+                .map(|s| s.to_string());
+            r
+        }
+        SearchProvider::Bing { .. } => parse_results(&v, &["webPages", "value"], "name", "snippet"),
+        SearchProvider::Brave { .. } => {
+            parse_results(&v, &["web", "results"], "title", "description")
+        }
+    };
+    response.results.truncate(limit);
+    Ok(response)
+}
+
+fn parse_results(v: &Value, path: &[&str], title_key: &str, snippet_key: &str) -> SearchResponse {
+    let mut node = Some(v);
+    for key in path {
+        node = node.and_then(|n| n.get(*key));
+    }
+    let results = node
+        .and_then(|n| n.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .map(|item| SearchHit {
+                    title: strip_tags(str_field(Some(item), title_key)),
+                    url: str_field(Some(item), "url").to_string(),
+                    snippet: strip_tags(str_field(Some(item), snippet_key)),
+                })
+                .filter(|hit| !hit.url.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    SearchResponse {
+        answer: None,
+        results,
+    }
+}
+
+/// Brave / Bing 的摘要中会带 `<strong>` 等高亮标签
+fn strip_tags(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut in_tag = false;
+    for c in input.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
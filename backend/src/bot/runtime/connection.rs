@@ -1,22 +1,25 @@
 use crate::models::SharedState;
+use crate::plugin::PluginManager;
 use futures_util::{SinkExt, StreamExt};
 use reqwest::Client as HttpClient;
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::collections::VecDeque;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use tokio::sync::{mpsc, oneshot, watch, Mutex};
+use tokio::sync::{mpsc, watch, Mutex};
 use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::protocol::Message;
 use tracing::{info, warn};
 
 use super::command_exec::process_plugin_outputs_with_source;
-use super::message::handle_event;
+use super::inbound::InboundQueue;
+use super::msg_cache::MessageCache;
+use super::outbound::OutboundQueue;
+use super::rpc::{check_response, PendingCall, Rpc, RpcError, DEFAULT_TIMEOUT};
 
 pub type WsSender = mpsc::UnboundedSender<String>;
-pub type ResponseSender = oneshot::Sender<Value>;
 
 const GROUP_SEND_STATUS_TTL: Duration = Duration::from_secs(3);
 const DISCORD_MSG_INDEX_MAX: usize = 2048;
@@ -85,27 +88,45 @@ impl MessageDedup {
 
 pub struct BotRuntime {
     pub connections: Arc<RwLock<HashMap<String, BotConnection>>>,
-    pub pending_requests: Arc<RwLock<HashMap<String, ResponseSender>>>,
     pub message_dedup: Arc<Mutex<MessageDedup>>,
     self_id_cache: Arc<RwLock<HashMap<String, u64>>>,
     group_send_status_cache: Arc<Mutex<HashMap<(String, u64), CachedGroupSendStatus>>>,
     discord_msg_index: Arc<Mutex<HashMap<(String, u64), IndexedDiscordMessage>>>,
     discord_msg_fifo: Arc<Mutex<VecDeque<(String, u64)>>>,
+    /// 入站事件调度（同一会话依次处理，不同会话并发）
+    pub(super) inbound: InboundQueue,
+    /// 发送消息类 API 的出站队列（限速 / 优先级 / 重试）
+    pub(super) outbound: OutboundQueue,
+    /// OneBot 请求 / 响应关联与耗时统计
+    pub(super) rpc: Arc<Rpc>,
+    /// 近期收发的消息，撤回通知时找回原内容
+    pub(super) msg_cache: MessageCache,
+    /// 用于发送前调用插件 preSend 钩子
+    pub(super) plugin_manager: OnceLock<Arc<PluginManager>>,
 }
 
 impl BotRuntime {
     pub fn new() -> Self {
         Self {
             connections: Arc::new(RwLock::new(HashMap::new())),
-            pending_requests: Arc::new(RwLock::new(HashMap::new())),
             message_dedup: Arc::new(Mutex::new(MessageDedup::new(5))), // 5秒去重窗口
             self_id_cache: Arc::new(RwLock::new(HashMap::new())),
             group_send_status_cache: Arc::new(Mutex::new(HashMap::new())),
             discord_msg_index: Arc::new(Mutex::new(HashMap::new())),
             discord_msg_fifo: Arc::new(Mutex::new(VecDeque::new())),
+            inbound: InboundQueue::new(),
+            outbound: OutboundQueue::new(),
+            rpc: Arc::new(Rpc::new()),
+            msg_cache: MessageCache::new(),
+            plugin_manager: OnceLock::new(),
         }
     }
 
+    /// 关联插件管理器，此后发出的消息会先经过插件的 preSend 钩子
+    pub fn set_plugin_manager(&self, manager: Arc<PluginManager>) {
+        let _ = self.plugin_manager.set(manager);
+    }
+
     pub async fn register_discord_connection(&self, bot_id: &str, conn: DiscordConnection) {
         self.connections
             .write()
@@ -169,58 +190,101 @@ impl BotRuntime {
         }
     }
 
-    /// 调用 OneBot API 并等待响应
+    /// 该 bot 当前是否为 Discord 连接
+    pub async fn is_discord(&self, bot_id: &str) -> bool {
+        matches!(
+            self.connections.read().await.get(bot_id),
+            Some(BotConnection::Discord(_))
+        )
+    }
+
+    /// 各 bot 入站事件队列的深度与处理统计
+    pub fn inbound_stats(&self) -> Value {
+        self.inbound.stats()
+    }
+
+    /// 各 bot 出站队列的深度与发送统计
+    pub fn outbound_stats(&self) -> Value {
+        self.outbound.stats()
+    }
+
+    /// 各 action 的调用耗时、失败与超时统计
+    pub fn rpc_stats(&self) -> Value {
+        self.rpc.stats()
+    }
+
+    /// 调用 OneBot API 并等待响应（原始响应，需自行检查 `status`）
     pub async fn call_api(&self, bot_id: &str, action: &str, params: Value) -> Option<Value> {
+        self.request(bot_id, action, params, DEFAULT_TIMEOUT)
+            .await
+            .ok()
+    }
+
+    /// 调用 API 并检查响应状态；`status: failed` 返回 [`RpcError::Failed`]
+    pub async fn call_api_checked(
+        &self,
+        bot_id: &str,
+        action: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value, RpcError> {
+        self.request(bot_id, action, params, timeout)
+            .await
+            .and_then(check_response)
+    }
+
+    async fn request(
+        &self,
+        bot_id: &str,
+        action: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value, RpcError> {
         let conn = self.connections.read().await.get(bot_id).cloned();
         match conn {
             Some(BotConnection::Discord(_)) => {
-                return self.call_discord_api(bot_id, action, params).await;
+                return self
+                    .call_discord_api(bot_id, action, params)
+                    .await
+                    .ok_or_else(|| RpcError::Failed {
+                        retcode: None,
+                        message: format!("Discord 不支持 {action}"),
+                    });
             }
             Some(BotConnection::OneBot { .. }) => {}
-            None => return None,
+            None => return Err(RpcError::NotConnected),
         }
 
-        let now_nanos = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map(|d| d.as_nanos())
-            .unwrap_or(0);
-        let echo = format!("{}_{}", action, now_nanos);
+        self.send_onebot_request(bot_id, action, params)
+            .await?
+            .wait(timeout)
+            .await
+    }
 
+    /// 发出 OneBot API 请求，返回等待响应的句柄（丢弃句柄即取消等待）
+    pub(super) async fn send_onebot_request(
+        &self,
+        bot_id: &str,
+        action: &str,
+        params: Value,
+    ) -> Result<PendingCall, RpcError> {
+        let call = self.rpc.register(bot_id, action);
         let msg = serde_json::json!({
             "action": action,
             "params": params,
-            "echo": echo
+            "echo": call.echo()
         });
 
-        info!("发送 API 请求: action={}, echo={}", action, echo);
+        info!("发送 API 请求: action={}, echo={}", action, call.echo());
 
-        // 创建响应通道
-        let (tx, rx) = oneshot::channel();
-        self.pending_requests.write().await.insert(echo.clone(), tx);
-
-        // 发送请求
         let conns = self.connections.read().await;
-        let sender = match conns.get(bot_id) {
-            Some(BotConnection::OneBot { sender }) => sender,
-            _ => {
-                self.pending_requests.write().await.remove(&echo);
-                return None;
-            }
+        let Some(BotConnection::OneBot { sender }) = conns.get(bot_id) else {
+            return Err(RpcError::NotConnected);
         };
         if sender.send(msg.to_string()).is_err() {
-            self.pending_requests.write().await.remove(&echo);
-            return None;
-        }
-        drop(conns);
-
-        // 等待响应（超时 15 秒）
-        match tokio::time::timeout(std::time::Duration::from_secs(15), rx).await {
-            Ok(Ok(response)) => Some(response),
-            _ => {
-                self.pending_requests.write().await.remove(&echo);
-                None
-            }
+            return Err(RpcError::SendFailed);
         }
+        Ok(call)
     }
 
     pub async fn get_self_id(&self, bot_id: &str) -> Option<u64> {
@@ -292,7 +356,7 @@ impl BotRuntime {
         };
 
         let member_resp = match self
-            .call_api(
+            .call_api_checked(
                 bot_id,
                 "get_group_member_info",
                 serde_json::json!({
@@ -300,17 +364,14 @@ impl BotRuntime {
                     "user_id": self_id,
                     "no_cache": true,
                 }),
+                DEFAULT_TIMEOUT,
             )
             .await
         {
-            Some(v) => v,
-            None => return GroupSendStatus::Unknown,
+            Ok(v) => v,
+            Err(_) => return GroupSendStatus::Unknown,
         };
 
-        if member_resp.get("status").and_then(|s| s.as_str()) != Some("ok") {
-            return GroupSendStatus::Unknown;
-        }
-
         let member_data = member_resp.get("data").unwrap_or(&member_resp);
 
         let shut_up_raw = member_data
@@ -536,20 +597,11 @@ async fn run_bot_connection(
                                     } else {
                                         echo.to_string().trim_matches('"').to_string()
                                     };
-                                    if let Some(sender) =
-                                        runtime.pending_requests.write().await.remove(&echo_str)
-                                    {
-                                        let _ = sender.send(event);
-                                    }
+                                    runtime.rpc.resolve(&echo_str, event);
                                 }
                             } else {
-                                // 其他事件异步处理，避免阻塞接收循环
-                                let state_cl = state.clone();
-                                let runtime_cl = runtime.clone();
-                                let bot_id_cl = bot_id_recv.clone();
-                                tokio::spawn(async move {
-                                    handle_event(&state_cl, &runtime_cl, &bot_id_cl, event).await;
-                                });
+                                // 其他事件交给调度器按会话排队处理，不阻塞接收循环
+                                InboundQueue::submit(&state, &runtime, &bot_id_recv, event);
                             }
                         }
                     }
@@ -568,6 +620,13 @@ async fn run_bot_connection(
             send_task.abort();
             tick_task.abort();
             runtime.unregister_connection(&bot_id).await;
+            let cancelled = runtime.rpc.cancel_bot(&bot_id);
+            if cancelled > 0 {
+                warn!(
+                    "[{}] 连接断开，取消 {} 个等待中的 API 调用",
+                    bot_id, cancelled
+                );
+            }
             info!("{} 连接已断开", bot_id);
         }
         Err(e) => {
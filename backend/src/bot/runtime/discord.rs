@@ -1,5 +1,6 @@
 use crate::models::SharedState;
 use crate::persistence::save_bots;
+use crate::segment::MessageSegment;
 use futures_util::{SinkExt, StreamExt};
 use reqwest::Client as HttpClient;
 use serde_json::{json, Value};
@@ -11,7 +12,7 @@ use tokio_tungstenite::tungstenite::protocol::Message;
 use tracing::{error, info, warn};
 
 use super::connection::{BotConnection, BotRuntime, DiscordConnection};
-use super::message::handle_event;
+use super::inbound::InboundQueue;
 
 const DISCORD_GATEWAY_URL: &str = "wss://gateway.discord.gg/?v=10&encoding=json";
 
@@ -133,10 +134,10 @@ fn build_onebot_like_event(bot_id: &str, msg: &Value) -> Option<Value> {
 
     let mut segments: Vec<Value> = Vec::new();
     if let Some(reply_id) = referenced_message_id(msg) {
-        segments.push(json!({ "type": "reply", "data": { "id": reply_id.to_string() } }));
+        segments.push(MessageSegment::reply(reply_id).to_value());
     }
     if !content.is_empty() {
-        segments.push(json!({ "type": "text", "data": { "text": content } }));
+        segments.push(MessageSegment::text(content).to_value());
     }
 
     if let Some(atts) = msg.get("attachments").and_then(|v| v.as_array()) {
@@ -173,7 +174,7 @@ fn build_indexed_msg_data(msg: &Value) -> Option<(u64, Value)> {
 
     let mut segments: Vec<Value> = Vec::new();
     if !content.is_empty() {
-        segments.push(json!({ "type": "text", "data": { "text": content } }));
+        segments.push(MessageSegment::text(content).to_value());
     }
     if let Some(atts) = msg.get("attachments").and_then(|v| v.as_array()) {
         for att in atts {
@@ -360,12 +361,7 @@ async fn discord_connect_and_run(
                                 }
 
                                 if let Some(event) = build_onebot_like_event(&bot_id, &d) {
-                                    let state_cl = state.clone();
-                                    let runtime_cl = runtime.clone();
-                                    let bot_id_cl = bot_id.clone();
-                                    tokio::spawn(async move {
-                                        handle_event(&state_cl, &runtime_cl, &bot_id_cl, event).await;
-                                    });
+                                    InboundQueue::submit(&state, &runtime, &bot_id, event);
                                 }
                             }
                             _ => {}
@@ -0,0 +1,299 @@
+//! OneBot 11 / NapCat 通知事件的类型化模型，以及插件 `onNotice` 上下文的构建。
+//!
+//! 所有通知的上下文都有同一组基础字段：
+//! `notice_type`、`sub_type`、`time`、`user_id`、`group_id`、`operator_id`、`target_id`、
+//! `message_id`、`self_id`、`self_id_str`、`bot_is_admin`、`bot_role`、`raw_event`，
+//! 已识别的通知再补充各自的字段（见 [`NoticeKind`]）。
+//! 未识别或字段不完整的通知只带基础字段，原始事件在 `raw_event` 中原样传递。
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+
+/// 各类通知共有的字段；ID 兼容数字与字符串，缺失或无法解析时取默认值
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(super) struct NoticeCommon {
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub(super) notice_type: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub(super) sub_type: String,
+    #[serde(default, deserialize_with = "lenient_i64")]
+    pub(super) time: i64,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    pub(super) user_id: u64,
+    #[serde(default, deserialize_with = "lenient_opt_u64")]
+    pub(super) group_id: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    pub(super) operator_id: u64,
+    /// 戳一戳 / 运气王的目标
+    #[serde(default, deserialize_with = "lenient_opt_u64")]
+    pub(super) target_id: Option<u64>,
+    #[serde(default, deserialize_with = "lenient_opt_i64")]
+    pub(super) message_id: Option<i64>,
+}
+
+/// 已识别的通知类型及其专属字段
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "notice_type", rename_all = "snake_case")]
+pub(super) enum NoticeKind {
+    /// 群文件上传
+    GroupUpload { file: GroupFile },
+    /// 群管理员变动，sub_type: set / unset
+    GroupAdmin,
+    /// 群成员减少，sub_type: leave / kick / kick_me
+    GroupDecrease,
+    /// 群成员增加，sub_type: approve / invite
+    GroupIncrease,
+    /// 群禁言，sub_type: ban / lift_ban；duration 为秒数
+    GroupBan {
+        #[serde(default, deserialize_with = "lenient_u64")]
+        duration: u64,
+    },
+    /// 好友添加
+    FriendAdd,
+    /// 群消息撤回
+    GroupRecall,
+    /// 好友消息撤回
+    FriendRecall,
+    /// 群 / 好友提示类通知（按 sub_type 区分）
+    Notify(Notify),
+    /// 精华消息，sub_type: add / delete（NapCat）
+    Essence {
+        #[serde(default, deserialize_with = "lenient_u64")]
+        sender_id: u64,
+    },
+    /// 群名片变更（NapCat）
+    GroupCard {
+        #[serde(default, deserialize_with = "lenient_string")]
+        card_new: String,
+        #[serde(default, deserialize_with = "lenient_string")]
+        card_old: String,
+    },
+    /// 群消息表情回应（NapCat）
+    GroupMsgEmojiLike {
+        #[serde(default)]
+        likes: Vec<EmojiLike>,
+        /// 新版 NapCat 提供：true 为添加，false 为取消
+        #[serde(default)]
+        is_add: Option<bool>,
+    },
+    /// 私聊离线文件
+    OfflineFile { file: OfflineFile },
+    /// 账号掉线（NapCat）
+    BotOffline {
+        #[serde(default, deserialize_with = "lenient_string")]
+        tag: String,
+        #[serde(default, deserialize_with = "lenient_string")]
+        message: String,
+    },
+}
+
+/// `notice_type = notify` 的子类型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "sub_type", rename_all = "snake_case")]
+pub(super) enum Notify {
+    /// 戳一戳：user_id 戳了 target_id
+    Poke {
+        #[serde(default)]
+        raw_info: Value,
+    },
+    /// 红包运气王：target_id 为运气王
+    LuckyKing,
+    /// 群荣誉变更，honor_type: talkative / performer / emotion
+    Honor {
+        #[serde(default, deserialize_with = "lenient_string")]
+        honor_type: String,
+    },
+    /// 群头衔变更（NapCat）
+    Title {
+        #[serde(default, deserialize_with = "lenient_string")]
+        title: String,
+    },
+    /// 对方正在输入（NapCat）
+    InputStatus {
+        #[serde(default, deserialize_with = "lenient_string")]
+        status_text: String,
+        #[serde(default, deserialize_with = "lenient_i64")]
+        event_type: i64,
+    },
+    /// 资料卡点赞（NapCat）
+    ProfileLike {
+        #[serde(default, deserialize_with = "lenient_string")]
+        operator_nick: String,
+        #[serde(default, deserialize_with = "lenient_u64")]
+        times: u64,
+    },
+    /// 灰条消息（NapCat）
+    GrayTip {
+        #[serde(default, deserialize_with = "lenient_string")]
+        busi_id: String,
+        #[serde(default, deserialize_with = "lenient_string")]
+        content: String,
+        #[serde(default)]
+        raw_info: Value,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct GroupFile {
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub(super) id: String,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub(super) name: String,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    pub(super) size: u64,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub(super) busid: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct OfflineFile {
+    #[serde(default, deserialize_with = "lenient_string")]
+    name: String,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    size: u64,
+    #[serde(default, deserialize_with = "lenient_string")]
+    url: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct EmojiLike {
+    #[serde(default, deserialize_with = "lenient_string")]
+    emoji_id: String,
+    #[serde(default, deserialize_with = "lenient_u64")]
+    count: u64,
+}
+
+/// 解析后的通知事件
+#[derive(Debug, Clone)]
+pub(super) struct Notice {
+    pub(super) common: NoticeCommon,
+    /// 未识别的通知为 `None`
+    pub(super) kind: Option<NoticeKind>,
+}
+
+/// 机器人在该群的身份（仅部分通知会查询）
+#[derive(Debug, Clone, Default)]
+pub(super) struct BotInfo {
+    pub(super) self_id: Option<u64>,
+    pub(super) is_admin: bool,
+    pub(super) role: Option<String>,
+}
+
+impl Notice {
+    pub(super) fn parse(event: &Value) -> Self {
+        let mut common = NoticeCommon::deserialize(event).unwrap_or_default();
+        let kind = NoticeKind::deserialize(event).ok();
+        // 精华消息的 user_id 部分实现不提供，以消息发送者为准
+        if let Some(NoticeKind::Essence { sender_id }) = &kind {
+            if common.user_id == 0 {
+                common.user_id = *sender_id;
+            }
+        }
+        Self { common, kind }
+    }
+
+    /// 构建插件 `onNotice` 上下文
+    pub(super) fn to_context(&self, raw_event: Value, bot: &BotInfo) -> Value {
+        let c = &self.common;
+        let mut ctx = json!({
+            "notice_type": c.notice_type,
+            "sub_type": c.sub_type,
+            "time": c.time,
+            "user_id": c.user_id,
+            "group_id": c.group_id,
+            "operator_id": c.operator_id,
+            "target_id": c.target_id,
+            "message_id": c.message_id,
+            "self_id": bot.self_id,
+            "self_id_str": bot.self_id.map(|sid| sid.to_string()),
+            "bot_is_admin": bot.is_admin,
+            "bot_role": bot.role,
+        });
+        if let (Some(kind), Some(obj)) = (&self.kind, ctx.as_object_mut()) {
+            if let Ok(Value::Object(fields)) = serde_json::to_value(kind) {
+                for (k, v) in fields {
+                    obj.entry(k).or_insert(v);
+                }
+            }
+        }
+        ctx["raw_event"] = raw_event;
+        ctx
+    }
+}
+
+fn lenient_u64<'de, D: Deserializer<'de>>(d: D) -> Result<u64, D::Error> {
+    Ok(lenient_opt_u64(d)?.unwrap_or(0))
+}
+
+fn lenient_opt_u64<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u64>, D::Error> {
+    let v = match Value::deserialize(d)? {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    Ok(v.filter(|v| *v > 0))
+}
+
+fn lenient_i64<'de, D: Deserializer<'de>>(d: D) -> Result<i64, D::Error> {
+    Ok(lenient_opt_i64(d)?.unwrap_or(0))
+}
+
+fn lenient_opt_i64<'de, D: Deserializer<'de>>(d: D) -> Result<Option<i64>, D::Error> {
+    Ok(match Value::deserialize(d)? {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    })
+}
+
+fn lenient_string<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    Ok(match Value::deserialize(d)? {
+        Value::String(s) => s,
+        Value::Null => String::new(),
+        other => other.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn napcat_notices_are_typed() {
+        let poke = Notice::parse(&json!({
+            "post_type": "notice", "notice_type": "notify", "sub_type": "poke",
+            "group_id": 100, "user_id": "200", "target_id": 300, "raw_info": []
+        }));
+        assert_eq!(poke.common.user_id, 200);
+        assert_eq!(poke.common.target_id, Some(300));
+        assert!(matches!(
+            poke.kind,
+            Some(NoticeKind::Notify(Notify::Poke { .. }))
+        ));
+
+        let like = Notice::parse(&json!({
+            "notice_type": "group_msg_emoji_like", "group_id": 1, "user_id": 2,
+            "message_id": -123, "likes": [{ "emoji_id": 76, "count": 1 }]
+        }));
+        let ctx = like.to_context(Value::Null, &BotInfo::default());
+        assert_eq!(ctx["message_id"], json!(-123));
+        assert_eq!(ctx["likes"], json!([{ "emoji_id": "76", "count": 1 }]));
+        assert_eq!(ctx["notice_type"], json!("group_msg_emoji_like"));
+    }
+
+    #[test]
+    fn unknown_notices_pass_through() {
+        let raw = json!({ "notice_type": "something_new", "user_id": 5, "extra": { "a": 1 } });
+        let notice = Notice::parse(&raw);
+        assert!(notice.kind.is_none());
+        let ctx = notice.to_context(raw.clone(), &BotInfo::default());
+        assert_eq!(ctx["user_id"], json!(5));
+        assert_eq!(ctx["sub_type"], json!(""));
+        assert_eq!(ctx["raw_event"], raw);
+
+        let essence = Notice::parse(&json!({
+            "notice_type": "essence", "sub_type": "add", "sender_id": 9, "operator_id": 8
+        }));
+        assert_eq!(essence.common.user_id, 9);
+    }
+}
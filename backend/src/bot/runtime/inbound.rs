@@ -0,0 +1,412 @@
+//! 入站事件调度：每个 bot 一个调度器，同一会话（群，或私聊 / 好友通知的对方用户）的事件按到达顺序
+//! 依次处理，不同会话并发处理，避免同一群内连续两条消息以相反顺序到达插件，也避免事件洪峰时
+//! 同时创建成千上万个任务。
+//!
+//! - 每个会话一个有界队列，有事件时启动一个 worker 依次处理，队列清空后退出；
+//!   meta_event 与无法归属会话的事件各自排在公共队列中
+//! - 同时处理中的事件数受 `NBOT_EVENT_CONCURRENCY` 限制，超出的会话在队列中等待
+//! - 单个事件处理超过 `NBOT_EVENT_SLOW_SECS` 仍未结束（如等待 LLM 回复）时转入后台继续执行，
+//!   该会话开始处理下一个事件，避免一个慢指令阻塞整个群
+//! - 背压：会话队列已满时按 `NBOT_EVENT_OVERFLOW` 丢弃最旧（`drop_oldest`）或最新（`drop_newest`）的事件，
+//!   所有会话的排队总数超过上限时丢弃新事件
+//!
+//! 环境变量：
+//! - `NBOT_EVENT_QUEUE_MAX`：每个会话的排队上限（默认 100）
+//! - `NBOT_EVENT_QUEUE_TOTAL`：每个 bot 所有会话的排队总数上限（默认 5000）
+//! - `NBOT_EVENT_CONCURRENCY`：每个 bot 同时处理的事件数（默认 32）
+//! - `NBOT_EVENT_SLOW_SECS`：单个事件阻塞所在会话的最长时间（默认 10，0 为一直等待，严格按顺序）
+//! - `NBOT_EVENT_OVERFLOW`：`drop_oldest`（默认）/ `drop_newest`
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::models::SharedState;
+
+use super::connection::BotRuntime;
+use super::message::handle_event;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Overflow {
+    DropOldest,
+    DropNewest,
+}
+
+impl Overflow {
+    fn as_str(self) -> &'static str {
+        match self {
+            Overflow::DropOldest => "drop_oldest",
+            Overflow::DropNewest => "drop_newest",
+        }
+    }
+}
+
+struct Settings {
+    queue_max: usize,
+    total_max: usize,
+    concurrency: usize,
+    /// 为 None 时一直等待事件处理结束
+    slow_after: Option<Duration>,
+    overflow: Overflow,
+}
+
+impl Settings {
+    fn from_env() -> Self {
+        let env_u64 = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        let overflow = match std::env::var("NBOT_EVENT_OVERFLOW")
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "drop_newest" | "newest" => Overflow::DropNewest,
+            _ => Overflow::DropOldest,
+        };
+        let slow_secs = env_u64("NBOT_EVENT_SLOW_SECS", 10);
+        Self {
+            queue_max: env_u64("NBOT_EVENT_QUEUE_MAX", 100).max(1) as usize,
+            total_max: env_u64("NBOT_EVENT_QUEUE_TOTAL", 5000).max(1) as usize,
+            concurrency: env_u64("NBOT_EVENT_CONCURRENCY", 32).max(1) as usize,
+            slow_after: (slow_secs > 0).then(|| Duration::from_secs(slow_secs)),
+            overflow,
+        }
+    }
+}
+
+/// 事件所属的会话：群事件按群号，私聊与好友通知按对方用户
+fn conversation_key(event: &Value) -> String {
+    let id = |key: &str| {
+        match event.get(key) {
+            Some(Value::Number(n)) => n.as_u64(),
+            Some(Value::String(s)) => s.trim().parse::<u64>().ok(),
+            _ => None,
+        }
+        .filter(|id| *id > 0)
+    };
+
+    match event.get("post_type").and_then(|v| v.as_str()) {
+        Some("message" | "notice" | "request") => {
+            if let Some(gid) = id("group_id") {
+                format!("group:{gid}")
+            } else if let Some(uid) = id("user_id") {
+                format!("private:{uid}")
+            } else {
+                "other".to_string()
+            }
+        }
+        Some("meta_event") => "meta".to_string(),
+        _ => "other".to_string(),
+    }
+}
+
+struct QueuedEvent {
+    event: Value,
+    enqueued_at: Instant,
+}
+
+#[derive(Default, Serialize)]
+struct Counters {
+    received: u64,
+    processed: u64,
+    /// 队列已满被丢弃
+    dropped: u64,
+    /// 超过 `NBOT_EVENT_SLOW_SECS` 转入后台
+    slow: u64,
+    /// 处理时 panic
+    failed: u64,
+}
+
+#[derive(Default)]
+struct DispatcherState {
+    /// 有 worker 在处理的会话及其排队事件
+    conversations: HashMap<String, VecDeque<QueuedEvent>>,
+    queued: usize,
+    counters: Counters,
+}
+
+struct BotDispatcher {
+    state: Mutex<DispatcherState>,
+    permits: Arc<Semaphore>,
+}
+
+impl BotDispatcher {
+    fn update(&self, f: impl FnOnce(&mut DispatcherState)) {
+        if let Ok(mut st) = self.state.lock() {
+            f(&mut st);
+        }
+    }
+
+    /// 入队；返回 true 表示该会话还没有 worker，需要启动
+    fn push(&self, settings: &Settings, bot_id: &str, key: &str, event: Value) -> bool {
+        let Ok(mut st) = self.state.lock() else {
+            return false;
+        };
+        st.counters.received += 1;
+        let item = QueuedEvent {
+            event,
+            enqueued_at: Instant::now(),
+        };
+
+        if st.queued >= settings.total_max {
+            st.counters.dropped += 1;
+            warn!("[{}] 事件队列总数已满，丢弃会话 {} 的新事件", bot_id, key);
+            return false;
+        }
+
+        let DispatcherState {
+            conversations,
+            queued,
+            counters,
+        } = &mut *st;
+        let start_worker = !conversations.contains_key(key);
+        let queue = conversations.entry(key.to_string()).or_default();
+        if queue.len() >= settings.queue_max {
+            counters.dropped += 1;
+            match settings.overflow {
+                Overflow::DropNewest => {
+                    warn!("[{}] 会话 {} 的事件队列已满，丢弃新事件", bot_id, key);
+                    return false;
+                }
+                Overflow::DropOldest => {
+                    warn!("[{}] 会话 {} 的事件队列已满，丢弃最早的事件", bot_id, key);
+                    queue.pop_front();
+                    *queued -= 1;
+                }
+            }
+        }
+        queue.push_back(item);
+        *queued += 1;
+        start_worker
+    }
+
+    /// 取出会话的下一个事件；队列已空时移除该会话，worker 随之退出
+    fn pop(&self, key: &str) -> Option<QueuedEvent> {
+        let mut st = self.state.lock().ok()?;
+        let next = st.conversations.get_mut(key).and_then(|q| q.pop_front());
+        match next {
+            Some(item) => {
+                st.queued -= 1;
+                Some(item)
+            }
+            None => {
+                st.conversations.remove(key);
+                None
+            }
+        }
+    }
+}
+
+/// 所有 bot 的入站事件调度器
+pub struct InboundQueue {
+    settings: Settings,
+    bots: Mutex<HashMap<String, Arc<BotDispatcher>>>,
+}
+
+impl InboundQueue {
+    pub fn new() -> Self {
+        Self {
+            settings: Settings::from_env(),
+            bots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn dispatcher_for(&self, bot_id: &str) -> Option<Arc<BotDispatcher>> {
+        let mut bots = self.bots.lock().ok()?;
+        let dispatcher = bots.entry(bot_id.to_string()).or_insert_with(|| {
+            Arc::new(BotDispatcher {
+                state: Mutex::new(DispatcherState::default()),
+                permits: Arc::new(Semaphore::new(self.settings.concurrency)),
+            })
+        });
+        Some(dispatcher.clone())
+    }
+
+    /// 提交一个事件（API 响应之外的 OneBot 事件，或转换后的 Discord 消息）
+    pub(super) fn submit(
+        state: &SharedState,
+        runtime: &Arc<BotRuntime>,
+        bot_id: &str,
+        event: Value,
+    ) {
+        let inbound = &runtime.inbound;
+        let Some(dispatcher) = inbound.dispatcher_for(bot_id) else {
+            return;
+        };
+        let key = conversation_key(&event);
+        if dispatcher.push(&inbound.settings, bot_id, &key, event) {
+            tokio::spawn(run_conversation(
+                state.clone(),
+                runtime.clone(),
+                bot_id.to_string(),
+                dispatcher,
+                key,
+            ));
+        }
+    }
+
+    /// 各 bot 的排队深度、处理中的事件数与计数
+    pub fn stats(&self) -> Value {
+        let bots: Vec<(String, Arc<BotDispatcher>)> = match self.bots.lock() {
+            Ok(bots) => bots.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            Err(_) => Vec::new(),
+        };
+        let now = Instant::now();
+        let mut out = serde_json::Map::new();
+        for (bot_id, dispatcher) in bots {
+            let in_flight = self
+                .settings
+                .concurrency
+                .saturating_sub(dispatcher.permits.available_permits());
+            let Ok(st) = dispatcher.state.lock() else {
+                continue;
+            };
+            let max_depth = st.conversations.values().map(|q| q.len()).max();
+            let oldest_wait_ms = st
+                .conversations
+                .values()
+                .filter_map(|q| q.front())
+                .map(|item| now.saturating_duration_since(item.enqueued_at).as_millis() as u64)
+                .max();
+            out.insert(
+                bot_id,
+                json!({
+                    "conversations": st.conversations.len(),
+                    "queued": st.queued,
+                    "max_conversation_depth": max_depth.unwrap_or(0),
+                    "oldest_wait_ms": oldest_wait_ms.unwrap_or(0),
+                    "in_flight": in_flight,
+                    "counters": st.counters,
+                }),
+            );
+        }
+        json!({
+            "settings": {
+                "queue_max": self.settings.queue_max,
+                "queue_total": self.settings.total_max,
+                "concurrency": self.settings.concurrency,
+                "slow_secs": self.settings.slow_after.map(|d| d.as_secs()).unwrap_or(0),
+                "overflow": self.settings.overflow.as_str(),
+            },
+            "bots": out,
+        })
+    }
+}
+
+/// 依次处理一个会话的事件，队列清空后退出
+async fn run_conversation(
+    state: SharedState,
+    runtime: Arc<BotRuntime>,
+    bot_id: String,
+    dispatcher: Arc<BotDispatcher>,
+    key: String,
+) {
+    let slow_after = runtime.inbound.settings.slow_after;
+    while let Some(item) = dispatcher.pop(&key) {
+        let Ok(permit) = dispatcher.permits.clone().acquire_owned().await else {
+            return;
+        };
+        let task = {
+            let (state, runtime, bot_id) = (state.clone(), runtime.clone(), bot_id.clone());
+            let dispatcher = dispatcher.clone();
+            // 单独的任务中处理：panic 不影响 worker，转入后台后仍占用并发名额直到结束
+            tokio::spawn(async move {
+                let _permit = permit;
+                handle_event(&state, &runtime, &bot_id, item.event).await;
+                dispatcher.update(|st| st.counters.processed += 1);
+            })
+        };
+        let result = match slow_after {
+            Some(limit) => match tokio::time::timeout(limit, task).await {
+                Ok(result) => result,
+                Err(_) => {
+                    warn!(
+                        "[{}] 会话 {} 的事件处理超过 {} 秒，转入后台继续执行",
+                        bot_id,
+                        key,
+                        limit.as_secs()
+                    );
+                    dispatcher.update(|st| st.counters.slow += 1);
+                    continue;
+                }
+            },
+            None => task.await,
+        };
+        if let Err(e) = result {
+            warn!("[{}] 会话 {} 的事件处理失败: {}", bot_id, key, e);
+            dispatcher.update(|st| st.counters.failed += 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(overflow: Overflow) -> Settings {
+        Settings {
+            queue_max: 2,
+            total_max: 3,
+            concurrency: 1,
+            slow_after: None,
+            overflow,
+        }
+    }
+
+    fn dispatcher() -> BotDispatcher {
+        BotDispatcher {
+            state: Mutex::new(DispatcherState::default()),
+            permits: Arc::new(Semaphore::new(1)),
+        }
+    }
+
+    #[test]
+    fn conversation_key_by_group_or_user() {
+        let group = json!({ "post_type": "message", "group_id": 123, "user_id": 1 });
+        let private = json!({ "post_type": "message", "group_id": 0, "user_id": "42" });
+        let notice = json!({ "post_type": "notice", "notice_type": "friend_add", "user_id": 7 });
+        let meta = json!({ "post_type": "meta_event", "meta_event_type": "heartbeat" });
+        assert_eq!(conversation_key(&group), "group:123");
+        assert_eq!(conversation_key(&private), "private:42");
+        assert_eq!(conversation_key(&notice), "private:7");
+        assert_eq!(conversation_key(&meta), "meta");
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let d = dispatcher();
+        let s = settings(Overflow::DropOldest);
+        assert!(d.push(&s, "bot", "group:1", json!(1)));
+        assert!(!d.push(&s, "bot", "group:1", json!(2)));
+        assert!(!d.push(&s, "bot", "group:1", json!(3)));
+        assert_eq!(d.pop("group:1").map(|i| i.event), Some(json!(2)));
+        assert_eq!(d.pop("group:1").map(|i| i.event), Some(json!(3)));
+        assert!(d.pop("group:1").is_none());
+        // 会话清空后再次入队需要重新启动 worker
+        assert!(d.push(&s, "bot", "group:1", json!(4)));
+
+        let d = dispatcher();
+        let s = settings(Overflow::DropNewest);
+        d.push(&s, "bot", "group:1", json!(1));
+        d.push(&s, "bot", "group:1", json!(2));
+        d.push(&s, "bot", "group:1", json!(3));
+        d.push(&s, "bot", "group:2", json!(4));
+        // 总数上限
+        d.push(&s, "bot", "group:3", json!(5));
+        assert_eq!(d.pop("group:1").map(|i| i.event), Some(json!(1)));
+        assert_eq!(d.pop("group:1").map(|i| i.event), Some(json!(2)));
+        assert!(d.pop("group:1").is_none());
+        assert!(d.pop("group:3").is_none());
+        let st = d.state.lock().unwrap();
+        assert_eq!(st.counters.dropped, 2);
+        assert_eq!(st.queued, 1);
+    }
+}
@@ -0,0 +1,486 @@
+//! 长消息处理：`send_reply` 发出的文本超过阈值时，按策略拆成多条（在段落 / 代码块边界切分并编号）、
+//! 转为合并转发或渲染为图片，插件与 LLM 输出不必各自处理 QQ 的长度限制。
+//! Discord 单条 2000 字的限制同样由这里的切分处理。
+//!
+//! 由内置模块 `long_message` 配置（可按 bot 覆盖），`groups` 中按群覆盖部分字段：
+//! `{ "mode": "split", "max_chars": 3000, "max_parts": 5, "groups": { "123456": { "mode": "forward" } } }`
+//! - `mode`：`split`（拆分）/ `forward`（合并转发）/ `image`（渲染为图片）/ `off`（不处理）
+//! - `max_chars`：文本超过该字数时处理，也是拆分时每条的字数上限（Discord 不超过 2000）
+//! - `max_parts`：拆分超过该条数时改用合并转发
+//!
+//! 图片模式只处理纯文本消息，含 @、图片等消息段时改用合并转发；渲染失败时改为拆分。
+//! 配置变更在该 bot 的下一个事件时生效。
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::models::SharedState;
+use crate::render_image::render_markdown_image;
+use crate::segment::{self, MessageSegment};
+
+use super::api::DISCORD_MAX_CONTENT_CHARS;
+use super::connection::BotRuntime;
+
+pub const MODULE_ID: &str = "long_message";
+/// 合并转发每个节点的字数
+const FORWARD_NODE_CHARS: usize = 3000;
+const IMAGE_WIDTH: u32 = 520;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Off,
+    Split,
+    Forward,
+    Image,
+}
+
+impl Mode {
+    fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" | "none" => Some(Self::Off),
+            "split" => Some(Self::Split),
+            "forward" => Some(Self::Forward),
+            "image" => Some(Self::Image),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rule {
+    mode: Mode,
+    max_chars: usize,
+    max_parts: usize,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Split,
+            max_chars: 3000,
+            max_parts: 5,
+        }
+    }
+}
+
+impl Rule {
+    /// 以 `self` 为基础，用配置中给出的字段覆盖
+    fn merge(self, v: &Value) -> Self {
+        let get_usize = |key: &str| v.get(key).and_then(|x| x.as_u64()).map(|x| x as usize);
+        Self {
+            mode: v
+                .get("mode")
+                .and_then(|m| m.as_str())
+                .and_then(Mode::parse)
+                .unwrap_or(self.mode),
+            max_chars: get_usize("max_chars")
+                .unwrap_or(self.max_chars)
+                .clamp(100, 20_000),
+            max_parts: get_usize("max_parts")
+                .unwrap_or(self.max_parts)
+                .clamp(1, 50),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Policy {
+    rule: Rule,
+    groups: HashMap<u64, Rule>,
+    /// 合并转发节点显示的名称
+    bot_name: String,
+}
+
+impl Policy {
+    fn from_state(state: &SharedState, bot_id: &str) -> Self {
+        let bot_name = state
+            .bots
+            .get(bot_id)
+            .map(|b| b.value().name.clone())
+            .unwrap_or_default();
+        let Some(module) = crate::module::get_effective_module(state, bot_id, MODULE_ID) else {
+            return Self {
+                bot_name,
+                ..Default::default()
+            };
+        };
+        if !module.enabled {
+            let rule = Rule {
+                mode: Mode::Off,
+                ..Default::default()
+            };
+            return Self {
+                rule,
+                groups: HashMap::new(),
+                bot_name,
+            };
+        }
+        let rule = Rule::default().merge(&module.config);
+        let groups = module
+            .config
+            .get("groups")
+            .and_then(|g| g.as_object())
+            .map(|groups| {
+                groups
+                    .iter()
+                    .filter_map(|(gid, v)| Some((gid.trim().parse().ok()?, rule.merge(v))))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            rule,
+            groups,
+            bot_name,
+        }
+    }
+
+    fn rule_for(&self, group_id: Option<u64>) -> Rule {
+        group_id
+            .and_then(|gid| self.groups.get(&gid).copied())
+            .unwrap_or(self.rule)
+    }
+}
+
+/// 各 bot 的长消息策略；在收到事件时刷新，模块配置保存后清空
+static POLICIES: Lazy<DashMap<String, Policy>> = Lazy::new(DashMap::new);
+
+pub(super) fn refresh_policy(state: &SharedState, bot_id: &str) {
+    POLICIES.insert(bot_id.to_string(), Policy::from_state(state, bot_id));
+}
+
+/// `long_message` 模块配置变更后清空缓存的策略
+pub(super) fn invalidate() {
+    POLICIES.clear();
+}
+
+/// 该 bot / 群适用的规则与 bot 名称；尚未刷新的 bot 按当前配置解析，无法解析时使用默认策略
+fn rule_for(bot_id: &str, group_id: Option<u64>) -> (Rule, String) {
+    if let Some(policy) = POLICIES.get(bot_id) {
+        return (policy.rule_for(group_id), policy.bot_name.clone());
+    }
+    let Some(state) = super::app_state() else {
+        return (Rule::default(), String::new());
+    };
+    let policy = Policy::from_state(&state, bot_id);
+    let resolved = (policy.rule_for(group_id), policy.bot_name.clone());
+    POLICIES.insert(bot_id.to_string(), policy);
+    resolved
+}
+
+/// 处理后的发送方式
+pub(super) enum Delivery {
+    /// 依次发送的普通消息（OneBot 消息段数组或 CQ 码字符串）
+    Messages(Vec<Value>),
+    /// 合并转发节点
+    Forward(Vec<Value>),
+}
+
+/// 按该 bot / 群的策略处理一条待发送的消息；未超过阈值时原样返回
+pub(super) async fn prepare(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    group_id: Option<u64>,
+    message: Value,
+) -> Delivery {
+    let (mut rule, bot_name) = rule_for(bot_id, group_id);
+    let is_discord = runtime.is_discord(bot_id).await;
+    if is_discord {
+        rule.max_chars = rule.max_chars.min(DISCORD_MAX_CONTENT_CHARS);
+    }
+    if rule.mode == Mode::Off {
+        return Delivery::Messages(vec![message]);
+    }
+    let segments = segment::parse_message(&message);
+    if text_chars(&segments) <= rule.max_chars {
+        return Delivery::Messages(vec![message]);
+    }
+
+    let mut mode = rule.mode;
+    if mode == Mode::Image {
+        match render_image(&segments, &bot_name).await {
+            Some(Ok(image)) => return Delivery::Messages(vec![segment::to_onebot(&image)]),
+            Some(Err(e)) => {
+                warn!("[{}] 长消息渲染图片失败，改为拆分发送: {}", bot_id, e);
+                mode = Mode::Split;
+            }
+            None => mode = Mode::Forward,
+        }
+    }
+    if mode == Mode::Split {
+        let chunks = split_numbered(&segments, rule.max_chars);
+        // Discord 的合并转发同样是逐条发送，不必改用合并转发
+        if chunks.len() <= rule.max_parts || is_discord {
+            return Delivery::Messages(number_chunks(chunks));
+        }
+    }
+
+    let uin = runtime.get_self_id(bot_id).await.unwrap_or(10000);
+    let name = if bot_name.trim().is_empty() {
+        "nBot".to_string()
+    } else {
+        bot_name
+    };
+    // 回复引用在合并转发节点中无效
+    let segments: Vec<MessageSegment> = segments
+        .into_iter()
+        .filter(|seg| !matches!(seg, MessageSegment::Reply { .. }))
+        .collect();
+    let nodes = split_segments(&segments, FORWARD_NODE_CHARS)
+        .into_iter()
+        .map(|chunk| {
+            json!({
+                "type": "node",
+                "data": {
+                    "name": name,
+                    "uin": uin.to_string(),
+                    "content": segment::to_onebot(&chunk)
+                }
+            })
+        })
+        .collect();
+    Delivery::Forward(nodes)
+}
+
+fn text_chars(segments: &[MessageSegment]) -> usize {
+    segments
+        .iter()
+        .map(|seg| match seg {
+            MessageSegment::Text { text } => text.chars().count(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// 纯文本（可带回复引用）渲染为一张图片；含其他消息段时返回 None
+async fn render_image(
+    segments: &[MessageSegment],
+    title: &str,
+) -> Option<Result<Vec<MessageSegment>, String>> {
+    let mut prefix = Vec::new();
+    let mut text = String::new();
+    for seg in segments {
+        match seg {
+            MessageSegment::Reply { .. } if text.is_empty() => prefix.push(seg.clone()),
+            MessageSegment::Text { text: t } => text.push_str(t),
+            _ => return None,
+        }
+    }
+    let image = render_markdown_image(title, "", &text, IMAGE_WIDTH).await;
+    Some(image.map(|data| {
+        prefix.push(MessageSegment::image_base64(&data));
+        prefix
+    }))
+}
+
+/// 序号前缀 `（n/m）\n` 最多占用的字数
+fn number_prefix_chars(total: usize) -> usize {
+    format!("（{total}/{total}）\n").chars().count()
+}
+
+/// 拆分后需要编号时为序号前缀预留字数，保证加上序号后每条仍不超过 `max_chars`
+fn split_numbered(segments: &[MessageSegment], max_chars: usize) -> Vec<Vec<MessageSegment>> {
+    let mut chunks = split_segments(segments, max_chars);
+    let mut reserved = 0;
+    // 预留字数使条数增加、序号变长时再拆一次
+    while chunks.len() > 1 && number_prefix_chars(chunks.len()) > reserved {
+        reserved = number_prefix_chars(chunks.len());
+        chunks = split_segments(segments, max_chars.saturating_sub(reserved).max(1));
+    }
+    chunks
+}
+
+/// 多条时在每条开头加上序号（放在回复引用之后）
+fn number_chunks(chunks: Vec<Vec<MessageSegment>>) -> Vec<Value> {
+    let total = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(idx, mut chunk)| {
+            if total > 1 {
+                let at = chunk
+                    .iter()
+                    .take_while(|seg| matches!(seg, MessageSegment::Reply { .. }))
+                    .count();
+                chunk.insert(
+                    at,
+                    MessageSegment::text(format!("（{}/{}）\n", idx + 1, total)),
+                );
+            }
+            segment::to_onebot(&chunk)
+        })
+        .collect()
+}
+
+/// 按文本字数切分消息段：文本在行与代码块边界切开，其他消息段不拆分、不计字数
+fn split_segments(segments: &[MessageSegment], max_chars: usize) -> Vec<Vec<MessageSegment>> {
+    let mut chunks: Vec<Vec<MessageSegment>> = Vec::new();
+    let mut current: Vec<MessageSegment> = Vec::new();
+    let mut current_len = 0usize;
+
+    for seg in segments {
+        let MessageSegment::Text { text } = seg else {
+            current.push(seg.clone());
+            continue;
+        };
+        for unit in bounded_units(text, max_chars) {
+            let unit_len = unit.chars().count();
+            if current_len + unit_len > max_chars && current_len > 0 {
+                chunks.push(std::mem::take(&mut current));
+                current_len = 0;
+            }
+            current_len += unit_len;
+            current.push(MessageSegment::text(unit));
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+        .into_iter()
+        .map(trim_chunk)
+        .filter(|chunk| {
+            chunk.iter().any(|seg| match seg {
+                MessageSegment::Text { text } => !text.trim().is_empty(),
+                _ => true,
+            })
+        })
+        .collect()
+}
+
+/// 去掉切分处多余的换行，并合并相邻的文本段
+fn trim_chunk(chunk: Vec<MessageSegment>) -> Vec<MessageSegment> {
+    let mut out: Vec<MessageSegment> = Vec::with_capacity(chunk.len());
+    for seg in chunk {
+        match (out.last_mut(), seg) {
+            (Some(MessageSegment::Text { text }), MessageSegment::Text { text: next }) => {
+                text.push_str(&next);
+            }
+            (_, seg) => out.push(seg),
+        }
+    }
+    if let Some(MessageSegment::Text { text }) = out.first_mut() {
+        *text = text.trim_start_matches('\n').to_string();
+    }
+    if let Some(MessageSegment::Text { text }) = out.last_mut() {
+        *text = text.trim_end_matches('\n').to_string();
+    }
+    out.retain(|seg| !matches!(seg, MessageSegment::Text { text } if text.is_empty()));
+    out
+}
+
+/// 把 markdown 拆成打包单元：普通行各为一个单元，围栏代码块整体为一个单元
+fn markdown_units(markdown: &str) -> Vec<String> {
+    let mut units: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut in_block = false;
+
+    for line in markdown.split_inclusive('\n') {
+        let is_fence = line.trim_start().starts_with("```");
+        if is_fence && !in_block {
+            if !current.is_empty() {
+                units.push(std::mem::take(&mut current));
+            }
+            current.push_str(line);
+            in_block = true;
+            continue;
+        }
+        current.push_str(line);
+        if is_fence {
+            in_block = false;
+        }
+        if !in_block {
+            units.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        units.push(current);
+    }
+    units
+}
+
+/// 打包单元，单个单元超长（超大代码块或超长行）时按字符硬切
+fn bounded_units(markdown: &str, max_chars: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    for unit in markdown_units(markdown) {
+        if unit.chars().count() <= max_chars {
+            out.push(unit);
+            continue;
+        }
+        let chars: Vec<char> = unit.chars().collect();
+        out.extend(chars.chunks(max_chars).map(|piece| piece.iter().collect()));
+    }
+    out
+}
+
+/// 按字数把 markdown 切成多段；尽量在行边界切分，且不拆开放得下的代码块
+pub(super) fn split_text(markdown: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(100);
+    split_segments(&[MessageSegment::text(markdown)], max_chars)
+        .into_iter()
+        .map(|chunk| {
+            chunk
+                .into_iter()
+                .filter_map(|seg| match seg {
+                    MessageSegment::Text { text } => Some(text),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_keeps_code_blocks_and_segments() {
+        let code = format!("```\n{}\n```\n", "x = 1\n".repeat(20));
+        let text = format!("{}\n{}{}", "a".repeat(90), code, "b".repeat(50));
+        let chunks = split_text(&text, 150);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[1].starts_with("```") && chunks[1].ends_with("```"));
+
+        let segments = vec![
+            MessageSegment::reply(7),
+            MessageSegment::text("c".repeat(120)),
+            MessageSegment::At {
+                qq: "1".to_string(),
+                extra: Default::default(),
+            },
+            MessageSegment::text(format!("\n{}", "d".repeat(120))),
+        ];
+        let chunks = split_segments(&segments, 150);
+        assert_eq!(chunks.len(), 2);
+        assert!(matches!(chunks[0][0], MessageSegment::Reply { .. }));
+        assert!(matches!(chunks[0][2], MessageSegment::At { .. }));
+        let numbered = number_chunks(chunks);
+        assert_eq!(numbered[0][1]["data"]["text"], json!("（1/2）\n"));
+        assert_eq!(numbered[1][0]["data"]["text"], json!("（2/2）\n"));
+    }
+
+    #[test]
+    fn numbered_chunks_stay_within_limit() {
+        let text = "e".repeat(1998) + "\n" + &"f".repeat(1998);
+        let segments = [MessageSegment::text(text)];
+        let numbered = number_chunks(split_numbered(&segments, 2000));
+        assert!(numbered.len() > 1);
+        for message in &numbered {
+            let chars = text_chars(&segment::parse_message(message));
+            assert!(chars <= 2000, "{chars}");
+        }
+    }
+
+    #[test]
+    fn group_rules_override_bot_rule() {
+        let rule = Rule::default().merge(&json!({ "mode": "forward", "max_chars": 500 }));
+        assert_eq!(rule.mode, Mode::Forward);
+        let group = rule.merge(&json!({ "mode": "image" }));
+        assert_eq!((group.mode, group.max_chars), (Mode::Image, 500));
+    }
+}
@@ -1,6 +1,7 @@
 use crate::command::{Command, CommandAction};
 use crate::models::SharedState;
 use crate::qq_face;
+use crate::segment::{self, MessageSegment};
 use dashmap::DashMap;
 use once_cell::sync::Lazy;
 use serde_json::{json, Value};
@@ -12,7 +13,13 @@ use tracing::info;
 
 use super::command_exec::{execute_command, process_plugin_outputs_with_source, CommandExecInput};
 use super::connection::{BotRuntime, GroupSendStatus};
+use super::event::{BotInfo, Notice, NoticeKind};
+use super::long_message;
+use super::message_archive;
+use super::msg_cache;
+use super::outbound::{self, Priority};
 use super::privacy;
+use super::prompt;
 
 mod reply;
 
@@ -131,22 +138,12 @@ fn parse_reply_id_from_raw(raw_message: &str) -> Option<u64> {
 }
 
 fn collect_cq_at_ids_from_raw(raw_message: &str, out: &mut HashSet<String>) {
-    let mut rest = raw_message;
-    while let Some(start) = rest.find("[CQ:at,qq=") {
-        rest = &rest[start + "[CQ:at,qq=".len()..];
-        let end = match rest.find(']') {
-            Some(i) => i,
-            None => break,
-        };
-        let seg = &rest[..end];
-        rest = &rest[end + 1..];
-
-        let qq_raw = seg.split(',').next().unwrap_or("").trim();
-        if qq_raw.eq_ignore_ascii_case("all") || qq_raw.is_empty() {
-            continue;
-        }
-        if qq_raw.chars().all(|c| c.is_ascii_digit()) {
-            out.insert(qq_raw.to_string());
+    for seg in segment::parse_cq(raw_message) {
+        if let MessageSegment::At { qq, .. } = seg {
+            let qq = qq.trim();
+            if !qq.is_empty() && qq.chars().all(|c| c.is_ascii_digit()) {
+                out.insert(qq.to_string());
+            }
         }
     }
 }
@@ -396,21 +393,39 @@ async fn handle_message(
         }
     }
 
-    privacy::with_sensitive_ids(sensitive_ids, async {
+    // 管理员触发的回复优先于其他消息发送
+    let priority = if is_admin(state, bot_id, user_id) {
+        Priority::High
+    } else {
+        Priority::Normal
+    };
+
+    message_archive::refresh_settings(state, bot_id);
+    long_message::refresh_policy(state, bot_id);
+    let handle = privacy::with_sensitive_ids(state, bot_id, sensitive_ids, async {
         info!(
             "[{}] 收到消息 ({}) from {}: {}",
             bot_id,
             message_type,
             user_id,
-            raw_message.as_str()
+            privacy::redact_text(bot_id, privacy::Scope::Logs, &raw_message)
         );
+        msg_cache::record_inbound(runtime, bot_id, &event);
 
         let is_admin = is_admin(state, bot_id, user_id);
         let is_super_admin = is_super_admin(state, bot_id, user_id);
 
+        let message_segments = decorate_message_segments_for_plugins(event.get("message"));
+
+        // 插件正在等待该用户回复（ctx.prompt）：交给插件，不再经过 preMessage 与指令解析
+        if prompt::capture(state, runtime, bot_id, user_id, group_id, &event, &message_segments)
+            .await
+        {
+            return;
+        }
+
         // If the message is replying to another message, fetch the replied content so plugins can use it.
         let reply_message = reply::get_reply_message_content(runtime, bot_id, group_id, &event).await;
-        let message_segments = decorate_message_segments_for_plugins(event.get("message"));
 
         // 调用插件 preMessage 钩子（包括白名单过滤等）
         let pre_msg_ctx = json!({
@@ -530,8 +545,8 @@ async fn handle_message(
             )
             .await;
         }
-    })
-    .await;
+    });
+    outbound::with_priority(priority, handle).await;
 }
 
 /// 检查是否为管理员
@@ -632,27 +647,26 @@ pub fn find_command(state: &SharedState, name: &str) -> Option<Command> {
     best.map(|(_, _, cmd)| cmd)
 }
 
-/// 处理 notice 事件（通知类事件，如灰条消息、成员变动等）
+/// 处理 notice 事件（通知类事件，如灰条消息、成员变动、戳一戳、撤回等）
 async fn handle_notice(
     state: &SharedState,
     runtime: &Arc<BotRuntime>,
     bot_id: &str,
     event: Value,
 ) {
-    let notice_type = event["notice_type"].as_str().unwrap_or("unknown");
-    let sub_type = event["sub_type"].as_str().unwrap_or("");
+    let notice = Notice::parse(&event);
+    let notice_type = notice.common.notice_type.as_str();
+    let sub_type = notice.common.sub_type.as_str();
 
     info!(
         "[{}] 通知: {} {}",
         bot_id, notice_type, sub_type
     );
 
-    // 构建通用的 notice 上下文
-    let user_id = parse_u64_field(event.get("user_id")).unwrap_or(0);
-    let group_id = parse_u64_field(event.get("group_id"));
-    let operator_id = parse_u64_field(event.get("operator_id")).unwrap_or(0);
+    let user_id = notice.common.user_id;
+    let group_id = notice.common.group_id;
+    let operator_id = notice.common.operator_id;
     let self_id = runtime.get_self_id(bot_id).await;
-    let self_id_str = self_id.map(|sid| sid.to_string());
 
     let (bot_is_admin, bot_role) = if let (Some(gid), Some(sid)) = (group_id, self_id) {
         // Only check for relevant notice types to avoid extra API calls.
@@ -702,153 +716,44 @@ async fn handle_notice(
         (false, None)
     };
 
-    let mut sensitive_ids: HashSet<String> = HashSet::new();
-    if user_id > 0 {
-        sensitive_ids.insert(user_id.to_string());
+    // 群文件上传（群文件系统）：与消息事件中的文件段可能重复上报
+    if let (Some(NoticeKind::GroupUpload { file }), Some(gid)) = (&notice.kind, group_id) {
+        let file_id = file.id.trim();
+        if !file_id.is_empty() {
+            let key = format!("g:{gid}:u:{user_id}:fid:{file_id}:busid:{}", file.busid);
+            if !should_process_file_event(&key) {
+                info!("[{}] 忽略重复的群文件上传通知: file_id={}", bot_id, file_id);
+                return;
+            }
+        }
     }
-    if operator_id > 0 {
-        sensitive_ids.insert(operator_id.to_string());
+
+    let mut sensitive_ids: HashSet<String> = HashSet::new();
+    for id in [user_id, operator_id, notice.common.target_id.unwrap_or(0)] {
+        if id > 0 {
+            sensitive_ids.insert(id.to_string());
+        }
     }
     if let Some(sid) = self_id {
         sensitive_ids.insert(sid.to_string());
     }
 
-    let notice_ctx = match notice_type {
-        // 群文件上传（群文件系统）
-        "group_upload" => {
-            let file = event.get("file").cloned().unwrap_or(Value::Null);
-            let file_id = file
-                .get("id")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .trim()
-                .to_string();
-            let busid = file
-                .get("busid")
-                .map(|v| match v {
-                    Value::String(s) => s.clone(),
-                    Value::Number(n) => n.to_string(),
-                    _ => String::new(),
-                })
-                .unwrap_or_default();
-
-            if let Some(gid) = group_id {
-                if !file_id.is_empty() {
-                    let key = format!("g:{gid}:u:{user_id}:fid:{file_id}:busid:{busid}");
-                    if !should_process_file_event(&key) {
-                        info!("[{}] 忽略重复的群文件上传通知: file_id={}", bot_id, file_id);
-                        return;
-                    }
-                }
-            }
-
-            json!({
-                "notice_type": notice_type,
-                "sub_type": sub_type,
-                "user_id": user_id,
-                "group_id": group_id,
-                "self_id": self_id,
-                "self_id_str": self_id_str,
-                "file": file,
-                "raw_event": event,
-                "bot_is_admin": bot_is_admin,
-                "bot_role": bot_role,
-            })
-        }
-        // 灰条消息事件
-        "notify" if sub_type == "gray_tip" => {
-            let message_id = event.get("message_id").cloned().unwrap_or(Value::Null);
-            let busi_id = event["busi_id"].as_str().unwrap_or("");
-            let content = event["content"].as_str().unwrap_or("");
-
-            json!({
-                "notice_type": notice_type,
-                "sub_type": sub_type,
-                "user_id": user_id,
-                "group_id": group_id,
-                "self_id": self_id,
-                "self_id_str": self_id_str,
-                "message_id": message_id,
-                "busi_id": busi_id,
-                "content": content,
-                "raw_info": event.get("raw_info").cloned().unwrap_or(Value::Null),
-            })
-        }
-        // 群成员增加事件
-        "group_increase" => {
-            json!({
-                "notice_type": notice_type,
-                "sub_type": sub_type, // "approve" (管理员同意) 或 "invite" (被邀请)
-                "user_id": user_id,   // 新成员 QQ
-                "group_id": group_id,
-                "self_id": self_id,
-                "self_id_str": self_id_str,
-                "operator_id": operator_id, // 操作者 QQ（同意入群的管理员或邀请者）
-                "bot_is_admin": bot_is_admin,
-                "bot_role": bot_role,
-            })
-        }
-        // 群成员减少事件
-        "group_decrease" => {
-            json!({
-                "notice_type": notice_type,
-                "sub_type": sub_type, // "leave" (主动退群), "kick" (被踢), "kick_me" (机器人被踢)
-                "user_id": user_id,   // 离开的成员 QQ
-                "group_id": group_id,
-                "self_id": self_id,
-                "self_id_str": self_id_str,
-                "operator_id": operator_id, // 操作者 QQ（踢人的管理员）
-                "bot_is_admin": bot_is_admin,
-                "bot_role": bot_role,
-            })
-        }
-        // 群管理员变动
-        "group_admin" => {
-            json!({
-                "notice_type": notice_type,
-                "sub_type": sub_type, // "set" (设置管理员) 或 "unset" (取消管理员)
-                "user_id": user_id,   // 被操作的成员 QQ
-                "group_id": group_id,
-                "self_id": self_id,
-                "self_id_str": self_id_str,
-                "bot_is_admin": bot_is_admin,
-                "bot_role": bot_role,
-            })
-        }
-        // 群禁言
-        "group_ban" => {
-            let duration = event["duration"].as_u64().unwrap_or(0);
-            json!({
-                "notice_type": notice_type,
-                "sub_type": sub_type, // "ban" (禁言) 或 "lift_ban" (解除禁言)
-                "user_id": user_id,   // 被禁言的成员 QQ
-                "group_id": group_id,
-                "self_id": self_id,
-                "self_id_str": self_id_str,
-                "operator_id": operator_id,
-                "duration": duration, // 禁言时长（秒），0 表示解除禁言
-                "bot_is_admin": bot_is_admin,
-                "bot_role": bot_role,
-            })
-        }
-        // 其他通知类型，传递原始事件
-        _ => {
-            json!({
-                "notice_type": notice_type,
-                "sub_type": sub_type,
-                "user_id": user_id,
-                "group_id": group_id,
-                "self_id": self_id,
-                "self_id_str": self_id_str,
-                "operator_id": operator_id,
-                "raw_event": event,
-                "bot_is_admin": bot_is_admin,
-                "bot_role": bot_role,
-            })
-        }
+    let bot = BotInfo {
+        self_id,
+        is_admin: bot_is_admin,
+        role: bot_role,
     };
+    let mut notice_ctx = notice.to_context(event, &bot);
+    // 撤回通知：附上缓存中的原消息（未缓存或已过期时为 null）
+    if matches!(notice.kind, Some(NoticeKind::GroupRecall | NoticeKind::FriendRecall)) {
+        let recalled = notice
+            .common
+            .message_id
+            .and_then(|id| runtime.msg_cache.get(bot_id, id));
+        notice_ctx["recalled_message"] = json!(recalled);
+    }
 
-    privacy::with_sensitive_ids(sensitive_ids, async {
+    privacy::with_sensitive_ids(state, bot_id, sensitive_ids, async {
         // 调用插件 onNotice 钩子
         let notice_result = state.plugin_manager.on_notice(notice_ctx).await;
 
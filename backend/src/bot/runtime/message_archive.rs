@@ -0,0 +1,470 @@
+//! 本地消息存档：按 bot 把收到与发出的消息写入本地 SQLite（`data/archive/messages.db`），
+//! 支持全文检索（FTS5 trigram 分词，可检索中文）以及按群 / 私聊对象 / 发送者 / 时间过滤。
+//! WebUI 聊天记录与插件 `queryMessageHistory` 在开启后从这里读取，不再依赖 QQ 客户端的漫游记录，
+//! Discord bot 也能查看历史。
+//!
+//! 默认关闭，启用内置模块 `message_archive`（可按 bot 覆盖）后开始记录，配置：
+//! `{ "retention_days": 30, "max_messages": 200000 }`，超过保留天数或条数的旧消息定期删除。
+//! 文本写入前按隐私策略（日志位置）脱敏；配置变更在该 bot 的下一个事件时生效。
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use super::connection::BotRuntime;
+use crate::models::SharedState;
+
+pub const MODULE_ID: &str = "message_archive";
+/// FTS5 trigram 分词只能匹配不少于 3 个字符的词，更短的改用 LIKE
+const FTS_MIN_CHARS: usize = 3;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+const MAX_QUERY_LIMIT: usize = 500;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS messages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    bot_id TEXT NOT NULL,
+    message_id INTEGER,
+    group_id INTEGER,
+    peer_id INTEGER,
+    user_id INTEGER NOT NULL,
+    sender_name TEXT NOT NULL DEFAULT '',
+    outgoing INTEGER NOT NULL DEFAULT 0,
+    time INTEGER NOT NULL,
+    text TEXT NOT NULL DEFAULT '',
+    message TEXT NOT NULL DEFAULT '[]'
+);
+CREATE INDEX IF NOT EXISTS idx_messages_group ON messages(bot_id, group_id, time);
+CREATE INDEX IF NOT EXISTS idx_messages_peer ON messages(bot_id, peer_id, time);
+CREATE INDEX IF NOT EXISTS idx_messages_user ON messages(bot_id, user_id, time);
+CREATE INDEX IF NOT EXISTS idx_messages_msg ON messages(bot_id, message_id);
+CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts
+    USING fts5(text, content='messages', content_rowid='id', tokenize='trigram');
+CREATE TRIGGER IF NOT EXISTS messages_ai AFTER INSERT ON messages BEGIN
+    INSERT INTO messages_fts(rowid, text) VALUES (new.id, new.text);
+END;
+CREATE TRIGGER IF NOT EXISTS messages_ad AFTER DELETE ON messages BEGIN
+    INSERT INTO messages_fts(messages_fts, rowid, text) VALUES ('delete', old.id, old.text);
+END;
+";
+
+#[derive(Debug, Clone, Copy)]
+struct ArchiveSettings {
+    retention_days: i64,
+    max_messages: i64,
+}
+
+impl ArchiveSettings {
+    /// 未开启时返回 None
+    fn from_state(state: &SharedState, bot_id: &str) -> Option<Self> {
+        let module = crate::module::get_effective_module(state, bot_id, MODULE_ID)?;
+        if !module.enabled {
+            return None;
+        }
+        let get_u64 = |key: &str, default: u64| {
+            module
+                .config
+                .get(key)
+                .and_then(|v| v.as_u64())
+                .unwrap_or(default)
+        };
+        Some(Self {
+            retention_days: get_u64("retention_days", 30).clamp(1, 3650) as i64,
+            max_messages: get_u64("max_messages", 200_000).clamp(100, 50_000_000) as i64,
+        })
+    }
+}
+
+/// 各 bot 的存档设置（None 表示未开启）；在收到事件时刷新，模块配置保存后清空
+static SETTINGS: Lazy<DashMap<String, Option<ArchiveSettings>>> = Lazy::new(DashMap::new);
+
+/// 按模块配置刷新该 bot 的存档设置，返回是否开启
+pub fn refresh_settings(state: &SharedState, bot_id: &str) -> bool {
+    let settings = ArchiveSettings::from_state(state, bot_id);
+    SETTINGS.insert(bot_id.to_string(), settings);
+    settings.is_some()
+}
+
+/// 缓存的存档设置；尚未刷新的 bot 按当前配置解析
+fn cached_settings(bot_id: &str) -> Option<ArchiveSettings> {
+    if let Some(settings) = SETTINGS.get(bot_id) {
+        return *settings;
+    }
+    let state = super::app_state()?;
+    let settings = ArchiveSettings::from_state(&state, bot_id);
+    SETTINGS.insert(bot_id.to_string(), settings);
+    settings
+}
+
+/// `message_archive` 模块配置变更后清空缓存的设置
+pub(super) fn invalidate() {
+    SETTINGS.clear();
+}
+
+/// 该 bot 当前是否开启了存档
+pub(super) fn is_enabled(bot_id: &str) -> bool {
+    cached_settings(bot_id).is_some()
+}
+
+/// 一条待写入的消息（文本已脱敏）
+#[derive(Debug, Clone)]
+pub(super) struct NewMessage {
+    pub(super) message_id: Option<i64>,
+    pub(super) group_id: Option<u64>,
+    /// 私聊对象（群消息为 None）
+    pub(super) peer_id: Option<u64>,
+    pub(super) user_id: u64,
+    pub(super) sender_name: String,
+    pub(super) outgoing: bool,
+    pub(super) time: i64,
+    pub(super) text: String,
+    pub(super) message: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchivedMessage {
+    pub id: i64,
+    pub bot_id: String,
+    pub message_id: Option<i64>,
+    pub group_id: Option<u64>,
+    pub peer_id: Option<u64>,
+    pub user_id: u64,
+    pub sender_name: String,
+    pub outgoing: bool,
+    pub time: i64,
+    pub text: String,
+    pub message: Value,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ArchiveQuery {
+    #[serde(default)]
+    pub bot_id: String,
+    /// 群聊
+    #[serde(default)]
+    pub group_id: Option<u64>,
+    /// 与该用户的私聊
+    #[serde(default)]
+    pub peer_id: Option<u64>,
+    /// 发送者
+    #[serde(default)]
+    pub user_id: Option<u64>,
+    /// 关键词（空格分隔，需全部匹配）
+    #[serde(default)]
+    pub keyword: Option<String>,
+    /// 起止时间（Unix 秒）
+    #[serde(default)]
+    pub since: Option<i64>,
+    #[serde(default)]
+    pub until: Option<i64>,
+    /// 分页：只返回 id 小于该值的记录
+    #[serde(default)]
+    pub before_id: Option<i64>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+struct Store {
+    path: PathBuf,
+    conn: Mutex<Option<Connection>>,
+    last_prune: DashMap<String, Instant>,
+}
+
+static STORE: Lazy<Store> = Lazy::new(|| {
+    let data_dir = std::env::var("NBOT_DATA_DIR").unwrap_or_else(|_| "data".to_string());
+    Store {
+        path: Path::new(&data_dir).join("archive").join("messages.db"),
+        conn: Mutex::new(None),
+        last_prune: DashMap::new(),
+    }
+});
+
+fn sql_err(e: rusqlite::Error) -> String {
+    format!("消息存档数据库错误: {e}")
+}
+
+impl Store {
+    fn with_conn<T>(&self, f: impl FnOnce(&Connection) -> Result<T, String>) -> Result<T, String> {
+        let mut guard = self
+            .conn
+            .lock()
+            .map_err(|_| "消息存档数据库锁已损坏".to_string())?;
+        if guard.is_none() {
+            if let Some(dir) = self.path.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| format!("创建存档目录失败: {e}"))?;
+            }
+            let conn = Connection::open(&self.path).map_err(sql_err)?;
+            conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")
+                .map_err(sql_err)?;
+            conn.execute_batch(SCHEMA).map_err(sql_err)?;
+            *guard = Some(conn);
+        }
+        match guard.as_ref() {
+            Some(conn) => f(conn),
+            None => Err("消息存档数据库未打开".to_string()),
+        }
+    }
+
+    fn insert(&self, bot_id: &str, msg: &NewMessage) -> Result<(), String> {
+        let message = serde_json::to_string(&msg.message).unwrap_or_else(|_| "[]".to_string());
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO messages
+                    (bot_id, message_id, group_id, peer_id, user_id, sender_name, outgoing, time, text, message)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    bot_id,
+                    msg.message_id,
+                    msg.group_id.map(|v| v as i64),
+                    msg.peer_id.map(|v| v as i64),
+                    msg.user_id as i64,
+                    msg.sender_name,
+                    msg.outgoing,
+                    msg.time,
+                    msg.text,
+                    message,
+                ],
+            )
+            .map_err(sql_err)?;
+            Ok(())
+        })
+    }
+
+    /// 删除超过保留天数或条数上限的旧消息
+    fn prune(&self, bot_id: &str, settings: ArchiveSettings) -> Result<usize, String> {
+        let now = chrono::Utc::now().timestamp();
+        let cutoff = now - settings.retention_days * 86_400;
+        self.with_conn(|conn| {
+            let mut removed = conn
+                .execute(
+                    "DELETE FROM messages WHERE bot_id = ?1 AND time < ?2",
+                    params![bot_id, cutoff],
+                )
+                .map_err(sql_err)?;
+            removed += conn
+                .execute(
+                    "DELETE FROM messages WHERE bot_id = ?1 AND id <= (
+                        SELECT id FROM messages WHERE bot_id = ?1 ORDER BY id DESC LIMIT 1 OFFSET ?2
+                    )",
+                    params![bot_id, settings.max_messages],
+                )
+                .map_err(sql_err)?;
+            Ok(removed)
+        })
+    }
+
+    fn query(&self, q: &ArchiveQuery) -> Result<Vec<ArchivedMessage>, String> {
+        let mut sql = String::from(
+            "SELECT id, bot_id, message_id, group_id, peer_id, user_id, sender_name, outgoing, time, text, message
+             FROM messages WHERE bot_id = ?",
+        );
+        let mut args: Vec<SqlValue> = vec![SqlValue::Text(q.bot_id.clone())];
+        let mut push = |cond: &str, arg: SqlValue| {
+            sql.push_str(" AND ");
+            sql.push_str(cond);
+            args.push(arg);
+        };
+        if let Some(gid) = q.group_id {
+            push("group_id = ?", SqlValue::Integer(gid as i64));
+        }
+        if let Some(pid) = q.peer_id {
+            push("peer_id = ?", SqlValue::Integer(pid as i64));
+        }
+        if let Some(uid) = q.user_id {
+            push("user_id = ?", SqlValue::Integer(uid as i64));
+        }
+        if let Some(since) = q.since {
+            push("time >= ?", SqlValue::Integer(since));
+        }
+        if let Some(until) = q.until {
+            push("time <= ?", SqlValue::Integer(until));
+        }
+        if let Some(before) = q.before_id {
+            push("id < ?", SqlValue::Integer(before));
+        }
+        let keyword = q.keyword.as_deref().unwrap_or("");
+        let (fts_terms, like_terms) = split_keyword(keyword);
+        if !fts_terms.is_empty() {
+            push(
+                "id IN (SELECT rowid FROM messages_fts WHERE messages_fts MATCH ?)",
+                SqlValue::Text(fts_terms.join(" AND ")),
+            );
+        }
+        for term in like_terms {
+            push("text LIKE ? ESCAPE '\\'", SqlValue::Text(term));
+        }
+        let limit = q.limit.unwrap_or(50).clamp(1, MAX_QUERY_LIMIT);
+        sql.push_str(&format!(" ORDER BY id DESC LIMIT {limit}"));
+
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&sql).map_err(sql_err)?;
+            let rows = stmt
+                .query_map(rusqlite::params_from_iter(args), |row| {
+                    let message: String = row.get(10)?;
+                    Ok(ArchivedMessage {
+                        id: row.get(0)?,
+                        bot_id: row.get(1)?,
+                        message_id: row.get(2)?,
+                        group_id: row.get::<_, Option<i64>>(3)?.map(|v| v as u64),
+                        peer_id: row.get::<_, Option<i64>>(4)?.map(|v| v as u64),
+                        user_id: row.get::<_, i64>(5)? as u64,
+                        sender_name: row.get(6)?,
+                        outgoing: row.get(7)?,
+                        time: row.get(8)?,
+                        text: row.get(9)?,
+                        message: serde_json::from_str(&message).unwrap_or(Value::Null),
+                    })
+                })
+                .map_err(sql_err)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(sql_err)
+        })
+    }
+
+    fn clear(&self, bot_id: &str) -> Result<usize, String> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM messages WHERE bot_id = ?1", params![bot_id])
+                .map_err(sql_err)
+        })
+    }
+}
+
+/// 关键词拆成 FTS 短语（不少于 3 个字符）与 LIKE 模式（更短的词）
+fn split_keyword(keyword: &str) -> (Vec<String>, Vec<String>) {
+    let mut fts = Vec::new();
+    let mut like = Vec::new();
+    for term in keyword.split_whitespace() {
+        if term.chars().count() >= FTS_MIN_CHARS {
+            fts.push(format!("\"{}\"", term.replace('"', "\"\"")));
+        } else {
+            let escaped = term
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            like.push(format!("%{escaped}%"));
+        }
+    }
+    (fts, like)
+}
+
+/// 写入一条消息（后台执行）；该 bot 未开启存档时忽略
+pub(super) fn record(bot_id: &str, msg: NewMessage) {
+    let Some(settings) = cached_settings(bot_id) else {
+        return;
+    };
+    let bot_id = bot_id.to_string();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = STORE.insert(&bot_id, &msg) {
+            warn!("[{}] {}", bot_id, e);
+            return;
+        }
+        let due = STORE
+            .last_prune
+            .get(&bot_id)
+            .is_none_or(|t| t.elapsed() >= PRUNE_INTERVAL);
+        if due {
+            STORE.last_prune.insert(bot_id.clone(), Instant::now());
+            if let Err(e) = STORE.prune(&bot_id, settings) {
+                warn!("[{}] 清理消息存档失败: {}", bot_id, e);
+            }
+        }
+    });
+}
+
+/// 记录未经 API 分发发出的消息（如 WebUI 聊天页），resp 为发送接口的返回
+pub async fn record_sent(runtime: &BotRuntime, bot_id: &str, params: &Value, resp: &Value) {
+    if !is_enabled(bot_id) {
+        return;
+    }
+    let group_id = params.get("group_id").and_then(Value::as_u64);
+    let peer_id = match group_id {
+        Some(_) => None,
+        None => params.get("user_id").and_then(Value::as_u64),
+    };
+    let message = params.get("message").unwrap_or(&Value::Null);
+    let (text, message) = super::msg_cache::redact_message(bot_id, message);
+    let user_id = runtime.get_self_id(bot_id).await.unwrap_or(0);
+    record(
+        bot_id,
+        NewMessage {
+            message_id: resp.pointer("/data/message_id").and_then(Value::as_i64),
+            group_id,
+            peer_id,
+            user_id,
+            sender_name: String::new(),
+            outgoing: true,
+            time: chrono::Utc::now().timestamp(),
+            text,
+            message,
+        },
+    );
+}
+
+/// 按条件查询存档（按时间倒序）
+pub async fn query_messages(q: ArchiveQuery) -> Result<Vec<ArchivedMessage>, String> {
+    tokio::task::spawn_blocking(move || STORE.query(&q))
+        .await
+        .map_err(|e| format!("查询消息存档失败: {e}"))?
+}
+
+/// 删除该 bot 的全部存档，返回删除的条数
+pub async fn clear_messages(bot_id: String) -> Result<usize, String> {
+    tokio::task::spawn_blocking(move || STORE.clear(&bot_id))
+        .await
+        .map_err(|e| format!("清除消息存档失败: {e}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fts_search_handles_cjk_and_short_terms() {
+        let dir = std::env::temp_dir().join(format!("nbot_archive_test_{}", std::process::id()));
+        let store = Store {
+            path: dir.join("messages.db"),
+            conn: Mutex::new(None),
+            last_prune: DashMap::new(),
+        };
+        let msg = |text: &str, group_id: u64| NewMessage {
+            message_id: Some(1),
+            group_id: Some(group_id),
+            peer_id: None,
+            user_id: 10,
+            sender_name: "a".to_string(),
+            outgoing: false,
+            time: 1_700_000_000,
+            text: text.to_string(),
+            message: Value::Null,
+        };
+        store
+            .insert("bot", &msg("今天的会议改到下午三点", 1))
+            .unwrap();
+        store.insert("bot", &msg("hello world", 1)).unwrap();
+        store.insert("bot", &msg("会议取消", 2)).unwrap();
+
+        let search = |keyword: &str, group_id: Option<u64>| {
+            store
+                .query(&ArchiveQuery {
+                    bot_id: "bot".to_string(),
+                    group_id,
+                    keyword: Some(keyword.to_string()),
+                    ..Default::default()
+                })
+                .unwrap()
+                .len()
+        };
+        assert_eq!(search("下午三点", None), 1);
+        assert_eq!(search("会议", None), 2);
+        assert_eq!(search("会议", Some(2)), 1);
+        assert_eq!(search("HELLO", None), 1);
+        assert_eq!(search("100%", None), 0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -2,9 +2,48 @@ mod api;
 mod command_exec;
 mod connection;
 mod discord;
+mod event;
 mod help_image;
+mod inbound;
+mod long_message;
 mod message;
+pub mod message_archive;
+mod msg_cache;
+mod outbound;
+mod pre_send;
 mod privacy;
+mod prompt;
+mod rpc;
 
+pub use command_exec::knowledge;
+pub use command_exec::llm_trace;
+pub use command_exec::media_cache;
+pub use command_exec::web_search;
 pub use connection::{start_bot_connections, BotRuntime, GroupSendStatus};
 pub use discord::start_discord_connections;
+pub use privacy::policy as privacy_policy;
+
+use std::sync::{Arc, OnceLock, Weak};
+
+use crate::models::{AppState, SharedState};
+
+static APP_STATE: OnceLock<Weak<AppState>> = OnceLock::new();
+
+/// 登记全局状态：拿不到 `SharedState` 的发送 / 脱敏路径在按 bot 缓存的配置未命中时据此解析
+pub fn bind_state(state: &SharedState) {
+    let _ = APP_STATE.set(Arc::downgrade(state));
+}
+
+fn app_state() -> Option<SharedState> {
+    APP_STATE.get()?.upgrade()
+}
+
+/// 模块配置（全局或某个 bot 的覆盖）保存后清除依赖它的缓存，下次使用时按新配置重新解析
+pub fn invalidate_module_caches(module_id: &str) {
+    match module_id {
+        "privacy" => privacy::policy::invalidate(),
+        message_archive::MODULE_ID => message_archive::invalidate(),
+        long_message::MODULE_ID => long_message::invalidate(),
+        _ => {}
+    }
+}
@@ -0,0 +1,327 @@
+//! 近期消息缓存：按 bot 保存最近收到与发出的消息，群消息 / 好友消息被撤回时，
+//! 把原消息内容与发送者附在撤回通知的插件上下文中（`recalled_message`），便于留存刷屏等证据。
+//!
+//! 写入前按 bot 的隐私策略（日志位置）对文本脱敏，缓存中不保留原文。
+//!
+//! 环境变量：
+//! - `NBOT_RECALL_CACHE_SECS`：缓存时长（秒，默认 600，设为 0 关闭）
+//! - `NBOT_RECALL_CACHE_MAX`：每个 bot 最多缓存的消息数（默认 2000）
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use super::connection::BotRuntime;
+use super::message_archive;
+use super::privacy::{self, Scope};
+use super::rpc::{send_ack, Ack, RpcError};
+
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct CachedMessage {
+    message_id: i64,
+    user_id: u64,
+    /// 群名片，没有时为昵称
+    nickname: String,
+    group_id: Option<u64>,
+    /// 文本段拼接的纯文本（已脱敏）
+    text: String,
+    /// OneBot 消息段数组（文本段已脱敏）
+    message: Value,
+    time: i64,
+    /// 机器人自己发出的消息
+    outgoing: bool,
+    #[serde(skip)]
+    cached_at: Instant,
+}
+
+#[derive(Default)]
+struct BotMessages {
+    entries: HashMap<i64, CachedMessage>,
+    order: VecDeque<i64>,
+}
+
+pub(super) struct MessageCache {
+    ttl: Duration,
+    max_per_bot: usize,
+    bots: Mutex<HashMap<String, BotMessages>>,
+}
+
+impl MessageCache {
+    pub(super) fn new() -> Self {
+        let env_u64 = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            ttl: Duration::from_secs(env_u64("NBOT_RECALL_CACHE_SECS", 600)),
+            max_per_bot: env_u64("NBOT_RECALL_CACHE_MAX", 2000).max(1) as usize,
+            bots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    fn insert(&self, bot_id: &str, msg: CachedMessage) {
+        let Ok(mut bots) = self.bots.lock() else {
+            return;
+        };
+        let bot = bots.entry(bot_id.to_string()).or_default();
+        let now = Instant::now();
+        while let Some(id) = bot.order.front().copied() {
+            let expired = bot
+                .entries
+                .get(&id)
+                .is_none_or(|m| now.duration_since(m.cached_at) >= self.ttl);
+            if !expired && bot.order.len() < self.max_per_bot {
+                break;
+            }
+            bot.order.pop_front();
+            bot.entries.remove(&id);
+        }
+        if bot.entries.insert(msg.message_id, msg.clone()).is_none() {
+            bot.order.push_back(msg.message_id);
+        }
+    }
+
+    /// 按 message_id 查找未过期的消息
+    pub(super) fn get(&self, bot_id: &str, message_id: i64) -> Option<CachedMessage> {
+        let bots = self.bots.lock().ok()?;
+        let msg = bots.get(bot_id)?.entries.get(&message_id)?;
+        (msg.cached_at.elapsed() < self.ttl).then(|| msg.clone())
+    }
+}
+
+fn parse_i64(v: Option<&Value>) -> Option<i64> {
+    match v? {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn parse_u64(v: Option<&Value>) -> Option<u64> {
+    match v? {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// 对消息中的文本段脱敏，返回（纯文本，消息段数组）；CQ 码字符串先转为消息段
+pub(super) fn redact_message(bot_id: &str, message: &Value) -> (String, Value) {
+    let mut segments = match message {
+        Value::Array(items) => items.clone(),
+        Value::String(s) => crate::segment::parse_cq(s)
+            .iter()
+            .map(|seg| seg.to_value())
+            .collect(),
+        _ => Vec::new(),
+    };
+    let mut text = String::new();
+    for seg in &mut segments {
+        // 发出的 base64 媒体体积大，不缓存内容
+        let is_base64 = seg
+            .pointer("/data/file")
+            .and_then(|v| v.as_str())
+            .is_some_and(|f| f.starts_with("base64://"));
+        if is_base64 {
+            seg["data"]["file"] = Value::String("base64://".to_string());
+        }
+        if seg.get("type").and_then(|t| t.as_str()) != Some("text") {
+            continue;
+        }
+        let Some(raw) = seg.pointer("/data/text").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let redacted = privacy::redact_text(bot_id, Scope::Logs, raw);
+        text.push_str(&redacted);
+        seg["data"]["text"] = Value::String(redacted);
+    }
+    (text, Value::Array(segments))
+}
+
+/// 记录收到的消息（近期缓存与消息存档）；需在该事件的隐私上下文（`with_sensitive_ids`）内调用
+pub(super) fn record_inbound(runtime: &BotRuntime, bot_id: &str, event: &Value) {
+    let archive = message_archive::is_enabled(bot_id);
+    if !runtime.msg_cache.enabled() && !archive {
+        return;
+    }
+    let message_id = parse_i64(event.get("message_id"));
+    let sender = event.get("sender");
+    let nickname = ["card", "nickname"]
+        .iter()
+        .filter_map(|k| sender?.get(*k)?.as_str())
+        .map(str::trim)
+        .find(|s| !s.is_empty())
+        .unwrap_or_default()
+        .to_string();
+    let (text, message) = redact_message(bot_id, event.get("message").unwrap_or(&Value::Null));
+    let user_id = parse_u64(event.get("user_id")).unwrap_or(0);
+    let group_id = parse_u64(event.get("group_id")).filter(|g| *g > 0);
+    let time = parse_i64(event.get("time")).unwrap_or_else(now_secs);
+    if archive {
+        message_archive::record(
+            bot_id,
+            message_archive::NewMessage {
+                message_id,
+                group_id,
+                peer_id: group_id.is_none().then_some(user_id),
+                user_id,
+                sender_name: nickname.clone(),
+                outgoing: false,
+                time,
+                text: text.clone(),
+                message: message.clone(),
+            },
+        );
+    }
+    let Some(message_id) = message_id else {
+        return;
+    };
+    if runtime.msg_cache.enabled() {
+        runtime.msg_cache.insert(
+            bot_id,
+            CachedMessage {
+                message_id,
+                user_id,
+                nickname,
+                group_id,
+                text,
+                message,
+                time,
+                outgoing: false,
+                cached_at: Instant::now(),
+            },
+        );
+    }
+}
+
+fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// 发出的消息：在回执中取得 message_id 后写入缓存与消息存档，再把结果转交原回执
+pub(super) fn track_outgoing(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    action: &str,
+    params: &Value,
+    group_id: Option<u64>,
+    ack: Option<Ack>,
+) -> Option<Ack> {
+    let archive = message_archive::is_enabled(bot_id);
+    if !(runtime.msg_cache.enabled() || archive)
+        || !matches!(action, "send_group_msg" | "send_private_msg" | "send_msg")
+    {
+        return ack;
+    }
+    let (text, message) = redact_message(bot_id, params.get("message").unwrap_or(&Value::Null));
+    let peer_id = if group_id.is_none() {
+        parse_u64(params.get("user_id"))
+    } else {
+        None
+    };
+    let (tx, rx) = oneshot::channel::<Result<Value, RpcError>>();
+    let runtime = runtime.clone();
+    let bot_id = bot_id.to_string();
+    tokio::spawn(async move {
+        let Ok(result) = rx.await else {
+            return;
+        };
+        let sent = result.is_ok();
+        let message_id = result
+            .as_ref()
+            .ok()
+            .and_then(|resp| parse_i64(resp.pointer("/data/message_id")));
+        if !sent {
+            send_ack(ack, result);
+            return;
+        }
+        let self_id = runtime.get_self_id(&bot_id).await.unwrap_or(0);
+        let time = now_secs();
+        if archive {
+            message_archive::record(
+                &bot_id,
+                message_archive::NewMessage {
+                    message_id,
+                    group_id,
+                    peer_id,
+                    user_id: self_id,
+                    sender_name: String::new(),
+                    outgoing: true,
+                    time,
+                    text: text.clone(),
+                    message: message.clone(),
+                },
+            );
+        }
+        if let (Some(message_id), true) = (message_id, runtime.msg_cache.enabled()) {
+            runtime.msg_cache.insert(
+                &bot_id,
+                CachedMessage {
+                    message_id,
+                    user_id: self_id,
+                    nickname: String::new(),
+                    group_id,
+                    text,
+                    message,
+                    time,
+                    outgoing: true,
+                    cached_at: Instant::now(),
+                },
+            );
+        }
+        send_ack(ack, result);
+    });
+    Some(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(id: i64) -> CachedMessage {
+        CachedMessage {
+            message_id: id,
+            user_id: 1,
+            nickname: String::new(),
+            group_id: Some(2),
+            text: format!("m{id}"),
+            message: Value::Null,
+            time: 0,
+            outgoing: false,
+            cached_at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn bounded_per_bot_with_ttl() {
+        let cache = MessageCache {
+            ttl: Duration::from_secs(60),
+            max_per_bot: 2,
+            bots: Mutex::new(HashMap::new()),
+        };
+        cache.insert("a", msg(1));
+        cache.insert("a", msg(2));
+        cache.insert("a", msg(3));
+        cache.insert("b", msg(1));
+        assert!(cache.get("a", 1).is_none());
+        assert_eq!(cache.get("a", 3).map(|m| m.text), Some("m3".to_string()));
+        assert!(cache.get("b", 1).is_some());
+
+        let mut old = msg(4);
+        old.cached_at = Instant::now() - Duration::from_secs(120);
+        cache.insert("c", old);
+        assert!(cache.get("c", 4).is_none());
+    }
+}
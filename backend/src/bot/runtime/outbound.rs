@@ -0,0 +1,752 @@
+//! 出站发送队列：每个 bot 一个队列，按账号与群的令牌桶限速、按优先级出队，
+//! 每条发送前加入随机延迟，临时性失败的 retcode 自动重试，避免插件短时间批量发送触发 QQ 风控。
+//!
+//! 只有发送消息类 API（`send_*_msg`、合并转发、上传文件）进入队列，其余 API 直接发送。
+//! 同一群（私聊为同一用户）内同一优先级的消息按入队顺序发出：上一条收到回执（或重试结束）前，
+//! 后面的消息留在队列中。
+//!
+//! 环境变量：
+//! - `NBOT_SEND_RATE_PER_MIN`：每个账号每分钟发送条数（默认 40，设为 0 关闭队列直接发送）
+//! - `NBOT_SEND_BURST`：账号突发条数（默认 5）
+//! - `NBOT_SEND_GROUP_RATE_PER_MIN`：每个群每分钟发送条数（默认 20）
+//! - `NBOT_SEND_GROUP_BURST`：单群突发条数（默认 3）
+//! - `NBOT_SEND_JITTER_MS`：每条发送前随机延迟的上限（默认 300）
+//! - `NBOT_SEND_MAX_RETRIES`：失败后的重试次数（默认 2，最多 5）
+//! - `NBOT_SEND_QUEUE_MAX`：每个 bot 的排队上限（默认 500），超出时丢弃优先级最低的消息
+//! - `NBOT_SEND_MAX_WAIT_SECS`：排队超过该时长的消息直接丢弃（默认 300）
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio::task_local;
+use tracing::{info, warn};
+
+use super::api::discord_send_api;
+use super::connection::{BotConnection, BotRuntime};
+use super::rpc::{check_response, send_ack, Ack, RpcError, DEFAULT_TIMEOUT};
+
+/// 可重试的临时性失败：操作失败、工作线程未就绪、NapCat 等实现的通用发送失败（多为超时或风控）。
+/// 参数错误、不支持的 API 等其他 retcode 重试无意义
+const RETRYABLE_RETCODES: [i64; 3] = [103, 201, 1200];
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+const MAX_RETRIES_LIMIT: u32 = 5;
+
+/// 第 `attempt` 次失败后的重试间隔（指数退避）
+fn retry_delay(attempt: u32) -> Duration {
+    RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt))
+}
+
+/// 发送优先级：管理员触发的回复优先于普通回复，批量公告最后
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Bulk,
+}
+
+impl Priority {
+    const ALL: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Bulk];
+
+    fn lane(self) -> usize {
+        self as usize
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "high" => Some(Self::High),
+            "normal" => Some(Self::Normal),
+            "bulk" | "low" => Some(Self::Bulk),
+            _ => None,
+        }
+    }
+}
+
+task_local! {
+    static SEND_PRIORITY: Priority;
+}
+
+/// 在指定优先级下执行：其中发出的消息按该优先级排队
+pub(super) async fn with_priority<T>(
+    priority: Priority,
+    fut: impl std::future::Future<Output = T>,
+) -> T {
+    SEND_PRIORITY.scope(priority, fut).await
+}
+
+pub(super) fn current_priority() -> Priority {
+    SEND_PRIORITY.try_with(|p| *p).unwrap_or_default()
+}
+
+/// 需要经过队列限速的 API
+pub(super) fn is_queued_action(action: &str) -> bool {
+    matches!(
+        action,
+        "send_group_msg"
+            | "send_private_msg"
+            | "send_msg"
+            | "send_forward_msg"
+            | "send_group_forward_msg"
+            | "send_private_forward_msg"
+            | "upload_group_file"
+            | "upload_private_file"
+    )
+}
+
+struct Settings {
+    account_per_min: f64,
+    account_burst: f64,
+    group_per_min: f64,
+    group_burst: f64,
+    jitter_ms: u64,
+    max_retries: u32,
+    max_queue: usize,
+    max_wait: Duration,
+}
+
+impl Settings {
+    fn from_env() -> Self {
+        let env_u64 = |key: &str, default: u64| {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            account_per_min: env_u64("NBOT_SEND_RATE_PER_MIN", 40) as f64,
+            account_burst: env_u64("NBOT_SEND_BURST", 5).max(1) as f64,
+            group_per_min: env_u64("NBOT_SEND_GROUP_RATE_PER_MIN", 20).max(1) as f64,
+            group_burst: env_u64("NBOT_SEND_GROUP_BURST", 3).max(1) as f64,
+            jitter_ms: env_u64("NBOT_SEND_JITTER_MS", 300),
+            max_retries: env_u64("NBOT_SEND_MAX_RETRIES", 2).min(u64::from(MAX_RETRIES_LIMIT))
+                as u32,
+            max_queue: env_u64("NBOT_SEND_QUEUE_MAX", 500).max(1) as usize,
+            max_wait: Duration::from_secs(env_u64("NBOT_SEND_MAX_WAIT_SECS", 300).max(1)),
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    capacity: f64,
+    per_sec: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: f64, per_min: f64, now: Instant) -> Self {
+        Self {
+            tokens: capacity,
+            capacity,
+            per_sec: per_min / 60.0,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// 距离下一个令牌可用的时间（已有令牌时为 0）
+    fn wait_time(&self) -> Duration {
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.per_sec)
+        }
+    }
+
+    fn take(&mut self) {
+        self.tokens -= 1.0;
+    }
+}
+
+struct OutboundItem {
+    action: String,
+    params: Value,
+    group_id: Option<u64>,
+    priority: Priority,
+    attempt: u32,
+    enqueued_at: Instant,
+    /// 调用方等待的发送结果
+    ack: Option<Ack>,
+}
+
+/// 保序单位：同一优先级下的同一群，或同一私聊用户
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct OrderKey {
+    lane: usize,
+    group_id: Option<u64>,
+    user_id: Option<u64>,
+}
+
+impl OutboundItem {
+    fn order_key(&self) -> OrderKey {
+        OrderKey {
+            lane: self.priority.lane(),
+            group_id: self.group_id,
+            user_id: if self.group_id.is_some() {
+                None
+            } else {
+                self.params.get("user_id").and_then(|v| v.as_u64())
+            },
+        }
+    }
+
+    fn finish(self, result: Result<Value, RpcError>) {
+        send_ack(self.ack, result);
+    }
+}
+
+#[derive(Default, Serialize)]
+struct Counters {
+    enqueued: u64,
+    sent: u64,
+    retried: u64,
+    failed: u64,
+    /// 已发出但未收到回执（可能已送达，不重试）
+    timeouts: u64,
+    /// 队列已满被丢弃
+    dropped: u64,
+    /// 排队超时被丢弃
+    expired: u64,
+}
+
+struct QueueState {
+    lanes: [VecDeque<OutboundItem>; 3],
+    account: TokenBucket,
+    groups: HashMap<u64, TokenBucket>,
+    /// 已发出、等待回执或等待重试的保序单位，其后的消息暂不出队
+    in_flight: HashSet<OrderKey>,
+    counters: Counters,
+}
+
+impl QueueState {
+    fn depth(&self) -> usize {
+        self.lanes.iter().map(|l| l.len()).sum()
+    }
+}
+
+struct BotQueue {
+    state: Mutex<QueueState>,
+    notify: Notify,
+}
+
+enum Next {
+    Item(OutboundItem),
+    Wait(Duration),
+    Idle,
+}
+
+impl BotQueue {
+    fn update(&self, f: impl FnOnce(&mut QueueState)) {
+        if let Ok(mut st) = self.state.lock() {
+            f(&mut st);
+        }
+    }
+
+    /// 在途的消息已有结果（不再重试），放行同一保序单位的后续消息
+    fn release(&self, key: OrderKey) {
+        self.update(|st| {
+            st.in_flight.remove(&key);
+        });
+        self.notify.notify_one();
+    }
+
+    fn push(&self, settings: &Settings, item: OutboundItem, bot_id: &str) {
+        let Ok(mut st) = self.state.lock() else {
+            return;
+        };
+        if item.attempt > 0 {
+            // 重试的消息排回队首，同时放行其保序单位
+            st.in_flight.remove(&item.order_key());
+        }
+        if st.depth() >= settings.max_queue {
+            // 丢弃优先级更低的最新一条；没有更低优先级时丢弃本条
+            let victim = (item.priority.lane() + 1..Priority::ALL.len())
+                .rev()
+                .find(|&lane| !st.lanes[lane].is_empty());
+            match victim.and_then(|lane| st.lanes[lane].pop_back()) {
+                Some(dropped) => {
+                    dropped.finish(Err(RpcError::Rejected("发送队列已满".to_string())));
+                }
+                None => {
+                    st.counters.dropped += 1;
+                    warn!("[{}] 发送队列已满，丢弃 {}", bot_id, item.action);
+                    item.finish(Err(RpcError::Rejected("发送队列已满".to_string())));
+                    return;
+                }
+            }
+            st.counters.dropped += 1;
+            warn!("[{}] 发送队列已满，丢弃一条低优先级消息", bot_id);
+        }
+        if item.attempt == 0 {
+            st.counters.enqueued += 1;
+            st.lanes[item.priority.lane()].push_back(item);
+        } else {
+            st.lanes[item.priority.lane()].push_front(item);
+        }
+        drop(st);
+        self.notify.notify_one();
+    }
+
+    /// 取出下一条可发送的消息：高优先级优先，账号与所在群都有令牌才可发送
+    fn next_ready(&self, settings: &Settings) -> Next {
+        let Ok(mut st) = self.state.lock() else {
+            return Next::Idle;
+        };
+        let now = Instant::now();
+        let QueueState {
+            lanes,
+            account,
+            groups,
+            in_flight,
+            counters,
+        } = &mut *st;
+
+        for lane in lanes.iter_mut() {
+            let mut kept = VecDeque::with_capacity(lane.len());
+            for item in lane.drain(..) {
+                if now.saturating_duration_since(item.enqueued_at) <= settings.max_wait {
+                    kept.push_back(item);
+                } else {
+                    counters.expired += 1;
+                    item.finish(Err(RpcError::Rejected("排队超时".to_string())));
+                }
+            }
+            *lane = kept;
+        }
+        groups.retain(|_, bucket| {
+            bucket.refill(now);
+            bucket.tokens < bucket.capacity
+        });
+        if lanes.iter().all(|l| l.is_empty()) {
+            return Next::Idle;
+        }
+
+        account.refill(now);
+        let account_wait = account.wait_time();
+        if !account_wait.is_zero() {
+            return Next::Wait(account_wait);
+        }
+
+        let mut min_wait: Option<Duration> = None;
+        for lane in lanes.iter_mut() {
+            let mut ready = None;
+            for (idx, item) in lane.iter().enumerate() {
+                if in_flight.contains(&item.order_key()) {
+                    continue;
+                }
+                let Some(gid) = item.group_id else {
+                    ready = Some(idx);
+                    break;
+                };
+                let bucket = groups.entry(gid).or_insert_with(|| {
+                    TokenBucket::new(settings.group_burst, settings.group_per_min, now)
+                });
+                let wait = bucket.wait_time();
+                if wait.is_zero() {
+                    bucket.take();
+                    ready = Some(idx);
+                    break;
+                }
+                min_wait = Some(min_wait.map_or(wait, |w| w.min(wait)));
+            }
+            if let Some(item) = ready.and_then(|idx| lane.remove(idx)) {
+                account.take();
+                return Next::Item(item);
+            }
+        }
+        Next::Wait(min_wait.unwrap_or(Duration::from_secs(1)))
+    }
+}
+
+/// 所有 bot 的出站队列
+pub struct OutboundQueue {
+    settings: Settings,
+    bots: Mutex<HashMap<String, Arc<BotQueue>>>,
+}
+
+impl OutboundQueue {
+    pub fn new() -> Self {
+        Self {
+            settings: Settings::from_env(),
+            bots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(super) fn enabled(&self) -> bool {
+        self.settings.account_per_min > 0.0
+    }
+
+    /// 获取 bot 的队列，首次使用时启动发送任务
+    fn queue_for(runtime: &Arc<BotRuntime>, bot_id: &str) -> Option<Arc<BotQueue>> {
+        let settings = &runtime.outbound.settings;
+        let mut bots = runtime.outbound.bots.lock().ok()?;
+        if let Some(queue) = bots.get(bot_id) {
+            return Some(queue.clone());
+        }
+        let queue = Arc::new(BotQueue {
+            state: Mutex::new(QueueState {
+                lanes: Default::default(),
+                account: TokenBucket::new(
+                    settings.account_burst,
+                    settings.account_per_min,
+                    Instant::now(),
+                ),
+                groups: HashMap::new(),
+                in_flight: HashSet::new(),
+                counters: Counters::default(),
+            }),
+            notify: Notify::new(),
+        });
+        bots.insert(bot_id.to_string(), queue.clone());
+        tokio::spawn(run_worker(
+            runtime.clone(),
+            bot_id.to_string(),
+            queue.clone(),
+        ));
+        Some(queue)
+    }
+
+    /// 按当前上下文的优先级入队；`ack` 在最终发送成功或失败后收到结果
+    pub(super) fn enqueue(
+        runtime: &Arc<BotRuntime>,
+        bot_id: &str,
+        action: &str,
+        params: Value,
+        group_id: Option<u64>,
+        ack: Option<Ack>,
+    ) {
+        let Some(queue) = Self::queue_for(runtime, bot_id) else {
+            send_ack(ack, Err(RpcError::Rejected("发送队列不可用".to_string())));
+            return;
+        };
+        let item = OutboundItem {
+            action: action.to_string(),
+            params,
+            group_id,
+            priority: current_priority(),
+            attempt: 0,
+            enqueued_at: Instant::now(),
+            ack,
+        };
+        queue.push(&runtime.outbound.settings, item, bot_id);
+    }
+
+    /// 各 bot 的队列深度与发送统计
+    pub fn stats(&self) -> Value {
+        let bots: Vec<(String, Arc<BotQueue>)> = match self.bots.lock() {
+            Ok(bots) => bots.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            Err(_) => Vec::new(),
+        };
+        let mut out = serde_json::Map::new();
+        for (bot_id, queue) in bots {
+            let Ok(st) = queue.state.lock() else {
+                continue;
+            };
+            let mut depth = serde_json::Map::new();
+            for p in Priority::ALL {
+                depth.insert(
+                    serde_json::to_value(p)
+                        .ok()
+                        .and_then(|v| v.as_str().map(|s| s.to_string()))
+                        .unwrap_or_default(),
+                    json!(st.lanes[p.lane()].len()),
+                );
+            }
+            out.insert(
+                bot_id,
+                json!({
+                    "depth": st.depth(),
+                    "depth_by_priority": depth,
+                    "counters": st.counters,
+                }),
+            );
+        }
+        json!({
+            "enabled": self.enabled(),
+            "settings": {
+                "rate_per_min": self.settings.account_per_min,
+                "burst": self.settings.account_burst,
+                "group_rate_per_min": self.settings.group_per_min,
+                "group_burst": self.settings.group_burst,
+                "jitter_ms": self.settings.jitter_ms,
+                "max_retries": self.settings.max_retries,
+                "queue_max": self.settings.max_queue,
+                "max_wait_secs": self.settings.max_wait.as_secs(),
+            },
+            "bots": out,
+        })
+    }
+}
+
+async fn run_worker(runtime: Arc<BotRuntime>, bot_id: String, queue: Arc<BotQueue>) {
+    loop {
+        match queue.next_ready(&runtime.outbound.settings) {
+            Next::Item(item) => {
+                let jitter = runtime.outbound.settings.jitter_ms;
+                if jitter > 0 {
+                    let ms = rand::rng().random_range(0..=jitter);
+                    tokio::time::sleep(Duration::from_millis(ms)).await;
+                }
+                dispatch(&runtime, &bot_id, &queue, item).await;
+            }
+            Next::Wait(wait) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = queue.notify.notified() => {}
+                }
+            }
+            Next::Idle => queue.notify.notified().await,
+        }
+    }
+}
+
+/// 发出一条消息。OneBot 在后台等待回执，失败时按 retcode 决定是否重新入队
+async fn dispatch(
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    queue: &Arc<BotQueue>,
+    item: OutboundItem,
+) {
+    let conn = runtime.connections.read().await.get(bot_id).cloned();
+    match conn {
+        None => {
+            warn!("[{}] 无法发送API调用，连接不存在", bot_id);
+            queue.update(|st| st.counters.failed += 1);
+            item.finish(Err(RpcError::NotConnected));
+        }
+        Some(BotConnection::Discord(conn)) => {
+            // Discord 请求自带限流重试
+            match discord_send_api(runtime, bot_id, &conn, &item.action, &item.params).await {
+                Ok(()) => {
+                    info!("[{}] Discord API: {}", bot_id, item.action);
+                    queue.update(|st| st.counters.sent += 1);
+                    item.finish(Ok(json!({ "status": "ok", "retcode": 0 })));
+                }
+                Err(e) => {
+                    warn!("[{}] Discord API {} 失败: {}", bot_id, item.action, e);
+                    queue.update(|st| st.counters.failed += 1);
+                    item.finish(Err(RpcError::Failed {
+                        retcode: None,
+                        message: e,
+                    }));
+                }
+            }
+        }
+        Some(BotConnection::OneBot { .. }) => {
+            let call = match runtime
+                .send_onebot_request(bot_id, &item.action, item.params.clone())
+                .await
+            {
+                Ok(call) => call,
+                Err(e) => {
+                    warn!("[{}] 发送API调用失败: {}: {}", bot_id, item.action, e);
+                    queue.update(|st| st.counters.failed += 1);
+                    item.finish(Err(e));
+                    return;
+                }
+            };
+            info!("[{}] 发送API: {}", bot_id, item.action);
+
+            // 回执返回前，同一群 / 私聊的后续消息不出队，重试时仍能保持顺序
+            let key = item.order_key();
+            queue.update(|st| {
+                st.in_flight.insert(key);
+            });
+            let runtime = runtime.clone();
+            let bot_id = bot_id.to_string();
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                let result = call.wait(DEFAULT_TIMEOUT).await.and_then(check_response);
+                let retcode = match &result {
+                    Ok(_) => {
+                        queue.update(|st| st.counters.sent += 1);
+                        queue.release(key);
+                        item.finish(result);
+                        return;
+                    }
+                    Err(RpcError::Timeout) => {
+                        // 可能已送达，不重试以免重复
+                        warn!("[{}] {} 未收到回执", bot_id, item.action);
+                        queue.update(|st| st.counters.timeouts += 1);
+                        queue.release(key);
+                        item.finish(result);
+                        return;
+                    }
+                    Err(RpcError::Failed { retcode, .. }) => *retcode,
+                    Err(e) => {
+                        warn!("[{}] {} 发送失败: {}", bot_id, item.action, e);
+                        queue.update(|st| st.counters.failed += 1);
+                        queue.release(key);
+                        item.finish(result);
+                        return;
+                    }
+                };
+
+                let retryable = retcode.is_some_and(|c| RETRYABLE_RETCODES.contains(&c));
+                let settings = &runtime.outbound.settings;
+                let reason = result
+                    .as_ref()
+                    .err()
+                    .map(|e| e.to_string())
+                    .unwrap_or_default();
+                if !retryable || item.attempt >= settings.max_retries {
+                    warn!("[{}] {} 发送失败: {}", bot_id, item.action, reason);
+                    queue.update(|st| st.counters.failed += 1);
+                    queue.release(key);
+                    item.finish(result);
+                    return;
+                }
+
+                let delay = retry_delay(item.attempt);
+                warn!(
+                    "[{}] {} 发送失败: {}，{} 秒后重试",
+                    bot_id,
+                    item.action,
+                    reason,
+                    delay.as_secs()
+                );
+                queue.update(|st| st.counters.retried += 1);
+                tokio::time::sleep(delay).await;
+                let item = OutboundItem {
+                    attempt: item.attempt + 1,
+                    enqueued_at: Instant::now(),
+                    ..item
+                };
+                queue.push(settings, item, &bot_id);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket_limits_burst_and_refills() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2.0, 60.0, start);
+        bucket.take();
+        bucket.take();
+        assert!(!bucket.wait_time().is_zero());
+        bucket.refill(start + Duration::from_millis(1500));
+        assert!(bucket.wait_time().is_zero());
+        bucket.refill(start + Duration::from_secs(60));
+        assert_eq!(bucket.tokens, 2.0);
+    }
+
+    fn test_settings() -> Settings {
+        Settings {
+            account_per_min: 600.0,
+            account_burst: 10.0,
+            group_per_min: 600.0,
+            group_burst: 10.0,
+            jitter_ms: 0,
+            max_retries: 2,
+            max_queue: 10,
+            max_wait: Duration::from_secs(60),
+        }
+    }
+
+    fn test_queue(settings: &Settings) -> BotQueue {
+        BotQueue {
+            state: Mutex::new(QueueState {
+                lanes: Default::default(),
+                account: TokenBucket::new(
+                    settings.account_burst,
+                    settings.account_per_min,
+                    Instant::now(),
+                ),
+                groups: HashMap::new(),
+                in_flight: HashSet::new(),
+                counters: Counters::default(),
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    fn group_item(group_id: u64, seq: u64) -> OutboundItem {
+        OutboundItem {
+            action: "send_group_msg".to_string(),
+            params: json!({ "group_id": group_id, "message": seq }),
+            group_id: Some(group_id),
+            priority: Priority::Normal,
+            attempt: 0,
+            enqueued_at: Instant::now(),
+            ack: None,
+        }
+    }
+
+    fn next_seq(queue: &BotQueue, settings: &Settings) -> Option<OutboundItem> {
+        match queue.next_ready(settings) {
+            Next::Item(item) => Some(item),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn retry_keeps_group_order() {
+        let settings = test_settings();
+        let queue = test_queue(&settings);
+        queue.push(&settings, group_item(1, 1), "bot");
+        queue.push(&settings, group_item(1, 2), "bot");
+        queue.push(&settings, group_item(2, 3), "bot");
+
+        let first = next_seq(&queue, &settings).unwrap();
+        assert_eq!(first.params["message"], 1);
+        queue.update(|st| {
+            st.in_flight.insert(first.order_key());
+        });
+
+        // 群 1 的第一条在途时，第二条不出队，其他群不受影响
+        let other = next_seq(&queue, &settings).unwrap();
+        assert_eq!(other.params["message"], 3);
+        assert!(next_seq(&queue, &settings).is_none());
+
+        // 重试排回队首，先于后入队的消息发出
+        queue.push(&settings, group_item(1, 4), "bot");
+        let retry = OutboundItem {
+            attempt: 1,
+            ..first
+        };
+        queue.push(&settings, retry, "bot");
+        let order: Vec<_> = std::iter::from_fn(|| next_seq(&queue, &settings))
+            .map(|item| item.params["message"].as_u64().unwrap())
+            .collect();
+        assert_eq!(order, vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn release_lets_next_message_out() {
+        let settings = test_settings();
+        let queue = test_queue(&settings);
+        queue.push(&settings, group_item(1, 1), "bot");
+        queue.push(&settings, group_item(1, 2), "bot");
+        let first = next_seq(&queue, &settings).unwrap();
+        queue.update(|st| {
+            st.in_flight.insert(first.order_key());
+        });
+        assert!(next_seq(&queue, &settings).is_none());
+        queue.release(first.order_key());
+        assert_eq!(next_seq(&queue, &settings).unwrap().params["message"], 2);
+    }
+
+    #[test]
+    fn retry_delay_does_not_overflow() {
+        assert_eq!(retry_delay(0), Duration::from_secs(2));
+        assert_eq!(retry_delay(2), Duration::from_secs(8));
+        assert!(retry_delay(u32::MAX) >= retry_delay(MAX_RETRIES_LIMIT));
+    }
+}
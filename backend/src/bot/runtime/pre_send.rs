@@ -0,0 +1,109 @@
+//! 发送前钩子：`send_reply` 与发送消息类 API（`send_group_msg` / `send_private_msg` / `send_msg`，
+//! 以及合并转发 `send_forward_msg` / `send_group_forward_msg` / `send_private_forward_msg` 的每个节点）
+//! 发出前调用插件的 `preSend(ctx)`，插件可返回改写后的消息（消息段数组或 CQ 码字符串），
+//! 或返回 `false` 拦截发送（如追加签名、翻译、屏蔽敏感词）。加载插件时记录其是否导出了 `preSend`，
+//! 只有导出的插件会被调用。
+//!
+//! 防循环：
+//! - 由插件发出的消息不会交给该插件自己的 preSend；
+//! - 同一条消息每个插件只处理一次，改写结果依次传给后续插件；
+//! - preSend 中调用的发送等接口不会执行；
+//! - `send_reply` 经过钩子后拆分出的多条消息不再重复调用。
+
+use serde_json::{json, Value};
+use tokio::task_local;
+use tracing::info;
+
+use super::connection::BotRuntime;
+
+task_local! {
+    /// 发出消息的插件
+    static SEND_SOURCE: String;
+    /// 已经过 preSend 的发送
+    static HOOKED: ();
+}
+
+/// 在插件上下文中执行 `fut`：其中发出的消息记为该插件发出
+pub(super) async fn with_send_source<T>(
+    plugin_id: &str,
+    fut: impl std::future::Future<Output = T>,
+) -> T {
+    SEND_SOURCE.scope(plugin_id.to_string(), fut).await
+}
+
+/// 执行已经过 preSend 的发送，其中的 API 调用不再触发钩子
+pub(super) async fn skip_hooks<T>(fut: impl std::future::Future<Output = T>) -> T {
+    HOOKED.scope((), fut).await
+}
+
+/// 当前发送是否需要调用 preSend：已经过钩子时返回 None，否则返回发出消息的插件（框架发出时为 None）
+fn pending_hook() -> Option<Option<String>> {
+    if HOOKED.try_with(|_| ()).is_ok() {
+        return None;
+    }
+    Some(SEND_SOURCE.try_with(|s| s.clone()).ok())
+}
+
+/// 消息发出前调用插件 preSend；返回改写后（或原样）的消息，被拦截时返回 None
+pub(super) async fn run(
+    runtime: &BotRuntime,
+    bot_id: &str,
+    action: &str,
+    user_id: Option<u64>,
+    group_id: Option<u64>,
+    message: Value,
+) -> Option<Value> {
+    let Some(source) = pending_hook() else {
+        return Some(message);
+    };
+    let Some(manager) = runtime.plugin_manager.get() else {
+        return Some(message);
+    };
+    if !manager.has_pre_send_hook(source.as_deref()) {
+        return Some(message);
+    }
+    let ctx = json!({
+        "bot_id": bot_id,
+        "action": action,
+        "message_type": if group_id.is_some() { "group" } else { "private" },
+        "user_id": user_id,
+        "group_id": group_id,
+        "segments": crate::segment::to_onebot(&crate::segment::parse_message(&message)),
+        "source_plugin_id": source,
+    });
+    let result = manager.pre_send(ctx, source.as_deref()).await;
+    if let Some(plugin_id) = result.blocked_by {
+        info!(
+            "[{}] 插件 {} 在 preSend 中拦截了 {}",
+            bot_id, plugin_id, action
+        );
+        return None;
+    }
+    Some(result.message.unwrap_or(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn records_source_plugin() {
+        assert_eq!(pending_hook(), Some(None));
+        let inner = with_send_source("echo", async { pending_hook() }).await;
+        assert_eq!(inner, Some(Some("echo".to_string())));
+        // 嵌套时以最内层插件为准
+        let nested = with_send_source("a", with_send_source("b", async { pending_hook() })).await;
+        assert_eq!(nested, Some(Some("b".to_string())));
+    }
+
+    #[tokio::test]
+    async fn skip_hooks_covers_whole_scope() {
+        assert_eq!(skip_hooks(async { pending_hook() }).await, None);
+        let inside_source = skip_hooks(with_send_source("echo", async { pending_hook() })).await;
+        assert_eq!(inside_source, None);
+        let around_source = with_send_source("echo", skip_hooks(async { pending_hook() })).await;
+        assert_eq!(around_source, None);
+        // 作用域结束后恢复
+        assert_eq!(pending_hook(), Some(None));
+    }
+}
@@ -1,14 +1,24 @@
 use std::collections::HashSet;
 use tokio::task_local;
 
+use crate::models::SharedState;
+
+pub mod policy;
+
+pub(super) use policy::{Action, Scope};
+
 task_local! {
     static SENSITIVE_IDS: HashSet<String>;
 }
 
+/// 进入事件上下文：记录本次事件涉及的成员 QQ 号，并刷新该 bot 的隐私策略缓存
 pub(super) async fn with_sensitive_ids<T>(
+    state: &SharedState,
+    bot_id: &str,
     ids: HashSet<String>,
     fut: impl std::future::Future<Output = T>,
 ) -> T {
+    policy::policy_for_bot(state, bot_id);
     SENSITIVE_IDS.scope(ids, fut).await
 }
 
@@ -18,3 +28,7 @@ pub(super) fn get_sensitive_ids() -> Vec<String> {
         .unwrap_or_default()
 }
 
+/// 按 bot 的隐私策略对文本脱敏
+pub(super) fn redact_text(bot_id: &str, scope: Scope, text: &str) -> String {
+    policy::cached_policy(bot_id).redact(text, scope, &get_sensitive_ids(), None)
+}
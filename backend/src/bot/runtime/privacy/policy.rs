@@ -0,0 +1,568 @@
+//! 隐私脱敏策略：按 bot 配置的检测器（QQ 号、手机号、邮箱、身份证号、自定义正则），
+//! 在不同位置（发出的消息、LLM 提示词、日志）对匹配内容执行 mask / hash / drop / allow。
+//!
+//! 配置位于 `privacy` 模块的 `detectors`（可按 bot 覆盖），以检测器名称为键：
+//! 与内置检测器同名时覆盖其设置，其余名称为自定义正则检测器。
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use crate::models::SharedState;
+
+/// 脱敏生效的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// 发出的消息（含渲染为图片的 LLM 结果）
+    Outgoing,
+    /// 发送给 LLM 的提示词与文档
+    Llm,
+    /// 日志与 LLM 调用追踪
+    Logs,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::Outgoing, Scope::Llm, Scope::Logs];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// 替换为掩码（QQ 号在发出的消息中替换为群名片/昵称）
+    Mask,
+    /// 替换为稳定的短哈希，便于在脱敏后区分不同的值
+    Hash,
+    /// 直接删除
+    Drop,
+    /// 保留原文（匹配到的内容不会再被其他检测器处理，可作白名单）
+    Allow,
+}
+
+#[derive(Debug, Clone)]
+enum DetectorKind {
+    QqId,
+    Phone,
+    Email,
+    IdCard,
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+struct Detector {
+    name: String,
+    kind: DetectorKind,
+    action: Action,
+    scopes: Vec<Scope>,
+    /// mask 时使用的替换文本（为空则按检测器类型生成）
+    replacement: Option<String>,
+}
+
+/// 一处匹配：`start..end` 为整体替换范围，`value_start..value_end` 为敏感值本身
+/// （如 `@123456` 中的数字部分，hash 时只替换这一段）
+#[derive(Debug, Clone)]
+struct Span {
+    start: usize,
+    end: usize,
+    value_start: usize,
+    value_end: usize,
+    mask: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RedactionMatch {
+    pub detector: String,
+    pub action: Action,
+    pub start: usize,
+    pub end: usize,
+    pub original: String,
+    pub replacement: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PrivacyPolicy {
+    detectors: Vec<Detector>,
+}
+
+static EMAIL_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").expect("email regex")
+});
+static PHONE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?:\+?86[- ]?)?1[3-9]\d{9}").expect("phone regex"));
+static ID_CARD_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d{17}[\dXx]").expect("id card regex"));
+
+fn builtin_detectors() -> Vec<Detector> {
+    let all = Scope::ALL.to_vec();
+    vec![
+        Detector {
+            name: "qq_id".to_string(),
+            kind: DetectorKind::QqId,
+            action: Action::Mask,
+            scopes: all,
+            replacement: None,
+        },
+        Detector {
+            name: "phone".to_string(),
+            kind: DetectorKind::Phone,
+            action: Action::Mask,
+            scopes: vec![Scope::Logs],
+            replacement: None,
+        },
+        Detector {
+            name: "email".to_string(),
+            kind: DetectorKind::Email,
+            action: Action::Mask,
+            scopes: vec![Scope::Logs],
+            replacement: None,
+        },
+        Detector {
+            name: "id_card".to_string(),
+            kind: DetectorKind::IdCard,
+            action: Action::Mask,
+            scopes: vec![Scope::Logs],
+            replacement: None,
+        },
+    ]
+}
+
+fn parse_kind(name: &str, v: &Value) -> Result<Option<DetectorKind>, String> {
+    let kind = v.get("kind").and_then(|k| k.as_str()).map(str::trim);
+    let pattern = v.get("pattern").and_then(|p| p.as_str());
+    match (kind, pattern) {
+        (Some("qq_id"), _) => Ok(Some(DetectorKind::QqId)),
+        (Some("phone"), _) => Ok(Some(DetectorKind::Phone)),
+        (Some("email"), _) => Ok(Some(DetectorKind::Email)),
+        (Some("id_card"), _) => Ok(Some(DetectorKind::IdCard)),
+        (Some("regex") | None, Some(pattern)) => {
+            if pattern.len() > 2000 {
+                return Err(format!("检测器 {name} 的正则过长"));
+            }
+            let re = regex::RegexBuilder::new(pattern)
+                .size_limit(1 << 20)
+                .build()
+                .map_err(|e| format!("检测器 {name} 的正则无效: {e}"))?;
+            Ok(Some(DetectorKind::Regex(re)))
+        }
+        (Some("regex"), None) => Err(format!("检测器 {name} 缺少 pattern")),
+        (Some(other), _) => Err(format!("检测器 {name} 的类型未知: {other}")),
+        (None, None) => Ok(None),
+    }
+}
+
+fn parse_action(name: &str, v: &Value) -> Result<Option<Action>, String> {
+    match v.get("action") {
+        None | Some(Value::Null) => Ok(None),
+        Some(a) => serde_json::from_value(a.clone()).map(Some).map_err(|_| {
+            format!("检测器 {name} 的 action 无效（可选 mask / hash / drop / allow）")
+        }),
+    }
+}
+
+fn parse_scopes(name: &str, v: &Value) -> Result<Option<Vec<Scope>>, String> {
+    match v.get("scopes") {
+        None | Some(Value::Null) => Ok(None),
+        Some(s) => serde_json::from_value(s.clone())
+            .map(Some)
+            .map_err(|_| format!("检测器 {name} 的 scopes 无效（可选 outgoing / llm / logs）")),
+    }
+}
+
+impl Default for PrivacyPolicy {
+    fn default() -> Self {
+        Self {
+            detectors: builtin_detectors(),
+        }
+    }
+}
+
+impl PrivacyPolicy {
+    /// 解析 `privacy` 模块配置；`detectors` 中与内置检测器同名的条目覆盖其设置
+    pub fn from_config(config: &Value) -> Result<Self, String> {
+        let mut detectors = builtin_detectors();
+        let Some(entries) = config.get("detectors").and_then(|d| d.as_object()) else {
+            return Ok(Self { detectors });
+        };
+
+        for (name, v) in entries {
+            let name = name.trim();
+            if name.is_empty() {
+                continue;
+            }
+            let kind = parse_kind(name, v)?;
+            let action = parse_action(name, v)?;
+            let scopes = parse_scopes(name, v)?;
+            let replacement = v
+                .get("replacement")
+                .and_then(|r| r.as_str())
+                .map(|s| s.to_string());
+
+            if let Some(existing) = detectors.iter_mut().find(|d| d.name == name) {
+                if let Some(kind) = kind {
+                    existing.kind = kind;
+                }
+                if let Some(action) = action {
+                    existing.action = action;
+                }
+                if let Some(scopes) = scopes {
+                    existing.scopes = scopes;
+                }
+                if replacement.is_some() {
+                    existing.replacement = replacement;
+                }
+                continue;
+            }
+
+            let kind = kind.ok_or_else(|| format!("检测器 {name} 缺少 kind 或 pattern"))?;
+            detectors.push(Detector {
+                name: name.to_string(),
+                kind,
+                action: action.unwrap_or(Action::Mask),
+                scopes: scopes.unwrap_or_else(|| Scope::ALL.to_vec()),
+                replacement,
+            });
+        }
+        Ok(Self { detectors })
+    }
+
+    /// 指定检测器在该位置的动作（未启用时为 allow）
+    pub fn action_for(&self, detector: &str, scope: Scope) -> Action {
+        self.detectors
+            .iter()
+            .find(|d| d.name == detector && d.scopes.contains(&scope))
+            .map(|d| d.action)
+            .unwrap_or(Action::Allow)
+    }
+
+    /// 查找该位置下的所有匹配（互不重叠；起点靠前者优先，起点相同时取更长的）
+    pub fn find_matches(
+        &self,
+        text: &str,
+        scope: Scope,
+        sensitive_ids: &[String],
+        skip: Option<&str>,
+    ) -> Vec<RedactionMatch> {
+        let mut candidates: Vec<(usize, Span)> = Vec::new();
+        for (idx, d) in self.detectors.iter().enumerate() {
+            if !d.scopes.contains(&scope) || skip == Some(d.name.as_str()) {
+                continue;
+            }
+            for span in detect(&d.kind, text, sensitive_ids) {
+                candidates.push((idx, span));
+            }
+        }
+        candidates.sort_by(|a, b| {
+            a.1.start
+                .cmp(&b.1.start)
+                .then((b.1.end - b.1.start).cmp(&(a.1.end - a.1.start)))
+                .then(a.0.cmp(&b.0))
+        });
+
+        let mut out: Vec<RedactionMatch> = Vec::new();
+        let mut last_end = 0usize;
+        for (idx, span) in candidates {
+            if span.start < last_end {
+                continue;
+            }
+            last_end = span.end;
+            let d = &self.detectors[idx];
+            let replacement = match d.action {
+                Action::Allow => text[span.start..span.end].to_string(),
+                Action::Drop => String::new(),
+                Action::Mask => d.replacement.clone().unwrap_or(span.mask.clone()),
+                Action::Hash => format!(
+                    "{}#{}{}",
+                    &text[span.start..span.value_start],
+                    short_hash(&text[span.value_start..span.value_end]),
+                    &text[span.value_end..span.end]
+                ),
+            };
+            out.push(RedactionMatch {
+                detector: d.name.clone(),
+                action: d.action,
+                start: span.start,
+                end: span.end,
+                original: text[span.start..span.end].to_string(),
+                replacement,
+            });
+        }
+        out
+    }
+
+    /// 按该位置的规则脱敏；`skip` 指定的检测器不参与（由调用方单独处理）
+    pub fn redact(
+        &self,
+        text: &str,
+        scope: Scope,
+        sensitive_ids: &[String],
+        skip: Option<&str>,
+    ) -> String {
+        let matches = self.find_matches(text, scope, sensitive_ids, skip);
+        if matches.is_empty() {
+            return text.to_string();
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0usize;
+        for m in matches {
+            out.push_str(&text[last..m.start]);
+            out.push_str(&m.replacement);
+            last = m.end;
+        }
+        out.push_str(&text[last..]);
+        out
+    }
+}
+
+fn short_hash(value: &str) -> String {
+    let digest = Sha256::digest(value.as_bytes());
+    digest.iter().take(4).map(|b| format!("{b:02x}")).collect()
+}
+
+fn is_digit_at(text: &str, idx: usize) -> bool {
+    text.as_bytes().get(idx).is_some_and(|b| b.is_ascii_digit())
+}
+
+/// 前后不紧邻其他数字
+fn digit_bounded(text: &str, start: usize, end: usize) -> bool {
+    (start == 0 || !is_digit_at(text, start - 1)) && !is_digit_at(text, end)
+}
+
+fn whole_span(start: usize, end: usize, mask: String) -> Span {
+    Span {
+        start,
+        end,
+        value_start: start,
+        value_end: end,
+        mask,
+    }
+}
+
+fn mask_middle(value: &str, keep_head: usize, keep_tail: usize) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= keep_head + keep_tail {
+        return "*".repeat(chars.len());
+    }
+    let mut out: String = chars[..keep_head].iter().collect();
+    out.push_str(&"*".repeat(chars.len() - keep_head - keep_tail));
+    out.extend(&chars[chars.len() - keep_tail..]);
+    out
+}
+
+/// 身份证号校验位（GB 11643）
+fn id_card_checksum_ok(value: &str) -> bool {
+    const WEIGHTS: [u32; 17] = [7, 9, 10, 5, 8, 4, 2, 1, 6, 3, 7, 9, 10, 5, 8, 4, 2];
+    const CHECK: [u8; 11] = *b"10X98765432";
+    let bytes = value.as_bytes();
+    if bytes.len() != 18 {
+        return false;
+    }
+    let sum: u32 = bytes[..17]
+        .iter()
+        .zip(WEIGHTS)
+        .map(|(b, w)| (b - b'0') as u32 * w)
+        .sum();
+    CHECK[(sum % 11) as usize] == bytes[17].to_ascii_uppercase()
+}
+
+fn detect(kind: &DetectorKind, text: &str, sensitive_ids: &[String]) -> Vec<Span> {
+    match kind {
+        DetectorKind::QqId => detect_qq_ids(text, sensitive_ids),
+        DetectorKind::Phone => PHONE_RE
+            .find_iter(text)
+            .filter(|m| digit_bounded(text, m.start(), m.end()))
+            .map(|m| whole_span(m.start(), m.end(), mask_middle(m.as_str(), 3, 4)))
+            .collect(),
+        DetectorKind::Email => EMAIL_RE
+            .find_iter(text)
+            .map(|m| {
+                let value = m.as_str();
+                let (local, domain) = value.split_once('@').unwrap_or((value, ""));
+                whole_span(
+                    m.start(),
+                    m.end(),
+                    format!("{}@{}", mask_middle(local, 1, 0), domain),
+                )
+            })
+            .collect(),
+        DetectorKind::IdCard => ID_CARD_RE
+            .find_iter(text)
+            .filter(|m| digit_bounded(text, m.start(), m.end()) && id_card_checksum_ok(m.as_str()))
+            .map(|m| whole_span(m.start(), m.end(), mask_middle(m.as_str(), 6, 4)))
+            .collect(),
+        DetectorKind::Regex(re) => re
+            .find_iter(text)
+            .filter(|m| !m.as_str().is_empty())
+            .map(|m| whole_span(m.start(), m.end(), "***".to_string()))
+            .collect(),
+    }
+}
+
+/// QQ 号：`@123456`、`(123456)`、`qq=123456`、`uin=123456`，以及当前事件中出现的成员 QQ 号
+fn detect_qq_ids(input: &str, sensitive_ids: &[String]) -> Vec<Span> {
+    fn digit_span(bytes: &[u8], start: usize, max_len: usize) -> usize {
+        let mut i = start;
+        while i < bytes.len() && bytes[i].is_ascii_digit() && (i - start) < max_len {
+            i += 1;
+        }
+        i
+    }
+
+    let bytes = input.as_bytes();
+    let mut out: Vec<Span> = Vec::new();
+    let mut i = 0usize;
+
+    while i < bytes.len() {
+        // @123456
+        if bytes[i] == b'@' {
+            let j = digit_span(bytes, i + 1, 12);
+            let len = j.saturating_sub(i + 1);
+            if (5..=12).contains(&len) {
+                out.push(Span {
+                    start: i,
+                    end: j,
+                    value_start: i + 1,
+                    value_end: j,
+                    mask: "@用户".to_string(),
+                });
+                i = j;
+                continue;
+            }
+        }
+
+        // (123456789)
+        if bytes[i] == b'(' {
+            let j = digit_span(bytes, i + 1, 12);
+            let len = j.saturating_sub(i + 1);
+            if (5..=12).contains(&len) && j < bytes.len() && bytes[j] == b')' {
+                out.push(Span {
+                    start: i,
+                    end: j + 1,
+                    value_start: i + 1,
+                    value_end: j,
+                    mask: "(已隐藏)".to_string(),
+                });
+                i = j + 1;
+                continue;
+            }
+        }
+
+        // qq=123456 or uin=123456 (case-insensitive)
+        let prefix_len = if bytes[i..].len() >= 3 && bytes[i..i + 3].eq_ignore_ascii_case(b"qq=") {
+            3
+        } else if bytes[i..].len() >= 4 && bytes[i..i + 4].eq_ignore_ascii_case(b"uin=") {
+            4
+        } else {
+            0
+        };
+        if prefix_len > 0 {
+            let j = digit_span(bytes, i + prefix_len, 12);
+            let len = j.saturating_sub(i + prefix_len);
+            if (5..=12).contains(&len) {
+                out.push(whole_span(i + prefix_len, j, "已隐藏".to_string()));
+                i = j;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    for id in sensitive_ids {
+        if id.len() < 5 || !id.bytes().all(|b| b.is_ascii_digit()) {
+            continue;
+        }
+        for (start, _) in input.match_indices(id.as_str()) {
+            let end = start + id.len();
+            if digit_bounded(input, start, end) {
+                out.push(whole_span(start, end, "***".to_string()));
+            }
+        }
+    }
+
+    out
+}
+
+/// 已编译的各 bot 策略（按配置哈希判断是否需要重新编译）
+static POLICY_CACHE: Lazy<DashMap<String, (u64, Arc<PrivacyPolicy>)>> = Lazy::new(DashMap::new);
+static DEFAULT_POLICY: Lazy<Arc<PrivacyPolicy>> = Lazy::new(|| Arc::new(PrivacyPolicy::default()));
+
+/// bot 的有效策略；`privacy` 模块未启用或配置无效时使用内置默认规则
+pub fn policy_for_bot(state: &SharedState, bot_id: &str) -> Arc<PrivacyPolicy> {
+    let config = crate::module::get_effective_module(state, bot_id, "privacy")
+        .filter(|m| m.enabled)
+        .map(|m| m.config)
+        .unwrap_or(Value::Null);
+    let hash = {
+        let mut hasher = DefaultHasher::new();
+        config.to_string().hash(&mut hasher);
+        hasher.finish()
+    };
+    if let Some(entry) = POLICY_CACHE.get(bot_id) {
+        if entry.0 == hash {
+            return entry.1.clone();
+        }
+    }
+
+    let policy = match PrivacyPolicy::from_config(&config) {
+        Ok(p) => Arc::new(p),
+        Err(e) => {
+            tracing::warn!("[{}] 隐私策略配置无效，使用默认规则: {}", bot_id, e);
+            DEFAULT_POLICY.clone()
+        }
+    };
+    POLICY_CACHE.insert(bot_id.to_string(), (hash, policy.clone()));
+    policy
+}
+
+/// 最近一次为该 bot 编译的策略（拿不到 `SharedState` 的发送路径使用），尚未编译时按当前配置编译
+pub fn cached_policy(bot_id: &str) -> Arc<PrivacyPolicy> {
+    if let Some(entry) = POLICY_CACHE.get(bot_id) {
+        return entry.1.clone();
+    }
+    match super::super::app_state() {
+        Some(state) => policy_for_bot(&state, bot_id),
+        None => DEFAULT_POLICY.clone(),
+    }
+}
+
+/// `privacy` 模块配置变更后清空已编译的策略
+pub(in super::super) fn invalidate() {
+    POLICY_CACHE.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn default_policy_keeps_legacy_qq_masks() {
+        let policy = PrivacyPolicy::default();
+        let out = policy.redact("@123456 说 (987654321) qq=55555 ok", Scope::Llm, &[], None);
+        assert_eq!(out, "@用户 说 (已隐藏) qq=已隐藏 ok");
+    }
+
+    #[test]
+    fn configured_detectors_apply_per_scope() {
+        let policy = PrivacyPolicy::from_config(&json!({
+            "detectors": {
+                "phone": { "action": "hash", "scopes": ["llm"] },
+                "ticket": { "pattern": "T-\\d{4}", "action": "drop", "scopes": ["outgoing"] }
+            }
+        }))
+        .unwrap();
+
+        let text = "电话13812345678 单号T-1234";
+        let llm = policy.redact(text, Scope::Llm, &[], None);
+        assert!(llm.starts_with("电话#") && llm.ends_with("单号T-1234"));
+        assert_eq!(
+            policy.redact(text, Scope::Outgoing, &[], None),
+            "电话13812345678 单号"
+        );
+        assert_eq!(policy.action_for("phone", Scope::Logs), Action::Allow);
+    }
+}
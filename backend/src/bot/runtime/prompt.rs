@@ -0,0 +1,253 @@
+//! 会话式提问：插件调用 `ctx.prompt(content, options)` 后，等待该用户在同一会话（同一群 / 私聊）
+//! 中的下一条消息，多步交互（入群验证、追问"你指的是哪个文件"）不必在插件 storage 里自己写状态机。
+//!
+//! - 会话由这里统一维护，按 (bot, 群 / 私聊, 用户) 区分；同一用户在同一会话中只保留最近一次提问，
+//!   先前的提问以 `replaced` 结束；
+//! - 被捕获的消息直接交给发起提问的插件，不再经过 preMessage 与指令解析；
+//! - 消息与取消关键词（默认 `取消`）一致时以 `cancelled` 结束，`timeoutMs`（默认 60 秒，
+//!   最长 30 分钟）内无回复则以 `timeout` 结束；
+//! - 会话记录发起时插件的加载编号，插件被卸载、禁用或重载（JS 中等待的 Promise 随旧运行时丢失）后，
+//!   其会话作废，用户的消息按普通消息处理。
+//!
+//! 结果通过 `__nbotDispatchPromptReply` 交给 runtime.js，取消 / 过期时 `ctx.prompt` 返回 null。
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::models::SharedState;
+use crate::plugin::PluginOutputWithSource;
+use crate::segment::{self, MessageSegment};
+
+use super::command_exec::process_plugin_outputs_with_source;
+use super::connection::BotRuntime;
+
+const MIN_TIMEOUT_MS: u64 = 1_000;
+const MAX_TIMEOUT_MS: u64 = 30 * 60 * 1_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SessionKey {
+    bot_id: String,
+    group_id: Option<u64>,
+    user_id: u64,
+}
+
+struct Session {
+    id: u64,
+    plugin_id: String,
+    /// 发起提问时插件运行时的加载编号
+    plugin_load_id: u64,
+    request_id: String,
+    cancel_keywords: Vec<String>,
+}
+
+static SESSIONS: Lazy<DashMap<SessionKey, Session>> = Lazy::new(DashMap::new);
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// 会话所属的插件运行时是否仍在
+fn is_live(state: &SharedState, session: &Session) -> bool {
+    state.plugin_manager.load_id(&session.plugin_id) == Some(session.plugin_load_id)
+}
+
+/// 登记一次提问，超时后自动结束
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn begin(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    plugin_id: &str,
+    request_id: &str,
+    user_id: u64,
+    group_id: Option<u64>,
+    timeout_ms: u64,
+    cancel_keywords: &[String],
+) {
+    let Some(plugin_load_id) = state.plugin_manager.load_id(plugin_id) else {
+        return;
+    };
+    // 顺带清理已卸载 / 重载插件遗留的会话
+    SESSIONS.retain(|_, session| is_live(state, session));
+
+    let key = SessionKey {
+        bot_id: bot_id.to_string(),
+        group_id,
+        user_id,
+    };
+    let id = NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed);
+    let session = Session {
+        id,
+        plugin_id: plugin_id.to_string(),
+        plugin_load_id,
+        request_id: request_id.to_string(),
+        cancel_keywords: cancel_keywords
+            .iter()
+            .map(|k| k.trim().to_string())
+            .filter(|k| !k.is_empty())
+            .collect(),
+    };
+    let previous = SESSIONS.insert(key.clone(), session);
+
+    let timeout = Duration::from_millis(timeout_ms.clamp(MIN_TIMEOUT_MS, MAX_TIMEOUT_MS));
+    let (state_clone, runtime_clone) = (state.clone(), runtime.clone());
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        if let Some((key, session)) = SESSIONS.remove_if(&key, |_, s| s.id == id) {
+            if !is_live(&state_clone, &session) {
+                return;
+            }
+            info!(
+                "[{}] 插件 {} 等待用户 {} 回复超时",
+                key.bot_id, session.plugin_id, key.user_id
+            );
+            finish(
+                &state_clone,
+                &runtime_clone,
+                &key.bot_id,
+                session,
+                json!({ "status": "timeout" }),
+            )
+            .await;
+        }
+    });
+
+    if let Some(previous) = previous.filter(|p| is_live(state, p)) {
+        finish(
+            state,
+            runtime,
+            bot_id,
+            previous,
+            json!({ "status": "replaced" }),
+        )
+        .await;
+    }
+}
+
+/// 若该用户在此会话中有等待中的提问，把消息交给发起提问的插件并返回 true（不再继续处理该消息）
+pub(super) async fn capture(
+    state: &SharedState,
+    runtime: &Arc<BotRuntime>,
+    bot_id: &str,
+    user_id: u64,
+    group_id: Option<u64>,
+    event: &Value,
+    message_segments: &Value,
+) -> bool {
+    if SESSIONS.is_empty() {
+        return false;
+    }
+    let key = SessionKey {
+        bot_id: bot_id.to_string(),
+        group_id,
+        user_id,
+    };
+    let Some((_, session)) = SESSIONS.remove(&key) else {
+        return false;
+    };
+    if !is_live(state, &session) {
+        info!(
+            "[{}] 插件 {} 已卸载或重载，丢弃其等待用户 {} 回复的提问",
+            bot_id, session.plugin_id, user_id
+        );
+        return false;
+    }
+
+    let raw_message = event["raw_message"].as_str().unwrap_or("");
+    let text = message_text(event.get("message"), raw_message);
+    let cancelled = session
+        .cancel_keywords
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(text.trim()));
+    let reply = if cancelled {
+        info!(
+            "[{}] 用户 {} 取消了插件 {} 的提问",
+            bot_id, user_id, session.plugin_id
+        );
+        json!({ "status": "cancelled" })
+    } else {
+        json!({
+            "status": "ok",
+            "text": text,
+            "message": message_segments,
+            "raw_message": raw_message,
+            "message_id": event.get("message_id").cloned().unwrap_or(Value::Null),
+            "user_id": user_id,
+            "group_id": group_id,
+        })
+    };
+    finish(state, runtime, bot_id, session, reply).await;
+    true
+}
+
+/// 消息中的纯文本（不含 @、图片等消息段）
+fn message_text(message: Option<&Value>, raw_message: &str) -> String {
+    let segments = match message {
+        Some(value @ Value::Array(_)) => segment::parse_message(value),
+        _ => segment::parse_cq(raw_message),
+    };
+    segments
+        .iter()
+        .filter_map(|seg| match seg {
+            MessageSegment::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// 把结果交给插件，并处理回调中产生的输出（可能再次提问）。
+/// 返回装箱的 `Send` future，打断 begin → finish → 处理输出 → begin 的递归，超时任务才能 spawn
+fn finish<'a>(
+    state: &'a SharedState,
+    runtime: &'a Arc<BotRuntime>,
+    bot_id: &'a str,
+    session: Session,
+    reply: Value,
+) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+    Box::pin(async move {
+        let outputs = match state
+            .plugin_manager
+            .on_prompt_reply(&session.plugin_id, &session.request_id, reply)
+            .await
+        {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                warn!(
+                    "[{}] 插件 {} onPromptReply 失败: {}",
+                    bot_id, session.plugin_id, e
+                );
+                return;
+            }
+        };
+        let outputs: Vec<PluginOutputWithSource> = outputs
+            .into_iter()
+            .map(|output| PluginOutputWithSource {
+                plugin_id: session.plugin_id.clone(),
+                output,
+            })
+            .collect();
+        process_plugin_outputs_with_source(state, runtime, bot_id, &outputs).await;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_text_ignores_non_text_segments() {
+        let message = json!([
+            { "type": "at", "data": { "qq": "10001" } },
+            { "type": "text", "data": { "text": " 取消 " } },
+            { "type": "image", "data": { "file": "a.png" } }
+        ]);
+        assert_eq!(message_text(Some(&message), ""), "取消");
+        assert_eq!(message_text(None, "[CQ:at,qq=1] 1234"), "1234");
+    }
+}
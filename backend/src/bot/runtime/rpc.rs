@@ -0,0 +1,261 @@
+//! OneBot 请求 / 响应层：每次调用生成唯一 echo，按调用设置超时；
+//! 调用方取消、超时或连接断开时都会清理等待表，并按 action 统计调用耗时。
+
+use dashmap::DashMap;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// 默认等待响应的时长
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// API 调用失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcError {
+    /// 连接不存在
+    NotConnected,
+    /// 写入连接失败
+    SendFailed,
+    /// 等待响应超时（请求可能已被执行）
+    Timeout,
+    /// 等待期间连接断开
+    Disconnected,
+    /// 未发出：被本地拦截（禁言、队列已满、排队超时等）
+    Rejected(String),
+    /// 平台返回 `status: failed`
+    Failed {
+        retcode: Option<i64>,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotConnected => write!(f, "连接不存在"),
+            Self::SendFailed => write!(f, "发送请求失败"),
+            Self::Timeout => write!(f, "等待响应超时"),
+            Self::Disconnected => write!(f, "等待响应时连接断开"),
+            Self::Rejected(reason) => write!(f, "未发送: {reason}"),
+            Self::Failed { retcode, message } => match retcode {
+                Some(code) => write!(f, "调用失败 (retcode={code}): {message}"),
+                None => write!(f, "调用失败: {message}"),
+            },
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// 检查 OneBot 响应：`status: failed` 或非 0/1 的 retcode 视为失败
+pub fn check_response(resp: Value) -> Result<Value, RpcError> {
+    if !is_failed(&resp) {
+        return Ok(resp);
+    }
+    let retcode = resp.get("retcode").and_then(|v| v.as_i64());
+    let message = resp
+        .get("message")
+        .or_else(|| resp.get("wording"))
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+    Err(RpcError::Failed { retcode, message })
+}
+
+fn is_failed(resp: &Value) -> bool {
+    let status = resp.get("status").and_then(|v| v.as_str());
+    let retcode = resp.get("retcode").and_then(|v| v.as_i64());
+    status == Some("failed") || retcode.is_some_and(|c| c != 0 && c != 1)
+}
+
+/// 发送结果回执
+pub(super) type Ack = oneshot::Sender<Result<Value, RpcError>>;
+
+pub(super) fn send_ack(ack: Option<Ack>, result: Result<Value, RpcError>) {
+    if let Some(ack) = ack {
+        let _ = ack.send(result);
+    }
+}
+
+#[derive(Default, Serialize)]
+struct ActionStats {
+    calls: u64,
+    ok: u64,
+    failed: u64,
+    timeouts: u64,
+    total_ms: u64,
+    max_ms: u64,
+}
+
+enum Outcome {
+    Ok,
+    Failed,
+    Timeout,
+}
+
+pub(super) struct Rpc {
+    /// 进程启动时的随机前缀，避免重启后把旧连接的迟到响应当成新请求的
+    boot: u32,
+    seq: AtomicU64,
+    /// echo → (bot_id, 响应通道)
+    pending: DashMap<String, (String, oneshot::Sender<Value>)>,
+    stats: Mutex<HashMap<String, ActionStats>>,
+}
+
+impl Rpc {
+    pub(super) fn new() -> Self {
+        Self {
+            boot: rand::random(),
+            seq: AtomicU64::new(1),
+            pending: DashMap::new(),
+            stats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 登记一次调用，返回其 echo 与等待句柄；请求由调用方发出
+    pub(super) fn register(self: &Arc<Self>, bot_id: &str, action: &str) -> PendingCall {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let echo = format!("{}:{:08x}:{}", action, self.boot, seq);
+        let (tx, rx) = oneshot::channel();
+        self.pending.insert(echo.clone(), (bot_id.to_string(), tx));
+        PendingCall {
+            rpc: self.clone(),
+            echo,
+            action: action.to_string(),
+            started: Instant::now(),
+            rx: Some(rx),
+        }
+    }
+
+    /// 分发带 echo 的响应；没有等待方（已超时或取消）时返回 false
+    pub(super) fn resolve(&self, echo: &str, resp: Value) -> bool {
+        match self.pending.remove(echo) {
+            Some((_, (_, tx))) => tx.send(resp).is_ok(),
+            None => false,
+        }
+    }
+
+    /// 连接断开：立即结束该 bot 的所有等待
+    pub(super) fn cancel_bot(&self, bot_id: &str) -> usize {
+        let before = self.pending.len();
+        self.pending.retain(|_, (owner, _)| owner != bot_id);
+        before.saturating_sub(self.pending.len())
+    }
+
+    fn record(&self, action: &str, elapsed: Duration, outcome: Outcome) {
+        let Ok(mut stats) = self.stats.lock() else {
+            return;
+        };
+        let entry = stats.entry(action.to_string()).or_default();
+        let ms = elapsed.as_millis() as u64;
+        entry.calls += 1;
+        entry.total_ms += ms;
+        entry.max_ms = entry.max_ms.max(ms);
+        match outcome {
+            Outcome::Ok => entry.ok += 1,
+            Outcome::Failed => entry.failed += 1,
+            Outcome::Timeout => entry.timeouts += 1,
+        }
+    }
+
+    /// 各 action 的调用次数、失败 / 超时次数与耗时
+    pub(super) fn stats(&self) -> Value {
+        let actions: serde_json::Map<String, Value> = match self.stats.lock() {
+            Ok(stats) => stats
+                .iter()
+                .map(|(action, s)| {
+                    let avg_ms = s.total_ms.checked_div(s.calls).unwrap_or(0);
+                    (
+                        action.clone(),
+                        json!({
+                            "calls": s.calls,
+                            "ok": s.ok,
+                            "failed": s.failed,
+                            "timeouts": s.timeouts,
+                            "avg_ms": avg_ms,
+                            "max_ms": s.max_ms,
+                        }),
+                    )
+                })
+                .collect(),
+            Err(_) => serde_json::Map::new(),
+        };
+        json!({ "pending": self.pending.len(), "actions": actions })
+    }
+}
+
+/// 一次进行中的调用；被丢弃（超时、调用方取消）时从等待表移除
+pub(super) struct PendingCall {
+    rpc: Arc<Rpc>,
+    echo: String,
+    action: String,
+    started: Instant,
+    rx: Option<oneshot::Receiver<Value>>,
+}
+
+impl PendingCall {
+    pub(super) fn echo(&self) -> &str {
+        &self.echo
+    }
+
+    /// 等待原始响应（不检查 `status`，见 [`check_response`]）
+    pub(super) async fn wait(mut self, timeout: Duration) -> Result<Value, RpcError> {
+        let Some(rx) = self.rx.take() else {
+            return Err(RpcError::Disconnected);
+        };
+        let result = match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(resp)) => Ok(resp),
+            Ok(Err(_)) => Err(RpcError::Disconnected),
+            Err(_) => Err(RpcError::Timeout),
+        };
+        let outcome = match &result {
+            Ok(resp) if !is_failed(resp) => Outcome::Ok,
+            Err(RpcError::Timeout) => Outcome::Timeout,
+            _ => Outcome::Failed,
+        };
+        self.rpc
+            .record(&self.action, self.started.elapsed(), outcome);
+        result
+    }
+}
+
+impl Drop for PendingCall {
+    fn drop(&mut self) {
+        self.rpc.pending.remove(&self.echo);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn echo_ids_are_unique_and_cleaned_up() {
+        let rpc = Arc::new(Rpc::new());
+        let a = rpc.register("bot", "send_group_msg");
+        let b = rpc.register("bot", "send_group_msg");
+        assert_ne!(a.echo(), b.echo());
+        assert_eq!(rpc.pending.len(), 2);
+        drop(a);
+        assert_eq!(rpc.pending.len(), 1);
+        assert_eq!(rpc.cancel_bot("bot"), 1);
+    }
+
+    #[test]
+    fn failed_status_is_typed() {
+        let err = check_response(json!({ "status": "failed", "retcode": 1200, "message": "x" }))
+            .unwrap_err();
+        assert_eq!(
+            err,
+            RpcError::Failed {
+                retcode: Some(1200),
+                message: "x".to_string()
+            }
+        );
+        assert!(check_response(json!({ "status": "async", "retcode": 1 })).is_ok());
+    }
+}
@@ -9,9 +9,11 @@ mod models;
 mod module;
 mod persistence;
 mod plugin;
+mod outbound;
 mod plugin_handlers;
 pub mod qq_face;
 mod render_image;
+mod segment;
 mod task;
 mod tool;
 pub mod utils;
@@ -31,7 +33,7 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilte
 
 use crate::auth::{load_or_create_api_token, require_api_token, AuthState};
 use crate::bot::{
-    docker_status_sync_loop, napcat_login_monitor, start_bot_connections,
+    bind_state, docker_status_sync_loop, napcat_login_monitor, start_bot_connections,
     start_discord_connections, BotRuntime,
 };
 use crate::command::CommandRegistry;
@@ -229,6 +231,8 @@ async fn main() {
 
     // Start bot message listener
     let bot_runtime = Arc::new(BotRuntime::new());
+    bot_runtime.set_plugin_manager(state.plugin_manager.clone());
+    bind_state(&state);
     let state_cl4 = state.clone();
     let runtime_cl = bot_runtime.clone();
     tokio::spawn(async move {
@@ -280,6 +284,9 @@ async fn main() {
         .route("/system/export", get(bot::system_export_handler))
         .route("/docker/info", get(bot::get_docker_info_handler))
         .route("/message/stats", get(bot::get_message_stats_handler))
+        .route("/message/inbound", get(bot::get_inbound_stats_handler))
+        .route("/message/outbound", get(bot::get_outbound_stats_handler))
+        .route("/message/rpc", get(bot::get_rpc_stats_handler))
         .route("/bots", post(bot::create_bot_handler))
         .route("/bots/list", get(bot::list_bots_for_link_handler))
         .route("/bots/:id", get(bot::get_bot_handler))
@@ -369,7 +376,36 @@ async fn main() {
         .route("/llm/test", post(module::llm_test_handler))
         .route("/llm/models", post(module::llm_models_handler))
         .route("/llm/chat", post(module::llm_chat_handler))
-        .route("/llm/tavily/test", post(module::tavily_test_handler))
+        .route("/llm/search/test", post(module::search_test_handler))
+        .route(
+            "/llm/traces",
+            get(bot::list_llm_traces_handler).delete(bot::clear_llm_traces_handler),
+        )
+        .route("/llm/traces/:id", get(bot::get_llm_trace_handler))
+        .route(
+            "/llm/media-cache",
+            get(bot::get_media_cache_stats_handler).delete(bot::purge_media_cache_handler),
+        )
+        // Privacy routes
+        .route("/privacy/preview", post(bot::privacy_preview_handler))
+        // Knowledge base routes
+        .route(
+            "/knowledge",
+            get(bot::list_knowledge_bases_handler).post(bot::create_knowledge_base_handler),
+        )
+        .route(
+            "/knowledge/:id",
+            get(bot::get_knowledge_base_handler).delete(bot::delete_knowledge_base_handler),
+        )
+        .route(
+            "/knowledge/:id/documents",
+            post(bot::ingest_knowledge_document_handler),
+        )
+        .route(
+            "/knowledge/:id/documents/:doc_id",
+            delete(bot::delete_knowledge_document_handler),
+        )
+        .route("/knowledge/:id/query", post(bot::query_knowledge_base_handler))
         // Command routes
         .route("/commands", get(command::list_commands_handler))
         .route("/commands", post(command::create_command_handler))
@@ -391,6 +427,10 @@ async fn main() {
         // Chat routes
         .route("/chat/history", get(bot::get_chat_history_handler))
         .route("/chat/send", post(bot::send_chat_message_handler))
+        .route(
+            "/chat/archive",
+            get(bot::search_chat_archive_handler).delete(bot::clear_chat_archive_handler),
+        )
         .layer(Extension(bot_runtime.clone()))
         .with_state(state.clone())
         .layer(middleware::from_fn_with_state(
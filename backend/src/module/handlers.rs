@@ -23,7 +23,10 @@ pub async fn enable_module_handler(
     Path(id): Path<String>,
 ) -> Json<serde_json::Value> {
     match state.modules.enable(&id) {
-        Ok(_) => Json(serde_json::json!({ "status": "success" })),
+        Ok(_) => {
+            crate::bot::invalidate_module_caches(&id);
+            Json(serde_json::json!({ "status": "success" }))
+        }
         Err(e) => Json(serde_json::json!({ "status": "error", "message": e })),
     }
 }
@@ -33,7 +36,10 @@ pub async fn disable_module_handler(
     Path(id): Path<String>,
 ) -> Json<serde_json::Value> {
     match state.modules.disable(&id) {
-        Ok(_) => Json(serde_json::json!({ "status": "success" })),
+        Ok(_) => {
+            crate::bot::invalidate_module_caches(&id);
+            Json(serde_json::json!({ "status": "success" }))
+        }
         Err(e) => Json(serde_json::json!({ "status": "error", "message": e })),
     }
 }
@@ -49,7 +55,10 @@ pub async fn update_module_config_handler(
     Json(payload): Json<UpdateConfigPayload>,
 ) -> Json<serde_json::Value> {
     match state.modules.update_config(&id, payload.config) {
-        Ok(_) => Json(serde_json::json!({ "status": "success" })),
+        Ok(_) => {
+            crate::bot::invalidate_module_caches(&id);
+            Json(serde_json::json!({ "status": "success" }))
+        }
         Err(e) => Json(serde_json::json!({ "status": "error", "message": e })),
     }
 }
@@ -207,7 +216,8 @@ pub async fn get_llm_config_handler(State(state): State<SharedState>) -> Json<se
                 "model_library": config.get("model_library").cloned().unwrap_or(json!([])),
                 "mappings": config.get("models").cloned().unwrap_or(json!({})),
                 "default_model": config.get("default_model").and_then(|v| v.as_str()).unwrap_or("default"),
-                "tavily_api_key": config.get("tavily_api_key").and_then(|v| v.as_str()).unwrap_or("")
+                "search": crate::bot::web_search::effective_search_config(config),
+                "trace": config.get("trace").cloned().unwrap_or(json!({ "enabled": false }))
             }))
         }
         None => Json(json!({
@@ -216,7 +226,8 @@ pub async fn get_llm_config_handler(State(state): State<SharedState>) -> Json<se
             "model_library": [],
             "mappings": {},
             "default_model": "default",
-            "tavily_api_key": ""
+            "search": crate::bot::web_search::effective_search_config(&json!({})),
+            "trace": { "enabled": false }
         })),
     }
 }
@@ -228,8 +239,12 @@ pub struct UpdateLLMConfigPayload {
     pub model_library: serde_json::Value,
     pub mappings: serde_json::Value,
     pub default_model: String,
+    /// 联网搜索设置（provider / max_results / 各服务参数），不传则保持不变
+    #[serde(default)]
+    pub search: Option<serde_json::Value>,
+    /// LLM 调用追踪设置（enabled / retention_days / max_entries ...），不传则保持不变
     #[serde(default)]
-    pub tavily_api_key: String,
+    pub trace: Option<serde_json::Value>,
 }
 
 /// Update LLM configuration
@@ -237,13 +252,27 @@ pub async fn update_llm_config_handler(
     State(state): State<SharedState>,
     Json(payload): Json<UpdateLLMConfigPayload>,
 ) -> Json<serde_json::Value> {
-    let new_config = json!({
-        "providers": payload.providers,
-        "model_library": payload.model_library,
-        "models": payload.mappings,
-        "default_model": payload.default_model,
-        "tavily_api_key": payload.tavily_api_key
-    });
+    // 保留未在此处编辑的配置项（如 limits / trace）
+    let mut new_config = state
+        .modules
+        .get("llm")
+        .map(|m| m.config.clone())
+        .filter(|c| c.is_object())
+        .unwrap_or_else(|| json!({}));
+    new_config["providers"] = payload.providers;
+    new_config["model_library"] = payload.model_library;
+    new_config["models"] = payload.mappings;
+    new_config["default_model"] = json!(payload.default_model);
+    if let Some(search) = payload.search {
+        new_config["search"] = search;
+        // 旧字段已由 search.tavily.api_key 取代
+        if let Some(obj) = new_config.as_object_mut() {
+            obj.remove("tavily_api_key");
+        }
+    }
+    if let Some(trace) = payload.trace {
+        new_config["trace"] = trace;
+    }
 
     match state.modules.update_config("llm", new_config) {
         Ok(_) => Json(json!({ "status": "success" })),
@@ -252,47 +281,40 @@ pub async fn update_llm_config_handler(
 }
 
 #[derive(serde::Deserialize)]
-pub struct TavilyTestPayload {
-    pub api_key: String,
+pub struct SearchTestPayload {
+    /// 与 LLM 模块配置中的 `search` 结构相同
+    pub search: serde_json::Value,
+    #[serde(default)]
+    pub query: Option<String>,
 }
 
-/// Test Tavily API key
-pub async fn tavily_test_handler(Json(payload): Json<TavilyTestPayload>) -> Json<serde_json::Value> {
-    if payload.api_key.is_empty() {
-        return Json(json!({ "status": "error", "message": "API Key 不能为空" }));
-    }
-
-    // Tavily API keys start with "tvly-"
-    if !payload.api_key.starts_with("tvly-") {
-        return Json(json!({ "status": "error", "message": "Tavily API Key 格式不正确，应以 tvly- 开头" }));
-    }
+/// Test web search provider settings
+pub async fn search_test_handler(Json(payload): Json<SearchTestPayload>) -> Json<serde_json::Value> {
+    use crate::bot::web_search::{parse_search_settings, web_search};
 
-    let client = reqwest::Client::new();
-    let body = json!({
-        "api_key": payload.api_key,
-        "query": "test",
-        "max_results": 1
-    });
-
-    match client
-        .post("https://api.tavily.com/search")
-        .json(&body)
-        .send()
-        .await
-    {
-        Ok(resp) => {
-            let status = resp.status();
-            if status.is_success() {
-                Json(json!({ "status": "success", "message": "Tavily API 连接成功" }))
-            } else {
-                let text = match resp.text().await {
-                    Ok(t) => t,
-                    Err(e) => format!("(无法读取响应正文: {e})"),
-                };
-                Json(json!({ "status": "error", "message": format!("HTTP {}: {}", status, text) }))
-            }
-        }
-        Err(e) => Json(json!({ "status": "error", "message": e.to_string() })),
+    let settings = match parse_search_settings(&payload.search) {
+        Ok(s) => s,
+        Err(e) => return Json(json!({ "status": "error", "message": e })),
+    };
+    let query = payload
+        .query
+        .as_deref()
+        .map(str::trim)
+        .filter(|q| !q.is_empty())
+        .unwrap_or("test");
+
+    match web_search(&settings, query).await {
+        Ok(resp) => Json(json!({
+            "status": "success",
+            "message": format!(
+                "{} 连接成功，返回 {} 条结果",
+                settings.provider.name(),
+                resp.results.len()
+            ),
+            "answer": resp.answer,
+            "results": resp.results,
+        })),
+        Err(e) => Json(json!({ "status": "error", "message": e })),
     }
 }
 
@@ -51,7 +51,50 @@ impl ModuleRegistry {
                     "model_library": [],
                     "models": {},
                     "default_model": "default",
-                    "tavily_api_key": ""
+                    "search": {
+                        "provider": "tavily",
+                        "max_results": 5,
+                        "tavily": { "api_key": "" }
+                    }
+                }),
+            },
+            BotModule {
+                id: "privacy".to_string(),
+                name: "隐私脱敏".to_string(),
+                description: "对发出的消息、LLM 提示词与日志中的 QQ 号、手机号等敏感信息脱敏"
+                    .to_string(),
+                icon: "shield".to_string(),
+                enabled: true,
+                builtin: true,
+                config: serde_json::json!({
+                    "detectors": {}
+                }),
+            },
+            BotModule {
+                id: "long_message".to_string(),
+                name: "长消息处理".to_string(),
+                description: "超长文本自动拆分编号、转为合并转发或渲染为图片，可按群设置"
+                    .to_string(),
+                icon: "scissors".to_string(),
+                enabled: true,
+                builtin: true,
+                config: serde_json::json!({
+                    "mode": "split",
+                    "max_chars": 3000,
+                    "max_parts": 5,
+                    "groups": {}
+                }),
+            },
+            BotModule {
+                id: "message_archive".to_string(),
+                name: "消息存档".to_string(),
+                description: "将收发的消息存入本地数据库，支持全文检索与历史查询".to_string(),
+                icon: "archive".to_string(),
+                enabled: false,
+                builtin: true,
+                config: serde_json::json!({
+                    "retention_days": 30,
+                    "max_messages": 200000
                 }),
             },
             BotModule {
@@ -0,0 +1,386 @@
+//! 出站 HTTP：按用户 / 插件给出的 URL 发起请求时统一使用这里的客户端
+//!
+//! - DNS 解析结果中的内网、回环、链路本地等地址会被过滤，重定向的每一跳都会重新检查
+//! - 全局主机黑名单与内网例外（环境变量）
+//! - 插件 manifest 中声明的 `network.allowHosts` / `network.denyHosts`
+//! - 可选出站代理
+//!
+//! 环境变量：
+//! - `NBOT_OUTBOUND_PROXY`：出站代理（如 `http://127.0.0.1:7890`）。未设置时直连，不读取系统代理
+//! - `NBOT_OUTBOUND_ALLOW_PRIVATE`：为 true 时不拦截内网地址（仅限可信环境）
+//! - `NBOT_OUTBOUND_PRIVATE_HOSTS`：逗号分隔，允许解析到内网地址的主机（如自建的文件服务）
+//! - `NBOT_OUTBOUND_DENY_HOSTS`：逗号分隔，始终拒绝的主机
+//!
+//! 主机规则支持精确匹配（`example.com`）与子域通配（`*.example.com`，不含 example.com 本身）。
+//!
+//! 注意：配置代理后由代理完成目标域名解析，此时只能在发起请求前检查一次解析结果，
+//! 重定向后的域名不再做 DNS 检查（IP 字面量与主机名单仍会检查）。
+
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{redirect, Method, RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use tokio::task_local;
+use tracing::warn;
+
+const MAX_REDIRECTS: usize = 10;
+const CONNECT_TIMEOUT_SECS: u64 = 15;
+
+/// 主机访问策略（插件 manifest 的 `network` 字段）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostPolicy {
+    /// 非空时只允许访问列表中的主机
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_hosts: Vec<String>,
+    /// 禁止访问的主机
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny_hosts: Vec<String>,
+}
+
+impl HostPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.allow_hosts.is_empty() && self.deny_hosts.is_empty()
+    }
+}
+
+struct OutboundSettings {
+    proxy: Option<reqwest::Proxy>,
+    allow_private: bool,
+    private_hosts: Vec<String>,
+    deny_hosts: Vec<String>,
+}
+
+impl OutboundSettings {
+    fn from_env() -> Self {
+        let proxy = std::env::var("NBOT_OUTBOUND_PROXY")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .and_then(|url| match reqwest::Proxy::all(&url) {
+                Ok(proxy) => Some(proxy),
+                Err(e) => {
+                    warn!("NBOT_OUTBOUND_PROXY 无效，已忽略: {}", e);
+                    None
+                }
+            });
+        Self {
+            proxy,
+            allow_private: env_flag("NBOT_OUTBOUND_ALLOW_PRIVATE"),
+            private_hosts: env_list("NBOT_OUTBOUND_PRIVATE_HOSTS"),
+            deny_hosts: env_list("NBOT_OUTBOUND_DENY_HOSTS"),
+        }
+    }
+
+    fn private_allowed(&self, host: &str) -> bool {
+        self.allow_private || self.private_hosts.iter().any(|p| host_matches(p, host))
+    }
+}
+
+fn env_flag(key: &str) -> bool {
+    matches!(
+        std::env::var(key)
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+fn env_list(key: &str) -> Vec<String> {
+    std::env::var(key)
+        .unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+static SETTINGS: Lazy<OutboundSettings> = Lazy::new(OutboundSettings::from_env);
+static CLIENTS: Lazy<DashMap<HostPolicy, reqwest::Client>> = Lazy::new(DashMap::new);
+
+task_local! {
+    static HOST_POLICY: Arc<HostPolicy>;
+}
+
+/// 在指定主机策略下执行 `fut`：其中通过 [`get`] 发起的下载都会遵循该策略
+pub async fn with_host_policy<T>(policy: HostPolicy, fut: impl Future<Output = T>) -> T {
+    HOST_POLICY.scope(Arc::new(policy), fut).await
+}
+
+/// 当前上下文的主机策略（不在插件上下文中时为空策略）
+pub fn current_policy() -> HostPolicy {
+    HOST_POLICY
+        .try_with(|p| HostPolicy::clone(p))
+        .unwrap_or_default()
+}
+
+/// 被出站策略拒绝时的错误，会出现在 reqwest 错误的 source 链中
+#[derive(Debug)]
+struct Blocked(String);
+
+impl std::fmt::Display for Blocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "目标地址被出站策略拒绝：{}", self.0)
+    }
+}
+
+impl std::error::Error for Blocked {}
+
+/// 将 reqwest 错误转为可读文本；被出站策略拦截时返回拦截原因
+pub fn describe_error(e: &reqwest::Error) -> String {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(e);
+    while let Some(err) = source {
+        if let Some(blocked) = err.downcast_ref::<Blocked>() {
+            return blocked.to_string();
+        }
+        source = err.source();
+    }
+    e.to_string()
+}
+
+/// 匹配主机规则：`example.com` 精确匹配，`*.example.com` 匹配其子域
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().trim_end_matches('.').to_ascii_lowercase();
+    let host = host.trim_end_matches('.');
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host
+            .strip_suffix(suffix)
+            .is_some_and(|prefix| prefix.ends_with('.') && prefix.len() > 1),
+        None => host.eq_ignore_ascii_case(&pattern),
+    }
+}
+
+fn is_blocked_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // 100.64.0.0/10 运营商级 NAT
+        || (a == 100 && (64..128).contains(&b))
+        // 192.0.0.0/24 IETF 协议分配
+        || (a == 192 && b == 0 && c == 0)
+        // 198.18.0.0/15 基准测试
+        || (a == 198 && (b == 18 || b == 19))
+        // 240.0.0.0/4 保留
+        || a >= 240
+}
+
+fn is_blocked_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_blocked_ipv4(v4);
+    }
+    let seg = ip.segments();
+    // 64:ff9b::/96 NAT64，按内嵌的 IPv4 判断
+    if seg[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let [_, _, _, _, _, _, hi, lo] = seg;
+        return is_blocked_ipv4(Ipv4Addr::from(((hi as u32) << 16) | lo as u32));
+    }
+    ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // fc00::/7 唯一本地地址
+        || (seg[0] & 0xfe00) == 0xfc00
+        // fe80::/10 链路本地、fec0::/10 站点本地（已废弃）
+        || (seg[0] & 0xffc0) == 0xfe80
+        || (seg[0] & 0xffc0) == 0xfec0
+        // 2001:db8::/32 文档
+        || (seg[0] == 0x2001 && seg[1] == 0xdb8)
+        // ::/96 IPv4 兼容地址（已废弃）
+        || seg[..6] == [0; 6]
+}
+
+/// 是否为不允许访问的地址（内网、回环、链路本地、保留地址等）
+pub fn is_blocked_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_blocked_ipv4(ip),
+        IpAddr::V6(ip) => is_blocked_ipv6(ip),
+    }
+}
+
+/// URL 中的主机名（小写，IPv6 去掉方括号）
+fn url_host(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Some(host.to_ascii_lowercase())
+}
+
+/// 不需要 DNS 的检查：协议、主机名单、IP 字面量
+fn check_url_static(url: &Url, policy: &HostPolicy) -> Result<(), Blocked> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(Blocked(format!("不支持的协议 {}", url.scheme())));
+    }
+    let Some(host) = url_host(url) else {
+        return Err(Blocked("URL 缺少主机名".to_string()));
+    };
+    let ip = host.parse::<IpAddr>().ok();
+
+    let settings = &*SETTINGS;
+    if settings.deny_hosts.iter().any(|p| host_matches(p, &host)) {
+        return Err(Blocked(format!("{host} 在全局黑名单中")));
+    }
+    if policy.deny_hosts.iter().any(|p| host_matches(p, &host)) {
+        return Err(Blocked(format!("插件禁止访问 {host}")));
+    }
+    if !policy.allow_hosts.is_empty() && !policy.allow_hosts.iter().any(|p| host_matches(p, &host))
+    {
+        return Err(Blocked(format!("{host} 不在插件允许访问的主机列表中")));
+    }
+    if let Some(ip) = ip {
+        if is_blocked_ip(ip) && !settings.private_allowed(&host) {
+            return Err(Blocked(format!("{host} 是内网或保留地址")));
+        }
+    }
+    Ok(())
+}
+
+/// 请求前的完整检查。直连时域名在连接阶段由 [`GuardedResolver`] 检查；
+/// 使用代理时目标域名由代理解析，这里先自行解析一次
+async fn check_url(url: &Url, policy: &HostPolicy) -> Result<(), Blocked> {
+    check_url_static(url, policy)?;
+    let settings = &*SETTINGS;
+    let Some(host) = url_host(url).filter(|h| h.parse::<IpAddr>().is_err()) else {
+        return Ok(());
+    };
+    if settings.proxy.is_none() || settings.private_allowed(&host) {
+        return Ok(());
+    }
+    let addrs = tokio::net::lookup_host((host.as_str(), 0))
+        .await
+        .map_err(|e| Blocked(format!("无法解析 {host}: {e}")))?;
+    for addr in addrs {
+        if is_blocked_ip(addr.ip()) {
+            return Err(Blocked(format!(
+                "{host} 解析到内网或保留地址 {}",
+                addr.ip()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// 过滤掉内网地址的 DNS 解析器
+struct GuardedResolver;
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if SETTINGS.private_allowed(&host) {
+                return Ok(Box::new(addrs.into_iter()) as Addrs);
+            }
+            let allowed: Vec<SocketAddr> = addrs
+                .into_iter()
+                .filter(|addr| !is_blocked_ip(addr.ip()))
+                .collect();
+            if allowed.is_empty() {
+                return Err(Box::new(Blocked(format!("{host} 解析到内网或保留地址"))) as _);
+            }
+            Ok(Box::new(allowed.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn build_client(policy: &HostPolicy) -> Result<reqwest::Client, String> {
+    let redirect_policy = {
+        let policy = policy.clone();
+        redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error(format!("重定向次数超过 {MAX_REDIRECTS} 次"));
+            }
+            match check_url_static(attempt.url(), &policy) {
+                Ok(()) => attempt.follow(),
+                Err(blocked) => attempt.error(blocked),
+            }
+        })
+    };
+    let mut builder = reqwest::Client::builder()
+        .dns_resolver(Arc::new(GuardedResolver))
+        .redirect(redirect_policy)
+        .connect_timeout(Duration::from_secs(CONNECT_TIMEOUT_SECS));
+    builder = match &SETTINGS.proxy {
+        Some(proxy) => builder.proxy(proxy.clone()),
+        None => builder.no_proxy(),
+    };
+    builder
+        .build()
+        .map_err(|e| format!("创建出站 HTTP 客户端失败: {e}"))
+}
+
+/// 取得指定策略的共享客户端（连接池按策略复用）
+fn client(policy: &HostPolicy) -> Result<reqwest::Client, String> {
+    if let Some(client) = CLIENTS.get(policy) {
+        return Ok(client.clone());
+    }
+    let client = build_client(policy)?;
+    CLIENTS.insert(policy.clone(), client.clone());
+    Ok(client)
+}
+
+/// 检查 URL 后返回请求构造器
+pub async fn request(
+    method: Method,
+    url: &str,
+    policy: &HostPolicy,
+) -> Result<RequestBuilder, String> {
+    let url = Url::parse(url.trim()).map_err(|e| format!("URL 无效: {e}"))?;
+    check_url(&url, policy).await.map_err(|e| e.to_string())?;
+    Ok(client(policy)?.request(method, url))
+}
+
+/// 按当前上下文的主机策略发起 GET 请求
+pub async fn get(url: &str) -> Result<RequestBuilder, String> {
+    request(Method::GET, url, &current_policy()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_private_and_reserved_ranges() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.17.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.100.100.200",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(is_blocked_ip(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["1.1.1.1", "220.181.38.148", "2606:4700:4700::1111"] {
+            assert!(!is_blocked_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn host_patterns() {
+        assert!(host_matches("example.com", "example.com"));
+        assert!(host_matches("Example.com.", "example.com"));
+        assert!(!host_matches("example.com", "api.example.com"));
+        assert!(host_matches("*.example.com", "api.example.com"));
+        assert!(!host_matches("*.example.com", "example.com"));
+        assert!(!host_matches("*.example.com", "badexample.com"));
+    }
+}
@@ -13,6 +13,130 @@ const toBigInt = (v) => {
   }
 };
 
+// Pending promise-style LLM requests (nbot.llmChat / nbot.llmGenerateImage), keyed by requestId
+const pendingLlmRequests = new Map();
+let llmRequestSeq = 0;
+// Requests with no result after this long are rejected with type "timeout"
+const LLM_PENDING_TTL_MS = 10 * 60 * 1000;
+
+// Typed failure for promise-style LLM calls
+// type: "llm_error" | "invalid_schema" | "invalid_json" | "schema_mismatch" | "timeout"
+class LlmError extends Error {
+  constructor(type, message, details = {}) {
+    super(message);
+    this.name = "LlmError";
+    this.type = type;
+    this.errors = Array.isArray(details.errors) ? details.errors : [];
+    this.raw = details.raw ?? null;
+  }
+}
+
+const buildLlmChatPayload = (requestId, messages, options) => ({
+  request_id: String(requestId),
+  model_name: options.modelName ? String(options.modelName) : null,
+  messages: Array.isArray(messages) ? messages : [],
+  max_tokens: options.maxTokens || null,
+  knowledge_base: options.knowledgeBase ? String(options.knowledgeBase) : null,
+  knowledge_top_k: options.knowledgeTopK || null,
+  response_schema:
+    options.responseSchema && typeof options.responseSchema === "object"
+      ? options.responseSchema
+      : null,
+  schema_name: options.schemaName ? String(options.schemaName) : null,
+  json_mode: options.jsonMode ? String(options.jsonMode) : null,
+});
+
+const buildImageGenerationPayload = (requestId, prompt, options) => ({
+  request_id: requestId ? String(requestId) : null,
+  model_name: options.modelName ? String(options.modelName) : null,
+  prompt: String(prompt ?? ""),
+  image_url: options.imageUrl ? String(options.imageUrl) : null,
+  size: options.size ? String(options.size) : null,
+  quality: options.quality ? String(options.quality) : null,
+  n: options.n || null,
+  timeout_ms: options.timeoutMs || null,
+});
+
+// There is no timer between hook calls, so expired requests are swept whenever
+// a new request is made or a result arrives.
+const sweepPendingLlmRequests = () => {
+  const now = Date.now();
+  for (const [requestId, pending] of pendingLlmRequests) {
+    if (pending.expiresAt > now) continue;
+    pendingLlmRequests.delete(requestId);
+    pending.reject(new LlmError("timeout", "LLM request timed out"));
+  }
+};
+
+// Registers a promise-style request; `send` returns whether the host accepted it
+const trackLlmRequest = (requestId, resolve, reject, send) => {
+  sweepPendingLlmRequests();
+  pendingLlmRequests.set(requestId, {
+    resolve,
+    reject,
+    expiresAt: Date.now() + LLM_PENDING_TTL_MS,
+  });
+  if (!send()) {
+    pendingLlmRequests.delete(requestId);
+    return false;
+  }
+  return true;
+};
+
+// Called by the host for every LLM result: settles a pending llmChat promise,
+// otherwise forwards to the plugin's onLlmResponse hook.
+globalThis.__nbotDispatchLlmResponse = async (resp) => {
+  sweepPendingLlmRequests();
+  const pending = pendingLlmRequests.get(resp.requestId);
+  if (pending) {
+    pendingLlmRequests.delete(resp.requestId);
+    if (resp.success) {
+      pending.resolve({ requestId: resp.requestId, content: resp.content, data: resp.data });
+    } else {
+      const err = resp.error || {};
+      pending.reject(new LlmError(err.type || "llm_error", err.message || resp.content, err));
+    }
+    return;
+  }
+  if (globalThis.__plugin && globalThis.__plugin.onLlmResponse) {
+    await globalThis.__plugin.onLlmResponse(resp);
+  }
+};
+
+// Pending ctx.prompt / nbot.prompt calls, keyed by requestId
+const pendingPrompts = new Map();
+let promptSeq = 0;
+
+// Called by the host when a prompt is answered ("ok"), cancelled by keyword ("cancelled"),
+// expires ("timeout") or is superseded by a newer prompt ("replaced").
+globalThis.__nbotDispatchPromptReply = (requestId, reply) => {
+  const pending = pendingPrompts.get(requestId);
+  if (!pending) return;
+  pendingPrompts.delete(requestId);
+  const status = reply && reply.status;
+  if (status === "ok") {
+    const { status: _status, ...rest } = reply;
+    pending.resolve(rest);
+    return;
+  }
+  const notice =
+    status === "timeout" ? pending.timeoutText : status === "cancelled" ? pending.cancelText : null;
+  if (notice) globalThis.nbot.sendReply(pending.userId, pending.groupId, notice);
+  pending.resolve(null);
+};
+
+// Adds ctx.prompt(content, options) to hook contexts that carry a sender
+globalThis.__nbotWithPrompt = (ctx) => {
+  if (ctx && typeof ctx === "object" && ctx.user_id) {
+    Object.defineProperty(ctx, "prompt", {
+      value: (content, options = {}) =>
+        globalThis.nbot.prompt(ctx.user_id, ctx.group_id || 0, content, options),
+      enumerable: false,
+    });
+  }
+  return ctx;
+};
+
 globalThis.nbot = {
   // CQ helper: mention (at) a user
   at: (userId) => {
@@ -20,30 +144,117 @@ globalThis.nbot = {
     return qq ? `[CQ:at,qq=${qq}]` : "";
   },
 
+  // Message segment builders (OneBot style: { type, data }); pass an array of
+  // segments to sendReply instead of a CQ string. Discord renders them natively
+  // (mentions, reply reference, attachments).
+  segment: {
+    text: (text) => ({ type: "text", data: { text: String(text ?? "") } }),
+    at: (userId) => ({ type: "at", data: { qq: String(userId ?? "").trim() } }),
+    atAll: () => ({ type: "at", data: { qq: "all" } }),
+    reply: (messageId) => ({ type: "reply", data: { id: String(messageId) } }),
+    // file: URL, local path or "base64://..."
+    image: (file) => ({ type: "image", data: { file: String(file) } }),
+    record: (file) => ({ type: "record", data: { file: String(file) } }),
+    video: (file) => ({ type: "video", data: { file: String(file) } }),
+    file: (file, name) => {
+      const data = { file: String(file) };
+      if (name) data.name = String(name);
+      return { type: "file", data };
+    },
+    face: (id) => ({ type: "face", data: { id: String(id) } }),
+    forward: (id) => ({ type: "forward", data: { id: String(id) } }),
+  },
+
   // Send message to QQ group (legacy)
   sendMessage: (groupId, content) => {
     return core.ops.op_send_message(toBigInt(groupId), content);
   },
 
   // Send reply message
-  sendReply: (userId, groupId, content) => {
-    return core.ops.op_send_reply(toBigInt(userId), toBigInt(groupId || 0), content);
+  // options.priority (sendReply / callApi / sendForwardMessage): "high" | "normal" | "bulk"
+  //   Messages go through a rate-limited outbound queue; bulk messages (announcements,
+  //   batch prompts) are sent after everything else. Defaults to the triggering context.
+  // content: CQ string or an array of nbot.segment.* segments
+  sendReply: (userId, groupId, content, options = {}) => {
+    if (Array.isArray(content)) {
+      return core.ops.op_send_reply_segments(
+        toBigInt(userId),
+        toBigInt(groupId || 0),
+        JSON.stringify(content),
+        String(options.priority || "")
+      );
+    }
+    return core.ops.op_send_reply(
+      toBigInt(userId),
+      toBigInt(groupId || 0),
+      content,
+      String(options.priority || "")
+    );
+  },
+
+  // Wait for the user's next message in the same chat (group, or private when groupId is 0).
+  // Hook contexts (preMessage / preCommand / onCommand / onNotice) expose the same call as
+  // ctx.prompt(content, options), bound to the triggering user and chat.
+  // content: optional CQ string or segment array sent as the question
+  // options: { timeoutMs = 60000 (max 30 min), cancelKeywords, timeoutText?, cancelText? }
+  //   cancelKeywords defaults to ["\u53d6\u6d88"] ("cancel"); timeoutText / cancelText are sent when it ends that way
+  // Resolves with { text, message, raw_message, message_id, user_id, group_id }, or null when
+  // cancelled, expired, superseded by a newer prompt to the same user, or userId is invalid.
+  // The captured message skips preMessage and command parsing. Like llmChat, code after `await`
+  // runs when the reply arrives.
+  prompt: (userId, groupId, content, options = {}) => {
+    const requestId = `__prompt_${Date.now()}_${++promptSeq}`;
+    const cancelKeywords = Array.isArray(options.cancelKeywords)
+      ? options.cancelKeywords.map(String)
+      : ["\u53d6\u6d88"];
+    const timeoutMs = Math.min(Math.max(Math.floor(Number(options.timeoutMs) || 60000), 0), 1800000);
+    return new Promise((resolve) => {
+      pendingPrompts.set(requestId, {
+        resolve,
+        userId,
+        groupId: groupId || 0,
+        timeoutText: options.timeoutText,
+        cancelText: options.cancelText,
+      });
+      const accepted = core.ops.op_await_reply(
+        requestId,
+        toBigInt(userId),
+        toBigInt(groupId || 0),
+        timeoutMs,
+        JSON.stringify(cancelKeywords)
+      );
+      // No valid sender to wait for: settle right away instead of leaving the promise pending
+      if (!accepted) {
+        pendingPrompts.delete(requestId);
+        resolve(null);
+        return;
+      }
+      if (content !== undefined && content !== null && content !== "") {
+        globalThis.nbot.sendReply(userId, groupId, content);
+      }
+    });
   },
 
   // Call QQ API
-  callApi: (action, params = {}) => {
-    return core.ops.op_call_api(action, JSON.stringify(params));
+  callApi: (action, params = {}, options = {}) => {
+    return core.ops.op_call_api(action, JSON.stringify(params), String(options.priority || ""));
   },
 
   // Call LLM and send result as forward message
-  callLlmForward: (userId, groupId, systemPrompt, prompt, content, title) => {
+  // options.outputMode (all callLlmForward* functions): "image" | "forward" | "text" | "file"
+  callLlmForward: (userId, groupId, systemPrompt, prompt, content, title, options = {}) => {
+    const payload = {
+      content: String(content),
+      title: String(title),
+      model_name: options.modelName ? String(options.modelName) : null,
+      output_mode: options.outputMode ? String(options.outputMode) : null,
+    };
     return core.ops.op_call_llm_forward(
       toBigInt(userId),
       toBigInt(groupId || 0),
       systemPrompt,
       prompt,
-      content,
-      title
+      JSON.stringify(payload)
     );
   },
 
@@ -63,6 +274,7 @@ globalThis.nbot = {
   ) => {
     const payload = {
       model_name: options.modelName ? String(options.modelName) : null,
+      output_mode: options.outputMode ? String(options.outputMode) : null,
       url: String(url),
       title: String(title),
       file_name: fileName ? String(fileName) : null,
@@ -79,8 +291,10 @@ globalThis.nbot = {
     );
   },
 
-  // Call LLM by downloading an archive URL and extracting a log/text file (temp file, removed after processing)
-  // Supported: .zip / .tar / .tar.gz (.tgz) / .gz
+  // Call LLM by downloading an archive URL and extracting log/text files (temp file, removed after processing)
+  // Supported: .zip / .7z / .tar / .tar.gz / .tar.xz / .tar.bz2 / .tar.zst / .gz / .xz / .bz2 / .zst,
+  // nested archives are expanded up to 3 levels.
+  // options.maxBundleFiles: how many top-ranked files to send together (default 1, max 10)
   callLlmForwardArchiveFromUrl: (
     userId,
     groupId,
@@ -99,6 +313,7 @@ globalThis.nbot = {
   ) => {
     const payload = {
       model_name: options.modelName ? String(options.modelName) : null,
+      output_mode: options.outputMode ? String(options.outputMode) : null,
       url: String(url),
       title: String(title),
       file_name: fileName ? String(fileName) : null,
@@ -107,6 +322,7 @@ globalThis.nbot = {
       max_extract_bytes: maxExtractBytes,
       max_file_bytes: maxFileBytes,
       max_files: maxFiles,
+      max_bundle_files: options.maxBundleFiles != null ? Number(options.maxBundleFiles) : null,
       keywords: Array.isArray(keywords) ? keywords.map((x) => String(x)) : [],
     };
     return core.ops.op_call_llm_forward_archive_from_url(
@@ -137,6 +353,7 @@ globalThis.nbot = {
   ) => {
     const payload = {
       model_name: options.modelName ? String(options.modelName) : null,
+      output_mode: options.outputMode ? String(options.outputMode) : null,
       url: String(url),
       title: String(title),
       file_name: fileName ? String(fileName) : null,
@@ -181,6 +398,7 @@ globalThis.nbot = {
   ) => {
     const payload = {
       model_name: options.modelName ? String(options.modelName) : null,
+      output_mode: options.outputMode ? String(options.outputMode) : null,
       url: String(url),
       title: String(title),
       file_name: fileName ? String(fileName) : null,
@@ -224,6 +442,7 @@ globalThis.nbot = {
   ) => {
     const payload = {
       model_name: options.modelName ? String(options.modelName) : null,
+      output_mode: options.outputMode ? String(options.outputMode) : null,
       url: String(url),
       title: String(title),
       file_name: fileName ? String(fileName) : null,
@@ -255,6 +474,7 @@ globalThis.nbot = {
   ) => {
     const payload = {
       model_name: options.modelName ? String(options.modelName) : null,
+      output_mode: options.outputMode ? String(options.outputMode) : null,
       title: String(title || "Multimodal Analysis"),
       text: text ? String(text) : null,
       items: Array.isArray(items) ? items : [],
@@ -276,25 +496,96 @@ globalThis.nbot = {
     );
   },
 
+  // Text-to-speech: synthesize `text` and send it as a voice (record) message
+  // options: { modelName, voice, speed, instructions, maxSeconds }
+  sendSpeech: (userId, groupId, text, options = {}) => {
+    const payload = {
+      model_name: options.modelName ? String(options.modelName) : null,
+      text: String(text ?? ""),
+      voice: options.voice ? String(options.voice) : null,
+      speed: typeof options.speed === "number" ? options.speed : null,
+      instructions: options.instructions ? String(options.instructions) : null,
+      max_seconds: options.maxSeconds,
+    };
+    return core.ops.op_send_speech(
+      toBigInt(userId),
+      toBigInt(groupId || 0),
+      JSON.stringify(payload)
+    );
+  },
+
+  // Generate images and send them to the chat
+  // options: { modelName, imageUrl, size, quality, n, timeoutMs }
+  // imageUrl: optional reference image (uses /images/edits)
+  generateImage: (userId, groupId, prompt, options = {}) => {
+    return core.ops.op_generate_image(
+      toBigInt(userId),
+      toBigInt(groupId || 0),
+      JSON.stringify(buildImageGenerationPayload(null, prompt, options))
+    );
+  },
+
+  // Promise form of generateImage: images are returned instead of sent
+  // options: same as generateImage, plus { userId, groupId } for usage limits
+  // Resolves with { requestId, content, data: { images: [{ base64, mime, revisedPrompt }] } }
+  llmGenerateImage: (prompt, options = {}) => {
+    if (!String(prompt ?? "").trim()) {
+      return Promise.reject(new LlmError("llm_error", "prompt must be a non-empty string"));
+    }
+    const requestId = `__llm_${Date.now()}_${++llmRequestSeq}`;
+    return new Promise((resolve, reject) => {
+      const accepted = trackLlmRequest(requestId, resolve, reject, () =>
+        core.ops.op_generate_image(
+          toBigInt(options.userId || 0),
+          toBigInt(options.groupId || 0),
+          JSON.stringify(buildImageGenerationPayload(requestId, prompt, options))
+        )
+      );
+      if (!accepted) {
+        reject(new LlmError("llm_error", "invalid generateImage options"));
+      }
+    });
+  },
+
   // Call LLM for multi-turn chat (async, result returned via onLlmResponse hook)
   // requestId: unique identifier for matching response
   // messages: array of {role: "system"|"user"|"assistant", content: "..."}
-  // options: { modelName?: string, maxTokens?: number }
-  // Returns immediately; result delivered via onLlmResponse({ requestId, success, content })
+  // options: { modelName?: string, maxTokens?: number, knowledgeBase?: string, knowledgeTopK?: number,
+  //            responseSchema?: object, schemaName?: string, jsonMode?: "auto"|"response_format"|"tool"|"prompt" }
+  // knowledgeBase: retrieve top-k chunks from this knowledge base into the prompt
+  // responseSchema: JSON Schema the reply must match; validated by the host (one corrective retry)
+  // Returns immediately; result delivered via onLlmResponse({ requestId, success, content, data?, error? })
+  // With responseSchema: data is the parsed object on success; error is { type, message, errors, raw } on failure
   callLlmChat: (requestId, messages, options = {}) => {
-    const payload = {
-      request_id: String(requestId),
-      model_name: options.modelName ? String(options.modelName) : null,
-      messages: Array.isArray(messages) ? messages : [],
-      max_tokens: options.maxTokens || null,
-    };
+    const payload = buildLlmChatPayload(requestId, messages, options);
     return core.ops.op_call_llm_chat(JSON.stringify(payload));
   },
 
+  // Promise style of callLlmChat (same options)
+  // Resolves with { requestId, content, data }; rejects with LlmError { type, message, errors, raw }
+  // Note: the calling hook returns before the promise settles; code after `await` runs
+  // when the result arrives, so use sendReply with explicit userId/groupId there.
+  llmChat: (messages, options = {}) => {
+    if (!Array.isArray(messages) || messages.length === 0) {
+      return Promise.reject(new LlmError("llm_error", "messages must be a non-empty array"));
+    }
+    const requestId = `__llm_${Date.now()}_${++llmRequestSeq}`;
+    return new Promise((resolve, reject) => {
+      const accepted = trackLlmRequest(requestId, resolve, reject, () =>
+        core.ops.op_call_llm_chat(
+          JSON.stringify(buildLlmChatPayload(requestId, messages, options))
+        )
+      );
+      if (!accepted) {
+        reject(new LlmError("llm_error", "invalid llmChat options"));
+      }
+    });
+  },
+
   // Call LLM with web search capability (async, result returned via onLlmResponse hook)
   // requestId: unique identifier for matching response
   // messages: array of {role: "system"|"user"|"assistant", content: "..."}
-  // options: { modelName?: string, maxTokens?: number, enableSearch?: boolean }
+  // options: { modelName?: string, maxTokens?: number, enableSearch?: boolean, knowledgeBase?: string, knowledgeTopK?: number }
   // Returns immediately; result delivered via onLlmResponse({ requestId, success, content })
   callLlmChatWithSearch: (requestId, messages, options = {}) => {
     const payload = {
@@ -303,15 +594,56 @@ globalThis.nbot = {
       messages: Array.isArray(messages) ? messages : [],
       max_tokens: options.maxTokens || null,
       enable_search: options.enableSearch !== false, // default true
+      knowledge_base: options.knowledgeBase ? String(options.knowledgeBase) : null,
+      knowledge_top_k: options.knowledgeTopK || null,
     };
     return core.ops.op_call_llm_chat_with_search(JSON.stringify(payload));
   },
 
+  // Search a knowledge base (async, result returned via onGroupInfoResponse hook)
+  // requestId: unique identifier for matching response
+  // knowledgeBase: knowledge base ID
+  // query: text to search for
+  // options: { topK?: number, minScore?: number }
+  // Result: onGroupInfoResponse({ requestId, infoType: "knowledge", success, data: [{ doc_id, title, text, score }] })
+  searchKnowledge: (requestId, knowledgeBase, query, options = {}) => {
+    const payload = {
+      request_id: String(requestId),
+      knowledge_base: String(knowledgeBase),
+      query: String(query),
+      top_k: options.topK || null,
+      min_score: typeof options.minScore === "number" ? options.minScore : null,
+    };
+    return core.ops.op_search_knowledge(JSON.stringify(payload));
+  },
+
+  // Query the local message archive (requires the message_archive module; async, result returned via onGroupInfoResponse hook)
+  // requestId: unique identifier for matching response
+  // options: { groupId?, peerId?, userId?, keyword?, since?, until?, beforeId?, limit? }
+  //   peerId: private chat partner; since/until: unix seconds; beforeId: archive id for paging
+  // Result: onGroupInfoResponse({ requestId, infoType: "message_history", success,
+  //   data: [{ id, message_id, group_id, peer_id, user_id, sender_name, outgoing, time, text, message }] })
+  queryMessageHistory: (requestId, options = {}) => {
+    const optNum = (v) => (v === undefined || v === null || v === "" ? null : Number(v));
+    const payload = {
+      request_id: String(requestId),
+      group_id: optNum(options.groupId),
+      peer_id: optNum(options.peerId),
+      user_id: optNum(options.userId),
+      keyword: options.keyword ? String(options.keyword) : null,
+      since: optNum(options.since),
+      until: optNum(options.until),
+      before_id: optNum(options.beforeId),
+      limit: optNum(options.limit),
+    };
+    return core.ops.op_query_message_history(JSON.stringify(payload));
+  },
+
   // Send forward message (merged forward message)
   // userId: target user ID
   // groupId: target group ID (0 for private message)
   // nodes: array of { name: string, content: string | onebotMessageSegments }
-  sendForwardMessage: (userId, groupId, nodes) => {
+  sendForwardMessage: (userId, groupId, nodes, options = {}) => {
     const normalizeContent = (c) => {
       if (c === undefined || c === null) return "";
       if (typeof c === "string") return c;
@@ -324,6 +656,7 @@ globalThis.nbot = {
         name: String(n.name || ""),
         content: normalizeContent(n.content),
       })) : [],
+      priority: options.priority ? String(options.priority) : null,
     };
     return core.ops.op_send_forward_message(
       toBigInt(userId),
@@ -332,9 +665,41 @@ globalThis.nbot = {
     );
   },
 
-  // HTTP fetch (async) - download content from URL
-  httpFetch: (url, timeoutMs = 30000) => {
-    return core.ops.op_http_fetch(url, timeoutMs);
+  // HTTP request (async)
+  // - httpFetch(url, timeoutMs): legacy form, resolves to the response text
+  // - httpFetch(url, { method, headers, body, json, form, timeoutMs, maxBytes, responseType }):
+  //   resolves to { status, statusText, ok, url, headers, text, base64, bytes, data }
+  //   body may be a string, Uint8Array or ArrayBuffer; responseType: text | json | base64 | bytes
+  httpFetch: (url, options = 30000) => {
+    const empty = new Uint8Array(0);
+    if (options == null || typeof options === "number") {
+      const payload = { url: String(url), timeoutMs: Number(options ?? 30000) };
+      return core.ops.op_http_fetch(JSON.stringify(payload), empty).then((r) => r.text ?? "");
+    }
+
+    const { body, ...rest } = options;
+    const payload = { ...rest, url: String(url) };
+    let binary = empty;
+    if (body instanceof ArrayBuffer) {
+      binary = new Uint8Array(body);
+      payload.binaryBody = true;
+    } else if (ArrayBuffer.isView(body)) {
+      binary = new Uint8Array(body.buffer, body.byteOffset, body.byteLength);
+      payload.binaryBody = true;
+    } else if (body != null) {
+      payload.body = String(body);
+    }
+
+    return core.ops.op_http_fetch(JSON.stringify(payload), binary).then((r) => {
+      if (String(options.responseType || "").toLowerCase() === "json") {
+        try {
+          r.data = r.text ? JSON.parse(r.text) : null;
+        } catch (e) {
+          throw new Error(`HTTP ${r.status}: response is not valid JSON: ${e.message}`);
+        }
+      }
+      return r;
+    });
   },
 
   // Render Markdown into an image (base64) using core renderer
@@ -489,7 +854,9 @@ globalThis.definePlugin = (config) => {
 // Export for ES modules
 export const sendMessage = globalThis.nbot.sendMessage;
 export const sendReply = globalThis.nbot.sendReply;
+export const prompt = globalThis.nbot.prompt;
 export const at = globalThis.nbot.at;
+export const segment = globalThis.nbot.segment;
 export const callApi = globalThis.nbot.callApi;
 export const callLlmForward = globalThis.nbot.callLlmForward;
 export const callLlmForwardFromUrl = globalThis.nbot.callLlmForwardFromUrl;
@@ -498,8 +865,14 @@ export const callLlmForwardImageFromUrl = globalThis.nbot.callLlmForwardImageFro
 export const callLlmForwardVideoFromUrl = globalThis.nbot.callLlmForwardVideoFromUrl;
 export const callLlmForwardAudioFromUrl = globalThis.nbot.callLlmForwardAudioFromUrl;
 export const callLlmForwardMediaBundle = globalThis.nbot.callLlmForwardMediaBundle;
+export const sendSpeech = globalThis.nbot.sendSpeech;
+export const generateImage = globalThis.nbot.generateImage;
+export const llmGenerateImage = globalThis.nbot.llmGenerateImage;
 export const callLlmChat = globalThis.nbot.callLlmChat;
 export const callLlmChatWithSearch = globalThis.nbot.callLlmChatWithSearch;
+export const llmChat = globalThis.nbot.llmChat;
+export const searchKnowledge = globalThis.nbot.searchKnowledge;
+export const queryMessageHistory = globalThis.nbot.queryMessageHistory;
 export const sendForwardMessage = globalThis.nbot.sendForwardMessage;
 export const httpFetch = globalThis.nbot.httpFetch;
 export const renderMarkdownImage = globalThis.nbot.renderMarkdownImage;
@@ -519,3 +892,4 @@ export const fetchGroupList = globalThis.nbot.fetchGroupList;
 export const fetchGroupMemberList = globalThis.nbot.fetchGroupMemberList;
 export const downloadFile = globalThis.nbot.downloadFile;
 export const definePlugin = globalThis.definePlugin;
+export { LlmError };
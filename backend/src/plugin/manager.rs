@@ -1,6 +1,8 @@
+use crate::outbound::HostPolicy;
 use crate::plugin::runtime::{PluginOutput, PluginRuntime};
 use crate::plugin::types::{InstalledPlugin, PluginCodeType};
 use dashmap::DashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::{mpsc, oneshot};
 use tracing::info;
@@ -18,6 +20,14 @@ pub struct HookResult {
     pub outputs: Vec<PluginOutputWithSource>,
 }
 
+/// preSend 钩子结果
+pub struct PreSendResult {
+    /// 拦截发送的插件（None 表示允许发送）
+    pub blocked_by: Option<String>,
+    /// 改写后的消息（OneBot 消息段数组或 CQ 码字符串），未改写时为 None
+    pub message: Option<serde_json::Value>,
+}
+
 /// 插件请求类型
 pub enum PluginRequest {
     Load {
@@ -26,6 +36,7 @@ pub enum PluginRequest {
         entry: String,
         code_type: PluginCodeType,
         config: serde_json::Value,
+        network: HostPolicy,
         respond: oneshot::Sender<Result<(), String>>,
     },
     UpdateConfig {
@@ -47,6 +58,11 @@ pub enum PluginRequest {
         ctx: serde_json::Value,
         respond: oneshot::Sender<HookResult>,
     },
+    PreSend {
+        plugin_id: String,
+        ctx: serde_json::Value,
+        respond: oneshot::Sender<Result<(bool, Option<String>), String>>,
+    },
     OnCommand {
         plugin_id: String,
         ctx: serde_json::Value,
@@ -67,6 +83,7 @@ pub enum PluginRequest {
         request_id: String,
         success: bool,
         content: String,
+        extra: Option<serde_json::Value>,
         respond: oneshot::Sender<Result<Vec<PluginOutput>, String>>,
     },
     OnGroupInfoResponse {
@@ -77,14 +94,32 @@ pub enum PluginRequest {
         data: String,
         respond: oneshot::Sender<Result<Vec<PluginOutput>, String>>,
     },
+    OnPromptReply {
+        plugin_id: String,
+        request_id: String,
+        reply: serde_json::Value,
+        respond: oneshot::Sender<Result<Vec<PluginOutput>, String>>,
+    },
 }
 
 /// 插件管理器 - 管理所有插件运行时
 pub struct PluginManager {
     tx: mpsc::Sender<PluginRequest>,
-    loaded_plugins: Arc<DashMap<String, ()>>,
+    loaded_plugins: Arc<DashMap<String, LoadedPlugin>>,
+}
+
+/// 已加载插件的信息（加载时记录）
+#[derive(Debug, Clone, Copy)]
+struct LoadedPlugin {
+    /// 本次加载的编号
+    load_id: u64,
+    /// 是否导出了 preSend 钩子
+    has_pre_send: bool,
 }
 
+/// 每次加载（包括重载）插件运行时分配新的编号
+static NEXT_LOAD_ID: AtomicU64 = AtomicU64::new(1);
+
 impl PluginManager {
     pub fn new(data_dir: &str) -> Self {
         let (tx, rx) = mpsc::channel::<PluginRequest>(100);
@@ -123,6 +158,7 @@ impl PluginManager {
                 entry: plugin.manifest.entry.clone(),
                 code_type: plugin.manifest.code_type,
                 config: plugin.manifest.config.clone(),
+                network: plugin.manifest.network.clone(),
                 respond,
             })
             .await
@@ -260,6 +296,64 @@ impl PluginManager {
         }
     }
 
+    /// 调用 preSend 钩子 - 消息发出前依次交给插件，前一个插件的改写结果传给下一个。
+    /// `source_plugin_id` 为发出消息的插件，其自身的 preSend 不会被调用；钩子出错时不拦截发送
+    pub async fn pre_send(
+        &self,
+        mut ctx: serde_json::Value,
+        source_plugin_id: Option<&str>,
+    ) -> PreSendResult {
+        let mut result = PreSendResult {
+            blocked_by: None,
+            message: None,
+        };
+        for plugin_id in pre_send_targets(&self.loaded_plugins, source_plugin_id) {
+            let (respond, rx) = oneshot::channel();
+            if let Err(e) = self
+                .tx
+                .send(PluginRequest::PreSend {
+                    plugin_id: plugin_id.clone(),
+                    ctx: ctx.clone(),
+                    respond,
+                })
+                .await
+            {
+                tracing::error!("发送插件 preSend 请求失败: {}: {}", plugin_id, e);
+                continue;
+            }
+
+            match rx.await {
+                Ok(Ok((allow, message))) => {
+                    if !allow {
+                        result.blocked_by = Some(plugin_id);
+                        return result;
+                    }
+                    let Some(message) = message else {
+                        continue;
+                    };
+                    match serde_json::from_str::<serde_json::Value>(&message) {
+                        Ok(message) => {
+                            ctx["segments"] = crate::segment::to_onebot(
+                                &crate::segment::parse_message(&message),
+                            );
+                            result.message = Some(message);
+                        }
+                        Err(e) => {
+                            tracing::warn!("插件 {} preSend 返回的消息无效: {}", plugin_id, e);
+                        }
+                    }
+                }
+                Ok(Err(e)) => {
+                    tracing::error!("插件 {} preSend 失败: {}", plugin_id, e);
+                }
+                Err(e) => {
+                    tracing::error!("接收插件 preSend 响应失败: {}: {}", plugin_id, e);
+                }
+            }
+        }
+        result
+    }
+
     /// 调用 onCommand 钩子 - 执行插件命令
     pub async fn on_command(
         &self,
@@ -406,16 +500,17 @@ impl PluginManager {
             .iter()
             .map(|r| r.key().clone())
             .collect();
-
-        plugin_ids.sort_by(|a, b| {
-            let pa = plugin_priority(a);
-            let pb = plugin_priority(b);
-            pa.cmp(&pb).then_with(|| a.cmp(b))
-        });
-
+        sort_by_priority(&mut plugin_ids);
         plugin_ids
     }
 
+    /// 是否有插件（发出消息的插件除外）导出了 preSend，没有时发送路径不必构造钩子上下文
+    pub fn has_pre_send_hook(&self, source_plugin_id: Option<&str>) -> bool {
+        self.loaded_plugins
+            .iter()
+            .any(|p| p.has_pre_send && source_plugin_id != Some(p.key().as_str()))
+    }
+
     /// 调用 onLlmResponse 钩子 - LLM 调用完成后的回调
     pub async fn on_llm_response(
         &self,
@@ -423,6 +518,7 @@ impl PluginManager {
         request_id: &str,
         success: bool,
         content: &str,
+        extra: Option<serde_json::Value>,
     ) -> Result<Vec<PluginOutput>, String> {
         let (respond, rx) = oneshot::channel();
         self.tx
@@ -431,6 +527,7 @@ impl PluginManager {
                 request_id: request_id.to_string(),
                 success,
                 content: content.to_string(),
+                extra,
                 respond,
             })
             .await
@@ -466,10 +563,38 @@ impl PluginManager {
             .map_err(|_| "接收插件 onGroupInfoResponse 响应失败".to_string())?
     }
 
+    /// 回传 ctx.prompt 的结果（用户回复 / 取消 / 超时）
+    pub async fn on_prompt_reply(
+        &self,
+        plugin_id: &str,
+        request_id: &str,
+        reply: serde_json::Value,
+    ) -> Result<Vec<PluginOutput>, String> {
+        let (respond, rx) = oneshot::channel();
+        self.tx
+            .send(PluginRequest::OnPromptReply {
+                plugin_id: plugin_id.to_string(),
+                request_id: request_id.to_string(),
+                reply,
+                respond,
+            })
+            .await
+            .map_err(|e| format!("发送插件 onPromptReply 请求失败: {}", e))?;
+
+        rx.await
+            .map_err(|_| "接收插件 onPromptReply 响应失败".to_string())?
+    }
+
     /// 检查插件是否已加载
     pub fn is_loaded(&self, plugin_id: &str) -> bool {
         self.loaded_plugins.contains_key(plugin_id)
     }
+
+    /// 插件当前运行时的加载编号，每次加载 / 重载都会变化；未加载时为 None。
+    /// 用于判断为插件登记的状态（如等待中的提问）是否仍属于当前运行时
+    pub fn load_id(&self, plugin_id: &str) -> Option<u64> {
+        self.loaded_plugins.get(plugin_id).map(|p| p.load_id)
+    }
 }
 
 fn plugin_priority(plugin_id: &str) -> i32 {
@@ -480,6 +605,29 @@ fn plugin_priority(plugin_id: &str) -> i32 {
     }
 }
 
+fn sort_by_priority(plugin_ids: &mut [String]) {
+    plugin_ids.sort_by(|a, b| {
+        let pa = plugin_priority(a);
+        let pb = plugin_priority(b);
+        pa.cmp(&pb).then_with(|| a.cmp(b))
+    });
+}
+
+/// 需要调用 preSend 的插件（按优先级排序）：只包括导出了 preSend 的插件，
+/// 发出消息的插件自身除外
+fn pre_send_targets(
+    loaded: &DashMap<String, LoadedPlugin>,
+    source_plugin_id: Option<&str>,
+) -> Vec<String> {
+    let mut plugin_ids: Vec<String> = loaded
+        .iter()
+        .filter(|p| p.has_pre_send && source_plugin_id != Some(p.key().as_str()))
+        .map(|p| p.key().clone())
+        .collect();
+    sort_by_priority(&mut plugin_ids);
+    plugin_ids
+}
+
 #[derive(Clone)]
 struct LoadedPluginMeta {
     plugin_id: String,
@@ -487,6 +635,7 @@ struct LoadedPluginMeta {
     entry: String,
     code_type: PluginCodeType,
     config: serde_json::Value,
+    network: HostPolicy,
 }
 
 struct LoadedPluginRuntime {
@@ -497,7 +646,7 @@ struct LoadedPluginRuntime {
 /// 插件工作线程
 async fn plugin_worker(
     mut rx: mpsc::Receiver<PluginRequest>,
-    loaded: Arc<DashMap<String, ()>>,
+    loaded: Arc<DashMap<String, LoadedPlugin>>,
     data_dir: String,
 ) {
     let mut runtimes: std::collections::HashMap<String, LoadedPluginRuntime> =
@@ -509,7 +658,7 @@ async fn plugin_worker(
     async fn load_one(
         runtimes: &mut std::collections::HashMap<String, LoadedPluginRuntime>,
         load_stack: &mut Vec<String>,
-        loaded: &DashMap<String, ()>,
+        loaded: &DashMap<String, LoadedPlugin>,
         data_dir: &str,
         meta: LoadedPluginMeta,
     ) -> Result<(), String> {
@@ -517,9 +666,16 @@ async fn plugin_worker(
             return Ok(());
         }
         let plugin_id = meta.plugin_id.clone();
-        let mut runtime =
-            PluginRuntime::new(&plugin_id, meta.config.clone(), data_dir, &meta.plugin_root)?;
+        let mut runtime = PluginRuntime::new(
+            &plugin_id,
+            meta.config.clone(),
+            meta.network.clone(),
+            data_dir,
+            &meta.plugin_root,
+        )?;
         runtime.load_plugin(&meta.entry, meta.code_type).await?;
+        // 检查失败时按已导出处理，宁可多调用一次
+        let has_pre_send = runtime.exports_hook("preSend").unwrap_or(true);
         runtimes.insert(
             plugin_id.clone(),
             LoadedPluginRuntime {
@@ -527,7 +683,13 @@ async fn plugin_worker(
                 runtime,
             },
         );
-        loaded.insert(plugin_id.clone(), ());
+        loaded.insert(
+            plugin_id.clone(),
+            LoadedPlugin {
+                load_id: NEXT_LOAD_ID.fetch_add(1, Ordering::Relaxed),
+                has_pre_send,
+            },
+        );
         load_stack.push(plugin_id.clone());
         info!("插件 {} 已加载", plugin_id);
         Ok(())
@@ -536,7 +698,7 @@ async fn plugin_worker(
     async fn unload_last(
         runtimes: &mut std::collections::HashMap<String, LoadedPluginRuntime>,
         load_stack: &mut Vec<String>,
-        loaded: &DashMap<String, ()>,
+        loaded: &DashMap<String, LoadedPlugin>,
         plugin_id: &str,
     ) -> Result<LoadedPluginMeta, String> {
         let last = load_stack
@@ -570,6 +732,7 @@ async fn plugin_worker(
                 entry,
                 code_type,
                 config,
+                network,
                 respond,
             } => {
                 let meta = LoadedPluginMeta {
@@ -578,6 +741,7 @@ async fn plugin_worker(
                     entry,
                     code_type,
                     config,
+                    network,
                 };
                 let result = load_one(
                     &mut runtimes,
@@ -740,6 +904,18 @@ async fn plugin_worker(
                 };
                 let _ = respond.send(result);
             }
+            PluginRequest::PreSend {
+                plugin_id,
+                ctx,
+                respond,
+            } => {
+                let result = if let Some(entry) = runtimes.get_mut(&plugin_id) {
+                    entry.runtime.pre_send(&ctx).await
+                } else {
+                    Ok((true, None))
+                };
+                let _ = respond.send(result);
+            }
             PluginRequest::OnCommand {
                 plugin_id,
                 ctx,
@@ -823,11 +999,12 @@ async fn plugin_worker(
                 request_id,
                 success,
                 content,
+                extra,
                 respond,
             } => {
                 let result = if let Some(entry) = runtimes.get_mut(&plugin_id) {
                     entry.runtime
-                        .on_llm_response(&request_id, success, &content)
+                        .on_llm_response(&request_id, success, &content, extra.as_ref())
                         .await
                 } else {
                     Err(format!("插件 {} 未加载", plugin_id))
@@ -851,6 +1028,19 @@ async fn plugin_worker(
                 };
                 let _ = respond.send(result);
             }
+            PluginRequest::OnPromptReply {
+                plugin_id,
+                request_id,
+                reply,
+                respond,
+            } => {
+                let result = if let Some(entry) = runtimes.get_mut(&plugin_id) {
+                    entry.runtime.on_prompt_reply(&request_id, &reply).await
+                } else {
+                    Err(format!("插件 {} 未加载", plugin_id))
+                };
+                let _ = respond.send(result);
+            }
         }
     }
 
@@ -859,3 +1049,37 @@ async fn plugin_worker(
         let _ = unload_last(&mut runtimes, &mut load_stack, &loaded, &id).await;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pre_send_targets_skip_source_and_plugins_without_hook() {
+        let loaded = DashMap::new();
+        for (id, has_pre_send) in [
+            ("sign", true),
+            ("whitelist", true),
+            ("echo", false),
+            ("filter", true),
+        ] {
+            let plugin = LoadedPlugin {
+                load_id: 1,
+                has_pre_send,
+            };
+            loaded.insert(id.to_string(), plugin);
+        }
+        assert_eq!(
+            pre_send_targets(&loaded, None),
+            vec!["whitelist", "filter", "sign"]
+        );
+        assert_eq!(
+            pre_send_targets(&loaded, Some("sign")),
+            vec!["whitelist", "filter"]
+        );
+        assert_eq!(
+            pre_send_targets(&loaded, Some("echo")),
+            vec!["whitelist", "filter", "sign"]
+        );
+    }
+}
@@ -7,15 +7,19 @@ mod ops;
 mod state;
 
 use ops::*;
-use state::{get_hook_result, reset_hook_state, take_outputs, PluginOpState};
+use state::{
+    get_hook_result, reset_hook_state, take_hook_message, take_hook_result, take_outputs,
+    PluginOpState,
+};
 
 pub use state::{ForwardNode, MediaBundleItem, PluginOutput};
 
 use super::types::PluginCodeType;
+use crate::outbound::HostPolicy;
 
 extension!(
     nbot_plugin,
-    ops = [op_send_message, op_send_reply, op_call_api, op_log, op_set_hook_result, op_now, op_get_config, op_set_config, op_storage_set, op_storage_get, op_storage_delete, op_get_plugin_id, op_call_llm_forward, op_call_llm_forward_from_url, op_call_llm_forward_archive_from_url, op_call_llm_forward_image_from_url, op_call_llm_forward_video_from_url, op_call_llm_forward_audio_from_url, op_call_llm_forward_media_bundle, op_call_llm_chat, op_call_llm_chat_with_search, op_send_forward_message, op_http_fetch, op_render_markdown_image, op_render_html_image, op_fetch_group_notice, op_fetch_group_msg_history, op_fetch_group_files, op_fetch_group_file_url, op_fetch_friend_list, op_fetch_group_list, op_fetch_group_member_list, op_download_file],
+    ops = [op_send_message, op_send_reply, op_send_reply_segments, op_await_reply, op_call_api, op_log, op_set_hook_result, op_set_hook_message, op_now, op_get_config, op_set_config, op_storage_set, op_storage_get, op_storage_delete, op_get_plugin_id, op_call_llm_forward, op_call_llm_forward_from_url, op_call_llm_forward_archive_from_url, op_call_llm_forward_image_from_url, op_call_llm_forward_video_from_url, op_call_llm_forward_audio_from_url, op_call_llm_forward_media_bundle, op_send_speech, op_generate_image, op_call_llm_chat, op_call_llm_chat_with_search, op_search_knowledge, op_query_message_history, op_send_forward_message, op_http_fetch, op_render_markdown_image, op_render_html_image, op_fetch_group_notice, op_fetch_group_msg_history, op_fetch_group_files, op_fetch_group_file_url, op_fetch_friend_list, op_fetch_group_list, op_fetch_group_member_list, op_download_file],
     esm_entry_point = "ext:nbot_plugin/runtime.js",
     esm = [dir "src/plugin/js", "runtime.js"],
 );
@@ -30,6 +34,7 @@ impl PluginRuntime {
     pub fn new(
         plugin_id: &str,
         config: serde_json::Value,
+        network: HostPolicy,
         data_dir: &str,
         plugin_root: &str,
     ) -> Result<Self, String> {
@@ -46,7 +51,9 @@ impl PluginRuntime {
                 plugin_id: plugin_id.to_string(),
                 config,
                 data_dir: data_dir.to_string(),
+                network,
                 hook_result: None,
+                hook_message: None,
                 outputs: Vec::new(),
             });
         }
@@ -148,6 +155,22 @@ impl PluginRuntime {
         Ok(())
     }
 
+    /// 插件是否导出了指定钩子（加载完成后检查，用于跳过未实现的钩子）
+    pub fn exports_hook(&mut self, name: &str) -> Result<bool, String> {
+        take_hook_result(&mut self.runtime);
+        let name_json =
+            serde_json::to_string(name).map_err(|e| format!("Serialize hook name failed: {e}"))?;
+        let code = format!(
+            r#"Deno.core.ops.op_set_hook_result(
+                !!globalThis.__plugin && typeof globalThis.__plugin[{name_json}] === "function"
+            );"#
+        );
+        self.runtime
+            .execute_script("<exports>", code)
+            .map_err(|e| format!("Check hook {} failed: {}", name, e))?;
+        Ok(take_hook_result(&mut self.runtime).unwrap_or(false))
+    }
+
     pub async fn on_disable(&mut self) -> Result<(), String> {
         let code = r#"
             (async () => {
@@ -219,7 +242,7 @@ impl PluginRuntime {
             r#"
             (async () => {{
                 if (globalThis.__plugin && globalThis.__plugin.preCommand) {{
-                    const result = await globalThis.__plugin.preCommand({});
+                    const result = await globalThis.__plugin.preCommand(globalThis.__nbotWithPrompt({}));
                     Deno.core.ops.op_set_hook_result(result !== false);
                 }} else {{
                     Deno.core.ops.op_set_hook_result(true);
@@ -257,7 +280,7 @@ impl PluginRuntime {
             r#"
             (async () => {{
                 if (globalThis.__plugin && globalThis.__plugin.preMessage) {{
-                    const result = await globalThis.__plugin.preMessage({});
+                    const result = await globalThis.__plugin.preMessage(globalThis.__nbotWithPrompt({}));
                     Deno.core.ops.op_set_hook_result(result !== false);
                 }} else {{
                     Deno.core.ops.op_set_hook_result(true);
@@ -294,7 +317,7 @@ impl PluginRuntime {
             r#"
             (async () => {{
                 if (globalThis.__plugin && globalThis.__plugin.onCommand) {{
-                    await globalThis.__plugin.onCommand({});
+                    await globalThis.__plugin.onCommand(globalThis.__nbotWithPrompt({}));
                 }}
             }})()
             "#,
@@ -313,6 +336,55 @@ impl PluginRuntime {
         Ok(take_outputs(&mut self.runtime))
     }
 
+    /// preSend 钩子：消息发出前调用，返回（是否允许发送，改写后的消息 JSON）。
+    /// 钩子中产生的输出（如再次发送消息）会被丢弃，避免循环
+    pub async fn pre_send(
+        &mut self,
+        ctx: &serde_json::Value,
+    ) -> Result<(bool, Option<String>), String> {
+        reset_hook_state(&mut self.runtime);
+
+        let ctx_json =
+            serde_json::to_string(ctx).map_err(|e| format!("Serialize ctx failed: {e}"))?;
+        let code = format!(
+            r#"
+            (async () => {{
+                if (globalThis.__plugin && globalThis.__plugin.preSend) {{
+                    const result = await globalThis.__plugin.preSend({});
+                    Deno.core.ops.op_set_hook_result(result !== false);
+                    if (Array.isArray(result) || typeof result === "string") {{
+                        Deno.core.ops.op_set_hook_message(JSON.stringify(result));
+                    }}
+                }} else {{
+                    Deno.core.ops.op_set_hook_result(true);
+                }}
+            }})()
+            "#,
+            ctx_json
+        );
+
+        self.runtime
+            .execute_script("<preSend>", code)
+            .map_err(|e| format!("preSend failed: {}", e))?;
+
+        self.runtime
+            .run_event_loop(Default::default())
+            .await
+            .map_err(|e| format!("preSend event loop failed: {}", e))?;
+
+        let result = get_hook_result(&mut self.runtime);
+        let message = take_hook_message(&mut self.runtime);
+        let dropped = take_outputs(&mut self.runtime);
+        if !dropped.is_empty() {
+            debug!(
+                "[插件:{}] preSend 中产生的 {} 个输出已忽略",
+                self.plugin_id,
+                dropped.len()
+            );
+        }
+        Ok((result, message))
+    }
+
     /// onNotice 钩子：处理通知事件（如灰条消息）
     pub async fn on_notice(
         &mut self,
@@ -326,7 +398,7 @@ impl PluginRuntime {
             r#"
             (async () => {{
                 if (globalThis.__plugin && globalThis.__plugin.onNotice) {{
-                    const result = await globalThis.__plugin.onNotice({});
+                    const result = await globalThis.__plugin.onNotice(globalThis.__nbotWithPrompt({}));
                     Deno.core.ops.op_set_hook_result(result !== false);
                 }} else {{
                     Deno.core.ops.op_set_hook_result(true);
@@ -391,11 +463,14 @@ impl PluginRuntime {
     /// request_id: 请求 ID（与 callLlmChat 时传入的一致）
     /// success: 是否成功
     /// content: 成功时为 LLM 回复内容，失败时为错误信息
+    /// extra: 附加字段（结构化输出时的 data / error），合并到回调参数中
+    /// 若该请求由 nbot.llmChat 的 Promise 发起，则由 runtime.js 直接 resolve/reject，不再调用钩子
     pub async fn on_llm_response(
         &mut self,
         request_id: &str,
         success: bool,
         content: &str,
+        extra: Option<&serde_json::Value>,
     ) -> Result<Vec<PluginOutput>, String> {
         take_outputs(&mut self.runtime);
 
@@ -403,20 +478,25 @@ impl PluginRuntime {
             .map_err(|e| format!("Serialize request_id failed: {e}"))?;
         let content_json =
             serde_json::to_string(content).map_err(|e| format!("Serialize content failed: {e}"))?;
+        let extra_json = match extra {
+            Some(v) => {
+                serde_json::to_string(v).map_err(|e| format!("Serialize extra failed: {e}"))?
+            }
+            None => "{}".to_string(),
+        };
 
         let code = format!(
             r#"
             (async () => {{
-                if (globalThis.__plugin && globalThis.__plugin.onLlmResponse) {{
-                    await globalThis.__plugin.onLlmResponse({{
-                        requestId: {},
-                        success: {},
-                        content: {}
-                    }});
-                }}
+                await globalThis.__nbotDispatchLlmResponse({{
+                    ...{},
+                    requestId: {},
+                    success: {},
+                    content: {}
+                }});
             }})()
             "#,
-            request_id_json, success, content_json
+            extra_json, request_id_json, success, content_json
         );
 
         self.runtime
@@ -431,9 +511,42 @@ impl PluginRuntime {
         Ok(take_outputs(&mut self.runtime))
     }
 
+    /// ctx.prompt 的回复：用户回复、取消或超时后由宿主回调，结果交给 runtime.js 中等待的 Promise
+    pub async fn on_prompt_reply(
+        &mut self,
+        request_id: &str,
+        reply: &serde_json::Value,
+    ) -> Result<Vec<PluginOutput>, String> {
+        take_outputs(&mut self.runtime);
+
+        let request_id_json = serde_json::to_string(request_id)
+            .map_err(|e| format!("Serialize request_id failed: {e}"))?;
+        let reply_json =
+            serde_json::to_string(reply).map_err(|e| format!("Serialize reply failed: {e}"))?;
+        let code = format!(
+            r#"
+            (async () => {{
+                await globalThis.__nbotDispatchPromptReply({}, {});
+            }})()
+            "#,
+            request_id_json, reply_json
+        );
+
+        self.runtime
+            .execute_script("<onPromptReply>", code)
+            .map_err(|e| format!("onPromptReply failed: {}", e))?;
+
+        self.runtime
+            .run_event_loop(Default::default())
+            .await
+            .map_err(|e| format!("onPromptReply event loop failed: {}", e))?;
+
+        Ok(take_outputs(&mut self.runtime))
+    }
+
     /// onGroupInfoResponse hook: callback after group info fetch completes
     /// request_id: request ID (matches the one passed to fetchGroupNotice/fetchGroupMsgHistory/etc.)
-    /// info_type: type of info ("notice", "msg_history", "files", "file_url", "download")
+    /// info_type: type of info ("notice", "msg_history", "files", "file_url", "download", "knowledge")
     /// success: whether the request succeeded
     /// data: JSON string of the response data (or error message if failed)
     pub async fn on_group_info_response(
@@ -43,7 +43,8 @@ fn push_reply(state: &mut OpState, user_id: i64, group_id: i64, content: &str) {
             } else {
                 None
             },
-            content: content.to_string(),
+            content: content.to_string().into(),
+            priority: None,
         });
 }
 
@@ -1,7 +1,13 @@
 use deno_core::{op2, OpState};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use super::{PluginOpState, PluginOutput};
+use crate::segment::MessageSegment;
+
+fn non_empty(s: &str) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_string())
+}
 
 // Op: Send message to QQ group (legacy, use op_send_reply instead)
 #[op2(fast)]
@@ -20,7 +26,8 @@ pub(in super::super) fn op_send_message(
             } else {
                 None
             },
-            content: content.to_string(),
+            content: content.to_string().into(),
+            priority: None,
         });
 }
 
@@ -31,7 +38,54 @@ pub(in super::super) fn op_send_reply(
     #[bigint] user_id: i64,
     #[bigint] group_id: i64,
     #[string] content: &str,
+    #[string] priority: &str,
+) {
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::SendReply {
+            user_id: user_id as u64,
+            group_id: if group_id > 0 {
+                Some(group_id as u64)
+            } else {
+                None
+            },
+            content: content.to_string().into(),
+            priority: non_empty(priority),
+        });
+}
+
+// Op: 以消息段数组发送回复
+#[op2(fast)]
+pub(in super::super) fn op_send_reply_segments(
+    state: &mut OpState,
+    #[bigint] user_id: i64,
+    #[bigint] group_id: i64,
+    #[string] segments_json: &str,
+    #[string] priority: &str,
 ) {
+    let values: Vec<serde_json::Value> = match serde_json::from_str(segments_json) {
+        Ok(v) => v,
+        Err(e) => {
+            super::log_json_parse_error(&*state, "sendReply(segments)", &e);
+            return;
+        }
+    };
+    // 无效的消息段只跳过该段，不影响其余内容
+    let segments: Vec<MessageSegment> = values
+        .iter()
+        .filter_map(|value| {
+            let seg = MessageSegment::from_value(value);
+            if seg.is_none() {
+                let plugin_id = &state.borrow::<PluginOpState>().plugin_id;
+                warn!("[插件:{}] sendReply 忽略无效的消息段: {}", plugin_id, value);
+            }
+            seg
+        })
+        .collect();
+    if segments.is_empty() {
+        return;
+    }
     state
         .borrow_mut::<PluginOpState>()
         .outputs
@@ -42,16 +96,55 @@ pub(in super::super) fn op_send_reply(
             } else {
                 None
             },
-            content: content.to_string(),
+            content: segments.into(),
+            priority: non_empty(priority),
         });
 }
 
+// Op: 等待该用户在同一会话中的下一条消息（ctx.prompt），返回是否已受理
+#[op2(fast)]
+pub(in super::super) fn op_await_reply(
+    state: &mut OpState,
+    #[string] request_id: &str,
+    #[bigint] user_id: i64,
+    #[bigint] group_id: i64,
+    timeout_ms: u32,
+    #[string] cancel_keywords_json: &str,
+) -> bool {
+    if request_id.trim().is_empty() || user_id <= 0 {
+        return false;
+    }
+    let cancel_keywords: Vec<String> = match serde_json::from_str(cancel_keywords_json) {
+        Ok(v) => v,
+        Err(e) => {
+            super::log_json_parse_error(&*state, "prompt(cancelKeywords)", &e);
+            return false;
+        }
+    };
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::AwaitReply {
+            request_id: request_id.to_string(),
+            user_id: user_id as u64,
+            group_id: if group_id > 0 {
+                Some(group_id as u64)
+            } else {
+                None
+            },
+            timeout_ms: u64::from(timeout_ms),
+            cancel_keywords,
+        });
+    true
+}
+
 // Op: 调用 QQ API
 #[op2(fast)]
 pub(in super::super) fn op_call_api(
     state: &mut OpState,
     #[string] action: &str,
     #[string] params_json: &str,
+    #[string] priority: &str,
 ) {
     let params: serde_json::Value = match serde_json::from_str(params_json) {
         Ok(v) => v,
@@ -66,6 +159,7 @@ pub(in super::super) fn op_call_api(
         .push(PluginOutput::CallApi {
             action: action.to_string(),
             params,
+            priority: non_empty(priority),
         });
 }
 
@@ -91,6 +185,12 @@ pub(in super::super) fn op_set_hook_result(state: &mut OpState, result: bool) {
     state.borrow_mut::<PluginOpState>().hook_result = Some(result);
 }
 
+// Op: 设置 preSend 钩子改写后的消息（JSON：消息段数组或 CQ 码字符串）
+#[op2(fast)]
+pub(in super::super) fn op_set_hook_message(state: &mut OpState, #[string] message: &str) {
+    state.borrow_mut::<PluginOpState>().hook_message = Some(message.to_string());
+}
+
 // Op: 获取当前时间戳（毫秒）
 #[op2(fast)]
 pub(in super::super) fn op_now() -> f64 {
@@ -1,26 +1,229 @@
-use deno_core::op2;
+use base64::Engine;
+use deno_core::error::{generic_error, AnyError};
+use deno_core::{op2, JsBuffer, OpState, ToJsBuffer};
+use futures_util::StreamExt;
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
 
-// Op: HTTP fetch (async)
+use super::PluginOpState;
+
+const DEFAULT_TIMEOUT_MS: u64 = 30_000;
+const MAX_TIMEOUT_MS: u64 = 120_000;
+const DEFAULT_MAX_BYTES: u64 = 10_000_000;
+const MAX_BYTES_LIMIT: u64 = 50_000_000;
+
+#[derive(serde::Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct HttpFetchPayload {
+    url: String,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    headers: Map<String, Value>,
+    /// 文本请求体
+    #[serde(default)]
+    body: Option<String>,
+    /// 请求体在 op 的第二个参数中（Uint8Array / ArrayBuffer）
+    #[serde(default)]
+    binary_body: bool,
+    /// JSON 请求体
+    #[serde(default)]
+    json: Option<Value>,
+    /// application/x-www-form-urlencoded 请求体
+    #[serde(default)]
+    form: Option<Map<String, Value>>,
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+    #[serde(default)]
+    max_bytes: Option<u64>,
+    /// text（默认）| json | base64 | bytes
+    #[serde(default)]
+    response_type: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(in super::super) struct HttpFetchResponse {
+    status: u16,
+    status_text: String,
+    ok: bool,
+    /// 重定向后的最终地址
+    url: String,
+    /// 响应头（名称小写，同名多值以 ", " 合并）
+    headers: Map<String, Value>,
+    text: Option<String>,
+    base64: Option<String>,
+    bytes: Option<ToJsBuffer>,
+}
+
+fn value_to_plain_string(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// 按 Content-Type 中的 charset 解码文本，缺省 UTF-8
+fn decode_body(bytes: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(|ct| {
+            ct.split(';').skip(1).find_map(|part| {
+                let (key, value) = part.split_once('=')?;
+                key.trim()
+                    .eq_ignore_ascii_case("charset")
+                    .then(|| value.trim().trim_matches('"'))
+            })
+        })
+        .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    let (text, _, _) = encoding.decode(bytes);
+    text.into_owned()
+}
+
+// Op: HTTP 请求（async）。payload 为 JSON，二进制请求体通过 binary_body 传入
 #[op2(async)]
-#[string]
+#[serde]
 pub(in super::super) async fn op_http_fetch(
-    #[string] url: String,
-    #[bigint] timeout_ms: i64,
-) -> Result<String, deno_core::error::AnyError> {
-    let client = reqwest::Client::new();
-    let timeout = std::time::Duration::from_millis(timeout_ms.clamp(1000, 60000) as u64);
-
-    let resp = client
-        .get(&url)
-        .timeout(timeout)
-        .send()
-        .await
-        .map_err(|e| deno_core::error::generic_error(format!("HTTP request failed: {}", e)))?;
+    state: Rc<RefCell<OpState>>,
+    #[string] payload_json: String,
+    #[buffer] binary_body: JsBuffer,
+) -> Result<HttpFetchResponse, AnyError> {
+    let payload: HttpFetchPayload = serde_json::from_str(&payload_json)
+        .map_err(|e| generic_error(format!("httpFetch 参数解析失败: {e}")))?;
+    let policy = state.borrow().borrow::<PluginOpState>().network.clone();
 
-    let bytes = resp
-        .bytes()
+    let method = payload
+        .method
+        .as_deref()
+        .map(|m| m.trim().to_ascii_uppercase())
+        .filter(|m| !m.is_empty())
+        .unwrap_or_else(|| "GET".to_string());
+    let method = match method.as_str() {
+        "GET" | "POST" | "PUT" | "PATCH" | "DELETE" | "HEAD" | "OPTIONS" => {
+            reqwest::Method::from_bytes(method.as_bytes())
+                .map_err(|e| generic_error(e.to_string()))?
+        }
+        other => return Err(generic_error(format!("不支持的 HTTP 方法: {other}"))),
+    };
+    let timeout_ms = payload
+        .timeout_ms
+        .unwrap_or(DEFAULT_TIMEOUT_MS)
+        .clamp(1000, MAX_TIMEOUT_MS);
+    let max_bytes = payload
+        .max_bytes
+        .unwrap_or(DEFAULT_MAX_BYTES)
+        .clamp(1, MAX_BYTES_LIMIT);
+    let response_type = payload
+        .response_type
+        .as_deref()
+        .unwrap_or("text")
+        .to_ascii_lowercase();
+    if !matches!(response_type.as_str(), "text" | "json" | "base64" | "bytes") {
+        return Err(generic_error(format!(
+            "不支持的 responseType: {response_type}"
+        )));
+    }
+
+    let mut request = crate::outbound::request(method, &payload.url, &policy)
         .await
-        .map_err(|e| deno_core::error::generic_error(format!("Failed to read response: {}", e)))?;
+        .map_err(generic_error)?
+        .timeout(std::time::Duration::from_millis(timeout_ms));
+    for (name, value) in &payload.headers {
+        request = request.header(name.as_str(), value_to_plain_string(value));
+    }
+    let body_kinds = [
+        payload.binary_body,
+        payload.body.is_some(),
+        payload.json.is_some(),
+        payload.form.is_some(),
+    ];
+    if body_kinds.iter().filter(|b| **b).count() > 1 {
+        return Err(generic_error("body / json / form 只能指定一个"));
+    }
+    if payload.binary_body {
+        request = request.body(binary_body.to_vec());
+    } else if let Some(body) = payload.body {
+        request = request.body(body);
+    } else if let Some(json) = &payload.json {
+        request = request.json(json);
+    } else if let Some(form) = &payload.form {
+        let pairs: Vec<(&str, String)> = form
+            .iter()
+            .map(|(k, v)| (k.as_str(), value_to_plain_string(v)))
+            .collect();
+        request = request.form(&pairs);
+    }
+
+    let resp = request.send().await.map_err(|e| {
+        generic_error(format!(
+            "HTTP request failed: {}",
+            crate::outbound::describe_error(&e)
+        ))
+    })?;
+
+    if resp.content_length().is_some_and(|len| len > max_bytes) {
+        return Err(generic_error(format!(
+            "响应过大：Content-Length={} bytes，超过限制 {max_bytes} bytes",
+            resp.content_length().unwrap_or_default()
+        )));
+    }
+
+    let status = resp.status();
+    let final_url = resp.url().to_string();
+    let mut headers = Map::new();
+    for (name, value) in resp.headers() {
+        let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+        match headers.get_mut(name.as_str()) {
+            Some(Value::String(existing)) => {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            }
+            _ => {
+                headers.insert(name.as_str().to_string(), Value::String(value));
+            }
+        }
+    }
+    let content_type = headers
+        .get("content-type")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    let mut body: Vec<u8> = Vec::new();
+    let mut stream = resp.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| generic_error(format!("Failed to read response: {e}")))?;
+        if body.len() as u64 + chunk.len() as u64 > max_bytes {
+            return Err(generic_error(format!(
+                "响应超过 {max_bytes} bytes 上限（可通过 maxBytes 调整）"
+            )));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    let (text, base64, bytes) = match response_type.as_str() {
+        "base64" => (
+            None,
+            Some(base64::engine::general_purpose::STANDARD.encode(&body)),
+            None,
+        ),
+        "bytes" => (None, None, Some(ToJsBuffer::from(body))),
+        _ => (
+            Some(decode_body(&body, content_type.as_deref())),
+            None,
+            None,
+        ),
+    };
 
-    Ok(String::from_utf8_lossy(&bytes).to_string())
+    Ok(HttpFetchResponse {
+        status: status.as_u16(),
+        status_text: status.canonical_reason().unwrap_or_default().to_string(),
+        ok: status.is_success(),
+        url: final_url,
+        headers,
+        text,
+        base64,
+        bytes,
+    })
 }
@@ -11,14 +11,24 @@ struct CallLlmChatPayload {
     messages: Vec<serde_json::Value>,
     #[serde(default)]
     max_tokens: Option<u32>,
+    #[serde(default)]
+    knowledge_base: Option<String>,
+    #[serde(default)]
+    knowledge_top_k: Option<u32>,
+    #[serde(default)]
+    response_schema: Option<serde_json::Value>,
+    #[serde(default)]
+    schema_name: Option<String>,
+    #[serde(default)]
+    json_mode: Option<String>,
 }
 
-// Op: 调用 LLM 进行多轮对话（异步返回结果）
+// Op: 调用 LLM 进行多轮对话（异步返回结果），返回请求是否已受理
 #[op2(fast)]
 pub(in super::super) fn op_call_llm_chat(
     state: &mut OpState,
     #[string] payload_json: &str,
-) {
+) -> bool {
     let Some(payload) = super::parse_payload_or_reply::<CallLlmChatPayload>(
         state,
         0,
@@ -26,15 +36,15 @@ pub(in super::super) fn op_call_llm_chat(
         "callLlmChat",
         payload_json,
     ) else {
-        return;
+        return false;
     };
 
     if payload.request_id.trim().is_empty() {
-        return;
+        return false;
     }
 
     if payload.messages.is_empty() {
-        return;
+        return false;
     }
 
     state
@@ -45,7 +55,13 @@ pub(in super::super) fn op_call_llm_chat(
             model_name: payload.model_name,
             messages: payload.messages,
             max_tokens: payload.max_tokens,
+            knowledge_base: payload.knowledge_base.filter(|s| !s.trim().is_empty()),
+            knowledge_top_k: payload.knowledge_top_k.map(|k| k.clamp(1, 20)),
+            response_schema: payload.response_schema.filter(|v| !v.is_null()),
+            schema_name: payload.schema_name.filter(|s| !s.trim().is_empty()),
+            json_mode: payload.json_mode.filter(|s| !s.trim().is_empty()),
         });
+    true
 }
 
 #[derive(serde::Deserialize, Default)]
@@ -58,6 +74,10 @@ struct CallLlmChatWithSearchPayload {
     max_tokens: Option<u32>,
     #[serde(default)]
     enable_search: Option<bool>,
+    #[serde(default)]
+    knowledge_base: Option<String>,
+    #[serde(default)]
+    knowledge_top_k: Option<u32>,
 }
 
 // Op: 调用支持联网搜索的 LLM（异步返回结果）
@@ -93,6 +113,111 @@ pub(in super::super) fn op_call_llm_chat_with_search(
             messages: payload.messages,
             max_tokens: payload.max_tokens,
             enable_search: payload.enable_search,
+            knowledge_base: payload.knowledge_base.filter(|s| !s.trim().is_empty()),
+            knowledge_top_k: payload.knowledge_top_k.map(|k| k.clamp(1, 20)),
+        });
+}
+
+#[derive(serde::Deserialize, Default)]
+struct SearchKnowledgePayload {
+    request_id: String,
+    knowledge_base: String,
+    query: String,
+    #[serde(default)]
+    top_k: Option<u32>,
+    #[serde(default)]
+    min_score: Option<f32>,
+}
+
+// Op: 检索知识库（异步返回结果，通过 onGroupInfoResponse 回调，infoType = "knowledge"）
+#[op2(fast)]
+pub(in super::super) fn op_search_knowledge(
+    state: &mut OpState,
+    #[string] payload_json: &str,
+) {
+    let Some(payload) = super::parse_payload_or_reply::<SearchKnowledgePayload>(
+        state,
+        0,
+        0,
+        "searchKnowledge",
+        payload_json,
+    ) else {
+        return;
+    };
+
+    if payload.request_id.trim().is_empty()
+        || payload.knowledge_base.trim().is_empty()
+        || payload.query.trim().is_empty()
+    {
+        return;
+    }
+
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::SearchKnowledge {
+            request_id: payload.request_id,
+            knowledge_base: payload.knowledge_base,
+            query: payload.query,
+            top_k: payload.top_k.unwrap_or(4).clamp(1, 20),
+            min_score: payload.min_score.unwrap_or(0.0).clamp(-1.0, 1.0),
+        });
+}
+
+#[derive(serde::Deserialize, Default)]
+struct QueryMessageHistoryPayload {
+    request_id: String,
+    #[serde(default)]
+    group_id: Option<u64>,
+    #[serde(default)]
+    peer_id: Option<u64>,
+    #[serde(default)]
+    user_id: Option<u64>,
+    #[serde(default)]
+    keyword: Option<String>,
+    #[serde(default)]
+    since: Option<i64>,
+    #[serde(default)]
+    until: Option<i64>,
+    #[serde(default)]
+    before_id: Option<i64>,
+    #[serde(default)]
+    limit: Option<u32>,
+}
+
+// Op: 查询本地消息存档（异步返回结果，通过 onGroupInfoResponse 回调，infoType = "message_history"）
+#[op2(fast)]
+pub(in super::super) fn op_query_message_history(
+    state: &mut OpState,
+    #[string] payload_json: &str,
+) {
+    let Some(payload) = super::parse_payload_or_reply::<QueryMessageHistoryPayload>(
+        state,
+        0,
+        0,
+        "queryMessageHistory",
+        payload_json,
+    ) else {
+        return;
+    };
+
+    if payload.request_id.trim().is_empty() {
+        return;
+    }
+
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::QueryMessageHistory {
+            request_id: payload.request_id,
+            group_id: payload.group_id.filter(|v| *v > 0),
+            peer_id: payload.peer_id.filter(|v| *v > 0),
+            user_id: payload.user_id.filter(|v| *v > 0),
+            keyword: payload.keyword.filter(|s| !s.trim().is_empty()),
+            since: payload.since,
+            until: payload.until,
+            before_id: payload.before_id,
+            limit: payload.limit.unwrap_or(50).clamp(1, 200),
         });
 }
 
@@ -100,6 +225,8 @@ pub(in super::super) fn op_call_llm_chat_with_search(
 struct SendForwardMessagePayload {
     #[serde(default)]
     nodes: Vec<ForwardNode>,
+    #[serde(default)]
+    priority: Option<String>,
 }
 
 // Op: 发送合并转发消息
@@ -131,9 +258,20 @@ pub(in super::super) fn op_send_forward_message(
             user_id: user_id as u64,
             group_id: group_id as u64,
             nodes: payload.nodes,
+            priority: payload.priority,
         });
 }
 
+#[derive(serde::Deserialize, Default)]
+struct CallLlmForwardPayload {
+    content: String,
+    title: String,
+    #[serde(default)]
+    model_name: Option<String>,
+    #[serde(default)]
+    output_mode: Option<String>,
+}
+
 // Op: 调用 LLM 并发送合并转发消息
 #[op2(fast)]
 pub(in super::super) fn op_call_llm_forward(
@@ -142,20 +280,30 @@ pub(in super::super) fn op_call_llm_forward(
     #[bigint] group_id: i64,
     #[string] system_prompt: &str,
     #[string] prompt: &str,
-    #[string] content: &str,
-    #[string] title: &str,
+    #[string] payload_json: &str,
 ) {
+    let Some(payload) = super::parse_payload_or_reply::<CallLlmForwardPayload>(
+        state,
+        user_id,
+        group_id,
+        "callLlmForward",
+        payload_json,
+    ) else {
+        return;
+    };
+
     state
         .borrow_mut::<PluginOpState>()
         .outputs
         .push(PluginOutput::CallLlmAndForward {
             user_id: user_id as u64,
             group_id: group_id as u64,
-            model_name: None,
+            model_name: payload.model_name.filter(|s| !s.trim().is_empty()),
+            output_mode: payload.output_mode.filter(|s| !s.trim().is_empty()),
             system_prompt: system_prompt.to_string(),
             prompt: prompt.to_string(),
-            content: content.to_string(),
-            title: title.to_string(),
+            content: payload.content,
+            title: payload.title,
         });
 }
 
@@ -163,6 +311,8 @@ pub(in super::super) fn op_call_llm_forward(
 struct CallLlmForwardFromUrlPayload {
     #[serde(default)]
     model_name: Option<String>,
+    #[serde(default)]
+    output_mode: Option<String>,
     url: String,
     title: String,
     #[serde(default)]
@@ -207,6 +357,7 @@ pub(in super::super) fn op_call_llm_forward_from_url(
             user_id: user_id as u64,
             group_id: group_id as u64,
             model_name: payload.model_name.filter(|s| !s.trim().is_empty()),
+            output_mode: payload.output_mode.filter(|s| !s.trim().is_empty()),
             system_prompt: system_prompt.to_string(),
             prompt: prompt.to_string(),
             url: payload.url,
@@ -225,6 +376,8 @@ pub(in super::super) fn op_call_llm_forward_from_url(
 struct CallLlmForwardArchiveFromUrlPayload {
     #[serde(default)]
     model_name: Option<String>,
+    #[serde(default)]
+    output_mode: Option<String>,
     url: String,
     title: String,
     #[serde(default)]
@@ -240,6 +393,8 @@ struct CallLlmForwardArchiveFromUrlPayload {
     #[serde(default)]
     max_files: Option<u32>,
     #[serde(default)]
+    max_bundle_files: Option<u32>,
+    #[serde(default)]
     keywords: Option<Vec<String>>,
 }
 
@@ -283,6 +438,7 @@ pub(in super::super) fn op_call_llm_forward_archive_from_url(
             user_id: user_id as u64,
             group_id: group_id as u64,
             model_name: payload.model_name.filter(|s| !s.trim().is_empty()),
+            output_mode: payload.output_mode.filter(|s| !s.trim().is_empty()),
             system_prompt: system_prompt.to_string(),
             prompt: prompt.to_string(),
             url: payload.url,
@@ -302,6 +458,7 @@ pub(in super::super) fn op_call_llm_forward_archive_from_url(
                 .unwrap_or(15_000_000)
                 .clamp(100_000, 200_000_000),
             max_files: payload.max_files.unwrap_or(50).clamp(1, 500),
+            max_bundle_files: payload.max_bundle_files.unwrap_or(1).clamp(1, 10),
             keywords,
         });
 }
@@ -310,6 +467,8 @@ pub(in super::super) fn op_call_llm_forward_archive_from_url(
 struct CallLlmForwardImageFromUrlPayload {
     #[serde(default)]
     model_name: Option<String>,
+    #[serde(default)]
+    output_mode: Option<String>,
     url: String,
     title: String,
     #[serde(default)]
@@ -360,6 +519,7 @@ pub(in super::super) fn op_call_llm_forward_image_from_url(
             user_id: user_id as u64,
             group_id: group_id as u64,
             model_name: payload.model_name.filter(|s| !s.trim().is_empty()),
+            output_mode: payload.output_mode.filter(|s| !s.trim().is_empty()),
             system_prompt: system_prompt.to_string(),
             prompt: prompt.to_string(),
             url: payload.url,
@@ -384,6 +544,8 @@ pub(in super::super) fn op_call_llm_forward_image_from_url(
 struct CallLlmForwardVideoFromUrlPayload {
     #[serde(default)]
     model_name: Option<String>,
+    #[serde(default)]
+    output_mode: Option<String>,
     url: String,
     title: String,
     #[serde(default)]
@@ -446,6 +608,7 @@ pub(in super::super) fn op_call_llm_forward_video_from_url(
             user_id: user_id as u64,
             group_id: group_id as u64,
             model_name: payload.model_name.filter(|s| !s.trim().is_empty()),
+            output_mode: payload.output_mode.filter(|s| !s.trim().is_empty()),
             system_prompt: system_prompt.to_string(),
             prompt: prompt.to_string(),
             url: payload.url,
@@ -476,6 +639,8 @@ pub(in super::super) fn op_call_llm_forward_video_from_url(
 struct CallLlmForwardAudioFromUrlPayload {
     #[serde(default)]
     model_name: Option<String>,
+    #[serde(default)]
+    output_mode: Option<String>,
     url: String,
     title: String,
     #[serde(default)]
@@ -538,6 +703,7 @@ pub(in super::super) fn op_call_llm_forward_audio_from_url(
             user_id: user_id as u64,
             group_id: group_id as u64,
             model_name: payload.model_name.filter(|s| !s.trim().is_empty()),
+            output_mode: payload.output_mode.filter(|s| !s.trim().is_empty()),
             system_prompt: system_prompt.to_string(),
             prompt: prompt.to_string(),
             url: payload.url,
@@ -558,6 +724,8 @@ pub(in super::super) fn op_call_llm_forward_audio_from_url(
 struct CallLlmForwardMediaBundlePayload {
     #[serde(default)]
     model_name: Option<String>,
+    #[serde(default)]
+    output_mode: Option<String>,
     title: String,
     #[serde(default)]
     text: Option<String>,
@@ -620,6 +788,7 @@ pub(in super::super) fn op_call_llm_forward_media_bundle(
             user_id: user_id as u64,
             group_id: group_id as u64,
             model_name: payload.model_name.filter(|s| !s.trim().is_empty()),
+            output_mode: payload.output_mode.filter(|s| !s.trim().is_empty()),
             system_prompt: system_prompt.to_string(),
             prompt: prompt.to_string(),
             title: payload.title,
@@ -647,3 +816,116 @@ pub(in super::super) fn op_call_llm_forward_media_bundle(
                 .clamp(10_000, 200_000_000),
         });
 }
+
+#[derive(serde::Deserialize, Default)]
+struct SendSpeechPayload {
+    #[serde(default)]
+    model_name: Option<String>,
+    text: String,
+    #[serde(default)]
+    voice: Option<String>,
+    #[serde(default)]
+    speed: Option<f64>,
+    #[serde(default)]
+    instructions: Option<String>,
+    #[serde(default)]
+    max_seconds: Option<u32>,
+}
+
+// Op: 文本转语音（TTS）并以语音消息发送
+#[op2(fast)]
+pub(in super::super) fn op_send_speech(
+    state: &mut OpState,
+    #[bigint] user_id: i64,
+    #[bigint] group_id: i64,
+    #[string] payload_json: &str,
+) {
+    let Some(payload) = super::parse_payload_or_reply::<SendSpeechPayload>(
+        state,
+        user_id,
+        group_id,
+        "sendSpeech",
+        payload_json,
+    ) else {
+        return;
+    };
+
+    if payload.text.trim().is_empty() {
+        super::push_reply(state, user_id, group_id, "插件内部错误：参数缺失");
+        return;
+    }
+
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::SendSpeech {
+            user_id: user_id as u64,
+            group_id: group_id as u64,
+            model_name: payload.model_name.filter(|s| !s.trim().is_empty()),
+            text: payload.text,
+            voice: payload.voice.filter(|s| !s.trim().is_empty()),
+            speed: payload.speed.map(|s| s.clamp(0.25, 4.0)),
+            instructions: payload.instructions.filter(|s| !s.trim().is_empty()),
+            max_seconds: payload.max_seconds.unwrap_or(120).clamp(1, 600),
+        });
+}
+
+#[derive(serde::Deserialize, Default)]
+struct GenerateImagePayload {
+    #[serde(default)]
+    request_id: Option<String>,
+    #[serde(default)]
+    model_name: Option<String>,
+    prompt: String,
+    #[serde(default)]
+    image_url: Option<String>,
+    #[serde(default)]
+    size: Option<String>,
+    #[serde(default)]
+    quality: Option<String>,
+    #[serde(default)]
+    n: Option<u32>,
+    #[serde(default)]
+    timeout_ms: Option<u64>,
+}
+
+// Op: 图片生成（直接发送，或带 request_id 时通过 onLlmResponse 回传），返回是否已受理
+#[op2(fast)]
+pub(in super::super) fn op_generate_image(
+    state: &mut OpState,
+    #[bigint] user_id: i64,
+    #[bigint] group_id: i64,
+    #[string] payload_json: &str,
+) -> bool {
+    let Some(payload) = super::parse_payload_or_reply::<GenerateImagePayload>(
+        state,
+        user_id,
+        group_id,
+        "generateImage",
+        payload_json,
+    ) else {
+        return false;
+    };
+
+    if payload.prompt.trim().is_empty() {
+        super::push_reply(state, user_id, group_id, "插件内部错误：参数缺失");
+        return false;
+    }
+
+    state
+        .borrow_mut::<PluginOpState>()
+        .outputs
+        .push(PluginOutput::GenerateImage {
+            user_id: user_id as u64,
+            group_id: group_id as u64,
+            request_id: payload.request_id.filter(|s| !s.trim().is_empty()),
+            model_name: payload.model_name.filter(|s| !s.trim().is_empty()),
+            prompt: payload.prompt,
+            image_url: payload.image_url.filter(|s| !s.trim().is_empty()),
+            size: payload.size.filter(|s| !s.trim().is_empty()),
+            quality: payload.quality.filter(|s| !s.trim().is_empty()),
+            n: payload.n.unwrap_or(1).clamp(1, 4),
+            timeout_ms: payload.timeout_ms.unwrap_or(30000).clamp(1000, 120000),
+        });
+    true
+}
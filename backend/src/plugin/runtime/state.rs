@@ -1,3 +1,4 @@
+use crate::segment::MessageContent;
 use deno_core::JsRuntime;
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -28,12 +29,28 @@ pub enum PluginOutput {
     SendReply {
         user_id: u64,
         group_id: Option<u64>,
-        content: String,
+        /// CQ 码字符串或消息段数组
+        content: MessageContent,
+        /// 发送优先级：high / normal / bulk（为空则沿用触发上下文）
+        #[serde(default)]
+        priority: Option<String>,
+    },
+    /// 等待同一会话中该用户的下一条消息（ctx.prompt，结果通过 __nbotDispatchPromptReply 返回）
+    AwaitReply {
+        request_id: String,
+        user_id: u64,
+        group_id: Option<u64>,
+        timeout_ms: u64,
+        /// 取消关键词（整条消息与其一致时取消等待）
+        cancel_keywords: Vec<String>,
     },
     /// 调用 QQ API
     CallApi {
         action: String,
         params: serde_json::Value,
+        /// 发送优先级（仅发送消息类 API 生效）
+        #[serde(default)]
+        priority: Option<String>,
     },
     /// 调用 LLM 并发送结果（合并转发）
     CallLlmAndForward {
@@ -42,6 +59,9 @@ pub enum PluginOutput {
         /// 指定模型映射名称（为空则使用默认模型）
         #[serde(default)]
         model_name: Option<String>,
+        /// 结果发送方式：image / forward / text / file（为空则按 bot 配置）
+        #[serde(default)]
+        output_mode: Option<String>,
         system_prompt: String,
         prompt: String,
         content: String,
@@ -54,6 +74,9 @@ pub enum PluginOutput {
         /// 指定模型映射名称（为空则使用默认模型）
         #[serde(default)]
         model_name: Option<String>,
+        /// 结果发送方式：image / forward / text / file（为空则按 bot 配置）
+        #[serde(default)]
+        output_mode: Option<String>,
         system_prompt: String,
         prompt: String,
         url: String,
@@ -74,6 +97,9 @@ pub enum PluginOutput {
         /// 指定模型映射名称（为空则使用默认模型）
         #[serde(default)]
         model_name: Option<String>,
+        /// 结果发送方式：image / forward / text / file（为空则按 bot 配置）
+        #[serde(default)]
+        output_mode: Option<String>,
         system_prompt: String,
         prompt: String,
         url: String,
@@ -90,6 +116,9 @@ pub enum PluginOutput {
         max_file_bytes: u64,
         #[serde(default)]
         max_files: u32,
+        /// 发送给模型的文件数（按相关度取前 N 个，1 为单文件模式）
+        #[serde(default)]
+        max_bundle_files: u32,
         #[serde(default)]
         keywords: Vec<String>,
     },
@@ -100,6 +129,9 @@ pub enum PluginOutput {
         /// 指定模型映射名称（为空则使用默认模型）
         #[serde(default)]
         model_name: Option<String>,
+        /// 结果发送方式：image / forward / text / file（为空则按 bot 配置）
+        #[serde(default)]
+        output_mode: Option<String>,
         system_prompt: String,
         prompt: String,
         url: String,
@@ -126,6 +158,9 @@ pub enum PluginOutput {
         /// 指定模型映射名称（为空则使用默认模型）
         #[serde(default)]
         model_name: Option<String>,
+        /// 结果发送方式：image / forward / text / file（为空则按 bot 配置）
+        #[serde(default)]
+        output_mode: Option<String>,
         system_prompt: String,
         prompt: String,
         url: String,
@@ -165,6 +200,9 @@ pub enum PluginOutput {
         /// 指定模型映射名称（为空则使用默认模型）
         #[serde(default)]
         model_name: Option<String>,
+        /// 结果发送方式：image / forward / text / file（为空则按 bot 配置）
+        #[serde(default)]
+        output_mode: Option<String>,
         system_prompt: String,
         prompt: String,
         url: String,
@@ -190,6 +228,9 @@ pub enum PluginOutput {
         /// 指定模型映射名称（为空则使用默认模型）
         #[serde(default)]
         model_name: Option<String>,
+        /// 结果发送方式：image / forward / text / file（为空则按 bot 配置）
+        #[serde(default)]
+        output_mode: Option<String>,
         system_prompt: String,
         prompt: String,
         title: String,
@@ -216,6 +257,52 @@ pub enum PluginOutput {
         #[serde(default)]
         audio_max_bytes: u64,
     },
+    /// 文本转语音并以语音（record）消息发送
+    SendSpeech {
+        user_id: u64,
+        group_id: u64,
+        /// 指定模型映射名称（为空则使用 "tts" 映射，不存在时使用默认模型）
+        #[serde(default)]
+        model_name: Option<String>,
+        text: String,
+        /// 音色，覆盖模型映射中的 tts.voice
+        #[serde(default)]
+        voice: Option<String>,
+        /// 语速（0.25 ~ 4.0），覆盖模型映射中的 tts.speed
+        #[serde(default)]
+        speed: Option<f64>,
+        /// 语气/风格说明（支持 instructions 的模型可用）
+        #[serde(default)]
+        instructions: Option<String>,
+        /// 语音最长秒数，超出部分截断
+        #[serde(default)]
+        max_seconds: u32,
+    },
+    /// 生成图片：无 request_id 时直接以图片消息发送，否则通过 onLlmResponse 回传
+    GenerateImage {
+        user_id: u64,
+        group_id: u64,
+        /// 请求 ID（由 nbot.llmGenerateImage 生成），用于匹配响应
+        #[serde(default)]
+        request_id: Option<String>,
+        /// 指定模型映射名称（为空则使用 "image" 映射，不存在时使用默认模型）
+        #[serde(default)]
+        model_name: Option<String>,
+        prompt: String,
+        /// 参考图 URL（提供时调用 /images/edits）
+        #[serde(default)]
+        image_url: Option<String>,
+        /// 尺寸，如 "1024x1024"
+        #[serde(default)]
+        size: Option<String>,
+        #[serde(default)]
+        quality: Option<String>,
+        /// 生成张数
+        #[serde(default)]
+        n: u32,
+        #[serde(default)]
+        timeout_ms: u64,
+    },
     /// 调用 LLM 进行多轮对话（异步返回结果，不直接发送）
     CallLlmChat {
         /// 请求 ID，用于匹配响应
@@ -228,6 +315,21 @@ pub enum PluginOutput {
         /// 最大 token 数
         #[serde(default)]
         max_tokens: Option<u32>,
+        /// 检索增强：从该知识库检索参考资料插入提示词
+        #[serde(default)]
+        knowledge_base: Option<String>,
+        /// 检索的块数量（默认 4）
+        #[serde(default)]
+        knowledge_top_k: Option<u32>,
+        /// 结构化输出：回复必须符合的 JSON Schema（校验失败会带着错误重试一次）
+        #[serde(default)]
+        response_schema: Option<serde_json::Value>,
+        /// Schema 名称（用于 response_format / 工具名），默认 "result"
+        #[serde(default)]
+        schema_name: Option<String>,
+        /// 结构化输出方式："auto"（默认）| "response_format" | "tool" | "prompt"
+        #[serde(default)]
+        json_mode: Option<String>,
     },
     /// 调用支持联网搜索的 LLM（异步返回结果）
     CallLlmChatWithSearch {
@@ -244,6 +346,12 @@ pub enum PluginOutput {
         /// 是否启用联网搜索（默认 true）
         #[serde(default)]
         enable_search: Option<bool>,
+        /// 检索增强：从该知识库检索参考资料插入提示词
+        #[serde(default)]
+        knowledge_base: Option<String>,
+        /// 检索的块数量（默认 4）
+        #[serde(default)]
+        knowledge_top_k: Option<u32>,
     },
     /// 发送合并转发消息
     SendForwardMessage {
@@ -251,6 +359,9 @@ pub enum PluginOutput {
         group_id: u64,
         /// 转发消息节点列表 [{ name, content }]
         nodes: Vec<ForwardNode>,
+        /// 发送优先级：high / normal / bulk（为空则沿用触发上下文）
+        #[serde(default)]
+        priority: Option<String>,
     },
     /// 获取群公告（异步返回结果）
     FetchGroupNotice {
@@ -324,6 +435,46 @@ pub enum PluginOutput {
         #[serde(default)]
         headers: Option<Vec<String>>,
     },
+    /// 检索知识库（异步返回结果，infoType 为 "knowledge"）
+    SearchKnowledge {
+        /// 请求 ID，用于匹配响应
+        request_id: String,
+        /// 知识库 ID
+        knowledge_base: String,
+        /// 检索内容
+        query: String,
+        /// 返回的块数量
+        top_k: u32,
+        /// 最低相似度（0~1）
+        min_score: f32,
+    },
+    /// 查询本地消息存档（异步返回结果，infoType 为 "message_history"）
+    QueryMessageHistory {
+        /// 请求 ID，用于匹配响应
+        request_id: String,
+        /// 群号
+        #[serde(default)]
+        group_id: Option<u64>,
+        /// 私聊对象
+        #[serde(default)]
+        peer_id: Option<u64>,
+        /// 发送者
+        #[serde(default)]
+        user_id: Option<u64>,
+        /// 关键词（空格分隔，需全部匹配）
+        #[serde(default)]
+        keyword: Option<String>,
+        /// 起止时间（Unix 秒）
+        #[serde(default)]
+        since: Option<i64>,
+        #[serde(default)]
+        until: Option<i64>,
+        /// 分页：只返回存档 id 小于该值的记录
+        #[serde(default)]
+        before_id: Option<i64>,
+        /// 返回条数
+        limit: u32,
+    },
 }
 
 /// 合并转发消息节点
@@ -341,7 +492,11 @@ pub(super) struct PluginOpState {
     pub(super) plugin_id: String,
     pub(super) config: serde_json::Value,
     pub(super) data_dir: String,
+    /// manifest 中声明的可访问主机
+    pub(super) network: crate::outbound::HostPolicy,
     pub(super) hook_result: Option<bool>,
+    /// preSend 钩子改写后的消息（JSON）
+    pub(super) hook_message: Option<String>,
     pub(super) outputs: Vec<PluginOutput>,
 }
 
@@ -357,6 +512,7 @@ pub(super) fn reset_hook_state(runtime: &mut JsRuntime) {
     let mut op_state = op_state.borrow_mut();
     let state = op_state.borrow_mut::<PluginOpState>();
     state.hook_result = None;
+    state.hook_message = None;
     state.outputs.clear();
 }
 
@@ -366,3 +522,17 @@ pub(super) fn get_hook_result(runtime: &mut JsRuntime) -> bool {
     let state = op_state.borrow::<PluginOpState>();
     state.hook_result.unwrap_or(true)
 }
+
+pub(super) fn take_hook_result(runtime: &mut JsRuntime) -> Option<bool> {
+    let op_state = runtime.op_state();
+    let mut op_state = op_state.borrow_mut();
+    let state = op_state.borrow_mut::<PluginOpState>();
+    state.hook_result.take()
+}
+
+pub(super) fn take_hook_message(runtime: &mut JsRuntime) -> Option<String> {
+    let op_state = runtime.op_state();
+    let mut op_state = op_state.borrow_mut();
+    let state = op_state.borrow_mut::<PluginOpState>();
+    state.hook_message.take()
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::outbound::HostPolicy;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum PluginType {
@@ -79,6 +81,9 @@ pub struct PluginManifest {
     pub config_schema: Vec<ConfigSchemaItem>,
     #[serde(default)]
     pub config: serde_json::Value,
+    /// 插件可访问的主机（httpFetch 与插件发起的下载）
+    #[serde(default, skip_serializing_if = "HostPolicy::is_empty")]
+    pub network: HostPolicy,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,441 @@
+//! 统一的消息段模型，QQ（OneBot）与 Discord 共用。
+//!
+//! - 序列化为 OneBot 消息段：`{ "type": "text", "data": { "text": "..." } }`
+//! - 兼容旧的 CQ 码字符串（如 `[CQ:image,file=base64://...]`），可互相转换
+//! - Discord 发送时按消息段渲染为正文、提及、附件与回复引用（见 `bot::runtime::api`）
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Map, Value};
+
+/// 除文本外的类型化消息段带有 `extra`：未单独建模的其他 OneBot 字段（如图片的 `summary` /
+/// `sub_type` / `url`、视频的 `thumb`），转回 OneBot 时原样带上
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageSegment {
+    Text {
+        text: String,
+    },
+    /// `qq` 为 QQ 号 / Discord 用户 ID，`all` 表示全体成员
+    At {
+        qq: String,
+        extra: Map<String, Value>,
+    },
+    /// 回复（引用）某条消息
+    Reply {
+        id: String,
+        extra: Map<String, Value>,
+    },
+    /// `file` 可为 URL、本地路径或 `base64://...`
+    Image {
+        file: String,
+        extra: Map<String, Value>,
+    },
+    Record {
+        file: String,
+        extra: Map<String, Value>,
+    },
+    Video {
+        file: String,
+        extra: Map<String, Value>,
+    },
+    File {
+        file: String,
+        name: Option<String>,
+        extra: Map<String, Value>,
+    },
+    Face {
+        id: String,
+        extra: Map<String, Value>,
+    },
+    /// 已有的合并转发消息
+    Forward {
+        id: String,
+        extra: Map<String, Value>,
+    },
+    /// 其他类型，原样保留
+    Other {
+        kind: String,
+        data: Map<String, Value>,
+    },
+}
+
+// 构造器
+impl MessageSegment {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text { text: text.into() }
+    }
+
+    pub fn reply(id: impl ToString) -> Self {
+        Self::Reply {
+            id: id.to_string(),
+            extra: Map::new(),
+        }
+    }
+
+    /// base64 编码的图片（不含 `base64://` 前缀）
+    pub fn image_base64(data: &str) -> Self {
+        Self::Image {
+            file: format!("base64://{data}"),
+            extra: Map::new(),
+        }
+    }
+
+    pub fn record(file: impl Into<String>) -> Self {
+        Self::Record {
+            file: file.into(),
+            extra: Map::new(),
+        }
+    }
+}
+
+impl MessageSegment {
+    /// OneBot 消息段类型名
+    pub fn kind(&self) -> &str {
+        match self {
+            Self::Text { .. } => "text",
+            Self::At { .. } => "at",
+            Self::Reply { .. } => "reply",
+            Self::Image { .. } => "image",
+            Self::Record { .. } => "record",
+            Self::Video { .. } => "video",
+            Self::File { .. } => "file",
+            Self::Face { .. } => "face",
+            Self::Forward { .. } => "forward",
+            Self::Other { kind, .. } => kind,
+        }
+    }
+
+    /// 未单独建模的其他字段；文本段与 `Other` 没有
+    fn extra(&self) -> Option<&Map<String, Value>> {
+        match self {
+            Self::At { extra, .. }
+            | Self::Reply { extra, .. }
+            | Self::Image { extra, .. }
+            | Self::Record { extra, .. }
+            | Self::Video { extra, .. }
+            | Self::File { extra, .. }
+            | Self::Face { extra, .. }
+            | Self::Forward { extra, .. } => Some(extra),
+            Self::Text { .. } | Self::Other { .. } => None,
+        }
+    }
+
+    fn data(&self) -> Map<String, Value> {
+        let mut data = self.extra().cloned().unwrap_or_default();
+        let mut put = |k: &str, v: &str| {
+            data.insert(k.to_string(), Value::String(v.to_string()));
+        };
+        match self {
+            Self::Text { text } => put("text", text),
+            Self::At { qq, .. } => put("qq", qq),
+            Self::Reply { id, .. } | Self::Face { id, .. } | Self::Forward { id, .. } => {
+                put("id", id)
+            }
+            Self::Image { file, .. } | Self::Record { file, .. } | Self::Video { file, .. } => {
+                put("file", file)
+            }
+            Self::File { file, name, .. } => {
+                put("file", file);
+                if let Some(name) = name {
+                    put("name", name);
+                }
+            }
+            Self::Other { data: other, .. } => return other.clone(),
+        }
+        data
+    }
+
+    /// 转为 OneBot 消息段
+    pub fn to_value(&self) -> Value {
+        json!({ "type": self.kind(), "data": self.data() })
+    }
+
+    /// 解析 OneBot 消息段；缺少必要字段时返回 `None`
+    pub fn from_value(value: &Value) -> Option<Self> {
+        let kind = value.get("type")?.as_str()?.trim();
+        let data = value
+            .get("data")
+            .and_then(|d| d.as_object())
+            .cloned()
+            .unwrap_or_default();
+        Self::from_parts(kind, data)
+    }
+
+    /// 取出已建模的字段，其余字段留在 `extra` 中
+    fn from_parts(kind: &str, mut data: Map<String, Value>) -> Option<Self> {
+        fn take(data: &mut Map<String, Value>, k: &str) -> Option<String> {
+            let value = match data.get(k)? {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                _ => return None,
+            };
+            data.remove(k);
+            Some(value)
+        }
+        // 未给出 file 时用 url（此时 url 已作为 file 发送，不再重复）
+        fn take_file(data: &mut Map<String, Value>) -> Option<String> {
+            take(data, "file").or_else(|| take(data, "url"))
+        }
+        let seg = match kind {
+            "text" => Self::Text {
+                text: take(&mut data, "text")?,
+            },
+            "at" => Self::At {
+                qq: take(&mut data, "qq")?,
+                extra: data,
+            },
+            "reply" => Self::Reply {
+                id: take(&mut data, "id")?,
+                extra: data,
+            },
+            "image" => Self::Image {
+                file: take_file(&mut data)?,
+                extra: data,
+            },
+            "record" => Self::Record {
+                file: take_file(&mut data)?,
+                extra: data,
+            },
+            "video" => Self::Video {
+                file: take_file(&mut data)?,
+                extra: data,
+            },
+            "file" => Self::File {
+                file: take_file(&mut data)?,
+                name: take(&mut data, "name"),
+                extra: data,
+            },
+            "face" => Self::Face {
+                id: take(&mut data, "id")?,
+                extra: data,
+            },
+            "forward" => Self::Forward {
+                id: take(&mut data, "id")?,
+                extra: data,
+            },
+            "" => return None,
+            other => Self::Other {
+                kind: other.to_string(),
+                data,
+            },
+        };
+        Some(seg)
+    }
+
+    /// 转为 CQ 码（纯文本段只做转义）
+    pub fn to_cq(&self) -> String {
+        if let Self::Text { text } = self {
+            return escape_cq_text(text);
+        }
+        let mut out = format!("[CQ:{}", self.kind());
+        for (k, v) in self.data() {
+            let v = match v {
+                Value::String(s) => s,
+                other => other.to_string(),
+            };
+            out.push(',');
+            out.push_str(&k);
+            out.push('=');
+            out.push_str(&escape_cq_param(&v));
+        }
+        out.push(']');
+        out
+    }
+}
+
+impl Serialize for MessageSegment {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_value().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageSegment {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        Self::from_value(&value)
+            .ok_or_else(|| serde::de::Error::custom(format!("无效的消息段: {value}")))
+    }
+}
+
+/// 消息内容：CQ 码字符串（旧写法）或消息段数组
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Segments(Vec<MessageSegment>),
+}
+
+impl From<String> for MessageContent {
+    fn from(s: String) -> Self {
+        Self::Text(s)
+    }
+}
+
+impl From<Vec<MessageSegment>> for MessageContent {
+    fn from(segs: Vec<MessageSegment>) -> Self {
+        Self::Segments(segs)
+    }
+}
+
+/// 转为 OneBot 消息段数组
+pub fn to_onebot(segments: &[MessageSegment]) -> Value {
+    Value::Array(segments.iter().map(MessageSegment::to_value).collect())
+}
+
+/// 转为 CQ 码字符串
+pub fn to_cq_string(segments: &[MessageSegment]) -> String {
+    segments.iter().map(MessageSegment::to_cq).collect()
+}
+
+/// 解析 OneBot `message`：字符串按 CQ 码解析，数组逐段解析（无效段跳过）
+pub fn parse_message(value: &Value) -> Vec<MessageSegment> {
+    match value {
+        Value::String(s) => parse_cq(s),
+        Value::Array(items) => items
+            .iter()
+            .filter_map(MessageSegment::from_value)
+            .collect(),
+        Value::Object(_) => MessageSegment::from_value(value).into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 解析 CQ 码字符串；不完整的 CQ 码按纯文本处理
+pub fn parse_cq(message: &str) -> Vec<MessageSegment> {
+    let mut out: Vec<MessageSegment> = Vec::new();
+    let mut text = String::new();
+    let mut rest = message;
+
+    while let Some(start) = rest.find("[CQ:") {
+        text.push_str(&rest[..start]);
+        let after = &rest[start + 4..];
+        let Some(end) = after.find(']') else {
+            text.push_str(&rest[start..]);
+            rest = "";
+            break;
+        };
+        let body = &after[..end];
+        rest = &after[end + 1..];
+
+        let mut parts = body.split(',');
+        let kind = parts.next().unwrap_or("").trim();
+        let mut data = Map::new();
+        for part in parts {
+            if let Some((k, v)) = part.split_once('=') {
+                data.insert(k.trim().to_string(), Value::String(unescape_cq(v)));
+            }
+        }
+        match MessageSegment::from_parts(kind, data) {
+            Some(seg) => {
+                if !text.is_empty() {
+                    out.push(MessageSegment::Text {
+                        text: unescape_cq(&std::mem::take(&mut text)),
+                    });
+                }
+                out.push(seg);
+            }
+            None => {
+                text.push_str("[CQ:");
+                text.push_str(body);
+                text.push(']');
+            }
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        out.push(MessageSegment::Text {
+            text: unescape_cq(&text),
+        });
+    }
+    out
+}
+
+fn escape_cq_text(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('[', "&#91;")
+        .replace(']', "&#93;")
+}
+
+fn escape_cq_param(s: &str) -> String {
+    escape_cq_text(s).replace(',', "&#44;")
+}
+
+fn unescape_cq(s: &str) -> String {
+    s.replace("&#44;", ",")
+        .replace("&#91;", "[")
+        .replace("&#93;", "]")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cq_round_trip() {
+        let raw = "hi [CQ:at,qq=123] see [CQ:image,file=base64://AAA=] a&#44;b &#91;x&#93;";
+        let segs = parse_cq(raw);
+        assert_eq!(
+            segs,
+            vec![
+                MessageSegment::text("hi "),
+                MessageSegment::At {
+                    qq: "123".to_string(),
+                    extra: Map::new(),
+                },
+                MessageSegment::text(" see "),
+                MessageSegment::image_base64("AAA="),
+                MessageSegment::text(" a,b [x]"),
+            ]
+        );
+        assert_eq!(parse_cq(&to_cq_string(&segs)), segs);
+    }
+
+    #[test]
+    fn onebot_values_and_content() {
+        let content: MessageContent = serde_json::from_value(json!([
+            { "type": "reply", "data": { "id": 42 } },
+            { "type": "text", "data": { "text": "ok" } },
+            { "type": "poke", "data": { "qq": "1" } }
+        ]))
+        .unwrap();
+        let MessageContent::Segments(segs) = content else {
+            panic!("expected segments");
+        };
+        assert_eq!(segs[0], MessageSegment::reply(42));
+        assert_eq!(segs[2].kind(), "poke");
+        assert_eq!(
+            to_onebot(&segs)[0],
+            json!({ "type": "reply", "data": { "id": "42" } })
+        );
+        let text: MessageContent = serde_json::from_value(json!("[CQ:face,id=14]")).unwrap();
+        assert_eq!(text, MessageContent::Text("[CQ:face,id=14]".to_string()));
+        assert_eq!(
+            parse_cq("[CQ:face,id=14]"),
+            vec![MessageSegment::Face {
+                id: "14".to_string(),
+                extra: Map::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn extra_fields_survive_round_trip() {
+        let message = json!([
+            { "type": "image", "data": {
+                "file": "a.jpg", "url": "https://x/a.jpg", "summary": "[动画表情]", "sub_type": 1
+            } },
+            { "type": "video", "data": { "file": "v.mp4", "thumb": "t.jpg" } },
+            { "type": "image", "data": { "url": "https://x/b.jpg" } }
+        ]);
+        let segs = parse_message(&message);
+        let out = to_onebot(&segs);
+        assert_eq!(out[0], message[0]);
+        assert_eq!(out[1], message[1]);
+        assert_eq!(
+            out[2],
+            json!({ "type": "image", "data": { "file": "https://x/b.jpg" } })
+        );
+        let cq = parse_cq(&to_cq_string(&segs[..1]));
+        assert_eq!(cq[0].to_value()["data"]["summary"], "[动画表情]");
+    }
+}
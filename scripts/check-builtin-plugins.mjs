@@ -23,6 +23,11 @@ function createNbotStub(pluginId, config = {}) {
     callLlmForwardMediaBundle: (...args) => push("callLlmForwardMediaBundle", args),
     callLlmChat: (...args) => push("callLlmChat", args),
     callLlmChatWithSearch: (...args) => push("callLlmChatWithSearch", args),
+    llmChat: async (...args) => {
+      push("llmChat", args);
+      return { requestId: "", content: "", data: null };
+    },
+    searchKnowledge: (...args) => push("searchKnowledge", args),
     sendForwardMessage: (...args) => push("sendForwardMessage", args),
 
     httpFetch: async (...args) => {
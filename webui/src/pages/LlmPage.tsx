@@ -34,9 +34,20 @@ type LibraryModel = {
   enabled?: boolean;
 };
 
+type TtsSettings = {
+  voice?: string;
+  speed?: number;
+  format?: string;
+  instructions?: string;
+  endpoint?: string;
+  api_key?: string;
+  model?: string;
+};
+
 type ModelMapping = {
   provider: string;
   model: string;
+  tts?: TtsSettings;
 };
 
 type LlmConfigResponse = {
@@ -45,9 +56,35 @@ type LlmConfigResponse = {
   model_library: LibraryModel[];
   mappings: Record<string, ModelMapping>;
   default_model: string;
-  tavily_api_key: string;
+  search?: SearchConfig;
+};
+
+type SearchProviderKey = 'none' | 'tavily' | 'searxng' | 'bing' | 'brave';
+
+type SearchConfig = {
+  provider: SearchProviderKey;
+  max_results?: number;
+  tavily?: { api_key?: string };
+  searxng?: { base_url?: string; engines?: string; language?: string };
+  bing?: { api_key?: string; endpoint?: string; market?: string };
+  brave?: { api_key?: string };
 };
 
+function isSearchConfigured(search: SearchConfig): boolean {
+  switch (search.provider) {
+    case 'tavily':
+      return !!search.tavily?.api_key?.trim();
+    case 'searxng':
+      return !!search.searxng?.base_url?.trim();
+    case 'bing':
+      return !!search.bing?.api_key?.trim();
+    case 'brave':
+      return !!search.brave?.api_key?.trim();
+    default:
+      return false;
+  }
+}
+
 type TabKey = 'providers' | 'library' | 'mapping' | 'websearch' | 'chat';
 
 export function LlmPage() {
@@ -56,7 +93,7 @@ export function LlmPage() {
   const [modelLibrary, setModelLibrary] = useState<LibraryModel[]>([]);
   const [mappings, setMappings] = useState<Record<string, ModelMapping>>({});
   const [defaultAlias, setDefaultAlias] = useState('default');
-  const [tavilyKey, setTavilyKey] = useState('');
+  const [search, setSearch] = useState<SearchConfig>({ provider: 'tavily' });
   const [saving, setSaving] = useState(false);
   const [loadedOnce, setLoadedOnce] = useState(false);
 
@@ -73,7 +110,7 @@ export function LlmPage() {
     setModelLibrary(configQuery.data.model_library ?? []);
     setMappings(configQuery.data.mappings ?? {});
     setDefaultAlias(configQuery.data.default_model ?? 'default');
-    setTavilyKey(configQuery.data.tavily_api_key ?? '');
+    setSearch(configQuery.data.search ?? { provider: 'tavily' });
     setLoadedOnce(true);
   }, [configQuery.data, loadedOnce]);
 
@@ -95,7 +132,7 @@ export function LlmPage() {
         model_library: modelLibrary,
         mappings,
         default_model: defaultAlias.trim(),
-        tavily_api_key: tavilyKey.trim(),
+        search,
       });
       if (resp.data?.status === 'success') {
         toast.success('配置已保存');
@@ -171,7 +208,7 @@ export function LlmPage() {
               active={tab === 'websearch'}
               icon={<Search className="w-5 h-5" />}
               label="联网搜索"
-              count={tavilyKey.trim() ? '1' : ''}
+              count={isSearchConfigured(search) ? '1' : ''}
               onClick={() => setTab('websearch')}
             />
             <TabButton
@@ -207,7 +244,7 @@ export function LlmPage() {
                 setDefaultAlias={setDefaultAlias}
               />
             ) : tab === 'websearch' ? (
-              <WebSearchTab tavilyKey={tavilyKey} setTavilyKey={setTavilyKey} />
+              <WebSearchTab search={search} setSearch={setSearch} />
             ) : (
               <ChatTestTab providers={providers} enabledModels={enabledModels} />
             )}
@@ -728,7 +765,14 @@ function MappingTab({
   function updateMapping(alias: string, value: string) {
     const [pid, mid] = value.split('||');
     if (!pid || !mid) return;
-    setMappings({ ...mappings, [alias]: { provider: pid, model: mid } });
+    setMappings({ ...mappings, [alias]: { ...mappings[alias], provider: pid, model: mid } });
+  }
+
+  function updateVoice(alias: string, voice: string) {
+    const { tts, ...rest } = mappings[alias];
+    const nextTts: TtsSettings = { ...tts, voice: voice.trim() || undefined };
+    const hasTts = Object.values(nextTts).some((v) => v !== undefined && v !== '');
+    setMappings({ ...mappings, [alias]: hasTts ? { ...rest, tts: nextTts } : rest });
   }
 
   function remove(alias: string) {
@@ -750,7 +794,8 @@ function MappingTab({
 
       <div className="bg-brand-soft/50 rounded-2xl p-5 border border-brand/10 text-xs text-text-main/70 font-medium">
         别名映射允许你用自定义名称（如 <span className="font-mono">default</span> /{' '}
-        <span className="font-mono">fast</span>）引用具体模型，便于随时切换。
+        <span className="font-mono">fast</span>）引用具体模型，便于随时切换。名为{' '}
+        <span className="font-mono">tts</span> 的别名用于语音合成，可在「TTS 音色」中设置默认音色。
       </div>
 
       <div className="bg-white rounded-[28px] border border-brand-soft shadow-sm p-6 space-y-4">
@@ -767,7 +812,7 @@ function MappingTab({
               return (
                 <div
                   key={alias}
-                  className="grid grid-cols-1 md:grid-cols-[200px_minmax(0,1fr)_160px_180px] gap-3 items-center p-4 bg-brand-soft/30 rounded-2xl border border-transparent hover:border-brand-soft transition-all"
+                  className="grid grid-cols-1 md:grid-cols-[200px_minmax(0,1fr)_140px_160px_180px] gap-3 items-center p-4 bg-brand-soft/30 rounded-2xl border border-transparent hover:border-brand-soft transition-all"
                 >
                   <div className="font-black text-text-main truncate">{alias}</div>
                   <select
@@ -786,6 +831,13 @@ function MappingTab({
                       <option value={current}>{current}</option>
                     )}
                   </select>
+                  <input
+                    className="w-full min-w-0 px-4 py-2.5 rounded-2xl border border-brand-soft bg-white text-sm font-bold text-text-main focus:outline-none focus:ring-4 focus:ring-brand/10 transition-all"
+                    placeholder="TTS 音色"
+                    title="语音合成音色（仅用于 TTS 模型，如 alloy）"
+                    value={mapping.tts?.voice ?? ''}
+                    onChange={(e) => updateVoice(alias, e.target.value)}
+                  />
                   <div className="min-w-0 text-[11px] font-black text-brand/60 bg-white rounded-xl px-3 py-2 truncate">
                     {providerName}
                   </div>
@@ -852,22 +904,77 @@ function MappingTab({
   );
 }
 
-function WebSearchTab({
-  tavilyKey,
-  setTavilyKey,
+const SEARCH_PROVIDERS: Array<{ key: SearchProviderKey; label: string; hint: string }> = [
+  { key: 'tavily', label: 'Tavily', hint: 'AI 搜索引擎，需 tvly- 开头的 API Key' },
+  { key: 'searxng', label: 'SearXNG', hint: '自建元搜索实例，需在 settings.yml 中启用 json 输出格式' },
+  { key: 'bing', label: 'Bing', hint: 'Bing Web Search API（Ocp-Apim-Subscription-Key）' },
+  { key: 'brave', label: 'Brave', hint: 'Brave Search API（X-Subscription-Token）' },
+  { key: 'none', label: '不启用', hint: '插件调用联网对话时不提供搜索工具' },
+];
+
+function SearchField({
+  label,
+  value,
+  onChange,
+  placeholder,
+  secret,
 }: {
-  tavilyKey: string;
-  setTavilyKey: (next: string) => void;
+  label: string;
+  value: string;
+  onChange: (next: string) => void;
+  placeholder?: string;
+  secret?: boolean;
 }) {
   const [show, setShow] = useState(false);
+  return (
+    <div className="space-y-2">
+      <div className="text-[10px] font-black text-brand/40 uppercase tracking-widest ml-1">{label}</div>
+      <div className="relative">
+        <input
+          className="w-full px-5 py-3 pr-12 rounded-2xl border border-brand-soft bg-brand-soft/30 text-sm font-bold text-text-main focus:outline-none focus:ring-4 focus:ring-brand/10 transition-all"
+          type={secret && !show ? 'password' : 'text'}
+          placeholder={placeholder}
+          value={value}
+          onChange={(e) => onChange(e.target.value)}
+        />
+        {secret ? (
+          <button
+            className="absolute right-3 top-1/2 -translate-y-1/2 p-2 text-brand/30 hover:text-brand transition-colors"
+            onClick={() => setShow((v) => !v)}
+            type="button"
+            title={show ? '隐藏' : '显示'}
+          >
+            {show ? <EyeOff className="w-5 h-5" /> : <Eye className="w-5 h-5" />}
+          </button>
+        ) : null}
+      </div>
+    </div>
+  );
+}
+
+function WebSearchTab({
+  search,
+  setSearch,
+}: {
+  search: SearchConfig;
+  setSearch: (next: SearchConfig) => void;
+}) {
   const [testing, setTesting] = useState(false);
+  const [query, setQuery] = useState('');
+  const current = SEARCH_PROVIDERS.find((p) => p.key === search.provider) ?? SEARCH_PROVIDERS[0];
+
+  function updateSection<K extends 'tavily' | 'searxng' | 'bing' | 'brave'>(
+    key: K,
+    patch: NonNullable<SearchConfig[K]>,
+  ) {
+    setSearch({ ...search, [key]: { ...(search[key] ?? {}), ...patch } });
+  }
 
   async function test() {
-    const key = tavilyKey.trim();
-    if (!key || testing) return;
+    if (testing || !isSearchConfigured(search)) return;
     setTesting(true);
     try {
-      const resp = await api.post('/llm/tavily/test', { api_key: key });
+      const resp = await api.post('/llm/search/test', { search, query: query.trim() || undefined });
       if (resp.data?.status === 'success') {
         toast.success(resp.data?.message ?? '连接成功');
       } else {
@@ -888,7 +995,7 @@ function WebSearchTab({
       </div>
 
       <div className="bg-sky-50/50 rounded-2xl p-5 border border-sky-100 text-xs text-text-main/70 font-medium">
-        后端提供 Tavily API Key 连通性测试。填入 Key 并保存后，插件可按约定使用联网搜索能力。
+        选择搜索服务并保存后，插件调用联网对话时模型可通过 web_search 工具检索网页。国内网络可使用自建 SearXNG。
       </div>
 
       <div className="bg-white rounded-[28px] border border-brand-soft shadow-sm overflow-hidden">
@@ -898,47 +1005,109 @@ function WebSearchTab({
               <Search className="w-6 h-6" />
             </div>
             <div>
-              <div className="font-black text-text-main text-lg">Tavily Search</div>
-              <div className="text-[10px] font-black text-brand/40 uppercase tracking-widest">AI 搜索引擎</div>
+              <div className="font-black text-text-main text-lg">{current.label}</div>
+              <div className="text-[10px] font-black text-brand/40 uppercase tracking-widest">{current.hint}</div>
             </div>
           </div>
 
-          <div className="space-y-2">
-            <div className="text-[10px] font-black text-brand/40 uppercase tracking-widest ml-1">
-              API Key
-            </div>
-            <div className="relative">
-              <input
-                className="w-full px-5 py-3 pr-12 rounded-2xl border border-brand-soft bg-brand-soft/30 text-sm font-bold text-text-main focus:outline-none focus:ring-4 focus:ring-brand/10 transition-all"
-                type={show ? 'text' : 'password'}
-                placeholder="tvly-..."
-                value={tavilyKey}
-                onChange={(e) => setTavilyKey(e.target.value)}
-              />
-              <button
-                className="absolute right-3 top-1/2 -translate-y-1/2 p-2 text-brand/30 hover:text-brand transition-colors"
-                onClick={() => setShow((v) => !v)}
-                type="button"
-                title={show ? '隐藏' : '显示'}
+          <div className="grid grid-cols-1 md:grid-cols-2 gap-4">
+            <div className="space-y-2">
+              <div className="text-[10px] font-black text-brand/40 uppercase tracking-widest ml-1">搜索服务</div>
+              <select
+                className="w-full px-5 py-3 rounded-2xl border border-brand-soft bg-white text-sm font-black text-text-main focus:outline-none focus:ring-4 focus:ring-brand/10 transition-all"
+                value={search.provider}
+                onChange={(e) => setSearch({ ...search, provider: e.target.value as SearchProviderKey })}
               >
-                {show ? <EyeOff className="w-5 h-5" /> : <Eye className="w-5 h-5" />}
-              </button>
+                {SEARCH_PROVIDERS.map((p) => (
+                  <option key={p.key} value={p.key}>
+                    {p.label}
+                  </option>
+                ))}
+              </select>
             </div>
+            <SearchField
+              label="返回结果数"
+              value={String(search.max_results ?? 5)}
+              onChange={(v) => {
+                const n = Number.parseInt(v, 10);
+                setSearch({ ...search, max_results: Number.isFinite(n) ? Math.min(Math.max(n, 1), 20) : 5 });
+              }}
+            />
           </div>
 
-          <div className="flex items-center justify-between pt-2">
-            <a
-              className="text-xs text-brand hover:text-brand-hover font-bold transition-colors"
-              href="https://tavily.com/"
-              target="_blank"
-              rel="noreferrer"
-            >
-              获取 Tavily API Key
-            </a>
-            <button className="btn-secondary" onClick={test} disabled={testing || !tavilyKey.trim()}>
-              {testing ? '测试中...' : '连通测试'}
-            </button>
-          </div>
+          {search.provider === 'tavily' ? (
+            <SearchField
+              label="API Key"
+              secret
+              placeholder="tvly-..."
+              value={search.tavily?.api_key ?? ''}
+              onChange={(v) => updateSection('tavily', { api_key: v })}
+            />
+          ) : search.provider === 'searxng' ? (
+            <div className="space-y-4">
+              <SearchField
+                label="实例地址"
+                placeholder="http://searxng:8080"
+                value={search.searxng?.base_url ?? ''}
+                onChange={(v) => updateSection('searxng', { base_url: v })}
+              />
+              <div className="grid grid-cols-1 md:grid-cols-2 gap-4">
+                <SearchField
+                  label="引擎（可选，逗号分隔）"
+                  placeholder="bing,baidu,sogou"
+                  value={search.searxng?.engines ?? ''}
+                  onChange={(v) => updateSection('searxng', { engines: v })}
+                />
+                <SearchField
+                  label="语言（可选）"
+                  placeholder="zh-CN"
+                  value={search.searxng?.language ?? ''}
+                  onChange={(v) => updateSection('searxng', { language: v })}
+                />
+              </div>
+            </div>
+          ) : search.provider === 'bing' ? (
+            <div className="space-y-4">
+              <SearchField
+                label="API Key"
+                secret
+                value={search.bing?.api_key ?? ''}
+                onChange={(v) => updateSection('bing', { api_key: v })}
+              />
+              <div className="grid grid-cols-1 md:grid-cols-2 gap-4">
+                <SearchField
+                  label="Endpoint（可选）"
+                  placeholder="https://api.bing.microsoft.com/v7.0/search"
+                  value={search.bing?.endpoint ?? ''}
+                  onChange={(v) => updateSection('bing', { endpoint: v })}
+                />
+                <SearchField
+                  label="市场（可选）"
+                  placeholder="zh-CN"
+                  value={search.bing?.market ?? ''}
+                  onChange={(v) => updateSection('bing', { market: v })}
+                />
+              </div>
+            </div>
+          ) : search.provider === 'brave' ? (
+            <SearchField
+              label="API Key"
+              secret
+              value={search.brave?.api_key ?? ''}
+              onChange={(v) => updateSection('brave', { api_key: v })}
+            />
+          ) : null}
+
+          {search.provider !== 'none' ? (
+            <div className="flex items-end gap-3 pt-2">
+              <div className="flex-1">
+                <SearchField label="测试关键词（可选）" placeholder="test" value={query} onChange={setQuery} />
+              </div>
+              <button className="btn-secondary" onClick={test} disabled={testing || !isSearchConfigured(search)}>
+                {testing ? '测试中...' : '连通测试'}
+              </button>
+            </div>
+          ) : null}
         </div>
       </div>
     </div>